@@ -0,0 +1,40 @@
+// A sheet of the ready-made patterns: a spirograph, a Lissajous figure, a
+// fractal plant and a field of truchet tiles, one in each quarter of the bed.
+//
+//     cargo run --example patterns
+
+use artful_gcode::art;
+use artful_gcode::prelude::*;
+use artful_gcode::rng::Rng;
+use artful_gcode::sim;
+
+fn main() -> std::io::Result<()> {
+    let mut printer = Printer::new(PrinterConfig::default());
+    let (w, h) = (printer.width, printer.height);
+    let (cx, cy) = (w / 4.0, h / 4.0);
+
+    printer.draw_polyline(&art::spirograph((cx, cy), 35.0, 21.0, 15.0));
+    printer.draw_polyline(&art::lissajous((3.0 * cx, cy), (70.0, 70.0), 5, 4, 0.5));
+
+    let plant = art::lsystem("X", &[('X', "F+[[X]-X]-F[-FX]+X"), ('F', "FF")], 4);
+    printer.push_transform(AffineTransform::translate(cx, 2.0 * cy + 5.0));
+    art::interpret(&plant, 1.2, 25.0).draw(&mut printer);
+    printer.pop_transform();
+
+    let mut rng = Rng::new(7);
+    let tiles = art::truchet_grid(
+        (w / 2.0 + 5.0, h / 2.0 + 5.0),
+        (w - 5.0, h - 5.0),
+        8.0,
+        &mut rng,
+    );
+    for tile in &tiles {
+        printer.draw_polyline(tile);
+    }
+
+    printer.optimize();
+    printer.save("patterns.gcode")?;
+    let seconds = printer.estimated_duration().as_secs_f32();
+    println!("patterns.gcode, about {}", sim::format_time(seconds));
+    Ok(())
+}
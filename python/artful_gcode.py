@@ -0,0 +1,197 @@
+"""Python bindings for artful_gcode, over the C functions of src/python.rs.
+
+Build the library first, from the root of the repository:
+
+    cargo rustc --release --features python --lib --crate-type cdylib
+
+This module finds it in target/release, or wherever ARTFUL_GCODE_LIB
+points. The classes follow the Rust API:
+
+    from artful_gcode import Printer, PrinterConfig
+
+    config = PrinterConfig.prusa_mk3s()
+    config["xy_speed"] = 1500
+    printer = Printer(config)
+    printer.draw_circle(100, 80, 30)
+    printer.optimize()
+    printer.save("circle.gcode")
+"""
+
+import ctypes
+import os
+import sys
+
+_NAMES = {"darwin": "libartful_gcode.dylib", "win32": "artful_gcode.dll"}
+
+
+def _load():
+    path = os.environ.get("ARTFUL_GCODE_LIB")
+    if path is None:
+        root = os.path.dirname(os.path.dirname(os.path.abspath(__file__)))
+        name = _NAMES.get(sys.platform, "libartful_gcode.so")
+        path = os.path.join(root, "target", "release", name)
+    lib = ctypes.CDLL(path)
+
+    P = ctypes.c_void_p
+    F = ctypes.c_float
+    S = ctypes.c_char_p
+    floats = ctypes.POINTER(ctypes.c_float)
+    signatures = {
+        "ag_last_error": ([], S),
+        "ag_string_free": ([P], None),
+        "ag_config_default": ([], P),
+        "ag_config_prusa_mk3s": ([], P),
+        "ag_config_set": ([P, S, S], ctypes.c_int),
+        "ag_config_get": ([P, S], P),
+        "ag_config_free": ([P], None),
+        "ag_printer_new": ([P], P),
+        "ag_printer_free": ([P], None),
+        "ag_printer_size": ([P, floats], None),
+        "ag_draw_point": ([P, F, F], None),
+        "ag_draw_line": ([P, F, F, F, F], None),
+        "ag_draw_polyline": ([P, floats, ctypes.c_size_t], None),
+        "ag_draw_circle": ([P, F, F, F], None),
+        "ag_draw_arc": ([P, F, F, F, F, F], None),
+        "ag_draw_cubic_bezier": ([P, floats], None),
+        "ag_draw_text": ([P, F, F, F, S], None),
+        "ag_set_layer": ([P, S], None),
+        "ag_push_transform": ([P, floats], None),
+        "ag_pop_transform": ([P], None),
+        "ag_optimize": ([P], None),
+        "ag_dedupe": ([P, F], None),
+        "ag_estimated_duration": ([P], F),
+        "ag_save": ([P, S], ctypes.c_int),
+        "ag_to_string": ([P], P),
+    }
+    for name, (args, result) in signatures.items():
+        function = getattr(lib, name)
+        function.argtypes = args
+        function.restype = result
+    return lib
+
+
+_lib = _load()
+
+
+class Error(Exception):
+    pass
+
+
+def _error():
+    return Error(_lib.ag_last_error().decode())
+
+
+def _text(pointer):
+    if not pointer:
+        raise _error()
+    try:
+        return ctypes.string_at(pointer).decode()
+    finally:
+        _lib.ag_string_free(pointer)
+
+
+def _floats(values):
+    values = [float(v) for v in values]
+    return (ctypes.c_float * len(values))(*values)
+
+
+class PrinterConfig:
+    """A machine config. Entries are read and set by the keys of the
+    embedded config, e.g. config["z_draw"] = 4.2, and checked as they are
+    set."""
+
+    def __init__(self, handle=None):
+        self._handle = handle or _lib.ag_config_default()
+
+    @classmethod
+    def prusa_mk3s(cls):
+        return cls(_lib.ag_config_prusa_mk3s())
+
+    def __getitem__(self, key):
+        pointer = _lib.ag_config_get(self._handle, key.encode())
+        if not pointer:
+            raise KeyError(key)
+        return _text(pointer)
+
+    def __setitem__(self, key, value):
+        if isinstance(value, bool):
+            value = "true" if value else "false"
+        elif isinstance(value, (tuple, list)):
+            value = ",".join(str(v) for v in value)
+        if _lib.ag_config_set(self._handle, key.encode(), str(value).encode()) != 0:
+            raise _error()
+
+    def __del__(self):
+        if getattr(self, "_handle", None):
+            _lib.ag_config_free(self._handle)
+            self._handle = None
+
+
+class Printer:
+    def __init__(self, config=None):
+        config = config or PrinterConfig()
+        self._handle = _lib.ag_printer_new(config._handle)
+        if not self._handle:
+            raise _error()
+        size = (ctypes.c_float * 2)()
+        _lib.ag_printer_size(self._handle, size)
+        self.width, self.height = size[0], size[1]
+
+    def draw_point(self, x, y):
+        _lib.ag_draw_point(self._handle, x, y)
+
+    def draw_line(self, x0, y0, x1, y1):
+        _lib.ag_draw_line(self._handle, x0, y0, x1, y1)
+
+    def draw_polyline(self, points):
+        points = list(points)
+        xy = _floats(v for p in points for v in p)
+        _lib.ag_draw_polyline(self._handle, xy, len(points))
+
+    def draw_circle(self, cx, cy, r):
+        _lib.ag_draw_circle(self._handle, cx, cy, r)
+
+    def draw_arc(self, cx, cy, radius, start_angle, end_angle):
+        _lib.ag_draw_arc(self._handle, cx, cy, radius, start_angle, end_angle)
+
+    def draw_cubic_bezier(self, p0, c0, c1, p1):
+        _lib.ag_draw_cubic_bezier(self._handle, _floats([*p0, *c0, *c1, *p1]))
+
+    def draw_text(self, x, y, height, text):
+        _lib.ag_draw_text(self._handle, x, y, height, text.encode())
+
+    def set_layer(self, name):
+        _lib.ag_set_layer(self._handle, name.encode())
+
+    def push_transform(self, a, b, c, d, e, f):
+        """x' = a x + c y + e, y' = b x + d y + f, like AffineTransform::new"""
+        _lib.ag_push_transform(self._handle, _floats([a, b, c, d, e, f]))
+
+    def pop_transform(self):
+        _lib.ag_pop_transform(self._handle)
+
+    def optimize(self):
+        _lib.ag_optimize(self._handle)
+
+    def dedupe(self, tolerance):
+        _lib.ag_dedupe(self._handle, tolerance)
+
+    def estimated_duration(self):
+        """In seconds"""
+        return _lib.ag_estimated_duration(self._handle)
+
+    def save(self, filename):
+        if _lib.ag_save(self._handle, os.fsencode(filename)) != 0:
+            raise _error()
+
+    def to_string(self):
+        """The program as save would write it"""
+        return _text(_lib.ag_to_string(self._handle))
+
+    def __str__(self):
+        return self.to_string()
+
+    def __del__(self):
+        if getattr(self, "_handle", None):
+            _lib.ag_printer_free(self._handle)
+            self._handle = None
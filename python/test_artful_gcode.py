@@ -0,0 +1,83 @@
+"""Tests of artful_gcode.py against the built library, see src/python.rs.
+
+    cargo rustc --release --features python --lib --crate-type cdylib
+    python3 -m unittest discover python
+"""
+
+import os
+import tempfile
+import unittest
+
+from artful_gcode import Error, Printer, PrinterConfig
+
+
+class ConfigTest(unittest.TestCase):
+    def test_entries(self):
+        config = PrinterConfig.prusa_mk3s()
+        config["xy_speed"] = 1500
+        config["min"] = (0, 0)
+        config["embed_config"] = True
+        self.assertEqual(config["xy_speed"], "1500")
+        self.assertEqual(config["min"], "0,0")
+        self.assertEqual(config["embed_config"], "true")
+
+        with self.assertRaises(Error) as raised:
+            config["z0"] = "high"
+        self.assertIn("z0", str(raised.exception))
+        self.assertEqual(config["z0"], "6.5")
+        with self.assertRaises(KeyError):
+            config["nonsense"]
+
+    def test_invalid_config(self):
+        config = PrinterConfig.prusa_mk3s()
+        # Checked as it's set, so printers only ever get valid configs
+        with self.assertRaises(Error) as raised:
+            config["z_draw"] = 8
+        self.assertIn("z_draw", str(raised.exception))
+        self.assertEqual(config["z_draw"], "4")
+        Printer(config)
+
+
+class PrinterTest(unittest.TestCase):
+    def setUp(self):
+        config = PrinterConfig.prusa_mk3s()
+        config["xy_speed"] = 1500
+        self.printer = Printer(config)
+
+    def test_size(self):
+        self.assertEqual((self.printer.width, self.printer.height), (204, 177))
+
+    def test_drawing(self):
+        printer = self.printer
+        printer.draw_polyline([(0, 0), (10, 0), (10, 10)])
+        printer.push_transform(1, 0, 0, 1, 20, 0)
+        printer.draw_point(5, 5)
+        printer.pop_transform()
+        printer.set_layer("red")
+        printer.draw_circle(50, 50, 10)
+        printer.draw_arc(50, 50, 20, 0, 90)
+        printer.draw_cubic_bezier((0, 0), (0, 10), (10, 10), (10, 0))
+        printer.draw_line(0, 20, 30, 20)
+        printer.draw_text(0, 40, 5, "Hi")
+        printer.dedupe(0.05)
+        printer.optimize()
+        self.assertGreater(printer.estimated_duration(), 0)
+
+        program = printer.to_string()
+        self.assertIn("X10.0 Y10.0 F1500.0", program)
+        self.assertIn("X25.0 Y5.0 F1500.0", program)
+        self.assertEqual(str(printer), program)
+
+    def test_save(self):
+        self.printer.draw_point(5, 5)
+        with tempfile.TemporaryDirectory() as directory:
+            filename = os.path.join(directory, "point.gcode")
+            self.printer.save(filename)
+            with open(filename) as saved:
+                self.assertEqual(saved.read(), self.printer.to_string())
+            with self.assertRaises(Error):
+                self.printer.save(os.path.join(directory, "missing", "x.gcode"))
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1,472 @@
+use std::fmt;
+use std::sync::Arc;
+
+use crate::{Code, Dialect, Point};
+
+// How the machine gets the pen onto the paper and off it again. `z` and
+// `feed` are the height and speed a Z axis would use, other mechanisms are
+// free to ignore them.
+pub trait PenActuator: fmt::Debug + Send + Sync {
+    fn down(&self, z: f32, feed: f32) -> Vec<Code>;
+    fn up(&self, z: f32, feed: f32) -> Vec<Code>;
+
+    // Text form for embedded configs, which `parse` reads back
+    fn describe(&self) -> String {
+        format!("{:?}", self)
+    }
+
+    // Whether the pen height is a Z position that can change along the
+    // drawing moves, e.g. for tapered strokes
+    fn ramps_z(&self) -> bool {
+        false
+    }
+
+    // Holds the pen partly raised while it keeps drawing, `lift` of the way
+    // from down (0) to up (1). Nothing for pens that are either up or down.
+    fn ramp(&self, _lift: f32) -> Vec<Code> {
+        Vec::new()
+    }
+
+    // Whether the firmware knows the commands this emits
+    fn supports(&self, _dialect: Dialect) -> bool {
+        true
+    }
+}
+
+// Actuator from its `describe` text, for the built-in mechanisms
+pub fn parse(text: &str) -> Option<Arc<dyn PenActuator>> {
+    if let Some(rest) = text.strip_prefix("custom ") {
+        let (dwell_ms, commands) = rest.split_once(' ')?;
+        let (up, down) = if commands.starts_with('"') {
+            let (up, rest) = unquote(commands)?;
+            let (down, rest) = unquote(rest.strip_prefix(' ')?)?;
+            rest.is_empty().then_some((up, down))?
+        } else {
+            // Unquoted "up | down", as written before the commands were
+            // quoted
+            let (up, down) = commands.split_once(" | ")?;
+            (up.to_string(), down.to_string())
+        };
+        return Some(Arc::new(Custom {
+            up,
+            down,
+            dwell_ms: dwell_ms.parse().ok()?,
+        }));
+    }
+    let mut words = text.split_whitespace();
+    let kind = words.next()?;
+    let args: Vec<u32> = words.map(|w| w.parse().ok()).collect::<Option<_>>()?;
+    let actuator: Arc<dyn PenActuator> = match (kind, args.as_slice()) {
+        ("z", []) => Arc::new(ZAxis),
+        ("servo", &[index, up_angle, down_angle, dwell_ms]) => Arc::new(Servo {
+            index: index.try_into().ok()?,
+            up_angle: up_angle.try_into().ok()?,
+            down_angle: down_angle.try_into().ok()?,
+            dwell_ms,
+        }),
+        ("solenoid", &[fan, dwell_ms]) => Arc::new(Solenoid {
+            fan: fan.try_into().ok()?,
+            dwell_ms,
+        }),
+        ("laser", &[power]) => Arc::new(Laser { power }),
+        ("spindle", &[up, down, dwell_ms]) => Arc::new(Spindle { up, down, dwell_ms }),
+        _ => return None,
+    };
+    Some(actuator)
+}
+
+// A command in double quotes, with quotes, backslashes and line breaks
+// escaped so it survives as one word of a config line
+fn quote(command: &str) -> String {
+    let escaped = command
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n");
+    format!("\"{}\"", escaped)
+}
+
+// The command quoted at the start of `text` and the text after it
+fn unquote(text: &str) -> Option<(String, &str)> {
+    let mut chars = text.strip_prefix('"')?.char_indices();
+    let mut command = String::new();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '"' => return Some((command, &text[i + 2..])),
+            '\\' => command.push(match chars.next()?.1 {
+                'n' => '\n',
+                c @ ('"' | '\\') => c,
+                _ => return None,
+            }),
+            c => command.push(c),
+        }
+    }
+    None
+}
+
+fn dwell(ms: u32) -> Option<Code> {
+    (ms > 0).then(|| Code::Dwell(ms as f32 / 1000.0))
+}
+
+// The pen is mounted on the Z axis, like on a 3D printer
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub struct ZAxis;
+
+impl PenActuator for ZAxis {
+    fn down(&self, z: f32, feed: f32) -> Vec<Code> {
+        vec![Code::Move(
+            Point {
+                x: None,
+                y: None,
+                z: Some(z),
+            },
+            feed,
+        )]
+    }
+
+    fn up(&self, z: f32, feed: f32) -> Vec<Code> {
+        self.down(z, feed)
+    }
+
+    fn ramps_z(&self) -> bool {
+        true
+    }
+
+    fn describe(&self) -> String {
+        "z".to_string()
+    }
+}
+
+// Hobby servo on a Marlin servo header (M280), with a pause for it to settle
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Servo {
+    pub index: u8,
+    pub up_angle: u8,
+    pub down_angle: u8,
+    pub dwell_ms: u32,
+}
+
+impl PenActuator for Servo {
+    fn down(&self, _z: f32, _feed: f32) -> Vec<Code> {
+        let mut code = vec![Code::Pen(
+            true,
+            format!("M280 P{} S{}", self.index, self.down_angle),
+        )];
+        code.extend(dwell(self.dwell_ms));
+        code
+    }
+
+    fn up(&self, _z: f32, _feed: f32) -> Vec<Code> {
+        let mut code = vec![Code::Pen(
+            false,
+            format!("M280 P{} S{}", self.index, self.up_angle),
+        )];
+        code.extend(dwell(self.dwell_ms));
+        code
+    }
+
+    fn ramp(&self, lift: f32) -> Vec<Code> {
+        let (up, down) = (self.up_angle as f32, self.down_angle as f32);
+        let angle = (down + lift.clamp(0.0, 1.0) * (up - down)).round();
+        vec![Code::Pen(true, format!("M280 P{} S{}", self.index, angle))]
+    }
+
+    fn supports(&self, dialect: Dialect) -> bool {
+        dialect != Dialect::Grbl
+    }
+
+    fn describe(&self) -> String {
+        format!(
+            "servo {} {} {} {}",
+            self.index, self.up_angle, self.down_angle, self.dwell_ms
+        )
+    }
+}
+
+// Solenoid driven from a fan output, energized to push the pen down
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Solenoid {
+    pub fan: u8,
+    pub dwell_ms: u32,
+}
+
+impl PenActuator for Solenoid {
+    fn down(&self, _z: f32, _feed: f32) -> Vec<Code> {
+        let mut code = vec![Code::Pen(true, format!("M106 P{} S255", self.fan))];
+        code.extend(dwell(self.dwell_ms));
+        code
+    }
+
+    fn up(&self, _z: f32, _feed: f32) -> Vec<Code> {
+        let mut code = vec![Code::Pen(false, format!("M107 P{}", self.fan))];
+        code.extend(dwell(self.dwell_ms));
+        code
+    }
+
+    fn supports(&self, dialect: Dialect) -> bool {
+        dialect != Dialect::Grbl
+    }
+
+    fn describe(&self) -> String {
+        format!("solenoid {} {}", self.fan, self.dwell_ms)
+    }
+}
+
+// Laser module: "pen down" switches the beam on at `power`
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Laser {
+    pub power: u32,
+}
+
+impl PenActuator for Laser {
+    fn down(&self, _z: f32, _feed: f32) -> Vec<Code> {
+        vec![Code::Pen(true, format!("M3 S{}", self.power))]
+    }
+
+    fn up(&self, _z: f32, _feed: f32) -> Vec<Code> {
+        vec![Code::Pen(false, "M5".to_string())]
+    }
+
+    // A fainter burn stands in for a lighter touch
+    fn ramp(&self, lift: f32) -> Vec<Code> {
+        let power = (self.power as f32 * (1.0 - lift.clamp(0.0, 1.0))).round();
+        vec![Code::Pen(true, format!("M3 S{}", power))]
+    }
+
+    fn describe(&self) -> String {
+        format!("laser {}", self.power)
+    }
+}
+
+// Servo on the spindle PWM output, as GRBL pen plotter builds wire it, with
+// the spindle speed setting the angle
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Spindle {
+    pub up: u32,
+    pub down: u32,
+    pub dwell_ms: u32,
+}
+
+impl PenActuator for Spindle {
+    fn down(&self, _z: f32, _feed: f32) -> Vec<Code> {
+        let mut code = vec![Code::Pen(true, format!("M3 S{}", self.down))];
+        code.extend(dwell(self.dwell_ms));
+        code
+    }
+
+    fn up(&self, _z: f32, _feed: f32) -> Vec<Code> {
+        let mut code = vec![Code::Pen(false, format!("M3 S{}", self.up))];
+        code.extend(dwell(self.dwell_ms));
+        code
+    }
+
+    fn ramp(&self, lift: f32) -> Vec<Code> {
+        let (up, down) = (self.up as f32, self.down as f32);
+        let speed = (down + lift.clamp(0.0, 1.0) * (up - down)).round();
+        vec![Code::Pen(true, format!("M3 S{}", speed))]
+    }
+
+    fn describe(&self) -> String {
+        format!("spindle {} {} {}", self.up, self.down, self.dwell_ms)
+    }
+}
+
+// Any pair of commands, for firmware the built-in mechanisms don't cover
+#[derive(Debug, Clone, PartialEq)]
+pub struct Custom {
+    pub up: String,
+    pub down: String,
+    pub dwell_ms: u32,
+}
+
+impl PenActuator for Custom {
+    fn down(&self, _z: f32, _feed: f32) -> Vec<Code> {
+        let mut code = vec![Code::Pen(true, self.down.clone())];
+        code.extend(dwell(self.dwell_ms));
+        code
+    }
+
+    fn up(&self, _z: f32, _feed: f32) -> Vec<Code> {
+        let mut code = vec![Code::Pen(false, self.up.clone())];
+        code.extend(dwell(self.dwell_ms));
+        code
+    }
+
+    fn describe(&self) -> String {
+        format!(
+            "custom {} {} {}",
+            self.dwell_ms,
+            quote(&self.up),
+            quote(&self.down)
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{assert_within, test_config};
+    use crate::{Printer, sim};
+
+    fn lines(code: Vec<Code>) -> Vec<String> {
+        code.iter().map(|c| c.to_string()).collect()
+    }
+
+    #[test]
+    fn actuator_commands() {
+        assert_eq!(lines(ZAxis.down(4.0, 500.0)), ["G1 Z4.0 F500.0"]);
+
+        let servo = Servo {
+            index: 0,
+            up_angle: 90,
+            down_angle: 30,
+            dwell_ms: 150,
+        };
+        assert_eq!(lines(servo.down(4.0, 500.0)), ["M280 P0 S30", "G4 P150"]);
+        assert_eq!(lines(servo.up(6.5, 800.0)), ["M280 P0 S90", "G4 P150"]);
+
+        let solenoid = Solenoid {
+            fan: 1,
+            dwell_ms: 0,
+        };
+        assert_eq!(lines(solenoid.down(4.0, 500.0)), ["M106 P1 S255"]);
+        assert_eq!(lines(solenoid.up(6.5, 800.0)), ["M107 P1"]);
+
+        let laser = Laser { power: 800 };
+        assert_eq!(lines(laser.down(4.0, 500.0)), ["M3 S800"]);
+        assert_eq!(lines(laser.up(6.5, 800.0)), ["M5"]);
+
+        let spindle = Spindle {
+            up: 0,
+            down: 1000,
+            dwell_ms: 200,
+        };
+        assert_eq!(lines(spindle.down(4.0, 500.0)), ["M3 S1000", "G4 P200"]);
+        assert_eq!(lines(spindle.up(6.5, 800.0)), ["M3 S0", "G4 P200"]);
+        assert!(spindle.supports(Dialect::Grbl) && !servo.supports(Dialect::Grbl));
+
+        let custom = Custom {
+            up: "SP,1".to_string(),
+            down: "SP,0".to_string(),
+            dwell_ms: 0,
+        };
+        assert_eq!(lines(custom.down(4.0, 500.0)), ["SP,0"]);
+        assert_eq!(lines(custom.up(6.5, 800.0)), ["SP,1"]);
+    }
+
+    #[test]
+    fn describe_and_parse() {
+        let servo = Servo {
+            index: 1,
+            up_angle: 90,
+            down_angle: 30,
+            dwell_ms: 150,
+        };
+        assert_eq!(servo.describe(), "servo 1 90 30 150");
+
+        let custom = Custom {
+            up: "M3 S0".to_string(),
+            down: "M3 S30".to_string(),
+            dwell_ms: 100,
+        };
+        assert_eq!(custom.describe(), "custom 100 \"M3 S0\" \"M3 S30\"");
+
+        let actuators: [&dyn PenActuator; 6] = [
+            &custom,
+            &ZAxis,
+            &servo,
+            &Solenoid {
+                fan: 2,
+                dwell_ms: 0,
+            },
+            &Laser { power: 800 },
+            &Spindle {
+                up: 0,
+                down: 1000,
+                dwell_ms: 150,
+            },
+        ];
+        for a in actuators {
+            let parsed = parse(&a.describe()).unwrap();
+            assert_eq!(parsed.describe(), a.describe());
+            assert_eq!(lines(parsed.down(4.0, 500.0)), lines(a.down(4.0, 500.0)));
+        }
+        assert!(parse("servo 1 90").is_none());
+        assert!(parse("hydraulic").is_none());
+        assert!(parse("custom 100 M3 S0").is_none());
+        assert!(parse("custom 100 \"M3 S0\" \"M3").is_none());
+    }
+
+    #[test]
+    fn custom_commands_round_trip() {
+        let custom = Custom {
+            up: "M42 P8 S0 | echo \"up\"".to_string(),
+            down: "SP,0 \\ 1\nM400".to_string(),
+            dwell_ms: 50,
+        };
+        let described = custom.describe();
+        assert!(!described.contains('\n'));
+        let parsed = parse(&described).unwrap();
+        let pen = |code: Vec<Code>| match &code[0] {
+            Code::Pen(_, command) => command.clone(),
+            _ => panic!("no pen command"),
+        };
+        assert_eq!(pen(parsed.up(6.5, 800.0)), custom.up);
+        assert_eq!(pen(parsed.down(4.0, 500.0)), custom.down);
+        assert_eq!(parsed.describe(), described);
+
+        // Written before the commands were quoted
+        let old = parse("custom 100 M3 S0 | M3 S30").unwrap();
+        assert_eq!(old.describe(), "custom 100 \"M3 S0\" \"M3 S30\"");
+    }
+
+    #[test]
+    fn servo_actuator() {
+        let mut config = test_config();
+        config.actuator = Arc::new(Servo {
+            index: 0,
+            up_angle: 90,
+            down_angle: 30,
+            dwell_ms: 100,
+        });
+        let mut printer = Printer::new(config);
+        printer.draw_polyline(&[(0.0, 0.0), (10.0, 0.0)]);
+        printer.draw_point(20.0, 0.0);
+
+        let code: Vec<String> = printer.codes().iter().map(|c| c.to_string()).collect();
+        assert!(!code.iter().any(|l| l.contains('Z')));
+        assert_eq!(code.iter().filter(|l| *l == "M280 P0 S30").count(), 2);
+        assert_eq!(code.iter().filter(|l| *l == "G4 P100").count(), 4);
+
+        // The simulation still knows when the pen is down
+        let stats = printer.stats();
+        assert_within(stats.total.drawn, 10.0, 1e-4);
+        let extents = sim::drawn_extents(&printer.simulate());
+        assert_eq!(extents, Some(((0.0, 0.0), (20.0, 0.0))));
+    }
+
+    #[test]
+    fn pen_without_z() {
+        let mut config = test_config();
+        config.actuator = parse("custom 150 \"M3 S0\" \"M3 S30\"").unwrap();
+        let mut printer = Printer::new(config);
+        printer.draw_line(0.0, 0.0, 10.0, 10.0);
+        let mut memory = Vec::new();
+        printer.write_to(&mut memory).unwrap();
+        let program = String::from_utf8(memory).unwrap();
+
+        // The footer doesn't lift a Z axis that may not be there
+        assert!(
+            !program
+                .lines()
+                .any(|l| l.starts_with("G0") && l.contains('Z'))
+        );
+        let pen: Vec<&str> = program
+            .lines()
+            .filter(|l| l.starts_with("M3") || l.starts_with("G4"))
+            .collect();
+        assert_eq!(
+            pen,
+            ["M3 S0", "G4 P150", "M3 S30", "G4 P150", "M3 S0", "G4 P150"]
+        );
+    }
+}
@@ -0,0 +1,195 @@
+use std::f32::consts::PI;
+
+use crate::curve::{self, CircularArc};
+use crate::path::Path;
+use crate::rng::Rng;
+use crate::turtle::Turtle;
+
+// Ready-made patterns as polylines in drawing coordinates, to draw with
+// Printer::draw_polyline or to transform first
+
+// Points of a closed curve given by `f` over one period, enough of them that
+// no chord strays more than the default tolerance for curves from a circle
+// of `radius`
+fn sample(radius: f32, turns: f32, f: impl Fn(f32) -> (f32, f32)) -> Path {
+    let step = 2.0 * (1.0 - curve::TOLERANCE / radius.max(curve::TOLERANCE)).acos();
+    let n = ((2.0 * PI * turns / step.max(1e-3)).ceil() as usize).clamp(16, 200_000);
+    (0..=n)
+        .map(|i| f(2.0 * PI * turns * i as f32 / n as f32))
+        .collect()
+}
+
+fn gcd(a: u64, b: u64) -> u64 {
+    if b == 0 { a } else { gcd(b, a % b) }
+}
+
+// The curve a pen in a wheel of radius `rolling` traces as the wheel rolls
+// around the inside of a ring of radius `fixed`, at `offset` from the middle
+// of the wheel (a hypotrochoid). Negative radii roll the wheel around the
+// outside instead. Drawn until the curve closes, which for radii that have
+// little in common can take many turns.
+pub fn spirograph(center: (f32, f32), fixed: f32, rolling: f32, offset: f32) -> Path {
+    let difference = fixed - rolling;
+    // Turns until the wheel is back where it started, with the radii taken
+    // in hundredths of a millimetre
+    let units = |r: f32| ((r.abs() * 100.0).round() as u64).max(1);
+    let turns = (units(rolling) / gcd(units(fixed), units(rolling))).min(1000) as f32;
+    let reach = difference.abs() + offset.abs();
+    sample(reach, turns, |t| {
+        let wheel = difference / rolling * t;
+        (
+            center.0 + difference * t.cos() + offset * wheel.cos(),
+            center.1 + difference * t.sin() - offset * wheel.sin(),
+        )
+    })
+}
+
+// x and y swinging `a` and `b` times per period, with x ahead by `phase`
+// radians, over a rectangle of `size` around `center`
+pub fn lissajous(center: (f32, f32), size: (f32, f32), a: u32, b: u32, phase: f32) -> Path {
+    let (rx, ry) = (size.0 / 2.0, size.1 / 2.0);
+    let turns = a.max(b).max(1) as f32;
+    sample(rx.max(ry), turns, |t| {
+        let t = t / turns;
+        (
+            center.0 + rx * (a as f32 * t + phase).sin(),
+            center.1 + ry * (b as f32 * t).sin(),
+        )
+    })
+}
+
+// `axiom` with the rules applied `depth` times, every symbol with a rule
+// replaced by it at once
+pub fn lsystem(axiom: &str, rules: &[(char, &str)], depth: usize) -> String {
+    let mut text = axiom.to_string();
+    for _ in 0..depth {
+        text = text
+            .chars()
+            .map(|c| match rules.iter().find(|(from, _)| *from == c) {
+                Some((_, to)) => to.to_string(),
+                None => c.to_string(),
+            })
+            .collect();
+    }
+    text
+}
+
+// Runs the symbols of an L-system with a turtle starting at the origin and
+// facing up: F and G draw `step` forward, f moves without drawing, + and -
+// turn `angle` degrees left and right, | turns around and [ and ] save and
+// restore the position. Other symbols do nothing.
+pub fn interpret(commands: &str, step: f32, angle: f32) -> Turtle {
+    let mut turtle = Turtle::new(0.0, 0.0);
+    turtle.set_heading(90.0);
+    for c in commands.chars() {
+        match c {
+            'F' | 'G' => {
+                turtle.forward(step);
+            }
+            'f' => {
+                turtle.pen_up().forward(step).pen_down();
+            }
+            '+' => {
+                turtle.turn(angle);
+            }
+            '-' => {
+                turtle.turn(-angle);
+            }
+            '|' => {
+                turtle.turn(180.0);
+            }
+            '[' => {
+                turtle.push();
+            }
+            ']' => {
+                turtle.pop();
+            }
+            _ => {}
+        }
+    }
+    turtle
+}
+
+fn quarter(center: (f32, f32), radius: f32, start: f32) -> Path {
+    CircularArc::new(center, radius, start, PI / 2.0).flatten(curve::TOLERANCE)
+}
+
+// Smith's truchet tiles over min..max: square tiles of `tile` mm, each with
+// two quarter circles joining the middles of its edges, turned one of the
+// two ways at random. The arcs of neighbouring tiles meet in long winding
+// curves.
+pub fn truchet_grid(min: (f32, f32), max: (f32, f32), tile: f32, rng: &mut Rng) -> Vec<Path> {
+    let columns = ((max.0 - min.0) / tile).floor().max(0.0) as usize;
+    let rows = ((max.1 - min.1) / tile).floor().max(0.0) as usize;
+    let r = tile / 2.0;
+    let mut paths = Vec::new();
+    for row in 0..rows {
+        for column in 0..columns {
+            let (x, y) = (min.0 + column as f32 * tile, min.1 + row as f32 * tile);
+            if rng.below(2) == 0 {
+                paths.push(quarter((x, y), r, 0.0));
+                paths.push(quarter((x + tile, y + tile), r, PI));
+            } else {
+                paths.push(quarter((x + tile, y), r, PI / 2.0));
+                paths.push(quarter((x, y + tile), r, 1.5 * PI));
+            }
+        }
+    }
+    paths
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::path;
+
+    fn closed(p: &Path) -> bool {
+        path::dist(p[0], *p.last().unwrap()) < 1e-2
+    }
+
+    #[test]
+    fn curves_close() {
+        // 5 and 3 have nothing in common, so it takes 3 turns to close
+        let curve = spirograph((0.0, 0.0), 50.0, 30.0, 20.0);
+        assert!(closed(&curve));
+        assert!(path::dist(curve[0], (40.0, 0.0)) < 1e-3);
+        let reach = curve.iter().map(|&p| path::dist(p, (0.0, 0.0)));
+        assert!(reach.fold(0.0, f32::max) <= 40.0 + 1e-3);
+
+        let figure = lissajous((10.0, 10.0), (20.0, 10.0), 3, 2, PI / 2.0);
+        assert!(closed(&figure));
+        assert!(figure.iter().all(|&(x, y)| {
+            (-1e-3..=20.001).contains(&x) && (5.0 - 1e-3..=15.001).contains(&y)
+        }));
+    }
+
+    #[test]
+    fn lsystems() {
+        let koch = lsystem("F", &[('F', "F+F-F")], 2);
+        assert_eq!(koch, "F+F-F+F+F-F-F+F-F");
+
+        // A square, and a Y from its top corner
+        let turtle = interpret("F-F-F-F", 10.0, 90.0);
+        assert_eq!(turtle.strokes().len(), 1);
+        assert!(path::dist(turtle.position(), (0.0, 0.0)) < 1e-4);
+        let turtle = interpret("F[+F][-F]", 10.0, 30.0);
+        assert_eq!(turtle.strokes().len(), 2);
+    }
+
+    #[test]
+    fn truchet_tiles() {
+        let mut rng = Rng::new(3);
+        let arcs = truchet_grid((0.0, 0.0), (45.0, 20.0), 10.0, &mut rng);
+        // 4 by 2 tiles of two arcs each
+        assert_eq!(arcs.len(), 16);
+        for arc in &arcs {
+            // From the middle of one edge to the middle of the next
+            let ends = [arc[0], *arc.last().unwrap()];
+            assert!(ends.iter().all(|&(x, y)| {
+                let on_middle = |v: f32| ((v / 5.0).round() as i32) % 2 == 1;
+                (on_middle(x) && (y / 10.0).fract().abs() < 1e-3)
+                    || (on_middle(y) && (x / 10.0).fract().abs() < 1e-3)
+            }));
+        }
+    }
+}
@@ -0,0 +1,430 @@
+use std::f32::consts::PI;
+
+use crate::path::{self, Path};
+use crate::{Error, Printer};
+
+// Sound read from a WAV file, mixed down to mono
+#[derive(Debug, Clone, PartialEq)]
+pub struct Wav {
+    pub sample_rate: u32,
+    // -1..=1
+    pub samples: Vec<f32>,
+}
+
+fn invalid(message: &str) -> Error {
+    Error::Parse(message.to_string())
+}
+
+fn u16_at(data: &[u8], at: usize) -> u16 {
+    u16::from_le_bytes([data[at], data[at + 1]])
+}
+
+fn u32_at(data: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes([data[at], data[at + 1], data[at + 2], data[at + 3]])
+}
+
+impl Wav {
+    // PCM with 8 to 32 bit integer or 32 bit float samples
+    pub fn parse(data: &[u8]) -> Result<Wav, Error> {
+        if data.len() < 12 || &data[..4] != b"RIFF" || &data[8..12] != b"WAVE" {
+            return Err(invalid("not a WAV file"));
+        }
+        let mut format = None;
+        let mut samples = None;
+        let mut at = 12;
+        while at + 8 <= data.len() {
+            let size = u32_at(data, at + 4) as usize;
+            let body = &data[at + 8..(at + 8 + size).min(data.len())];
+            match &data[at..at + 4] {
+                b"fmt " if body.len() >= 16 => {
+                    let mut tag = u16_at(body, 0);
+                    // WAVE_FORMAT_EXTENSIBLE keeps the real format further on
+                    if tag == 0xfffe && body.len() >= 26 {
+                        tag = u16_at(body, 24);
+                    }
+                    format = Some((tag, u16_at(body, 2), u32_at(body, 4), u16_at(body, 14)));
+                }
+                b"data" => samples = Some(body),
+                _ => {}
+            }
+            // Chunks are padded to an even size
+            at += 8 + size + size % 2;
+        }
+        let (tag, channels, sample_rate, bits) = format.ok_or_else(|| invalid("no format"))?;
+        let samples = samples.ok_or_else(|| invalid("no samples"))?;
+        let width = bits as usize / 8;
+        let sample = |s: &[u8]| -> Option<f32> {
+            match (tag, bits) {
+                (1, 8) => Some((s[0] as f32 - 128.0) / 128.0),
+                (1, 16) => Some(i16::from_le_bytes([s[0], s[1]]) as f32 / 32768.0),
+                (1, 24) => Some(i32::from_le_bytes([0, s[0], s[1], s[2]]) as f32 / 2147483648.0),
+                (1, 32) => Some(i32::from_le_bytes([s[0], s[1], s[2], s[3]]) as f32 / 2147483648.0),
+                (3, 32) => Some(f32::from_le_bytes([s[0], s[1], s[2], s[3]])),
+                _ => None,
+            }
+        };
+        if channels == 0 || sample(&[0; 4]).is_none() {
+            return Err(invalid("unsupported sample format"));
+        }
+
+        let frame = width * channels as usize;
+        let samples = samples
+            .chunks_exact(frame)
+            .map(|f| {
+                let sum: f32 = f.chunks_exact(width).filter_map(sample).sum();
+                (sum / channels as f32).clamp(-1.0, 1.0)
+            })
+            .collect();
+        Ok(Wav {
+            sample_rate,
+            samples,
+        })
+    }
+
+    pub fn load(filename: &str) -> Result<Wav, Error> {
+        Wav::parse(&std::fs::read(filename)?)
+    }
+
+    pub fn seconds(&self) -> f32 {
+        self.samples.len() as f32 / self.sample_rate.max(1) as f32
+    }
+}
+
+// The loudness over time as one zigzag stroke of vertical bars, mirrored
+// around the middle
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Waveform {
+    pub columns: usize,
+}
+
+impl Waveform {
+    pub fn new(columns: usize) -> Self {
+        Waveform { columns }
+    }
+
+    // Loudest sample in every column, 0..=1
+    pub fn peaks(&self, wav: &Wav) -> Vec<f32> {
+        let columns = self.columns.max(1);
+        let n = wav.samples.len();
+        (0..columns)
+            .map(|c| {
+                let (from, to) = (
+                    c * n / columns,
+                    ((c + 1) * n / columns).max(c * n / columns + 1),
+                );
+                wav.samples[from.min(n)..to.min(n)]
+                    .iter()
+                    .fold(0.0, |peak, s| f32::max(peak, s.abs()))
+            })
+            .collect()
+    }
+
+    // The stroke, stretched over min..max with the loudest peak reaching
+    // the edges
+    pub fn paths(&self, wav: &Wav, min: (f32, f32), max: (f32, f32)) -> Vec<Path> {
+        let peaks = self.peaks(wav);
+        let loudest = peaks.iter().copied().fold(0.0, f32::max);
+        if loudest <= 0.0 {
+            return vec![vec![
+                (min.0, (min.1 + max.1) / 2.0),
+                (max.0, (min.1 + max.1) / 2.0),
+            ]];
+        }
+        let middle = (min.1 + max.1) / 2.0;
+        let half = (max.1 - min.1) / 2.0;
+        let step = (max.0 - min.0) / peaks.len() as f32;
+        let mut stroke = Vec::new();
+        for (i, peak) in peaks.iter().enumerate() {
+            let x = min.0 + (i as f32 + 0.5) * step;
+            let a = peak / loudest * half;
+            // Up and down alternately so the bars join into one stroke
+            let (from, to) = if i % 2 == 0 { (-a, a) } else { (a, -a) };
+            stroke.push((x, middle + from));
+            stroke.push((x, middle + to));
+        }
+        vec![stroke]
+    }
+
+    pub fn draw(&self, printer: &mut Printer, wav: &Wav, min: (f32, f32), max: (f32, f32)) {
+        for path in self.paths(wav, min, max) {
+            printer.draw_polyline(&path);
+        }
+    }
+}
+
+// In-place radix-2 FFT, the length has to be a power of two
+fn fft(re: &mut [f32], im: &mut [f32]) {
+    let n = re.len();
+    let mut j = 0;
+    for i in 1..n {
+        let mut bit = n >> 1;
+        while j & bit != 0 {
+            j ^= bit;
+            bit >>= 1;
+        }
+        j |= bit;
+        if i < j {
+            re.swap(i, j);
+            im.swap(i, j);
+        }
+    }
+    let mut size = 2;
+    while size <= n {
+        let angle = -2.0 * PI / size as f32;
+        for start in (0..n).step_by(size) {
+            for k in 0..size / 2 {
+                let (wr, wi) = ((angle * k as f32).cos(), (angle * k as f32).sin());
+                let (a, b) = (start + k, start + k + size / 2);
+                let (tr, ti) = (re[b] * wr - im[b] * wi, re[b] * wi + im[b] * wr);
+                re[b] = re[a] - tr;
+                im[b] = im[a] - ti;
+                re[a] += tr;
+                im[a] += ti;
+            }
+        }
+        size *= 2;
+    }
+}
+
+// Spectrogram as stacked ridges, one per slice of time from the top down,
+// like the cover of Unknown Pleasures. Every ridge hides what's behind it.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Ridges {
+    pub rows: usize,
+    // Points along every ridge, from low to high frequencies
+    pub bins: usize,
+    // How high the loudest peak rises, in distances between ridges
+    pub height: f32,
+}
+
+impl Ridges {
+    pub fn new(rows: usize) -> Self {
+        Ridges {
+            rows,
+            bins: 64,
+            height: 4.0,
+        }
+    }
+
+    // Magnitudes of every row, 0..=1 on a log scale
+    pub fn spectrum(&self, wav: &Wav) -> Vec<Vec<f32>> {
+        let bins = self.bins.max(2);
+        let size = (2 * bins).next_power_of_two();
+        let hann: Vec<f32> = (0..size)
+            .map(|i| 0.5 - 0.5 * (2.0 * PI * i as f32 / size as f32).cos())
+            .collect();
+        let n = wav.samples.len();
+        let rows: Vec<Vec<f32>> = (0..self.rows.max(1))
+            .map(|r| {
+                let center = (r * 2 + 1) * n / (2 * self.rows.max(1));
+                let start = center.saturating_sub(size / 2);
+                let mut re: Vec<f32> = (0..size)
+                    .map(|i| wav.samples.get(start + i).copied().unwrap_or(0.0) * hann[i])
+                    .collect();
+                let mut im = vec![0.0; size];
+                fft(&mut re, &mut im);
+                // Spread the positive frequencies over the bins
+                let half = size / 2;
+                (0..bins)
+                    .map(|b| {
+                        let (from, to) = (
+                            b * half / bins,
+                            ((b + 1) * half / bins).max(b * half / bins + 1),
+                        );
+                        let sum: f32 = (from..to)
+                            .map(|k| (re[k] * re[k] + im[k] * im[k]).sqrt())
+                            .sum();
+                        (1.0 + sum / (to - from) as f32).ln()
+                    })
+                    .collect()
+            })
+            .collect();
+        let loudest = rows.iter().flatten().copied().fold(0.0, f32::max);
+        if loudest <= 0.0 {
+            return rows;
+        }
+        rows.into_iter()
+            .map(|row| row.into_iter().map(|m| m / loudest).collect())
+            .collect()
+    }
+
+    // Visible parts of the ridges over min..max, the first row at the top.
+    // Peaks of the top row may rise above max.1.
+    pub fn paths(&self, wav: &Wav, min: (f32, f32), max: (f32, f32)) -> Vec<Path> {
+        let spectrum = self.spectrum(wav);
+        let rows = spectrum.len();
+        let gap = (max.1 - min.1) / rows as f32;
+        let bins = spectrum[0].len();
+        let x = |b: usize| min.0 + (max.0 - min.0) * b as f32 / (bins - 1) as f32;
+
+        // From the front, so the horizon hides what's behind
+        let mut horizon = vec![f32::NEG_INFINITY; bins];
+        let mut paths = Vec::new();
+        for (r, row) in spectrum.iter().enumerate().rev() {
+            let base = max.1 - (r as f32 + 1.0) * gap;
+            // Taper towards the sides so ridges rise out of flat ground
+            let y: Vec<f32> = row
+                .iter()
+                .enumerate()
+                .map(|(b, m)| {
+                    let edge = (PI * b as f32 / (bins - 1) as f32).sin();
+                    base + m * edge * self.height * gap
+                })
+                .collect();
+
+            let mut current: Path = Vec::new();
+            for b in 0..bins {
+                let visible = y[b] > horizon[b] + 1e-4;
+                if visible {
+                    if current.is_empty() && b > 0 {
+                        // Start where the previous segment comes out from behind
+                        current.push(emerge(
+                            (x(b - 1), y[b - 1]),
+                            (x(b), y[b]),
+                            horizon[b - 1],
+                            horizon[b],
+                        ));
+                    }
+                    current.push((x(b), y[b]));
+                } else if !current.is_empty() {
+                    current.push(emerge(
+                        (x(b - 1), y[b - 1]),
+                        (x(b), y[b]),
+                        horizon[b - 1],
+                        horizon[b],
+                    ));
+                    if current.len() > 1 {
+                        paths.push(std::mem::take(&mut current));
+                    }
+                    current.clear();
+                }
+            }
+            if current.len() > 1 {
+                paths.push(current);
+            }
+            for b in 0..bins {
+                horizon[b] = horizon[b].max(y[b]);
+            }
+        }
+        paths
+    }
+
+    pub fn draw(&self, printer: &mut Printer, wav: &Wav, min: (f32, f32), max: (f32, f32)) {
+        for path in self.paths(wav, min, max) {
+            printer.draw_polyline(&path);
+        }
+    }
+}
+
+// Where the segment a -> b crosses the horizon between h0 and h1
+fn emerge(a: (f32, f32), b: (f32, f32), h0: f32, h1: f32) -> (f32, f32) {
+    if !h0.is_finite() || !h1.is_finite() {
+        return if h0.is_finite() { b } else { a };
+    }
+    let (d0, d1) = (a.1 - h0, b.1 - h1);
+    let t = if (d0 - d1).abs() <= f32::EPSILON {
+        0.5
+    } else {
+        (d0 / (d0 - d1)).clamp(0.0, 1.0)
+    };
+    path::lerp(a, b, t)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 16 bit mono WAV of the samples
+    fn wav_file(rate: u32, samples: &[f32]) -> Vec<u8> {
+        let data: Vec<u8> = samples
+            .iter()
+            .flat_map(|s| ((s * 32767.0) as i16).to_le_bytes())
+            .collect();
+        let mut file = b"RIFF".to_vec();
+        file.extend((36 + data.len() as u32).to_le_bytes());
+        file.extend(b"WAVEfmt ");
+        file.extend(16u32.to_le_bytes());
+        file.extend(1u16.to_le_bytes());
+        file.extend(1u16.to_le_bytes());
+        file.extend(rate.to_le_bytes());
+        file.extend((rate * 2).to_le_bytes());
+        file.extend(2u16.to_le_bytes());
+        file.extend(16u16.to_le_bytes());
+        file.extend(b"data");
+        file.extend((data.len() as u32).to_le_bytes());
+        file.extend(data);
+        file
+    }
+
+    fn tone(rate: u32, frequency: f32, seconds: f32, volume: impl Fn(f32) -> f32) -> Vec<f32> {
+        (0..(rate as f32 * seconds) as usize)
+            .map(|i| {
+                let t = i as f32 / rate as f32;
+                volume(t) * (2.0 * PI * frequency * t).sin()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn reads_wav() {
+        let samples = [0.0, 0.5, -0.5, 1.0];
+        let wav = Wav::parse(&wav_file(8000, &samples)).unwrap();
+        assert_eq!(wav.sample_rate, 8000);
+        for (a, b) in wav.samples.iter().zip(samples) {
+            assert!((a - b).abs() < 1e-3, "{} {}", a, b);
+        }
+        assert!(Wav::parse(b"RIFF....WAVE").is_err());
+        assert!(Wav::parse(b"not a wav file").is_err());
+    }
+
+    #[test]
+    fn waveform_follows_loudness() {
+        // Fades in over a second
+        let wav = Wav {
+            sample_rate: 8000,
+            samples: tone(8000, 440.0, 1.0, |t| t),
+        };
+        let peaks = Waveform::new(10).peaks(&wav);
+        assert!(peaks.windows(2).all(|w| w[0] < w[1]), "{:?}", peaks);
+
+        let paths = Waveform::new(10).paths(&wav, (0.0, 0.0), (100.0, 20.0));
+        assert_eq!(paths.len(), 1);
+        assert_eq!(paths[0].len(), 20);
+        let top = paths[0].iter().map(|p| p.1).fold(0.0, f32::max);
+        assert!((top - 20.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn ridges_show_the_pitch() {
+        // A low tone, then a high one
+        let rate = 8000;
+        let mut samples = tone(rate, 500.0, 1.0, |_| 0.8);
+        samples.extend(tone(rate, 3000.0, 1.0, |_| 0.8));
+        let wav = Wav {
+            sample_rate: rate,
+            samples,
+        };
+        let ridges = Ridges::new(4);
+        let spectrum = ridges.spectrum(&wav);
+        let loudest = |row: &[f32]| {
+            (0..row.len())
+                .max_by(|&a, &b| row[a].total_cmp(&row[b]))
+                .unwrap()
+        };
+        // 64 bins up to 4kHz
+        assert_eq!(loudest(&spectrum[0]), 8);
+        assert_eq!(loudest(&spectrum[3]), 48);
+
+        // Nothing is drawn below a ridge in front of it
+        let paths = ridges.paths(&wav, (0.0, 0.0), (100.0, 100.0));
+        assert!(!paths.is_empty());
+        assert!(
+            paths
+                .iter()
+                .flatten()
+                .all(|p| p.0 >= -1e-3 && p.0 <= 100.0 + 1e-3)
+        );
+        let front = paths.iter().flatten().filter(|p| p.1 < 25.0).count();
+        assert!(front > 0);
+    }
+}
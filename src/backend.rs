@@ -0,0 +1,157 @@
+use crate::Code;
+use crate::sim::Machine;
+
+// Turns the codes of a program into the language of the machine, one code
+// at a time. Codes the language has nothing for are rendered as "".
+pub trait Backend {
+    // Set up before the first code
+    fn start(&mut self) -> String {
+        String::new()
+    }
+
+    fn render(&mut self, code: &Code) -> String;
+
+    // Wraps up after the last code
+    fn finish(&mut self) -> String {
+        String::new()
+    }
+}
+
+// G-code, as Code is displayed
+#[derive(Debug, Default, Copy, Clone)]
+pub struct GCode;
+
+impl Backend for GCode {
+    fn render(&mut self, code: &Code) -> String {
+        code.to_string()
+    }
+}
+
+// HP-GL plotter units per mm
+pub const HPGL_UNITS: f32 = 40.0;
+
+// HP-GL for pen plotters like the HP 7475A and vinyl cutters. HP-GL has no
+// heights, so the moves are replayed and the pen put down (PD) whenever it
+// reaches the paper and lifted (PU) when it leaves it. Curves are drawn as
+// short lines, and feeds, pauses and comments are left out.
+#[derive(Debug, Clone)]
+pub struct Hpgl {
+    machine: Machine,
+    z_draw: f32,
+    down: bool,
+    // The pen to select, 1 to 6 on the HP 7475A
+    pub pen: u32,
+}
+
+impl Hpgl {
+    // Starting at `start` like the G-code would, with the pen counted as
+    // down at or below `z_draw`
+    pub fn new(start: (f32, f32, f32), z_draw: f32) -> Self {
+        Hpgl {
+            machine: Machine::new(start, z_draw),
+            z_draw,
+            down: false,
+            pen: 1,
+        }
+    }
+
+    fn units(v: f32) -> i32 {
+        (v * HPGL_UNITS).round() as i32
+    }
+}
+
+impl Backend for Hpgl {
+    fn start(&mut self) -> String {
+        format!("IN;SP{};PU;", self.pen)
+    }
+
+    fn render(&mut self, code: &Code) -> String {
+        let mut out = String::new();
+        let mut points: Vec<String> = Vec::new();
+        for s in self.machine.step(code) {
+            let down = s.to.2 <= self.z_draw + 1e-4;
+            if down != self.down {
+                if !points.is_empty() {
+                    out += &format!("PA{};", points.join(","));
+                    points.clear();
+                }
+                out += if down { "PD;" } else { "PU;" };
+                self.down = down;
+            }
+            if s.from_xy() != s.to_xy() {
+                points.push(format!("{},{}", Self::units(s.to.0), Self::units(s.to.1)));
+            }
+        }
+        if !points.is_empty() {
+            out += &format!("PA{};", points.join(","));
+        }
+        out
+    }
+
+    fn finish(&mut self) -> String {
+        self.down = false;
+        "PU;SP0;".to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::curve::CircularArc;
+    use crate::tests::test_config;
+    use crate::{Point, Printer};
+
+    #[test]
+    fn hpgl() {
+        let mut hpgl = Hpgl::new((0.0, 0.0, 6.5), 4.0);
+        assert_eq!(hpgl.start(), "IN;SP1;PU;");
+        let mut render = |code: Code| hpgl.render(&code);
+        assert_eq!(
+            render(Code::Move(Point::xy(10.0, 5.0), 1000.0)),
+            "PA400,200;"
+        );
+        assert_eq!(render(Code::Move(Point::z(4.0), 500.0)), "PD;");
+        assert_eq!(
+            render(Code::Move(Point::xy(10.5, 5.0), 1000.0)),
+            "PA420,200;"
+        );
+        assert_eq!(render(Code::Comment("Pen up".to_string())), "");
+        let arc = CircularArc::new((10.5, 10.0), 5.0, -std::f32::consts::FRAC_PI_2, 1.0);
+        let drawn = render(Code::Arc(arc, 1000.0));
+        assert!(drawn.starts_with("PA") && drawn.matches(',').count() > 2);
+        assert_eq!(render(Code::Move(Point::z(6.5), 800.0)), "PU;");
+        // Pens lowered by a servo go down as well
+        assert_eq!(render(Code::Pen(true, "M280 P0 S30".to_string())), "PD;");
+        assert_eq!(hpgl.finish(), "PU;SP0;");
+
+        assert_eq!(GCode.render(&Code::Beep), "M300 S440 P200");
+    }
+
+    #[test]
+    fn hpgl_output() {
+        let mut printer = Printer::new(test_config());
+        printer.draw_polyline(&[(0.0, 0.0), (10.0, 0.0), (10.0, 10.0)]);
+        printer.draw_point(20.0, 5.0);
+        let mut hpgl = Hpgl::new(printer.origin(), printer.pen_z());
+        let mut out = Vec::new();
+        printer.write_backend(&mut out, &mut hpgl).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(lines.first(), Some(&"IN;SP1;PU;"));
+        assert_eq!(lines.last(), Some(&"PU;SP0;"));
+        let drawn: Vec<&str> = lines
+            .iter()
+            .copied()
+            .filter(|l| l.contains("PD;") || l.starts_with("PA"))
+            .collect();
+        assert_eq!(
+            drawn,
+            ["PD;", "PA400,0;", "PA400,400;", "PA800,200;", "PD;"]
+        );
+
+        let mut gcode = Vec::new();
+        printer.write_backend(&mut gcode, &mut GCode).unwrap();
+        let gcode = String::from_utf8(gcode).unwrap();
+        assert!(gcode.contains("G1 X10.0 Y10.0 F1000.0"));
+    }
+}
@@ -0,0 +1,285 @@
+use std::f32::consts::PI;
+
+use crate::Printer;
+use crate::path::Path;
+use crate::transform::AffineTransform;
+
+// Pattern running along the edges of the paper
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Style {
+    // `count` plain rectangles `gap` mm apart
+    Rules {
+        count: usize,
+        gap: f32,
+    },
+    // Square spirals standing on the outer rule of a `width` mm band
+    GreekKey {
+        width: f32,
+    },
+    // `strands` interlaced waves in a `width` mm band, roughly `wavelength`
+    // mm long each
+    Guilloche {
+        width: f32,
+        strands: usize,
+        wavelength: f32,
+    },
+}
+
+// What fills the squares where the bands of two sides meet
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Ornament {
+    None,
+    // Ring of overlapping circles
+    Rosette,
+    // A square with a diamond inside
+    Lozenge,
+}
+
+// Frame around the paper, fitted to the area inside the margins. The
+// patterns stretch a little so that every side ends on a whole repeat.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Border {
+    pub style: Style,
+    // Distance from the edge of the paper to the outside of the border, in mm
+    pub margin: f32,
+    pub corners: Ornament,
+}
+
+fn circle(center: (f32, f32), radius: f32) -> Path {
+    (0..=32)
+        .map(|i| {
+            let a = i as f32 / 32.0 * 2.0 * PI;
+            (center.0 + radius * a.cos(), center.1 + radius * a.sin())
+        })
+        .collect()
+}
+
+fn rect(min: (f32, f32), max: (f32, f32)) -> Path {
+    vec![min, (max.0, min.1), max, (min.0, max.1), min]
+}
+
+// Repeats that fit `length` best when each is about `period` long
+fn repeats(length: f32, period: f32) -> usize {
+    ((length / period.max(1e-3)).round() as usize).max(1)
+}
+
+impl Border {
+    pub fn new(style: Style) -> Self {
+        Border {
+            style,
+            margin: 10.0,
+            corners: Ornament::Rosette,
+        }
+    }
+
+    // Distance from the outside to the inside of the border, in mm
+    pub fn width(&self) -> f32 {
+        match self.style {
+            Style::Rules { count, gap } => count.saturating_sub(1) as f32 * gap,
+            Style::GreekKey { width } | Style::Guilloche { width, .. } => width,
+        }
+    }
+
+    // Area left inside the border on paper of `size`, for the artwork
+    pub fn inner(&self, size: (f32, f32)) -> ((f32, f32), (f32, f32)) {
+        let inset = self.margin + self.width();
+        ((inset, inset), (size.0 - inset, size.1 - inset))
+    }
+
+    // Pattern along one side in local coordinates: x runs along the side
+    // from 0 to `length`, y from the outside (0) to the inside (width)
+    fn side(&self, length: f32) -> Vec<Path> {
+        match self.style {
+            Style::Rules { .. } => Vec::new(),
+            Style::GreekKey { width } => {
+                // Spirals on a grid of a fifth of the band, one every 4 cells
+                let g = width / 5.0;
+                let n = repeats(length, 4.0 * g);
+                let gx = length / (4 * n) as f32;
+                let spiral = [
+                    (0, 0),
+                    (0, 4),
+                    (3, 4),
+                    (3, 1),
+                    (1, 1),
+                    (1, 3),
+                    (2, 3),
+                    (2, 2),
+                ];
+                (0..n)
+                    .map(|i| {
+                        let x0 = (4 * i) as f32 * gx + gx / 2.0;
+                        spiral
+                            .iter()
+                            .map(|&(x, y)| (x0 + x as f32 * gx, y as f32 * g))
+                            .collect()
+                    })
+                    .collect()
+            }
+            Style::Guilloche {
+                width,
+                strands,
+                wavelength,
+            } => {
+                let n = repeats(length, wavelength);
+                let steps = n * 24;
+                (0..strands)
+                    .map(|k| {
+                        let phase = 2.0 * PI * k as f32 / strands as f32;
+                        (0..=steps)
+                            .map(|i| {
+                                let x = length * i as f32 / steps as f32;
+                                let a = 2.0 * PI * n as f32 * x / length + phase;
+                                (x, width / 2.0 + 0.45 * width * a.sin())
+                            })
+                            .collect()
+                    })
+                    .collect()
+            }
+        }
+    }
+
+    // Ornament in a `w` mm square at the origin
+    fn ornament(&self, w: f32) -> Vec<Path> {
+        let c = (w / 2.0, w / 2.0);
+        match self.corners {
+            Ornament::None => Vec::new(),
+            Ornament::Rosette => {
+                let r = w / 4.5;
+                let mut paths: Vec<Path> = (0..6)
+                    .map(|i| {
+                        let a = i as f32 / 6.0 * 2.0 * PI;
+                        circle((c.0 + r * a.cos(), c.1 + r * a.sin()), r)
+                    })
+                    .collect();
+                paths.push(circle(c, r));
+                paths
+            }
+            Ornament::Lozenge => {
+                let i = w * 0.15;
+                vec![
+                    rect((i, i), (w - i, w - i)),
+                    vec![(c.0, i), (w - i, c.1), (c.0, w - i), (i, c.1), (c.0, i)],
+                ]
+            }
+        }
+    }
+
+    // The border on paper of `size` mm, in mm from its bottom left corner
+    pub fn paths(&self, size: (f32, f32)) -> Vec<Path> {
+        let m = self.margin;
+        let (x1, y1) = (size.0 - m, size.1 - m);
+        let w = self.width();
+
+        if let Style::Rules { count, gap } = self.style {
+            return (0..count)
+                .map(|i| {
+                    let inset = i as f32 * gap;
+                    rect((m + inset, m + inset), (x1 - inset, y1 - inset))
+                })
+                .collect();
+        }
+
+        let mut paths = vec![
+            rect((m, m), (x1, y1)),
+            rect((m + w, m + w), (x1 - w, y1 - w)),
+        ];
+        // Side lengths between the corner squares, and where each side starts
+        // with its direction: along the bottom, up the right, back along the
+        // top and down the left, always with the inside on the left
+        let (across, up) = (x1 - m - 2.0 * w, y1 - m - 2.0 * w);
+        let sides = [
+            (across, (m + w, m), 0.0),
+            (up, (x1, m + w), PI / 2.0),
+            (across, (x1 - w, y1), PI),
+            (up, (m, y1 - w), -PI / 2.0),
+        ];
+        for (length, start, angle) in sides {
+            let place =
+                AffineTransform::rotate(angle).then(&AffineTransform::translate(start.0, start.1));
+            paths.extend(self.side(length).iter().map(|p| place.apply_path(p)));
+        }
+
+        for corner in [(m, m), (x1 - w, m), (x1 - w, y1 - w), (m, y1 - w)] {
+            let place = AffineTransform::translate(corner.0, corner.1);
+            paths.extend(self.ornament(w).iter().map(|p| place.apply_path(p)));
+        }
+        paths
+    }
+
+    // Draws the border around the printer's whole drawing area
+    pub fn draw(&self, printer: &mut Printer) {
+        let paths = self.paths((printer.width, printer.height));
+        printer.draw_bed_paths(&paths, Some("border"));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn inside(paths: &[Path], min: (f32, f32), max: (f32, f32)) -> bool {
+        paths.iter().flatten().all(|&(x, y)| {
+            x >= min.0 - 1e-3 && x <= max.0 + 1e-3 && y >= min.1 - 1e-3 && y <= max.1 + 1e-3
+        })
+    }
+
+    #[test]
+    fn fits_inside_the_margins() {
+        let size = (297.0, 210.0);
+        for style in [
+            Style::Rules { count: 3, gap: 2.0 },
+            Style::GreekKey { width: 10.0 },
+            Style::Guilloche {
+                width: 8.0,
+                strands: 3,
+                wavelength: 20.0,
+            },
+        ] {
+            for corners in [Ornament::None, Ornament::Rosette, Ornament::Lozenge] {
+                let border = Border {
+                    corners,
+                    ..Border::new(style)
+                };
+                let paths = border.paths(size);
+                assert!(inside(&paths, (10.0, 10.0), (287.0, 200.0)), "{:?}", border);
+            }
+        }
+
+        let border = Border::new(Style::Rules { count: 3, gap: 2.0 });
+        assert_eq!(border.paths(size).len(), 3);
+        assert_eq!(border.inner(size), ((14.0, 14.0), (283.0, 196.0)));
+    }
+
+    #[test]
+    fn whole_repeats_per_side() {
+        // 297 - 20 margin - 20 corners = 257mm of spirals 8mm apart
+        let border = Border {
+            corners: Ornament::None,
+            ..Border::new(Style::GreekKey { width: 10.0 })
+        };
+        let spirals = border.side(257.0);
+        assert_eq!(spirals.len(), 32);
+        // As much room before the first as after the last
+        let first = spirals[0][0].0;
+        let last = spirals
+            .last()
+            .unwrap()
+            .iter()
+            .map(|p| p.0)
+            .fold(0.0, f32::max);
+        assert!((first - (257.0 - last)).abs() < 1e-3);
+
+        // Guilloche strands end at the height they start at, so both ends
+        // of a side look the same next to the corners
+        let border = Border::new(Style::Guilloche {
+            width: 8.0,
+            strands: 2,
+            wavelength: 20.0,
+        });
+        for strand in border.side(100.0) {
+            let (a, b) = (strand[0], *strand.last().unwrap());
+            assert!((a.1 - b.1).abs() < 1e-3);
+        }
+    }
+}
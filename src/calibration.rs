@@ -0,0 +1,148 @@
+use std::f32::consts::PI;
+
+use crate::path::Path;
+use crate::{Feed, Overrides, Printer};
+
+// Test plot for dialing in a pen: the same motif is drawn once for every
+// combination of feed and plunge depth, in a grid with one column per feed
+// and one row per depth. Every cell is labeled in a tiny font, the labels
+// themselves are drawn with the configured settings.
+#[derive(Debug, Clone)]
+pub struct FeedSweep {
+    // Feeds for the pen-down moves
+    pub feeds: Vec<Feed>,
+    // Pen-down heights
+    pub depths: Vec<f32>,
+    // Bottom left corner of the grid
+    pub origin: (f32, f32),
+    // Size of a single cell in mm, the motif takes up the top part of it
+    pub cell: f32,
+    // Height of the label capitals in mm
+    pub label_height: f32,
+}
+
+impl FeedSweep {
+    pub fn new(feeds: &[Feed], depths: &[f32]) -> Self {
+        FeedSweep {
+            feeds: feeds.to_vec(),
+            depths: depths.to_vec(),
+            origin: (0.0, 0.0),
+            cell: 20.0,
+            label_height: 1.5,
+        }
+    }
+
+    // Circle, zigzag and a dot in a `size` mm square at `at`
+    pub fn motif(at: (f32, f32), size: f32) -> Vec<Path> {
+        let p = |x: f32, y: f32| (at.0 + x * size, at.1 + y * size);
+
+        let circle = (0..=24)
+            .map(|i| {
+                let a = i as f32 / 24.0 * 2.0 * PI;
+                p(0.25 + 0.25 * a.cos(), 0.5 + 0.25 * a.sin())
+            })
+            .collect();
+        let zigzag = [0.6, 0.7, 0.8, 0.9, 1.0]
+            .iter()
+            .enumerate()
+            .map(|(i, &x)| p(x, if i % 2 == 0 { 0.0 } else { 1.0 }))
+            .collect();
+
+        vec![circle, zigzag, vec![p(0.25, 0.5)]]
+    }
+
+    // Text next to the motif drawn with `feed` and `depth`
+    pub fn label(feed: Feed, depth: f32) -> String {
+        format!("{} Z{:.2}", feed.word(0), depth)
+    }
+
+    pub fn draw(&self, printer: &mut Printer) {
+        let margin = self.cell * 0.1;
+        let size = self.cell * 0.6;
+
+        for (row, &depth) in self.depths.iter().enumerate() {
+            for (col, &feed) in self.feeds.iter().enumerate() {
+                let x = self.origin.0 + col as f32 * self.cell + margin;
+                let y = self.origin.1 + row as f32 * self.cell + margin;
+
+                printer.set_overrides(Overrides {
+                    draw_speed: Some(feed),
+                    z_draw: Some(depth),
+                    dwell: None,
+                    blending: None,
+                    travel_speed: None,
+                    acceleration: None,
+                });
+                for path in Self::motif((x, y + self.label_height * 2.0), size) {
+                    printer.draw_polyline(&path);
+                }
+
+                printer.set_overrides(Overrides::default());
+                printer.draw_text(x, y, self.label_height, &Self::label(feed, depth));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{assert_within, test_config};
+    use crate::text;
+
+    #[test]
+    fn labels() {
+        assert_eq!(
+            FeedSweep::label(Feed::mm_per_min(1200.0), 3.75),
+            "F1200 Z3.75"
+        );
+        // The default cell leaves enough room for the label
+        let sweep = FeedSweep::new(&[Feed::mm_per_min(1200.0)], &[3.75]);
+        assert!(
+            text::width(
+                &FeedSweep::label(Feed::mm_per_min(1200.0), 3.75),
+                sweep.label_height
+            ) < sweep.cell
+        );
+    }
+
+    #[test]
+    fn motif_fits_its_square() {
+        for path in FeedSweep::motif((10.0, 20.0), 12.0) {
+            for (x, y) in path {
+                assert!((10.0 - 1e-4..=22.0 + 1e-4).contains(&x));
+                assert!((20.0 - 1e-4..=32.0 + 1e-4).contains(&y));
+            }
+        }
+    }
+
+    #[test]
+    fn feed_sweep_overrides() {
+        let mut printer = Printer::new(test_config());
+        FeedSweep::new(
+            &[Feed::mm_per_min(600.0), Feed::mm_per_min(1500.0)],
+            &[4.2, 3.8],
+        )
+        .draw(&mut printer);
+
+        let code: Vec<String> = printer.codes().iter().map(|c| c.to_string()).collect();
+        for line in ["G1 Z4.2 F500.0", "G1 Z3.8 F500.0", "G1 Z4.0 F500.0"] {
+            assert!(code.iter().any(|l| l == line), "missing {}", line);
+        }
+        assert!(code.iter().any(|l| l.ends_with("F600.0")));
+        assert!(code.iter().any(|l| l.ends_with("F1500.0")));
+
+        // Strokes above the configured z_draw still count as drawn
+        let mut shallow = Printer::new(test_config());
+        shallow.set_overrides(Overrides {
+            draw_speed: None,
+            z_draw: Some(4.5),
+            dwell: None,
+            blending: None,
+            travel_speed: None,
+            acceleration: None,
+        });
+        shallow.draw_polyline(&[(0.0, 0.0), (10.0, 0.0)]);
+        assert_within(shallow.stats().total.drawn, 10.0, 1e-4);
+    }
+}
@@ -0,0 +1,243 @@
+use std::f32::consts::PI;
+
+use crate::curve;
+use crate::path::{self, Path};
+
+// The area that can be drawn on, e.g. round paper or a pre-cut shape. Strokes
+// are clipped against it where they leave the shape.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Canvas {
+    Rect { min: (f32, f32), max: (f32, f32) },
+    Circle { center: (f32, f32), radius: f32 },
+    // Any simple polygon, closing edge implied. Holes follow the even-odd
+    // rule when the outline crosses itself.
+    Polygon(Path),
+}
+
+// Where the segment a -> b crosses the edge a0 -> a1, as a fraction of a -> b
+fn crossing(a: (f32, f32), b: (f32, f32), e0: (f32, f32), e1: (f32, f32)) -> Option<f32> {
+    let d = (b.0 - a.0, b.1 - a.1);
+    let e = (e1.0 - e0.0, e1.1 - e0.1);
+    let denom = d.0 * e.1 - d.1 * e.0;
+    if denom.abs() <= f32::EPSILON {
+        return None;
+    }
+    let w = (e0.0 - a.0, e0.1 - a.1);
+    let t = (w.0 * e.1 - w.1 * e.0) / denom;
+    let u = (w.0 * d.1 - w.1 * d.0) / denom;
+    ((0.0..=1.0).contains(&t) && (0.0..=1.0).contains(&u)).then_some(t)
+}
+
+impl Canvas {
+    pub fn circle(center: (f32, f32), radius: f32) -> Self {
+        Canvas::Circle { center, radius }
+    }
+
+    pub fn contains(&self, p: (f32, f32)) -> bool {
+        match self {
+            Canvas::Rect { min, max } => path::inside_rect(p, *min, *max),
+            Canvas::Circle { center, radius } => path::dist(p, *center) <= *radius,
+            Canvas::Polygon(outline) => {
+                // Even-odd ray casting to the right of p
+                let mut inside = false;
+                let n = outline.len();
+                for i in 0..n {
+                    let (a, b) = (outline[i], outline[(i + 1) % n]);
+                    if (a.1 > p.1) != (b.1 > p.1) {
+                        let x = a.0 + (p.1 - a.1) / (b.1 - a.1) * (b.0 - a.0);
+                        if p.0 < x {
+                            inside = !inside;
+                        }
+                    }
+                }
+                inside
+            }
+        }
+    }
+
+    // Fractions of a -> b where it crosses the edge of the canvas
+    fn crossings(&self, a: (f32, f32), b: (f32, f32)) -> Vec<f32> {
+        match self {
+            Canvas::Rect { min, max } => {
+                let corners = [*min, (max.0, min.1), *max, (min.0, max.1)];
+                Canvas::Polygon(corners.to_vec()).crossings(a, b)
+            }
+            Canvas::Circle { center, radius } => {
+                // |a + t (b - a) - center| = radius
+                let d = (b.0 - a.0, b.1 - a.1);
+                let f = (a.0 - center.0, a.1 - center.1);
+                let qa = d.0 * d.0 + d.1 * d.1;
+                let qb = 2.0 * (f.0 * d.0 + f.1 * d.1);
+                let qc = f.0 * f.0 + f.1 * f.1 - radius * radius;
+                let disc = qb * qb - 4.0 * qa * qc;
+                if qa <= f32::EPSILON || disc < 0.0 {
+                    return Vec::new();
+                }
+                let root = disc.sqrt();
+                [(-qb - root) / (2.0 * qa), (-qb + root) / (2.0 * qa)]
+                    .into_iter()
+                    .filter(|t| (0.0..=1.0).contains(t))
+                    .collect()
+            }
+            Canvas::Polygon(outline) => {
+                let n = outline.len();
+                (0..n)
+                    .filter_map(|i| crossing(a, b, outline[i], outline[(i + 1) % n]))
+                    .collect()
+            }
+        }
+    }
+
+    // Pieces of the path inside the canvas. The path is split wherever it
+    // leaves the canvas, a dot is kept if it's inside.
+    pub fn clip(&self, input: &[(f32, f32)]) -> Vec<Path> {
+        if let Canvas::Rect { min, max } = self {
+            return path::clip_rect(input, *min, *max);
+        }
+        if input.len() == 1 {
+            return if self.contains(input[0]) {
+                vec![input.to_vec()]
+            } else {
+                Vec::new()
+            };
+        }
+
+        let mut pieces: Vec<Path> = Vec::new();
+        let mut current: Path = Vec::new();
+        for w in input.windows(2) {
+            let (a, b) = (w[0], w[1]);
+            let mut ts = self.crossings(a, b);
+            ts.extend([0.0, 1.0]);
+            ts.sort_by(f32::total_cmp);
+
+            for t in ts.windows(2) {
+                if t[1] - t[0] <= f32::EPSILON {
+                    continue;
+                }
+                let (p, q) = (path::lerp(a, b, t[0]), path::lerp(a, b, t[1]));
+                if self.contains(path::lerp(a, b, (t[0] + t[1]) / 2.0)) {
+                    if current.last() != Some(&p) {
+                        if current.len() > 1 {
+                            pieces.push(std::mem::take(&mut current));
+                        }
+                        current = vec![p];
+                    }
+                    current.push(q);
+                } else if current.len() > 1 {
+                    pieces.push(std::mem::take(&mut current));
+                } else {
+                    current.clear();
+                }
+            }
+        }
+        if current.len() > 1 {
+            pieces.push(current);
+        }
+        pieces
+    }
+
+    // Closed outline of the canvas, e.g. to draw its edge
+    pub fn outline(&self) -> Path {
+        let mut outline = match self {
+            Canvas::Rect { min, max } => vec![*min, (max.0, min.1), *max, (min.0, max.1)],
+            Canvas::Circle { center, radius } => {
+                // Chords within the curve tolerance of the circle
+                let step = 2.0 * (1.0 - curve::TOLERANCE / radius.max(curve::TOLERANCE)).acos();
+                let n = ((2.0 * PI / step.max(1e-3)).ceil() as usize).max(8);
+                (0..n)
+                    .map(|i| {
+                        let a = 2.0 * PI * i as f32 / n as f32;
+                        (center.0 + radius * a.cos(), center.1 + radius * a.sin())
+                    })
+                    .collect()
+            }
+            Canvas::Polygon(outline) => outline.clone(),
+        };
+        if let Some(&first) = outline.first() {
+            outline.push(first);
+        }
+        outline
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{assert_within, test_config};
+    use crate::{Printer, sim};
+
+    fn close(a: (f32, f32), b: (f32, f32)) -> bool {
+        path::dist(a, b) < 1e-3
+    }
+
+    #[test]
+    fn circle_clip() {
+        let canvas = Canvas::circle((0.0, 0.0), 10.0);
+        let pieces = canvas.clip(&[(-20.0, 0.0), (0.0, 0.0), (20.0, 0.0)]);
+        assert_eq!(pieces.len(), 1);
+        assert!(close(pieces[0][0], (-10.0, 0.0)));
+        assert!(close(*pieces[0].last().unwrap(), (10.0, 0.0)));
+
+        // Leaving and coming back splits the stroke
+        let pieces = canvas.clip(&[(-5.0, 5.0), (-5.0, 20.0), (5.0, 20.0), (5.0, 5.0)]);
+        assert_eq!(pieces.len(), 2);
+
+        assert_eq!(canvas.clip(&[(3.0, 3.0)]), vec![vec![(3.0, 3.0)]]);
+        assert!(canvas.clip(&[(9.0, 9.0)]).is_empty());
+        assert!(
+            canvas
+                .outline()
+                .iter()
+                .all(|&p| (path::dist(p, (0.0, 0.0)) - 10.0).abs() < 1e-4)
+        );
+    }
+
+    #[test]
+    fn concave_polygon_clip() {
+        // L shape, the notch is the top right quarter
+        let canvas = Canvas::Polygon(vec![
+            (0.0, 0.0),
+            (10.0, 0.0),
+            (10.0, 5.0),
+            (5.0, 5.0),
+            (5.0, 10.0),
+            (0.0, 10.0),
+        ]);
+        assert!(canvas.contains((2.0, 8.0)));
+        assert!(!canvas.contains((8.0, 8.0)));
+
+        let pieces = canvas.clip(&[(-1.0, 7.0), (11.0, 7.0)]);
+        assert_eq!(pieces.len(), 1);
+        assert!(close(pieces[0][0], (0.0, 7.0)));
+        assert!(close(pieces[0][1], (5.0, 7.0)));
+
+        // Down the right side, through the notch
+        let pieces = canvas.clip(&[(2.0, 8.0), (8.0, 8.0), (8.0, 2.0)]);
+        assert_eq!(pieces.len(), 2);
+        assert!(close(pieces[1][0], (8.0, 5.0)));
+        assert!(close(pieces[1][1], (8.0, 2.0)));
+    }
+
+    #[test]
+    fn round_canvas() {
+        let mut config = test_config();
+        config.canvas = Some(Canvas::circle((100.0, 100.0), 50.0));
+        let mut printer = Printer::new(config);
+        assert!(printer.canvas().contains((60.0, 100.0)));
+        assert!(!printer.canvas().contains((60.0, 60.0)));
+
+        // Across the middle, and a stroke and a dot outside
+        printer.draw_polyline(&[(0.0, 100.0), (200.0, 100.0)]);
+        printer.draw_polyline(&[(0.0, 0.0), (40.0, 0.0)]);
+        printer.draw_point(10.0, 10.0);
+        printer.draw_point(110.0, 110.0);
+
+        let stats = printer.stats();
+        assert_within(stats.total.drawn, 100.0, 1e-2);
+        let extents = sim::drawn_extents(&printer.simulate()).unwrap();
+        assert!((extents.0.0 - 50.0).abs() < 1e-3 && (extents.1.0 - 150.0).abs() < 1e-3);
+
+        let printer = Printer::new(test_config());
+        assert!(printer.canvas().contains((204.0, 177.0)));
+    }
+}
@@ -0,0 +1,142 @@
+use crate::Printer;
+use crate::edges;
+use crate::path::Path;
+use crate::raster::Bitmap;
+
+// Traces scanned line art along the middle of its lines, so every inked
+// line becomes one pen stroke. Tracing the outlines instead would draw each
+// line twice, once along either side.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Centerline {
+    // Pixels darker than this brightness (0..=1) are ink
+    pub threshold: f32,
+    // Strokes shorter than this many pixels are dropped as specks
+    pub min_length: usize,
+    // Tolerance for simplifying the traced strokes, in pixels
+    pub simplify: f32,
+}
+
+impl Centerline {
+    pub fn new() -> Self {
+        Centerline {
+            threshold: 0.5,
+            min_length: 3,
+            simplify: 0.5,
+        }
+    }
+
+    // The ink thinned to lines one pixel wide (Zhang-Suen), row by row from
+    // the top
+    pub fn skeleton(&self, bitmap: &Bitmap) -> Vec<bool> {
+        let (w, h) = (bitmap.width as i64, bitmap.height as i64);
+        let mut ink: Vec<bool> = (0..h)
+            .flat_map(|y| (0..w).map(move |x| (x, y)))
+            .map(|(x, y)| bitmap.brightness(x, y) < self.threshold)
+            .collect();
+        let at = |ink: &[bool], x: i64, y: i64| {
+            x >= 0 && y >= 0 && x < w && y < h && ink[(y * w + x) as usize]
+        };
+
+        loop {
+            let mut changed = false;
+            for pass in 0..2 {
+                let mut remove = Vec::new();
+                for y in 0..h {
+                    for x in 0..w {
+                        if !at(&ink, x, y) {
+                            continue;
+                        }
+                        // Neighbors clockwise from the top
+                        let p = [
+                            at(&ink, x, y - 1),
+                            at(&ink, x + 1, y - 1),
+                            at(&ink, x + 1, y),
+                            at(&ink, x + 1, y + 1),
+                            at(&ink, x, y + 1),
+                            at(&ink, x - 1, y + 1),
+                            at(&ink, x - 1, y),
+                            at(&ink, x - 1, y - 1),
+                        ];
+                        let count = p.iter().filter(|&&b| b).count();
+                        let transitions = (0..8).filter(|&i| !p[i] && p[(i + 1) % 8]).count();
+                        // Top, right, bottom and left
+                        let (n, e, s, west) = (p[0], p[2], p[4], p[6]);
+                        // First the south-east side, then the north-west one
+                        let side = if pass == 0 {
+                            !(e && s && (n || west))
+                        } else {
+                            !(n && west && (e || s))
+                        };
+                        if (2..=6).contains(&count) && transitions == 1 && side {
+                            remove.push((y * w + x) as usize);
+                        }
+                    }
+                }
+                changed |= !remove.is_empty();
+                for i in remove {
+                    ink[i] = false;
+                }
+            }
+            if !changed {
+                break;
+            }
+        }
+        ink
+    }
+
+    // Centerlines as strokes, with the bitmap stretched over min..max and its
+    // top row at max.1
+    pub fn strokes(&self, bitmap: &Bitmap, min: (f32, f32), max: (f32, f32)) -> Vec<Path> {
+        let skeleton = self.skeleton(bitmap);
+        edges::trace(skeleton, bitmap, self.min_length, self.simplify, (min, max))
+    }
+
+    // Draws the strokes with the bitmap over min..max in drawing coordinates
+    pub fn draw(&self, printer: &mut Printer, bitmap: &Bitmap, min: (f32, f32), max: (f32, f32)) {
+        for stroke in self.strokes(bitmap, min, max) {
+            printer.draw_polyline(&stroke);
+        }
+    }
+}
+
+impl Default for Centerline {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::path;
+
+    #[test]
+    fn thick_line_becomes_one_stroke() {
+        // A 5 pixel wide bar across the middle
+        let mut bitmap = Bitmap::new(40, 20, [255, 255, 255]);
+        for y in 8..13 {
+            for x in 5..35 {
+                bitmap.set(x, y, [20, 20, 20]);
+            }
+        }
+        let strokes = Centerline::new().strokes(&bitmap, (0.0, 0.0), (40.0, 20.0));
+        assert_eq!(strokes.len(), 1, "{:?}", strokes);
+        // Row 10 is the middle, 9.5mm up from the bottom
+        assert!(strokes[0].iter().all(|p| (p.1 - 9.5).abs() <= 1.0));
+        assert!(path::length(&strokes[0]) > 20.0);
+
+        // A ring stays a single closed loop
+        let mut bitmap = Bitmap::new(30, 30, [255, 255, 255]);
+        for y in 0..30 {
+            for x in 0..30 {
+                let r = path::dist((x as f32, y as f32), (15.0, 15.0));
+                if (8.0..11.0).contains(&r) {
+                    bitmap.set(x, y, [0, 0, 0]);
+                }
+            }
+        }
+        let strokes = Centerline::new().strokes(&bitmap, (0.0, 0.0), (30.0, 30.0));
+        assert_eq!(strokes.len(), 1, "{:?}", strokes);
+        assert!(path::is_closed(&strokes[0]));
+    }
+}
@@ -0,0 +1,305 @@
+use crate::{Code, Dialect, Feed, Point, Precision, recovery, render_arc, render_bezier};
+
+// How the moves are shortened on the way out
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Compaction {
+    // Axes that don't change and feed rates the machine is already at are
+    // left out, G-code keeps both until they're set again
+    Modal,
+    // As Modal, with runs of straight moves written relative to each other
+    // between G91 and G90, which is shorter for dense point clouds
+    Relative,
+}
+
+// Numbers in units of the last decimal place written, so relative moves
+// add up to exactly where the absolute ones would go
+fn units(v: f32, decimals: usize) -> i64 {
+    (v as f64 * 10f64.powi(decimals as i32)).round() as i64
+}
+
+fn number(units: i64, decimals: usize) -> String {
+    format!(
+        "{:.*}",
+        decimals,
+        units as f64 / 10f64.powi(decimals as i32)
+    )
+}
+
+// Whether any line of a command could move the machine, e.g. a custom pen
+// command or one inserted with Printer::emit_raw
+fn may_move(code: &str) -> bool {
+    code.lines()
+        .any(|line| (line.starts_with('G') && !line.starts_with("G4 ")) || line.starts_with('$'))
+}
+
+// Follows the program as it's written out, with the last position and feed
+// the machine was told
+#[derive(Debug)]
+pub(crate) struct Compactor {
+    mode: Compaction,
+    dialect: Dialect,
+    precision: Precision,
+    // X, Y and Z in units of their precision, None where unknown
+    at: [Option<i64>; 3],
+    feed: Option<i64>,
+    relative: bool,
+}
+
+impl Compactor {
+    pub(crate) fn new(mode: Compaction, dialect: Dialect, precision: Precision) -> Self {
+        Compactor {
+            mode,
+            dialect,
+            precision,
+            at: [None; 3],
+            feed: None,
+            relative: false,
+        }
+    }
+
+    // What to write for `code`, translated for the dialect
+    pub(crate) fn code(&mut self, code: Code) -> Vec<Code> {
+        match code {
+            Code::Move(point, feed) => self.move_to(crate::G_MODE, &point, feed),
+            Code::Rapid(point, feed) => self.move_to(0, &point, feed),
+            Code::Bezier(curve, feed) => {
+                let mut out = self.absolute();
+                out.push(Code::Raw(
+                    render_bezier(&curve, &feed, &self.precision).into(),
+                ));
+                self.arrive(curve.p1, feed);
+                out
+            }
+            Code::Arc(arc, feed) => {
+                let mut out = self.absolute();
+                out.push(Code::Raw(render_arc(&arc, &feed, &self.precision).into()));
+                self.arrive(arc.point(1.0), feed);
+                out
+            }
+            // A resumed program starts at a recovery point, so it mustn't
+            // depend on what came before
+            Code::Comment(ref c) if c.starts_with(recovery::TAG) => self.unknown(code),
+            Code::Raw(ref src) if may_move(&src.code) => self.unknown(code),
+            Code::Pen(_, ref line) if may_move(line) => self.unknown(code),
+            code => vec![self.dialect.translate(code)],
+        }
+    }
+
+    // Back to absolute coordinates at the end of the program
+    pub(crate) fn finish(&mut self) -> Vec<Code> {
+        self.absolute()
+    }
+
+    fn absolute(&mut self) -> Vec<Code> {
+        if !std::mem::take(&mut self.relative) {
+            return Vec::new();
+        }
+        vec![Code::Raw("G90".into())]
+    }
+
+    // A command that may have moved the machine or changed its modes
+    fn unknown(&mut self, code: Code) -> Vec<Code> {
+        let mut out = self.absolute();
+        out.push(self.dialect.translate(code));
+        self.at = [None; 3];
+        self.feed = None;
+        out
+    }
+
+    fn arrive(&mut self, (x, y): (f32, f32), feed: f32) {
+        self.at[0] = Some(units(x, self.precision.xy));
+        self.at[1] = Some(units(y, self.precision.xy));
+        self.feed = Some(units(feed, self.precision.feed));
+    }
+
+    // `g` is G_MODE, or 0 for a rapid
+    fn move_to(&mut self, g: u32, point: &Point, feed: f32) -> Vec<Code> {
+        let decimals = [self.precision.xy, self.precision.xy, self.precision.z];
+        let target: Vec<Option<i64>> = [point.x, point.y, point.z]
+            .iter()
+            .zip(decimals)
+            .map(|(v, d)| v.map(|v| units(v, d)))
+            .collect();
+        // Axes that change, with their old position if known
+        let changes: Vec<(usize, i64, Option<i64>)> = (0..3)
+            .filter_map(|i| Some((i, target[i]?, self.at[i])))
+            .filter(|&(_, to, from)| from != Some(to))
+            .collect();
+        if changes.is_empty() {
+            return Vec::new();
+        }
+
+        let relative =
+            self.mode == Compaction::Relative && changes.iter().all(|&(_, _, from)| from.is_some());
+        let mut out = if relative {
+            if self.relative {
+                Vec::new()
+            } else {
+                self.relative = true;
+                vec![Code::Raw("G91".into())]
+            }
+        } else {
+            self.absolute()
+        };
+
+        let mut words = vec![format!("G{}", g)];
+        for &(i, to, from) in &changes {
+            let value = if relative { to - from.unwrap() } else { to };
+            words.push(format!(
+                "{}{}",
+                ['X', 'Y', 'Z'][i],
+                number(value, decimals[i])
+            ));
+            self.at[i] = Some(to);
+        }
+        let rounded = units(feed, self.precision.feed);
+        if self.feed != Some(rounded) {
+            words.push(Feed::mm_per_min(feed).word(self.precision.feed));
+            self.feed = Some(rounded);
+        }
+        out.push(Code::Raw(words.join(" ").into()));
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::test_config;
+    use crate::{PRECISION, Printer, config};
+
+    fn xy(x: f32, y: f32) -> Code {
+        Code::Move(
+            Point {
+                x: Some(x),
+                y: Some(y),
+                z: None,
+            },
+            1000.0,
+        )
+    }
+
+    fn z(z: f32, feed: f32) -> Code {
+        Code::Move(
+            Point {
+                x: None,
+                y: None,
+                z: Some(z),
+            },
+            feed,
+        )
+    }
+
+    fn lines(mode: Compaction, code: Vec<Code>) -> Vec<String> {
+        let mut compactor = Compactor::new(mode, Dialect::Prusa, PRECISION);
+        let mut out: Vec<Code> = code.into_iter().flat_map(|c| compactor.code(c)).collect();
+        out.extend(compactor.finish());
+        out.iter().map(|c| c.to_string()).collect()
+    }
+
+    #[test]
+    fn modal_words() {
+        let code = vec![
+            xy(1.0, 2.0),
+            z(4.0, 500.0),
+            z(6.5, 800.0),
+            xy(3.0, 2.0),
+            Code::Raw("M220 S150".into()),
+            xy(3.0, 2.02),
+            Code::Raw("G92 X0 Y0".into()),
+            xy(3.0, 2.0),
+            xy(3.0, 2.0),
+            Code::Raw("M117 Homing\nG28".into()),
+            xy(3.0, 2.0),
+        ];
+        assert_eq!(
+            lines(Compaction::Modal, code),
+            [
+                "G1 X1.0 Y2.0 F1000.0",
+                "G1 Z4.0 F500.0",
+                "G1 Z6.5 F800.0",
+                "G1 X3.0 F1000.0",
+                "M220 S150",
+                // Rounds to where the machine already is
+                "G92 X0 Y0",
+                "G1 X3.0 Y2.0 F1000.0",
+                "M117 Homing\nG28",
+                "G1 X3.0 Y2.0 F1000.0",
+            ]
+        );
+    }
+
+    #[test]
+    fn relative_runs() {
+        let code = vec![
+            xy(1.0, 2.0),
+            z(4.0, 1000.0),
+            xy(1.25, 2.0),
+            xy(1.5, 2.0),
+            Code::Comment("between".to_string()),
+            xy(1.75, 2.0),
+            Code::Pen(false, "M280 P0 S90".to_string()),
+            Code::Raw("G28".into()),
+            xy(1.0, 1.0),
+        ];
+        // The quarter millimetres add up to the same rounded positions as
+        // absolute moves would, 1.3, 1.5 and 1.8
+        assert_eq!(
+            lines(Compaction::Relative, code),
+            [
+                "G1 X1.0 Y2.0 F1000.0",
+                "G1 Z4.0",
+                "G91",
+                "G1 X0.3",
+                "G1 X0.2",
+                "; between",
+                "G1 X0.3",
+                "M280 P0 S90",
+                "G90",
+                "G28",
+                "G1 X1.0 Y1.0 F1000.0",
+            ]
+        );
+    }
+
+    #[test]
+    fn compact_output() {
+        let written = |compact: Option<Compaction>| {
+            let mut config = test_config();
+            config.compact = compact;
+            config.embed_config = true;
+            config.recovery_interval = Some(5.0);
+            let mut printer = Printer::new(config);
+            for i in 0..100 {
+                printer.draw_point(100.0 + (i % 10) as f32 * 0.5, 100.0 + (i / 10) as f32 * 0.5);
+            }
+            let mut out = Vec::new();
+            printer.write_to(&mut out).unwrap();
+            String::from_utf8(out).unwrap()
+        };
+        let full = written(None);
+        let modal = written(Some(Compaction::Modal));
+        let relative = written(Some(Compaction::Relative));
+        assert!(relative.len() < modal.len() && modal.len() < full.len());
+        assert!(!modal.contains("G91"));
+        assert!(modal.contains("\nG1 Z4.0 F500.0\n"));
+
+        // Relative runs end before the footer and the config reads back
+        let footer = relative.rfind("G91").unwrap();
+        assert!(relative[footer..].contains("\nG90\n"));
+        let loaded = config::parse(&relative).unwrap();
+        assert_eq!(loaded.config.compact, Some(Compaction::Relative));
+
+        // Programs resumed at a recovery point start with a complete move
+        let lines: Vec<&str> = relative.lines().collect();
+        let markers = recovery::markers(&relative);
+        assert!(markers.len() > 2);
+        for marker in markers {
+            let next = lines[marker.line..]
+                .iter()
+                .find(|l| l.starts_with("G1"))
+                .unwrap();
+            assert!(["X", "Y", "F"].iter().all(|w| next.contains(w)), "{}", next);
+        }
+    }
+}
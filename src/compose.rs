@@ -0,0 +1,150 @@
+use std::fmt;
+use std::sync::Arc;
+
+use crate::{AffineTransform, Pen, Printer};
+
+type DrawCall = Arc<dyn Fn(&mut Printer)>;
+
+// One piece of a composition: draw calls in the piece's own coordinates,
+// placed on the sheet by `transform` and drawn with `pen`. Pieces don't
+// need to know about each other or where they end up.
+#[derive(Clone)]
+pub struct Layer {
+    pub name: String,
+    pub transform: AffineTransform,
+    // The pen the printer's layer of this name already has if not set
+    pub pen: Option<Pen>,
+    draws: Vec<DrawCall>,
+}
+
+impl fmt::Debug for Layer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Layer")
+            .field("name", &self.name)
+            .field("transform", &self.transform)
+            .field("pen", &self.pen)
+            .field("draws", &self.draws.len())
+            .finish()
+    }
+}
+
+impl Layer {
+    pub fn new(name: &str) -> Self {
+        Layer {
+            name: name.to_string(),
+            transform: AffineTransform::IDENTITY,
+            pen: None,
+            draws: Vec::new(),
+        }
+    }
+
+    // Placed with `t` after the transform it already has
+    pub fn transform(&mut self, t: AffineTransform) -> &mut Self {
+        self.transform = self.transform.then(&t);
+        self
+    }
+
+    pub fn pen(&mut self, pen: Pen) -> &mut Self {
+        self.pen = Some(pen);
+        self
+    }
+
+    // Queues draw calls, made on the printer when the layer is rendered,
+    // e.g. `layer.draw(|p| p.draw_circle(0.0, 0.0, 10.0))`
+    pub fn draw(&mut self, f: impl Fn(&mut Printer) + 'static) -> &mut Self {
+        self.draws.push(Arc::new(f));
+        self
+    }
+}
+
+impl Printer {
+    // Draws the layers one after the other, each on the printer layer of
+    // its name with its own transform and pen. Layers with the same pen are
+    // drawn together, in the order the pens first come up, so each pen is
+    // loaded once; otherwise later layers are drawn over earlier ones.
+    pub fn render(&mut self, layers: &[Layer]) {
+        let previous = self.layer;
+        let pen_name = |printer: &mut Printer, layer: &Layer| match &layer.pen {
+            Some(pen) => pen.name.clone(),
+            None => {
+                let i = printer.layer_index(&layer.name);
+                printer.pens[i].name.clone()
+            }
+        };
+        let mut pens: Vec<String> = Vec::new();
+        let mut rank = Vec::new();
+        for layer in layers {
+            let name = pen_name(self, layer);
+            rank.push(pens.iter().position(|p| *p == name).unwrap_or_else(|| {
+                pens.push(name);
+                pens.len() - 1
+            }));
+        }
+        let mut order: Vec<usize> = (0..layers.len()).collect();
+        order.sort_by_key(|&i| rank[i]);
+
+        for layer in order.into_iter().map(|i| &layers[i]) {
+            if let Some(pen) = &layer.pen {
+                self.set_layer_pen(&layer.name, pen.clone());
+            }
+            self.set_layer(&layer.name);
+            self.push_transform(layer.transform);
+            for draw in &layer.draws {
+                draw(self);
+            }
+            self.pop_transform();
+        }
+        self.layer = previous;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::test_config;
+
+    #[test]
+    fn composed_layers() {
+        let mut config = test_config();
+        config.pen_change = Some("Load {pen} pen".to_string());
+        let mut printer = Printer::new(config);
+        let square = [(0.0, 0.0), (5.0, 0.0), (5.0, 5.0), (0.0, 5.0), (0.0, 0.0)];
+        let mut layers = Vec::new();
+        for (i, pen) in ["black", "red", "black"].into_iter().enumerate() {
+            let mut layer = Layer::new(&format!("piece {}", i));
+            layer
+                .pen(Pen::new(pen))
+                .transform(AffineTransform::translate(20.0 * i as f32, 10.0))
+                .draw(move |p| p.draw_polyline(&square));
+            layers.push(layer);
+        }
+        printer.draw_line(0.0, 0.0, 1.0, 0.0);
+        printer.render(&layers);
+        printer.draw_line(0.0, 1.0, 1.0, 1.0);
+
+        // The black pieces together, each moved into place
+        let starts: Vec<(f32, f32)> = printer.ops().iter().map(|op| op.path()[0]).collect();
+        assert_eq!(
+            starts,
+            [
+                (0.0, 0.0),
+                (0.0, 10.0),
+                (40.0, 10.0),
+                (20.0, 10.0),
+                (0.0, 1.0)
+            ]
+        );
+        let layers: Vec<usize> = printer.ops().iter().map(|op| op.layer).collect();
+        assert_eq!(layers, [0, 1, 2, 3, 0]);
+        assert_eq!(printer.pens()[3].name, "red");
+        let loads: Vec<String> = printer
+            .codes()
+            .iter()
+            .map(|c| c.to_string())
+            .filter(|c| c.starts_with("M117 Load"))
+            .collect();
+        // Then back to the unnamed default pen
+        assert_eq!(loads.len(), 3);
+        assert_eq!(loads[..2], ["M117 Load black pen", "M117 Load red pen"]);
+    }
+}
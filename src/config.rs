@@ -0,0 +1,855 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+
+use crate::canvas::Canvas;
+use crate::{
+    Annotations, Code, Dialect, Engrave, Error, Feed, OutOfBounds, PrinterConfig, Taper, Wcs, ZHop,
+    actuator, compact, drying, feed, knife, progress,
+};
+
+// Why a config can't work, one variant per rule PrinterConfig::validate
+// checks
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConfigError {
+    // min has to be below and left of max
+    WorkArea { min: (f32, f32), max: (f32, f32) },
+    NonPositiveScale((f32, f32)),
+    // A speed, limit or distance that has to be above 0, by its key
+    NonPositive { key: &'static str, value: f32 },
+    // The pen would go up to draw
+    PenHeights { z0: f32, z_draw: f32 },
+    UnsupportedActuator { actuator: String, dialect: Dialect },
+    EmptyThumbnail((u32, u32)),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConfigError::WorkArea { min, max } => {
+                write!(f, "min {:?} must be below and left of max {:?}", min, max)
+            }
+            ConfigError::NonPositiveScale(scale) => {
+                write!(f, "scale must be positive, not {:?}", scale)
+            }
+            ConfigError::NonPositive { key, value } => {
+                write!(f, "{} must be positive, not {}", key, value)
+            }
+            ConfigError::PenHeights { z0, z_draw } => write!(
+                f,
+                "z_draw ({}) must be below z0 ({}) to lift the pen",
+                z_draw, z0
+            ),
+            ConfigError::UnsupportedActuator { actuator, dialect } => write!(
+                f,
+                "{} can't drive the pen with {:?} firmware",
+                actuator, dialect
+            ),
+            ConfigError::EmptyThumbnail((w, h)) => write!(f, "empty thumbnail size {}x{}", w, h),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+// Builds a config from the defaults (or another config) up, checking it
+// once at the end:
+//
+//     let config = PrinterConfig::builder()
+//         .min((0.0, 0.0))
+//         .max((300.0, 200.0))
+//         .z0(10.0)
+//         .z_draw(2.0)
+//         .build()?;
+#[derive(Debug, Clone)]
+pub struct PrinterConfigBuilder {
+    config: PrinterConfig,
+}
+
+macro_rules! setters {
+    ($($field:ident: $ty:ty),* $(,)?) => {
+        $(
+            pub fn $field(mut self, $field: $ty) -> Self {
+                self.config.$field = $field;
+                self
+            }
+        )*
+    };
+}
+
+impl PrinterConfigBuilder {
+    setters! {
+        model: Option<Code>,
+        min: (f32, f32),
+        max: (f32, f32),
+        scale: Option<(f32, f32)>,
+        z0: f32,
+        z_draw: f32,
+        xy_speed: Feed,
+        down_speed: Feed,
+        up_speed: Feed,
+        pen_wear_limit: Option<f32>,
+        stats_comments: bool,
+        thumbnails: Vec<(u32, u32)>,
+        metadata: bool,
+        dialect: Dialect,
+        native_curves: bool,
+        wcs: Option<Wcs>,
+        engrave: Option<Engrave>,
+        drag_knife: Option<knife::DragKnife>,
+        actuator: Arc<dyn actuator::PenActuator>,
+        drying: Option<drying::Drying>,
+        embed_config: bool,
+        canvas: Option<Canvas>,
+        taper: Option<Taper>,
+        adaptive_feed: Option<feed::AdaptiveFeed>,
+        acceleration: Option<f32>,
+        recovery_interval: Option<f32>,
+        out_of_bounds: Option<OutOfBounds>,
+        progress: progress::ProgressConfig,
+        pen_change: Option<String>,
+        tolerance: f32,
+        xy_decimals: usize,
+        z_decimals: usize,
+        feed_decimals: usize,
+        compact: Option<compact::Compaction>,
+        z_hop: Option<ZHop>,
+        max_feed: Option<Feed>,
+        max_draw_move: Option<f32>,
+        annotations: Annotations,
+    }
+
+    pub fn build(self) -> Result<PrinterConfig, ConfigError> {
+        self.config.validate()?;
+        Ok(self.config)
+    }
+}
+
+impl From<PrinterConfig> for PrinterConfigBuilder {
+    fn from(config: PrinterConfig) -> Self {
+        PrinterConfigBuilder { config }
+    }
+}
+
+impl PrinterConfig {
+    pub fn builder() -> PrinterConfigBuilder {
+        PrinterConfig::default().into()
+    }
+}
+
+// Comments with these tags carry the embedded config and the parameters of
+// the generator, as "; config: key = value"
+const CONFIG_TAG: &str = "config:";
+const PARAM_TAG: &str = "param:";
+
+// Everything needed to generate a plot again, as read back from its G-code
+#[derive(Debug, Clone)]
+pub struct Embedded {
+    pub config: PrinterConfig,
+    pub parameters: Vec<(String, String)>,
+}
+
+fn pair((a, b): (f32, f32)) -> String {
+    format!("{},{}", a, b)
+}
+
+fn optional<T>(value: Option<T>, f: impl Fn(T) -> String) -> String {
+    value.map_or("none".to_string(), f)
+}
+
+fn canvas(canvas: &Canvas) -> String {
+    let points = |path: &[(f32, f32)]| {
+        let pairs: Vec<String> = path.iter().map(|&p| pair(p)).collect();
+        pairs.join(" ")
+    };
+    match canvas {
+        Canvas::Rect { min, max } => format!("rect {}", points(&[*min, *max])),
+        Canvas::Circle { center, radius } => format!("circle {} {}", pair(*center), radius),
+        Canvas::Polygon(outline) => format!("polygon {}", points(outline)),
+    }
+}
+
+fn parse_canvas(text: &str) -> Option<Canvas> {
+    let mut words = text.split_whitespace();
+    let kind = words.next()?;
+    let words: Vec<&str> = words.collect();
+    let pair = |w: &str| {
+        let (a, b) = w.split_once(',')?;
+        Some((a.parse().ok()?, b.parse().ok()?))
+    };
+    match (kind, words.as_slice()) {
+        ("rect", [min, max]) => Some(Canvas::Rect {
+            min: pair(min)?,
+            max: pair(max)?,
+        }),
+        ("circle", [center, radius]) => Some(Canvas::Circle {
+            center: pair(center)?,
+            radius: radius.parse().ok()?,
+        }),
+        ("polygon", points) => Some(Canvas::Polygon(
+            points.iter().map(|w| pair(w)).collect::<Option<_>>()?,
+        )),
+        _ => None,
+    }
+}
+
+// The config as key/value pairs. Numbers are written in full so the config
+// reads back exactly.
+pub fn entries(config: &PrinterConfig) -> Vec<(String, String)> {
+    let mut out: Vec<(&str, String)> = vec![
+        (
+            "model",
+            match &config.model {
+                Some(Code::Model(model)) => model.clone(),
+                _ => "none".to_string(),
+            },
+        ),
+        ("min", pair(config.min)),
+        ("max", pair(config.max)),
+        ("scale", optional(config.scale, pair)),
+        ("z0", config.z0.to_string()),
+        ("z_draw", config.z_draw.to_string()),
+        ("xy_speed", config.xy_speed.to_string()),
+        ("down_speed", config.down_speed.to_string()),
+        ("up_speed", config.up_speed.to_string()),
+        (
+            "pen_wear_limit",
+            optional(config.pen_wear_limit, |v| v.to_string()),
+        ),
+        ("stats_comments", config.stats_comments.to_string()),
+        (
+            "thumbnails",
+            if config.thumbnails.is_empty() {
+                "none".to_string()
+            } else {
+                let sizes: Vec<String> = config
+                    .thumbnails
+                    .iter()
+                    .map(|(w, h)| format!("{}x{}", w, h))
+                    .collect();
+                sizes.join(",")
+            },
+        ),
+        ("metadata", config.metadata.to_string()),
+        ("dialect", format!("{:?}", config.dialect)),
+        ("native_curves", config.native_curves.to_string()),
+        ("wcs", optional(config.wcs, |w| format!("{:?}", w))),
+        ("actuator", config.actuator.describe()),
+        ("embed_config", config.embed_config.to_string()),
+        ("canvas", optional(config.canvas.as_ref(), canvas)),
+        (
+            "acceleration",
+            optional(config.acceleration, |v| v.to_string()),
+        ),
+        (
+            "recovery_interval",
+            optional(config.recovery_interval, |v| v.to_string()),
+        ),
+        (
+            "out_of_bounds",
+            optional(config.out_of_bounds, |p| format!("{:?}", p)),
+        ),
+        (
+            "progress.interval",
+            match config.progress.interval {
+                progress::Interval::Percent(p) => format!("{}%", p),
+                progress::Interval::Commands(n) => format!("{} commands", n),
+            },
+        ),
+        ("progress.template", config.progress.template.clone()),
+        ("progress.messages", config.progress.messages.to_string()),
+        ("pen_change", optional(config.pen_change.clone(), |m| m)),
+        ("tolerance", config.tolerance.to_string()),
+        ("xy_decimals", config.xy_decimals.to_string()),
+        ("z_decimals", config.z_decimals.to_string()),
+        ("feed_decimals", config.feed_decimals.to_string()),
+        ("compact", optional(config.compact, |c| format!("{:?}", c))),
+        ("max_feed", optional(config.max_feed, |v| v.to_string())),
+        (
+            "max_draw_move",
+            optional(config.max_draw_move, |v| v.to_string()),
+        ),
+        ("annotations", format!("{:?}", config.annotations)),
+    ];
+
+    // Optional groups only get their fields when they're set
+    match config.engrave {
+        Some(e) => out.extend([
+            ("engrave.depth", e.depth.to_string()),
+            ("engrave.step_down", e.step_down.to_string()),
+            ("engrave.plunge_speed", e.plunge_speed.to_string()),
+        ]),
+        None => out.push(("engrave", "none".to_string())),
+    }
+    match config.drag_knife {
+        Some(k) => out.extend([
+            ("drag_knife.offset", k.offset.to_string()),
+            ("drag_knife.threshold", k.threshold.to_string()),
+        ]),
+        None => out.push(("drag_knife", "none".to_string())),
+    }
+    match config.taper {
+        Some(t) => out.extend([
+            ("taper.length", t.length.to_string()),
+            ("taper.lift", t.lift.to_string()),
+        ]),
+        None => out.push(("taper", "none".to_string())),
+    }
+    match config.adaptive_feed {
+        Some(f) => out.extend([
+            ("adaptive_feed.min", f.min.to_string()),
+            ("adaptive_feed.max", f.max.to_string()),
+            ("adaptive_feed.radius", f.radius.to_string()),
+        ]),
+        None => out.push(("adaptive_feed", "none".to_string())),
+    }
+    match config.drying {
+        Some(d) => out.extend([
+            ("drying.seconds", d.seconds.to_string()),
+            ("drying.after_layer", d.after_layer.to_string()),
+            ("drying.density", optional(d.density, |v| v.to_string())),
+            ("drying.region", d.region.to_string()),
+            ("drying.park", optional(d.park, pair)),
+        ]),
+        None => out.push(("drying", "none".to_string())),
+    }
+    match config.z_hop {
+        Some(h) => out.extend([
+            ("z_hop.height", h.height.to_string()),
+            ("z_hop.distance", h.distance.to_string()),
+        ]),
+        None => out.push(("z_hop", "none".to_string())),
+    }
+
+    out.into_iter().map(|(k, v)| (k.to_string(), v)).collect()
+}
+
+// Tagged comments with the config and the generator parameters
+pub fn to_comments(config: &PrinterConfig, parameters: &[(String, String)]) -> Vec<Code> {
+    let config = entries(config).into_iter().map(|e| (CONFIG_TAG, e));
+    let parameters = parameters.iter().cloned().map(|e| (PARAM_TAG, e));
+    config
+        .chain(parameters)
+        .map(|(tag, (key, value))| {
+            // A line break would end the comment
+            Code::Comment(format!("{} {} = {}", tag, key, value.replace('\n', " ")))
+        })
+        .collect()
+}
+
+fn invalid(message: String) -> Error {
+    Error::Parse(message)
+}
+
+struct Values<'a>(HashMap<&'a str, &'a str>);
+
+impl<'a> Values<'a> {
+    fn text(&self, key: &str) -> Result<&'a str, Error> {
+        self.0
+            .get(key)
+            .copied()
+            .ok_or_else(|| invalid(format!("embedded config has no {}", key)))
+    }
+
+    fn value<T: std::str::FromStr>(&self, key: &str) -> Result<T, Error> {
+        let text = self.text(key)?;
+        text.parse()
+            .map_err(|_| invalid(format!("invalid {} in config: {}", key, text)))
+    }
+
+    // A feed in mm/min, from a number in mm/min or one with a unit
+    fn feed(&self, key: &str) -> Result<Feed, Error> {
+        self.value(key)
+    }
+
+    fn optional<T>(
+        &self,
+        key: &str,
+        f: impl Fn(&Self, &str) -> Result<T, Error>,
+    ) -> Result<Option<T>, Error> {
+        match self.text(key)? {
+            "none" => Ok(None),
+            _ => f(self, key).map(Some),
+        }
+    }
+
+    fn pair(&self, key: &str) -> Result<(f32, f32), Error> {
+        let text = self.text(key)?;
+        text.split_once(',')
+            .and_then(|(a, b)| Some((a.parse().ok()?, b.parse().ok()?)))
+            .ok_or_else(|| invalid(format!("invalid {} in config: {}", key, text)))
+    }
+
+    fn has(&self, key: &str) -> bool {
+        self.0.contains_key(key)
+    }
+}
+
+fn build(values: &Values) -> Result<PrinterConfig, Error> {
+    let thumbnails = match values.text("thumbnails")? {
+        "none" => Vec::new(),
+        text => text
+            .split(',')
+            .map(|size| {
+                size.split_once('x')
+                    .and_then(|(w, h)| Some((w.parse().ok()?, h.parse().ok()?)))
+                    .ok_or_else(|| invalid(format!("invalid thumbnail size: {}", size)))
+            })
+            .collect::<Result<_, _>>()?,
+    };
+    let dialect = match values.text("dialect")? {
+        "Prusa" => Dialect::Prusa,
+        "Marlin" => Dialect::Marlin,
+        "Grbl" => Dialect::Grbl,
+        other => return Err(invalid(format!("unknown dialect: {}", other))),
+    };
+    let wcs = match values.text("wcs")? {
+        "none" => None,
+        "G54" => Some(Wcs::G54),
+        "G55" => Some(Wcs::G55),
+        "G56" => Some(Wcs::G56),
+        "G57" => Some(Wcs::G57),
+        "G58" => Some(Wcs::G58),
+        "G59" => Some(Wcs::G59),
+        other => {
+            return Err(invalid(format!(
+                "unknown work coordinate system: {}",
+                other
+            )));
+        }
+    };
+    let out_of_bounds = match values.text("out_of_bounds")? {
+        "none" => None,
+        "Clamp" => Some(OutOfBounds::Clamp),
+        "Skip" => Some(OutOfBounds::Skip),
+        "Error" => Some(OutOfBounds::Error),
+        other => return Err(invalid(format!("unknown out of bounds policy: {}", other))),
+    };
+    let compact = match values.text("compact")? {
+        "none" => None,
+        "Modal" => Some(compact::Compaction::Modal),
+        "Relative" => Some(compact::Compaction::Relative),
+        other => return Err(invalid(format!("unknown compaction: {}", other))),
+    };
+    let annotations = match values.text("annotations")? {
+        "None" => Annotations::None,
+        "PerShape" => Annotations::PerShape,
+        "Verbose" => Annotations::Verbose,
+        other => return Err(invalid(format!("unknown annotations: {}", other))),
+    };
+    let interval = values.text("progress.interval")?;
+    let interval = if let Some(p) = interval.strip_suffix('%') {
+        p.parse().ok().map(progress::Interval::Percent)
+    } else if let Some(n) = interval.strip_suffix(" commands") {
+        n.parse().ok().map(progress::Interval::Commands)
+    } else {
+        None
+    }
+    .ok_or_else(|| invalid(format!("invalid progress interval: {}", interval)))?;
+    let progress = progress::ProgressConfig {
+        interval,
+        template: values.text("progress.template")?.to_string(),
+        messages: values.value("progress.messages")?,
+    };
+    let actuator = values.text("actuator")?;
+    let actuator = actuator::parse(actuator)
+        .ok_or_else(|| invalid(format!("unknown actuator: {}", actuator)))?;
+
+    let canvas = match values.text("canvas")? {
+        "none" => None,
+        text => {
+            Some(parse_canvas(text).ok_or_else(|| invalid(format!("invalid canvas: {}", text)))?)
+        }
+    };
+
+    let engrave = if values.has("engrave.depth") {
+        Some(Engrave {
+            depth: values.value("engrave.depth")?,
+            step_down: values.value("engrave.step_down")?,
+            plunge_speed: values.feed("engrave.plunge_speed")?,
+        })
+    } else {
+        None
+    };
+    let drag_knife = if values.has("drag_knife.offset") {
+        Some(knife::DragKnife {
+            offset: values.value("drag_knife.offset")?,
+            threshold: values.value("drag_knife.threshold")?,
+        })
+    } else {
+        None
+    };
+    let taper = if values.has("taper.length") {
+        Some(Taper {
+            length: values.value("taper.length")?,
+            lift: values.value("taper.lift")?,
+        })
+    } else {
+        None
+    };
+    let adaptive_feed = if values.has("adaptive_feed.min") {
+        Some(feed::AdaptiveFeed {
+            min: values.feed("adaptive_feed.min")?,
+            max: values.feed("adaptive_feed.max")?,
+            radius: values.value("adaptive_feed.radius")?,
+        })
+    } else {
+        None
+    };
+    let drying = if values.has("drying.seconds") {
+        Some(drying::Drying {
+            seconds: values.value("drying.seconds")?,
+            after_layer: values.value("drying.after_layer")?,
+            density: values.optional("drying.density", Values::value)?,
+            region: values.value("drying.region")?,
+            park: values.optional("drying.park", Values::pair)?,
+        })
+    } else {
+        None
+    };
+
+    let z_hop = if values.has("z_hop.height") {
+        Some(ZHop {
+            height: values.value("z_hop.height")?,
+            distance: values.value("z_hop.distance")?,
+        })
+    } else {
+        None
+    };
+
+    let config = PrinterConfig {
+        model: match values.text("model")? {
+            "none" => None,
+            model => Some(Code::Model(model.to_string())),
+        },
+        min: values.pair("min")?,
+        max: values.pair("max")?,
+        scale: values.optional("scale", Values::pair)?,
+        z0: values.value("z0")?,
+        z_draw: values.value("z_draw")?,
+        xy_speed: values.feed("xy_speed")?,
+        down_speed: values.feed("down_speed")?,
+        up_speed: values.feed("up_speed")?,
+        pen_wear_limit: values.optional("pen_wear_limit", Values::value)?,
+        stats_comments: values.value("stats_comments")?,
+        thumbnails,
+        metadata: values.value("metadata")?,
+        dialect,
+        native_curves: values.value("native_curves")?,
+        wcs,
+        engrave,
+        drag_knife,
+        actuator,
+        drying,
+        embed_config: values.value("embed_config")?,
+        canvas,
+        taper,
+        adaptive_feed,
+        acceleration: values.optional("acceleration", Values::value)?,
+        recovery_interval: values.optional("recovery_interval", Values::value)?,
+        out_of_bounds,
+        progress,
+        pen_change: values.optional("pen_change", |v, key| v.text(key).map(str::to_string))?,
+        tolerance: values.value("tolerance")?,
+        xy_decimals: values.value("xy_decimals")?,
+        z_decimals: values.value("z_decimals")?,
+        feed_decimals: values.value("feed_decimals")?,
+        compact,
+        z_hop,
+        max_feed: values.optional("max_feed", Values::feed)?,
+        max_draw_move: values.optional("max_draw_move", Values::value)?,
+        annotations,
+    };
+    config.validate()?;
+    Ok(config)
+}
+
+// Read the embedded config back from the text of a G-code file
+pub fn parse(text: &str) -> Result<Embedded, Error> {
+    let mut config = HashMap::new();
+    let mut parameters = Vec::new();
+    for line in text.lines() {
+        let Some(comment) = line.strip_prefix("; ") else {
+            continue;
+        };
+        if let Some((key, value)) = comment
+            .strip_prefix(CONFIG_TAG)
+            .and_then(|e| e.trim_start().split_once(" = "))
+        {
+            config.insert(key, value);
+        } else if let Some((key, value)) = comment
+            .strip_prefix(PARAM_TAG)
+            .and_then(|e| e.trim_start().split_once(" = "))
+        {
+            parameters.push((key.to_string(), value.to_string()));
+        }
+    }
+    if config.is_empty() {
+        return Err(invalid("no embedded config found".to_string()));
+    }
+
+    Ok(Embedded {
+        config: build(&Values(config))?,
+        parameters,
+    })
+}
+
+pub fn load(filename: &str) -> Result<Embedded, Error> {
+    parse(&std::fs::read_to_string(filename)?)
+}
+
+fn set(values: &mut HashMap<String, String>, key: String, value: String) {
+    // A group set to "none" loses the keys it had by default
+    let group = format!("{}.", key);
+    values.retain(|k, _| !k.starts_with(&group));
+    if let Some((group, _)) = key.split_once('.') {
+        values.remove(group);
+    }
+    values.insert(key, value);
+}
+
+// `config` with one entry changed, e.g. ("xy_speed", "1500") or
+// ("z_hop.height", "1"), checked as a whole config is
+pub fn with_entry(config: &PrinterConfig, key: &str, value: &str) -> Result<PrinterConfig, Error> {
+    let mut values: HashMap<String, String> = entries(config).into_iter().collect();
+    set(&mut values, key.to_string(), value.to_string());
+    let values = values.iter().map(|(k, v)| (k.as_str(), v.as_str()));
+    build(&Values(values.collect()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{temp_path, test_config};
+    use crate::{Printer, canvas};
+
+    #[test]
+    fn missing_or_invalid() {
+        let err = parse("G28 W\nG1 X1.0 Y1.0 F1000.0\n").unwrap_err();
+        assert_eq!(err.to_string(), "no embedded config found");
+
+        let err = parse("; config: z0 = 6.5\n").unwrap_err();
+        assert_eq!(err.to_string(), "embedded config has no thumbnails");
+
+        let err = parse("; config: thumbnails = 16y16\n").unwrap_err();
+        assert_eq!(err.to_string(), "invalid thumbnail size: 16y16");
+    }
+
+    #[test]
+    fn custom_actuator_round_trip() {
+        let config = PrinterConfig {
+            actuator: Arc::new(actuator::Custom {
+                up: "M42 P8 S0 | echo \"up\" = done".to_string(),
+                down: "SP,0\\1\nM400".to_string(),
+                dwell_ms: 50,
+            }),
+            ..PrinterConfig::default()
+        };
+        let program: String = to_comments(&config, &[])
+            .iter()
+            .map(|c| format!("{}\n", c))
+            .collect();
+        let read = parse(&program).unwrap().config;
+        assert_eq!(read.actuator.describe(), config.actuator.describe());
+    }
+
+    #[test]
+    fn builder() {
+        let config = PrinterConfig::builder()
+            .min((0.0, 0.0))
+            .max((300.0, 200.0))
+            .z0(10.0)
+            .z_draw(2.0)
+            .thumbnails(vec![(16, 16)])
+            .build()
+            .unwrap();
+        assert_eq!((config.max, config.z_draw), ((300.0, 200.0), 2.0));
+        assert_eq!(config.xy_speed, PrinterConfig::default().xy_speed);
+
+        let build = |builder: PrinterConfigBuilder| builder.build().unwrap_err();
+        let min = (50.0, 35.0);
+        assert_eq!(
+            build(PrinterConfig::builder().max((10.0, 300.0))),
+            ConfigError::WorkArea {
+                min,
+                max: (10.0, 300.0)
+            }
+        );
+        assert_eq!(
+            build(PrinterConfig::builder().scale(Some((0.0, 1.0)))),
+            ConfigError::NonPositiveScale((0.0, 1.0))
+        );
+        assert_eq!(
+            build(PrinterConfig::builder().xy_speed(Feed::mm_per_min(-5.0))),
+            ConfigError::NonPositive {
+                key: "xy_speed",
+                value: -5.0
+            }
+        );
+        assert_eq!(
+            build(PrinterConfig::builder().z_draw(6.5)),
+            ConfigError::PenHeights {
+                z0: 6.5,
+                z_draw: 6.5
+            }
+        );
+        assert_eq!(
+            build(PrinterConfig::builder().thumbnails(vec![(0, 16)])),
+            ConfigError::EmptyThumbnail((0, 16))
+        );
+        // Any config can be built on
+        let servo = PrinterConfigBuilder::from(PrinterConfig::prusa_mk3s())
+            .actuator(Arc::new(actuator::Servo {
+                index: 0,
+                up_angle: 90,
+                down_angle: 30,
+                dwell_ms: 150,
+            }))
+            .dialect(Dialect::Grbl);
+        assert!(matches!(
+            build(servo),
+            ConfigError::UnsupportedActuator { .. }
+        ));
+    }
+
+    #[test]
+    fn embedded_config() {
+        let mut config = test_config();
+        config.embed_config = true;
+        config.scale = Some((297.0, 210.0));
+        config.z_draw = 3.85;
+        config.thumbnails = vec![(16, 16), (220, 124)];
+        config.wcs = Some(Wcs::G56);
+        config.engrave = Some(Engrave {
+            depth: 0.3,
+            step_down: 0.1,
+            plunge_speed: Feed::mm_per_min(120.0),
+        });
+        config.actuator = Arc::new(actuator::Laser { power: 800 });
+        config.canvas = Some(canvas::Canvas::Polygon(vec![
+            (0.0, 0.0),
+            (120.5, 0.0),
+            (60.0, 90.0),
+        ]));
+        config.taper = Some(Taper {
+            length: 2.5,
+            lift: 0.4,
+        });
+        config.adaptive_feed = Some(feed::AdaptiveFeed::new(
+            Feed::mm_per_min(400.0),
+            Feed::mm_per_min(2500.0),
+        ));
+        config.acceleration = Some(1250.0);
+        config.recovery_interval = Some(300.0);
+        config.out_of_bounds = Some(OutOfBounds::Clamp);
+        config.pen_change = Some("Load {pen} pen".to_string());
+        config.tolerance = 0.02;
+        config.xy_decimals = 3;
+        config.z_hop = Some(ZHop {
+            height: 0.5,
+            distance: 5.0,
+        });
+        config.drying = Some(drying::Drying {
+            park: Some((0.0, 200.0)),
+            ..drying::Drying::new(30.0)
+        });
+
+        let mut printer = Printer::new(config.clone());
+        printer.set_parameter("seed", 42);
+        printer.set_parameter("title", "Waves");
+        printer.set_parameter("seed", 7);
+        printer.draw_polyline(&[(10.0, 10.0), (20.0, 10.0)]);
+
+        let filename = temp_path("embedded.gcode");
+        let filename = filename.to_str().unwrap();
+        printer.save(filename).unwrap();
+        let loaded = load(filename).unwrap();
+
+        assert_eq!(entries(&loaded.config), entries(&config));
+        assert_eq!(loaded.parameters, printer.parameters());
+        assert_eq!(
+            loaded.parameters,
+            [
+                ("seed".to_string(), "7".to_string()),
+                ("title".to_string(), "Waves".to_string())
+            ]
+        );
+
+        // Not embedded unless asked for
+        assert!(to_comments(&test_config(), &[]).len() > 10);
+        let mut plain = Printer::new(test_config());
+        plain.draw_point(1.0, 1.0);
+        plain.save(filename).unwrap();
+        assert!(load(filename).is_err());
+    }
+
+    #[test]
+    fn config_validation() {
+        assert!(PrinterConfig::default().validate().is_ok());
+        assert!(test_config().validate().is_ok());
+
+        let broken = [
+            PrinterConfig {
+                max: (20.0, 300.0),
+                ..PrinterConfig::prusa_mk3s()
+            },
+            PrinterConfig {
+                down_speed: Feed::mm_per_min(0.0),
+                ..PrinterConfig::prusa_mk3s()
+            },
+            PrinterConfig {
+                acceleration: Some(f32::NAN),
+                ..PrinterConfig::prusa_mk3s()
+            },
+            PrinterConfig {
+                z_draw: 8.0,
+                ..PrinterConfig::prusa_mk3s()
+            },
+        ];
+        let messages: Vec<String> = broken
+            .iter()
+            .map(|c| c.validate().unwrap_err().to_string())
+            .collect();
+        assert_eq!(
+            messages,
+            [
+                "min (50.0, 35.0) must be below and left of max (20.0, 300.0)",
+                "down_speed must be positive, not 0",
+                "acceleration must be positive, not NaN",
+                "z_draw (8) must be below z0 (6.5) to lift the pen",
+            ]
+        );
+        assert_eq!(
+            broken[1].validate(),
+            Err(ConfigError::NonPositive {
+                key: "down_speed",
+                value: 0.0
+            })
+        );
+        assert!(Printer::try_new(broken[3].clone()).is_err());
+
+        // A servo doesn't move Z at all
+        let servo = PrinterConfig {
+            z_draw: 8.0,
+            actuator: Arc::new(actuator::Servo {
+                index: 0,
+                up_angle: 90,
+                down_angle: 30,
+                dwell_ms: 150,
+            }),
+            ..PrinterConfig::prusa_mk3s()
+        };
+        assert!(servo.validate().is_ok());
+    }
+
+    #[test]
+    fn unchecked_printers() {
+        let config = PrinterConfig {
+            z_draw: 8.0,
+            ..PrinterConfig::prusa_mk3s()
+        };
+        assert!(Printer::try_new(config.clone()).is_err());
+        // new takes it unchecked, like it always has
+        assert_eq!(Printer::new(config).config.z_draw, 8.0);
+    }
+}
@@ -0,0 +1,332 @@
+use std::f32::consts::FRAC_PI_2;
+
+use crate::AffineTransform;
+use crate::path::{self, Path};
+
+// Default flattening tolerance in mm, well below the width of any pen
+pub const TOLERANCE: f32 = 0.05;
+
+// Recursion limit for adaptive flattening, 2^16 segments per curve is plenty
+const MAX_DEPTH: u32 = 16;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct CubicBezier {
+    pub p0: (f32, f32),
+    pub c0: (f32, f32),
+    pub c1: (f32, f32),
+    pub p1: (f32, f32),
+}
+
+fn dist_to_line(p: (f32, f32), a: (f32, f32), b: (f32, f32)) -> f32 {
+    let len = path::dist(a, b);
+    if len <= f32::EPSILON {
+        path::dist(p, a)
+    } else {
+        ((b.0 - a.0) * (a.1 - p.1) - (a.0 - p.0) * (b.1 - a.1)).abs() / len
+    }
+}
+
+impl CubicBezier {
+    pub fn new(p0: (f32, f32), c0: (f32, f32), c1: (f32, f32), p1: (f32, f32)) -> Self {
+        CubicBezier { p0, c0, c1, p1 }
+    }
+
+    pub fn point(&self, t: f32) -> (f32, f32) {
+        let u = 1.0 - t;
+        let w0 = u * u * u;
+        let w1 = 3.0 * u * u * t;
+        let w2 = 3.0 * u * t * t;
+        let w3 = t * t * t;
+        (
+            w0 * self.p0.0 + w1 * self.c0.0 + w2 * self.c1.0 + w3 * self.p1.0,
+            w0 * self.p0.1 + w1 * self.c0.1 + w2 * self.c1.1 + w3 * self.p1.1,
+        )
+    }
+
+    // de Casteljau split at t
+    pub fn split(&self, t: f32) -> (CubicBezier, CubicBezier) {
+        let a = path::lerp(self.p0, self.c0, t);
+        let b = path::lerp(self.c0, self.c1, t);
+        let c = path::lerp(self.c1, self.p1, t);
+        let d = path::lerp(a, b, t);
+        let e = path::lerp(b, c, t);
+        let m = path::lerp(d, e, t);
+        (
+            CubicBezier::new(self.p0, a, d, m),
+            CubicBezier::new(m, e, c, self.p1),
+        )
+    }
+
+    // Upper bound on how far the curve strays from the chord p0 -> p1
+    fn flatness(&self) -> f32 {
+        dist_to_line(self.c0, self.p0, self.p1).max(dist_to_line(self.c1, self.p0, self.p1))
+    }
+
+    // Adaptively subdivide into a polyline whose chords stay within
+    // `tolerance` (in mm) of the true curve
+    pub fn flatten(&self, tolerance: f32) -> Path {
+        let mut out = vec![self.p0];
+        self.flatten_into(tolerance.max(1e-4), 0, &mut out);
+        out
+    }
+
+    fn flatten_into(&self, tolerance: f32, depth: u32, out: &mut Path) {
+        if depth >= MAX_DEPTH || self.flatness() <= tolerance {
+            out.push(self.p1);
+        } else {
+            let (l, r) = self.split(0.5);
+            l.flatten_into(tolerance, depth + 1, out);
+            r.flatten_into(tolerance, depth + 1, out);
+        }
+    }
+}
+
+// Part of a circle, kept whole until emission so it can go out as a single
+// G2/G3 move. Angles are in radians counterclockwise from +x, a negative
+// sweep turns clockwise.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct CircularArc {
+    pub center: (f32, f32),
+    pub radius: f32,
+    pub start: f32,
+    pub sweep: f32,
+}
+
+impl CircularArc {
+    pub fn new(center: (f32, f32), radius: f32, start: f32, sweep: f32) -> Self {
+        CircularArc {
+            center,
+            radius,
+            start,
+            sweep,
+        }
+    }
+
+    // Point at `t` of the way along the arc
+    pub fn point(&self, t: f32) -> (f32, f32) {
+        let angle = self.start + self.sweep * t;
+        (
+            self.center.0 + self.radius * angle.cos(),
+            self.center.1 + self.radius * angle.sin(),
+        )
+    }
+
+    pub fn length(&self) -> f32 {
+        self.radius * self.sweep.abs()
+    }
+
+    pub fn clockwise(&self) -> bool {
+        self.sweep < 0.0
+    }
+
+    // Polyline whose chords stay within `tolerance` mm of the arc
+    pub fn flatten(&self, tolerance: f32) -> Path {
+        let tolerance = tolerance.max(1e-4);
+        let step = if tolerance >= self.radius {
+            FRAC_PI_2
+        } else {
+            2.0 * (1.0 - tolerance / self.radius).acos()
+        };
+        let n = ((self.sweep.abs() / step).ceil() as usize).max(1);
+        (0..=n).map(|i| self.point(i as f32 / n as f32)).collect()
+    }
+
+    // Bézier segments of at most a quarter turn each, close enough to the
+    // arc for any pen
+    pub fn beziers(&self) -> Vec<CubicBezier> {
+        let n = ((self.sweep.abs() / FRAC_PI_2).ceil() as usize).max(1);
+        let step = self.sweep / n as f32;
+        let k = 4.0 / 3.0 * (step / 4.0).tan();
+        (0..n)
+            .map(|i| {
+                let (a0, a1) = (
+                    self.start + step * i as f32,
+                    self.start + step * (i + 1) as f32,
+                );
+                let p0 = self.point(i as f32 / n as f32);
+                let p1 = self.point((i + 1) as f32 / n as f32);
+                let r = self.radius;
+                CubicBezier::new(
+                    p0,
+                    (p0.0 - k * r * a0.sin(), p0.1 + k * r * a0.cos()),
+                    (p1.0 + k * r * a1.sin(), p1.1 - k * r * a1.cos()),
+                    p1,
+                )
+            })
+            .collect()
+    }
+
+    // The arc under `t`, if `t` keeps circles round (no shear and the same
+    // scale along both axes)
+    pub fn transformed(&self, t: &AffineTransform) -> Option<CircularArc> {
+        let mirrored = t.determinant() < 0.0;
+        let (c, d) = if mirrored { (-t.c, -t.d) } else { (t.c, t.d) };
+        let tolerance = 1e-4 * t.scale_factor().max(1.0);
+        if (t.a - d).abs() > tolerance || (t.b + c).abs() > tolerance {
+            return None;
+        }
+        let center = t.apply(self.center);
+        let start = t.apply(self.point(0.0));
+        Some(CircularArc {
+            center,
+            radius: self.radius * t.scale_factor(),
+            start: (start.1 - center.1).atan2(start.0 - center.0),
+            sweep: if mirrored { -self.sweep } else { self.sweep },
+        })
+    }
+}
+
+// Flatten a chain of Bézier segments into a single polyline
+pub fn flatten(curves: &[CubicBezier], tolerance: f32) -> Path {
+    let mut out: Path = Vec::new();
+    for curve in curves {
+        let mut pts = curve.flatten(tolerance);
+        if !out.is_empty() {
+            pts.remove(0);
+        }
+        out.append(&mut pts);
+    }
+    out
+}
+
+// Uniform Catmull-Rom spline through `waypoints`, returned as the equivalent
+// cubic Bézier segments (one per pair of consecutive waypoints). The curve
+// passes through every waypoint; the end tangents are taken from the first and
+// last segments. If the first and last waypoints are equal the spline is
+// closed smoothly.
+pub fn catmull_rom(waypoints: &[(f32, f32)]) -> Vec<CubicBezier> {
+    let closed = path::is_closed(waypoints);
+    let pts = if closed {
+        &waypoints[..waypoints.len() - 1]
+    } else {
+        waypoints
+    };
+    let n = pts.len();
+    if n < 2 {
+        return Vec::new();
+    }
+
+    let at = |i: isize| -> (f32, f32) {
+        if closed {
+            pts[i.rem_euclid(n as isize) as usize]
+        } else {
+            pts[i.clamp(0, n as isize - 1) as usize]
+        }
+    };
+
+    let segments = if closed { n } else { n - 1 };
+    (0..segments as isize)
+        .map(|i| {
+            let (p0, p1, p2, p3) = (at(i - 1), at(i), at(i + 1), at(i + 2));
+            CubicBezier::new(
+                p1,
+                (p1.0 + (p2.0 - p0.0) / 6.0, p1.1 + (p2.1 - p0.1) / 6.0),
+                (p2.0 - (p3.0 - p1.0) / 6.0, p2.1 - (p3.1 - p1.1) / 6.0),
+                p2,
+            )
+        })
+        .collect()
+}
+
+// Dense stroke through `waypoints`, flattened to within `tolerance` mm
+pub fn spline(waypoints: &[(f32, f32)], tolerance: f32) -> Path {
+    if waypoints.len() < 3 {
+        return waypoints.to_vec();
+    }
+    flatten(&catmull_rom(waypoints), tolerance)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bezier_endpoints_and_split() {
+        let b = CubicBezier::new((0.0, 0.0), (0.0, 10.0), (10.0, 10.0), (10.0, 0.0));
+        assert_eq!(b.point(0.0), (0.0, 0.0));
+        assert_eq!(b.point(1.0), (10.0, 0.0));
+        assert_eq!(b.point(0.5), (5.0, 7.5));
+
+        let (l, r) = b.split(0.5);
+        assert_eq!(l.p1, (5.0, 7.5));
+        assert_eq!(r.p0, (5.0, 7.5));
+    }
+
+    #[test]
+    fn flatten_tolerance() {
+        let b = CubicBezier::new((0.0, 0.0), (0.0, 10.0), (10.0, 10.0), (10.0, 0.0));
+        let coarse = b.flatten(1.0);
+        let fine = b.flatten(0.01);
+        assert!(coarse.len() < fine.len());
+        assert_eq!(*fine.last().unwrap(), (10.0, 0.0));
+
+        for i in 0..=20 {
+            let p = b.point(i as f32 / 20.0);
+            let closest = fine
+                .iter()
+                .map(|q| path::dist(p, *q))
+                .fold(f32::MAX, f32::min);
+            assert!(closest < 0.5);
+        }
+    }
+
+    #[test]
+    fn spline_through_waypoints() {
+        let waypoints = vec![(0.0, 0.0), (10.0, 5.0), (20.0, 0.0), (30.0, 5.0)];
+        let curves = catmull_rom(&waypoints);
+        assert_eq!(curves.len(), 3);
+        for (c, w) in curves.iter().zip(waypoints.windows(2)) {
+            assert_eq!(c.p0, w[0]);
+            assert_eq!(c.p1, w[1]);
+        }
+
+        let stroke = spline(&waypoints, 0.05);
+        for w in &waypoints {
+            assert!(stroke.contains(w));
+        }
+
+        let closed = vec![(0.0, 0.0), (10.0, 0.0), (10.0, 10.0), (0.0, 0.0)];
+        let curves = catmull_rom(&closed);
+        assert_eq!(curves.len(), 3);
+        assert_eq!(curves[2].p1, (0.0, 0.0));
+    }
+
+    #[test]
+    fn arcs() {
+        let quarter = CircularArc::new((10.0, 0.0), 10.0, 0.0, FRAC_PI_2);
+        assert_eq!(quarter.point(0.0), (20.0, 0.0));
+        let end = quarter.point(1.0);
+        assert!(path::dist(end, (10.0, 10.0)) < 1e-4);
+        assert!((quarter.length() - 5.0 * std::f32::consts::PI).abs() < 1e-4);
+
+        let flat = quarter.flatten(0.05);
+        for w in flat.windows(2) {
+            let mid = path::lerp(w[0], w[1], 0.5);
+            assert!(10.0 - path::dist(mid, (10.0, 0.0)) <= 0.05);
+        }
+        // The Béziers stay on the circle
+        let circle = CircularArc::new((0.0, 0.0), 5.0, 1.0, -2.0 * std::f32::consts::PI);
+        let beziers = circle.beziers();
+        assert_eq!(beziers.len(), 4);
+        for p in flatten(&beziers, 0.01) {
+            assert!((path::dist(p, (0.0, 0.0)) - 5.0).abs() < 0.01, "{:?}", p);
+        }
+
+        // Rotated, scaled and mirrored it's still an arc, stretched it isn't
+        let t = AffineTransform::rotate(FRAC_PI_2)
+            .then(&AffineTransform::scale(-2.0, 2.0))
+            .then(&AffineTransform::translate(1.0, 1.0));
+        let moved = quarter.transformed(&t).unwrap();
+        assert_eq!(moved.radius, 20.0);
+        assert!(moved.clockwise());
+        for i in 0..=4 {
+            let u = i as f32 / 4.0;
+            assert!(path::dist(moved.point(u), t.apply(quarter.point(u))) < 1e-3);
+        }
+        assert!(
+            quarter
+                .transformed(&AffineTransform::scale(2.0, 1.0))
+                .is_none()
+        );
+    }
+}
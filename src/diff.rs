@@ -0,0 +1,542 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::Error;
+use crate::curve::{self, CubicBezier};
+use crate::parse::{strip, words};
+use crate::path::{self, Path};
+use crate::sim::{self, Segment};
+
+// What a program draws, with everything that doesn't end up on paper left out
+#[derive(Debug, Clone, PartialEq)]
+pub struct Stroke {
+    // A single point for a dot
+    pub points: Path,
+    // Feed of the first pen-down move, 0 for dots
+    pub feed: f32,
+}
+
+// A move read from G-code text. Besides z, the pen can be lowered with
+// M3/M5-style switches or an M280 servo, so every move keeps all three.
+struct RawMove {
+    from: (f32, f32),
+    to: (f32, f32),
+    feed: f32,
+    // Height, switch (0 on, 1 off) and servo angle before and after the move.
+    // Unknown values are infinite.
+    levels: [(f32, f32); 3],
+}
+
+// Replays G-code text like sim::simulate does for generated programs. The z
+// of the segments is the level of whatever lifts the pen: the Z axis, a
+// switch like M3/M5 or M106/M107, or an M280 servo, in that order of
+// preference. The lowest level seen counts as pen down.
+pub fn segments(text: &str) -> Vec<Segment> {
+    const UNKNOWN: f32 = f32::INFINITY;
+    let mut moves: Vec<RawMove> = Vec::new();
+    let mut pos = (0.0, 0.0);
+    let mut levels = [UNKNOWN; 3];
+    let mut feed = 0.0;
+    let mut relative = false;
+    let mut motion = 0;
+
+    for line in text.lines() {
+        let words = words(&strip(line));
+        let get = |letter| words.iter().find(|(c, _)| *c == letter).map(|w| w.1);
+        let code = |letter| get(letter).map(|v| v.round() as i32);
+
+        let before = levels;
+        match (code('G'), code('M')) {
+            (Some(0 | 1 | 5), _) | (None, None) => {}
+            (Some(28), _) => {
+                pos = (0.0, 0.0);
+                levels[0] = UNKNOWN;
+                continue;
+            }
+            (Some(90), _) => relative = false,
+            (Some(91), _) => relative = true,
+            (Some(92), _) => {
+                pos = (get('X').unwrap_or(pos.0), get('Y').unwrap_or(pos.1));
+                levels[0] = get('Z').unwrap_or(levels[0]);
+                continue;
+            }
+            (_, Some(3 | 4 | 106)) => levels[1] = 0.0,
+            (_, Some(5 | 107)) => levels[1] = 1.0,
+            (_, Some(280)) => levels[2] = get('S').unwrap_or(levels[2]),
+            _ => continue,
+        }
+        if levels != before {
+            moves.push(RawMove {
+                from: pos,
+                to: pos,
+                feed: 0.0,
+                levels: std::array::from_fn(|i| (before[i], levels[i])),
+            });
+            continue;
+        }
+
+        if let Some(g @ (0 | 1 | 5)) = code('G') {
+            motion = g;
+        }
+        if !words.iter().any(|(c, _)| "XYZ".contains(*c)) {
+            feed = get('F').unwrap_or(feed);
+            continue;
+        }
+        feed = get('F').unwrap_or(feed);
+
+        let axis = |letter, at: f32| match get(letter) {
+            Some(v) if relative => at + v,
+            Some(v) => v,
+            None => at,
+        };
+        let to = (axis('X', pos.0), axis('Y', pos.1));
+        let z = if relative && levels[0].is_infinite() {
+            levels[0]
+        } else {
+            axis('Z', levels[0])
+        };
+
+        let points = if motion == 5 {
+            let c0 = (
+                pos.0 + get('I').unwrap_or(0.0),
+                pos.1 + get('J').unwrap_or(0.0),
+            );
+            let c1 = (
+                to.0 + get('P').unwrap_or(0.0),
+                to.1 + get('Q').unwrap_or(0.0),
+            );
+            CubicBezier::new(pos, c0, c1, to).flatten(curve::TOLERANCE)[1..].to_vec()
+        } else {
+            vec![to]
+        };
+        for p in points {
+            moves.push(RawMove {
+                from: pos,
+                to: p,
+                feed,
+                levels: [
+                    (levels[0], z),
+                    (levels[1], levels[1]),
+                    (levels[2], levels[2]),
+                ],
+            });
+            pos = p;
+        }
+        levels[0] = z;
+    }
+
+    // Switches win over servos, which win over the Z axis
+    let finite = |i: usize| {
+        moves
+            .iter()
+            .flat_map(|m| [m.levels[i].0, m.levels[i].1])
+            .filter(|l| l.is_finite())
+            .collect::<Vec<f32>>()
+    };
+    let servo = finite(2);
+    let mode = if !finite(1).is_empty() {
+        1
+    } else if servo.iter().any(|&a| a != servo[0]) {
+        2
+    } else {
+        0
+    };
+    let lowest = finite(mode).into_iter().fold(UNKNOWN, f32::min);
+    let down = |l: f32| l.is_finite() && l <= lowest + 1e-4;
+
+    moves
+        .iter()
+        .map(|m| {
+            let (l0, l1) = m.levels[mode];
+            Segment {
+                from: (m.from.0, m.from.1, l0),
+                to: (m.to.0, m.to.1, l1),
+                feed: m.feed,
+                pen_down: down(l0) && down(l1),
+                cornering: 0.0,
+            }
+        })
+        .collect()
+}
+
+// Strokes and dots in the order they are drawn
+pub fn strokes(segments: &[Segment]) -> Vec<Stroke> {
+    let mut strokes: Vec<Stroke> = Vec::new();
+    let mut drawing = false;
+    for (i, s) in segments.iter().enumerate() {
+        if s.pen_down {
+            if s.from_xy() == s.to_xy() {
+                continue;
+            }
+            match strokes.last_mut() {
+                Some(stroke) if drawing => stroke.points.push(s.to_xy()),
+                _ => strokes.push(Stroke {
+                    points: vec![s.from_xy(), s.to_xy()],
+                    feed: s.feed,
+                }),
+            }
+            drawing = true;
+        } else {
+            drawing = false;
+            if sim::is_dot(s, segments.get(i + 1)) {
+                strokes.push(Stroke {
+                    points: vec![s.to_xy()],
+                    feed: 0.0,
+                });
+            }
+        }
+    }
+    strokes
+}
+
+// A difference between two programs, with stroke indices into the old and
+// the new list of strokes
+#[derive(Debug, Clone, PartialEq)]
+pub enum Change {
+    Added {
+        index: usize,
+        start: (f32, f32),
+        length: f32,
+    },
+    Removed {
+        index: usize,
+        start: (f32, f32),
+        length: f32,
+    },
+    // Same shape somewhere else
+    Moved {
+        old: usize,
+        new: usize,
+        offset: (f32, f32),
+    },
+    Feed {
+        old: usize,
+        new: usize,
+        from: f32,
+        to: f32,
+    },
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Change::Added {
+                index,
+                start,
+                length,
+            } => write!(
+                f,
+                "+ stroke {}: {:.1}mm from ({:.1}, {:.1})",
+                index, length, start.0, start.1
+            ),
+            Change::Removed {
+                index,
+                start,
+                length,
+            } => write!(
+                f,
+                "- stroke {}: {:.1}mm from ({:.1}, {:.1})",
+                index, length, start.0, start.1
+            ),
+            Change::Moved { old, new, offset } => write!(
+                f,
+                "~ stroke {} -> {}: moved by ({:.2}, {:.2})",
+                old, new, offset.0, offset.1
+            ),
+            Change::Feed { old, new, from, to } => write!(
+                f,
+                "~ stroke {} -> {}: feed F{:.0} -> F{:.0}",
+                old, new, from, to
+            ),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Diff {
+    pub changes: Vec<Change>,
+    // Strokes that are drawn the same in both, possibly in another order or
+    // direction
+    pub unchanged: usize,
+    pub reversed: usize,
+    // Places where the new program draws matching strokes out of the old order
+    pub reordered: usize,
+    // Pen-up distance between the strokes of the old and the new program
+    pub travel: (f32, f32),
+}
+
+impl Diff {
+    // Nothing drawn differently, the order and travel may still differ
+    pub fn same_drawing(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.same_drawing() && self.reversed == 0 && self.reordered == 0
+    }
+}
+
+impl fmt::Display for Diff {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for change in &self.changes {
+            writeln!(f, "{}", change)?;
+        }
+        writeln!(
+            f,
+            "{} unchanged ({} reversed), {} order changes",
+            self.unchanged, self.reversed, self.reordered
+        )?;
+        write!(f, "travel {:.1}mm -> {:.1}mm", self.travel.0, self.travel.1)
+    }
+}
+
+fn travel(strokes: &[Stroke]) -> f32 {
+    strokes
+        .windows(2)
+        .map(|w| path::dist(*w[0].points.last().unwrap(), w[1].points[0]))
+        .sum()
+}
+
+// Whether `b` is `a` moved by `offset`, point by point
+fn same_points(a: &[(f32, f32)], b: &[(f32, f32)], offset: (f32, f32), tolerance: f32) -> bool {
+    a.len() == b.len()
+        && a.iter()
+            .zip(b)
+            .all(|(p, q)| path::dist((p.0 + offset.0, p.1 + offset.1), *q) <= tolerance)
+}
+
+// The offset that moves `a` onto `b`, and whether it's drawn the other way
+fn placement(a: &Stroke, b: &Stroke, tolerance: f32) -> Option<((f32, f32), bool)> {
+    let offset = |p: (f32, f32), q: (f32, f32)| (q.0 - p.0, q.1 - p.1);
+    let forward = offset(a.points[0], b.points[0]);
+    if same_points(&a.points, &b.points, forward, tolerance) {
+        return Some((forward, false));
+    }
+    let reversed: Path = b.points.iter().rev().copied().collect();
+    let backward = offset(a.points[0], reversed[0]);
+    same_points(&a.points, &reversed, backward, tolerance).then_some((backward, true))
+}
+
+type Key = (usize, (i64, i64), (i64, i64));
+
+// Lookup key for strokes that may match. Strokes straddling a grid line can
+// be missed, so the grid is coarser than the tolerance.
+fn key(stroke: &Stroke, tolerance: f32, moved: bool) -> Key {
+    let cell = (tolerance * 4.0).max(1e-3);
+    let q = |p: (f32, f32)| ((p.0 / cell).round() as i64, (p.1 / cell).round() as i64);
+    let (a, b) = (stroke.points[0], *stroke.points.last().unwrap());
+    let (a, b) = (q(a), q(b));
+    if moved {
+        // Only the shape: the vector between the ends, either way round
+        let d = (b.0 - a.0, b.1 - a.1);
+        let d = d.max((-d.0, -d.1));
+        (stroke.points.len(), d, (0, 0))
+    } else {
+        (stroke.points.len(), a.min(b), a.max(b))
+    }
+}
+
+// Differences between the strokes of two programs. Strokes match when their
+// points are within `tolerance`, first in place and then anywhere else.
+pub fn diff(old: &[Stroke], new: &[Stroke], tolerance: f32) -> Diff {
+    let mut result = Diff {
+        travel: (travel(old), travel(new)),
+        ..Default::default()
+    };
+    let mut matched: Vec<Option<usize>> = vec![None; new.len()];
+    let mut used = vec![false; old.len()];
+
+    for moved in [false, true] {
+        let mut candidates: HashMap<Key, Vec<usize>> = HashMap::new();
+        for (i, s) in old.iter().enumerate() {
+            if !used[i] {
+                candidates
+                    .entry(key(s, tolerance, moved))
+                    .or_default()
+                    .push(i);
+            }
+        }
+
+        for (j, s) in new.iter().enumerate() {
+            // Dots have no shape to recognize them by elsewhere
+            if matched[j].is_some() || (moved && s.points.len() < 2) {
+                continue;
+            }
+            let Some(list) = candidates.get_mut(&key(s, tolerance, moved)) else {
+                continue;
+            };
+            let found = list.iter().enumerate().find_map(|(n, &i)| {
+                let (offset, reversed) = placement(&old[i], s, tolerance)?;
+                let in_place = offset.0.hypot(offset.1) <= tolerance;
+                (in_place != moved).then_some((n, i, offset, reversed))
+            });
+            let Some((n, i, offset, reversed)) = found else {
+                continue;
+            };
+            list.swap_remove(n);
+            matched[j] = Some(i);
+            used[i] = true;
+
+            if moved {
+                result.changes.push(Change::Moved {
+                    old: i,
+                    new: j,
+                    offset,
+                });
+            } else {
+                result.unchanged += 1;
+                result.reversed += reversed as usize;
+            }
+            if (old[i].feed - s.feed).abs() > 1e-3 {
+                result.changes.push(Change::Feed {
+                    old: i,
+                    new: j,
+                    from: old[i].feed,
+                    to: s.feed,
+                });
+            }
+        }
+    }
+
+    let order: Vec<usize> = matched.iter().flatten().copied().collect();
+    result.reordered = order.windows(2).filter(|w| w[1] < w[0]).count();
+
+    for (i, s) in old.iter().enumerate() {
+        if !used[i] {
+            result.changes.push(Change::Removed {
+                index: i,
+                start: s.points[0],
+                length: path::length(&s.points),
+            });
+        }
+    }
+    for (j, s) in new.iter().enumerate() {
+        if matched[j].is_none() {
+            result.changes.push(Change::Added {
+                index: j,
+                start: s.points[0],
+                length: path::length(&s.points),
+            });
+        }
+    }
+    result
+}
+
+// Differences between two G-code programs given as text
+pub fn diff_text(old: &str, new: &str, tolerance: f32) -> Diff {
+    diff(
+        &strokes(&segments(old)),
+        &strokes(&segments(new)),
+        tolerance,
+    )
+}
+
+pub fn diff_files(old: &str, new: &str, tolerance: f32) -> Result<Diff, Error> {
+    Ok(diff_text(
+        &std::fs::read_to_string(old)?,
+        &std::fs::read_to_string(new)?,
+        tolerance,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OLD: &str = "\
+; Start of generated code
+G28 W ; Home all without mesh bed level
+G0 Z6.5 F800.0
+G0 X50.0 Y35.0 F1000.0
+G92 X0 Y0 ; Set current position to origin
+G0 X0.0 Y0.0 F1000.0
+G0 Z4.0 F500.0
+G0 X10.0 Y0.0 F1000.0
+G0 X10.0 Y10.0 F1000.0
+G0 Z6.5 F800.0
+G0 X20.0 Y20.0 F1000.0
+G0 Z4.0 F500.0
+G0 Z6.5 F800.0
+G0 X30.0 Y0.0 F1000.0
+G0 Z4.0 F500.0
+G0 X40.0 Y0.0 F1000.0
+G0 Z6.5 F800.0
+";
+
+    #[test]
+    fn read_strokes() {
+        let strokes = strokes(&segments(OLD));
+        assert_eq!(strokes.len(), 3);
+        assert_eq!(strokes[0].points, [(0.0, 0.0), (10.0, 0.0), (10.0, 10.0)]);
+        assert_eq!(strokes[0].feed, 1000.0);
+        assert_eq!(strokes[1].points, [(20.0, 20.0)]);
+
+        // Servo pens, formatting and comments
+        let text =
+            "N1 g28\nG1x5 Y5 f600 *31\nM280 P0 S90\n(note) M280 P0 S30\nG1 X5 Y15\nM280 P0 S90";
+        let strokes = super::strokes(&segments(text));
+        assert_eq!(strokes.len(), 1);
+        assert_eq!(strokes[0].points, [(5.0, 5.0), (5.0, 15.0)]);
+    }
+
+    #[test]
+    fn meaningful_changes() {
+        // Reversed, feed changed and moved, the dot removed and a dot added,
+        // with different formatting and comments
+        let new = "\
+G28 W
+G0 Z6.5
+G0 X50 Y35
+G92 X0 Y0
+G0 X10 Y10 ; reversed
+G0 Z4
+G0 X10 Y0 F1000
+G0 X0 Y0
+G0 Z6.5
+G0 X31 Y1
+G0 Z4
+G1 X41 Y1 F600
+G0 Z6.5
+G0 X60 Y60
+G0 Z4
+G0 Z6.5
+";
+        let diff = diff_text(OLD, new, 0.01);
+        assert_eq!(diff.unchanged, 1);
+        assert_eq!(diff.reversed, 1);
+        assert_eq!(
+            diff.changes,
+            [
+                Change::Moved {
+                    old: 2,
+                    new: 1,
+                    offset: (1.0, 1.0)
+                },
+                Change::Feed {
+                    old: 2,
+                    new: 1,
+                    from: 1000.0,
+                    to: 600.0
+                },
+                Change::Removed {
+                    index: 1,
+                    start: (20.0, 20.0),
+                    length: 0.0
+                },
+                Change::Added {
+                    index: 2,
+                    start: (60.0, 60.0),
+                    length: 0.0
+                },
+            ]
+        );
+        assert_eq!(
+            diff.changes[0].to_string(),
+            "~ stroke 2 -> 1: moved by (1.00, 1.00)"
+        );
+
+        // Comments and formatting alone make no difference
+        let noisy = OLD.replace("G0 ", "g0  ").replace('\n', " ; note\n");
+        assert!(diff_text(OLD, &noisy, 0.01).is_empty());
+    }
+}
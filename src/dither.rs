@@ -0,0 +1,303 @@
+use crate::Printer;
+use crate::raster::Bitmap;
+use crate::rng::Rng;
+
+// How gray is turned into dots. Every algorithm leaves its own texture on
+// paper, and puts down a different number of dots for the same image.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Dither {
+    // Error diffusion, the smoothest tones with worm-like patterns
+    FloydSteinberg,
+    // Error diffusion that drops a quarter of the error, more contrast and
+    // fewer dots in the highlights
+    Atkinson,
+    // Threshold map of this size (2, 4, 8 or 16), regular crosshatch textures
+    Bayer(usize),
+    // Threshold map of evenly spread noise, no patterns and no worms
+    BlueNoise(u64),
+}
+
+// Side of the blue noise tile in pixels
+const TILE: usize = 32;
+
+// Bayer matrix of size `n`, a power of two, with thresholds 0..n*n
+fn bayer(n: usize) -> Vec<usize> {
+    let mut matrix = vec![0];
+    let mut size = 1;
+    while size < n {
+        let mut next = vec![0; 4 * size * size];
+        for y in 0..size {
+            for x in 0..size {
+                let v = 4 * matrix[y * size + x];
+                next[y * 2 * size + x] = v;
+                next[y * 2 * size + x + size] = v + 2;
+                next[(y + size) * 2 * size + x] = v + 3;
+                next[(y + size) * 2 * size + x + size] = v + 1;
+            }
+        }
+        matrix = next;
+        size *= 2;
+    }
+    matrix
+}
+
+// Ranks of the pixels of a tile that wraps around, each one put in the
+// biggest gap left by the ones before (void and cluster without the
+// clustering pass)
+fn blue_noise(seed: u64) -> Vec<usize> {
+    let n = TILE * TILE;
+    let sigma = 1.5f32;
+    let reach = 5i64;
+    let mut energy = vec![0.0f32; n];
+    let mut rank = vec![usize::MAX; n];
+    let mut rng = Rng::new(seed);
+    for r in 0..n {
+        let i = if r == 0 {
+            rng.below(n)
+        } else {
+            (0..n)
+                .filter(|&i| rank[i] == usize::MAX)
+                .min_by(|&a, &b| energy[a].total_cmp(&energy[b]))
+                .unwrap()
+        };
+        rank[i] = r;
+        let (x, y) = ((i % TILE) as i64, (i / TILE) as i64);
+        for dy in -reach..=reach {
+            for dx in -reach..=reach {
+                let nx = (x + dx).rem_euclid(TILE as i64) as usize;
+                let ny = (y + dy).rem_euclid(TILE as i64) as usize;
+                energy[ny * TILE + nx] +=
+                    (-((dx * dx + dy * dy) as f32) / (2.0 * sigma * sigma)).exp();
+            }
+        }
+    }
+    rank
+}
+
+impl Dither {
+    // Every algorithm, with `seed` for the blue noise
+    pub fn all(seed: u64) -> [Dither; 4] {
+        [
+            Dither::FloydSteinberg,
+            Dither::Atkinson,
+            Dither::Bayer(4),
+            Dither::BlueNoise(seed),
+        ]
+    }
+
+    // Which pixels get a dot, row by row from the top
+    pub fn apply(&self, bitmap: &Bitmap) -> Vec<bool> {
+        let (w, h) = (bitmap.width as usize, bitmap.height as usize);
+        let mut gray: Vec<f32> = (0..h as i64)
+            .flat_map(|y| (0..w as i64).map(move |x| (x, y)))
+            .map(|(x, y)| bitmap.brightness(x, y))
+            .collect();
+
+        // Error diffusion spreads the difference to the pixels not done yet,
+        // as (dx, dy, share)
+        let diffuse = |gray: &mut Vec<f32>, weights: &[(i64, i64, f32)]| {
+            let mut ink = vec![false; w * h];
+            for y in 0..h {
+                for x in 0..w {
+                    let old = gray[y * w + x];
+                    let new = if old < 0.5 { 0.0 } else { 1.0 };
+                    ink[y * w + x] = new == 0.0;
+                    let error = old - new;
+                    for &(dx, dy, share) in weights {
+                        let (nx, ny) = (x as i64 + dx, y as i64 + dy);
+                        if nx >= 0 && nx < w as i64 && ny < h as i64 {
+                            gray[ny as usize * w + nx as usize] += error * share;
+                        }
+                    }
+                }
+            }
+            ink
+        };
+        // Dot where the pixel is darker than the map's threshold
+        let threshold = |gray: &[f32], map: &[usize], size: usize| {
+            let levels = (size * size) as f32;
+            (0..w * h)
+                .map(|i| {
+                    let (x, y) = (i % w % size, i / w % size);
+                    1.0 - gray[i] > (map[y * size + x] as f32 + 0.5) / levels
+                })
+                .collect()
+        };
+
+        match *self {
+            Dither::FloydSteinberg => diffuse(
+                &mut gray,
+                &[
+                    (1, 0, 7.0 / 16.0),
+                    (-1, 1, 3.0 / 16.0),
+                    (0, 1, 5.0 / 16.0),
+                    (1, 1, 1.0 / 16.0),
+                ],
+            ),
+            Dither::Atkinson => diffuse(
+                &mut gray,
+                &[
+                    (1, 0, 0.125),
+                    (2, 0, 0.125),
+                    (-1, 1, 0.125),
+                    (0, 1, 0.125),
+                    (1, 1, 0.125),
+                    (0, 2, 0.125),
+                ],
+            ),
+            Dither::Bayer(size) => {
+                let size = size.clamp(2, 16).next_power_of_two();
+                threshold(&gray, &bayer(size), size)
+            }
+            Dither::BlueNoise(seed) => threshold(&gray, &blue_noise(seed), TILE),
+        }
+    }
+}
+
+// Number of dots and estimated plot time of a dithered image
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Estimate {
+    pub dither: Dither,
+    pub dots: usize,
+    pub seconds: f32,
+}
+
+// Raster image plotted as dots, one per pixel that the dither inks
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Halftone {
+    pub dither: Dither,
+}
+
+impl Halftone {
+    pub fn new(dither: Dither) -> Self {
+        Halftone { dither }
+    }
+
+    // Dots at the centers of the inked pixels, with the bitmap stretched
+    // over min..max and its top row at max.1
+    pub fn dots(&self, bitmap: &Bitmap, min: (f32, f32), max: (f32, f32)) -> Vec<(f32, f32)> {
+        let w = bitmap.width as usize;
+        let sx = (max.0 - min.0) / bitmap.width as f32;
+        let sy = (max.1 - min.1) / bitmap.height as f32;
+        let mut dots: Vec<(f32, f32)> = Vec::new();
+        for (i, _) in self
+            .dither
+            .apply(bitmap)
+            .into_iter()
+            .enumerate()
+            .filter(|(_, d)| *d)
+        {
+            let (x, y) = (i % w, i / w);
+            dots.push((min.0 + (x as f32 + 0.5) * sx, max.1 - (y as f32 + 0.5) * sy));
+        }
+        // Every other row backwards to save travel
+        dots.chunk_by_mut(|a, b| a.1 == b.1)
+            .enumerate()
+            .filter(|(row, _)| row % 2 == 1)
+            .for_each(|(_, dots)| dots.reverse());
+        dots
+    }
+
+    // Draws the dots with the bitmap over min..max in drawing coordinates
+    pub fn draw(&self, printer: &mut Printer, bitmap: &Bitmap, min: (f32, f32), max: (f32, f32)) {
+        for (x, y) in self.dots(bitmap, min, max) {
+            printer.draw_point(x, y);
+        }
+    }
+
+    // What every algorithm would cost on `printer`'s machine, to pick one
+    // before committing to a long plot
+    pub fn estimates(
+        printer: &Printer,
+        bitmap: &Bitmap,
+        min: (f32, f32),
+        max: (f32, f32),
+        seed: u64,
+    ) -> Vec<Estimate> {
+        Dither::all(seed)
+            .into_iter()
+            .map(|dither| {
+                let mut scratch = Printer::new(printer.config.clone());
+                Halftone::new(dither).draw(&mut scratch, bitmap, min, max);
+                Estimate {
+                    dither,
+                    dots: scratch.ops.len(),
+                    seconds: scratch.stats().total.time,
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::raster;
+    use crate::tests::{assert_within, test_config};
+
+    fn gray(level: u8) -> Bitmap {
+        Bitmap::new(32, 32, [level, level, level])
+    }
+
+    #[test]
+    fn tones_become_dot_densities() {
+        for dither in Dither::all(3) {
+            let coverage = |level: u8| {
+                let ink = dither.apply(&gray(level));
+                ink.iter().filter(|&&d| d).count() as f32 / ink.len() as f32
+            };
+            assert_eq!(coverage(255), 0.0, "{:?}", dither);
+            assert_eq!(coverage(0), 1.0, "{:?}", dither);
+            let half = coverage(128);
+            assert!((half - 0.5).abs() < 0.1, "{:?} {}", dither, half);
+        }
+        // Atkinson loses some of the error, so light grays thin out
+        let light = |dither: Dither| dither.apply(&gray(200)).iter().filter(|&&d| d).count();
+        assert!(light(Dither::Atkinson) < light(Dither::FloydSteinberg));
+    }
+
+    #[test]
+    fn bayer_matrix() {
+        assert_eq!(bayer(2), [0, 2, 3, 1]);
+        let mut four = bayer(4);
+        four.sort();
+        assert_eq!(four, (0..16).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn blue_noise_spreads_evenly() {
+        let rank = blue_noise(1);
+        // The first 10% of the pixels have no neighbors among themselves
+        let first: Vec<usize> = (0..rank.len()).filter(|&i| rank[i] < 100).collect();
+        for &a in &first {
+            for &b in &first {
+                let dx = (a % TILE).abs_diff(b % TILE);
+                let dy = (a / TILE).abs_diff(b / TILE);
+                let (dx, dy) = (dx.min(TILE - dx), dy.min(TILE - dy));
+                assert!(a == b || dx > 1 || dy > 1, "{} {}", a, b);
+            }
+        }
+    }
+
+    #[test]
+    fn dither_estimates() {
+        let mut bitmap = raster::Bitmap::new(20, 20, [230, 230, 230]);
+        for x in 0..10 {
+            for y in 0..20 {
+                bitmap.set(x, y, [60, 60, 60]);
+            }
+        }
+        let printer = Printer::new(test_config());
+        let estimates = Halftone::estimates(&printer, &bitmap, (0.0, 0.0), (20.0, 20.0), 1);
+        assert_eq!(estimates.len(), 4);
+        for e in &estimates {
+            let mut plot = Printer::new(test_config());
+            Halftone::new(e.dither).draw(&mut plot, &bitmap, (0.0, 0.0), (20.0, 20.0));
+            assert_eq!(e.dots, plot.ops().len());
+            assert_within(e.seconds, plot.stats().total.time, 1e-3);
+            assert!(e.dots > 100 && e.seconds > 0.0);
+        }
+        // The printer's own drawing isn't part of the estimate
+        assert!(printer.ops().is_empty());
+    }
+}
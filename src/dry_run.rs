@@ -0,0 +1,128 @@
+use crate::{Code, Point};
+
+// An air plot: the same moves with the pen held above the paper, to check
+// where a drawing lands on the sheet before it's drawn in ink
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct DryRun {
+    // Height above the pen-down height the pen stays at, in mm
+    pub hover: f32,
+    // Feeds of the drawing moves are multiplied by this, e.g. 2.0 to run
+    // through the plot twice as fast
+    pub feed_scale: f32,
+}
+
+impl Default for DryRun {
+    fn default() -> Self {
+        DryRun {
+            hover: 2.0,
+            feed_scale: 1.0,
+        }
+    }
+}
+
+impl DryRun {
+    // `code` with the pen kept off the paper. `pen_z` is the highest height
+    // at which the pen touches the paper, `z0` the travel height.
+    pub(crate) fn apply(&self, code: Code, pen_z: f32, z0: f32) -> Code {
+        let feed = |f: f32| f * self.feed_scale;
+        let hover = |z: f32| {
+            if z <= pen_z + 1e-4 {
+                (pen_z + self.hover).min(z0)
+            } else {
+                z
+            }
+        };
+        match code {
+            Code::Move(p, f) => {
+                let f = if p.x.is_some() || p.y.is_some() {
+                    feed(f)
+                } else {
+                    f
+                };
+                Code::Move(
+                    Point {
+                        z: p.z.map(hover),
+                        ..p
+                    },
+                    f,
+                )
+            }
+            Code::Rapid(p, f) => Code::Rapid(
+                Point {
+                    z: p.z.map(hover),
+                    ..p
+                },
+                f,
+            ),
+            Code::Bezier(curve, f) => Code::Bezier(curve, feed(f)),
+            Code::Arc(arc, f) => Code::Arc(arc, feed(f)),
+            // Servos, lasers and the like just don't go down
+            Code::Pen(true, line) => Code::Comment(format!("Dry run: {}", line)),
+            code => code,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Printer;
+    use crate::tests::{temp_path, test_config};
+
+    #[test]
+    fn hovers() {
+        let dry = DryRun {
+            hover: 1.5,
+            feed_scale: 2.0,
+        };
+        let apply = |x: Option<f32>, z: f32, feed: f32, z0: f32| {
+            let code = Code::Move(
+                Point {
+                    x,
+                    y: None,
+                    z: Some(z),
+                },
+                feed,
+            );
+            dry.apply(code, 4.0, z0).to_string()
+        };
+        assert_eq!(apply(None, 4.0, 500.0, 6.5), "G1 Z5.5 F500.0");
+        assert_eq!(apply(Some(1.0), 3.8, 1000.0, 6.5), "G1 X1.0 Z5.5 F2000.0");
+        // Travel height stays, and the hover never goes above it
+        assert_eq!(apply(None, 6.5, 800.0, 6.5), "G1 Z6.5 F800.0");
+        assert_eq!(apply(None, 4.0, 500.0, 5.0), "G1 Z5.0 F500.0");
+
+        let code = dry.apply(Code::Pen(true, "M3 S800".to_string()), 4.0, 6.5);
+        assert_eq!(code.to_string(), "; Dry run: M3 S800");
+    }
+
+    #[test]
+    fn dry_runs() {
+        let mut printer = Printer::new(test_config());
+        printer.draw_polyline(&[(0.0, 0.0), (10.0, 0.0), (10.0, 10.0)]);
+        printer.draw_point(20.0, 5.0);
+        let filename = temp_path("dry_run.gcode");
+        let filename = filename.to_str().unwrap();
+        let dry = DryRun {
+            hover: 1.0,
+            feed_scale: 2.0,
+        };
+        printer.save_dry_run(filename, &dry).unwrap();
+        let text = std::fs::read_to_string(filename).unwrap();
+        std::fs::remove_file(filename).unwrap();
+
+        let mut inked = Vec::new();
+        printer.write_to(&mut inked).unwrap();
+        let inked = String::from_utf8(inked).unwrap();
+        let xy = |text: &str| -> Vec<String> {
+            text.lines()
+                .filter(|l| l.starts_with("G1 X") || l.starts_with("G1 X"))
+                .map(|l| l.split(" F").next().unwrap().to_string())
+                .collect()
+        };
+        assert_eq!(xy(&text), xy(&inked));
+        assert!(!text.contains("Z4.0"));
+        assert_eq!(text.matches("G1 Z5.0 F500.0").count(), 2);
+        assert!(text.contains("G1 X10.0 Y0.0 F2000.0"));
+    }
+}
@@ -0,0 +1,118 @@
+use std::collections::HashMap;
+
+use crate::noise;
+use crate::path;
+
+// Pauses that give the ink time to dry before the next pass goes over it
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Drying {
+    // Length of every pause, in seconds
+    pub seconds: f32,
+    // Pause whenever the drawing moves on to another layer
+    pub after_layer: bool,
+    // Pause once any region has received more than this much ink since the
+    // last pause, in mm drawn per mm² of paper
+    pub density: Option<f32>,
+    // Size of the square regions for the density, in mm
+    pub region: f32,
+    // Where to wait with the pen up, so it doesn't hover over wet ink
+    pub park: Option<(f32, f32)>,
+}
+
+impl Drying {
+    pub fn new(seconds: f32) -> Self {
+        Drying {
+            seconds,
+            after_layer: true,
+            density: None,
+            region: 10.0,
+            park: None,
+        }
+    }
+}
+
+// Drawn length per region since the last pause
+#[derive(Debug, Default)]
+pub struct Ink {
+    cells: HashMap<(i64, i64), f32>,
+}
+
+impl Ink {
+    pub fn add(&mut self, stroke: &[(f32, f32)], region: f32) {
+        let region = region.max(1e-3);
+        // Short pieces so every bit of ink lands in the right region
+        for w in noise::split_long(stroke, region / 2.0).windows(2) {
+            let mid = path::lerp(w[0], w[1], 0.5);
+            let cell = (
+                (mid.0 / region).floor() as i64,
+                (mid.1 / region).floor() as i64,
+            );
+            *self.cells.entry(cell).or_default() += path::dist(w[0], w[1]);
+        }
+    }
+
+    // Highest density of any region, in mm per mm²
+    pub fn max_density(&self, region: f32) -> f32 {
+        let area = region * region;
+        self.cells.values().fold(0.0, |m, &ink| m.max(ink / area))
+    }
+
+    pub fn clear(&mut self) {
+        self.cells.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::test_config;
+    use crate::{Code, Printer};
+
+    #[test]
+    fn ink_per_region() {
+        let mut ink = Ink::default();
+        // 20mm across two regions, then 10mm more in the first one
+        ink.add(&[(0.0, 5.0), (20.0, 5.0)], 10.0);
+        ink.add(&[(5.0, 0.0), (5.0, 10.0)], 10.0);
+        assert!((ink.max_density(10.0) - 0.2).abs() < 1e-4);
+
+        ink.clear();
+        assert_eq!(ink.max_density(10.0), 0.0);
+    }
+
+    #[test]
+    fn drying_pauses() {
+        let mut config = test_config();
+        config.drying = Some(Drying {
+            park: Some((0.0, 150.0)),
+            ..Drying::new(30.0)
+        });
+        let mut printer = Printer::new(config.clone());
+        printer.draw_polyline(&[(0.0, 0.0), (10.0, 0.0)]);
+        printer.set_layer("red");
+        printer.draw_polyline(&[(0.0, 0.0), (10.0, 0.0)]);
+        printer.draw_polyline(&[(0.0, 1.0), (10.0, 1.0)]);
+
+        let code: Vec<String> = printer.codes().iter().map(|c| c.to_string()).collect();
+        let at = code.iter().position(|l| l == "G4 P30000").unwrap();
+        assert_eq!(code[at - 1], "G1 X0.0 Y150.0 F1000.0");
+        assert_eq!(code.iter().filter(|l| l.starts_with("G4")).count(), 1);
+        assert!(printer.stats().total.time > 30.0);
+
+        // Dense hatching pauses on its own
+        config.drying = Some(Drying {
+            after_layer: false,
+            density: Some(0.5),
+            ..Drying::new(30.0)
+        });
+        let mut printer = Printer::new(config);
+        for i in 0..20 {
+            let y = i as f32 * 0.5;
+            printer.draw_polyline(&[(0.0, y), (10.0, y)]);
+        }
+        let code = printer.codes();
+        let pauses = code.iter().filter(|c| matches!(c, Code::Dwell(_))).count();
+        // 10mm of ink per stroke in a 10x10mm region, pause after every 6th
+        assert_eq!(pauses, 3);
+    }
+}
@@ -0,0 +1,279 @@
+use crate::Printer;
+use crate::path::{self, Path};
+use crate::raster::Bitmap;
+
+// Line drawing of a photo: Canny edge detection on the bitmap, with the edge
+// pixels traced into strokes. Thresholds are fractions of the strongest
+// gradient in the image, so they work the same for dim and bright photos.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct EdgeDetect {
+    // Gaussian blur before detection, in pixels. Larger ignores finer detail.
+    pub blur: f32,
+    // Edges are followed down to `low` once they reach `high` somewhere
+    pub low: f32,
+    pub high: f32,
+    // Strokes shorter than this many pixels are dropped as noise
+    pub min_length: usize,
+    // Tolerance for simplifying the traced strokes, in pixels
+    pub simplify: f32,
+}
+
+// The eight neighbors, the four straight ones first so traces prefer them
+const NEIGHBORS: [(i64, i64); 8] = [
+    (1, 0),
+    (0, 1),
+    (-1, 0),
+    (0, -1),
+    (1, 1),
+    (-1, 1),
+    (-1, -1),
+    (1, -1),
+];
+
+fn blur(values: &[f32], width: usize, height: usize, sigma: f32) -> Vec<f32> {
+    if sigma <= 0.0 {
+        return values.to_vec();
+    }
+    let reach = (3.0 * sigma).ceil() as i64;
+    let kernel: Vec<f32> = (-reach..=reach)
+        .map(|i| (-(i * i) as f32 / (2.0 * sigma * sigma)).exp())
+        .collect();
+    let total: f32 = kernel.iter().sum();
+
+    // Rows then columns, repeating the border pixels
+    let pass = |input: &[f32], horizontal: bool| -> Vec<f32> {
+        let mut out = vec![0.0; input.len()];
+        for y in 0..height as i64 {
+            for x in 0..width as i64 {
+                let mut sum = 0.0;
+                for (k, w) in (-reach..=reach).zip(&kernel) {
+                    let (sx, sy) = if horizontal { (x + k, y) } else { (x, y + k) };
+                    let sx = sx.clamp(0, width as i64 - 1) as usize;
+                    let sy = sy.clamp(0, height as i64 - 1) as usize;
+                    sum += w * input[sy * width + sx];
+                }
+                out[y as usize * width + x as usize] = sum / total;
+            }
+        }
+        out
+    };
+    pass(&pass(values, true), false)
+}
+
+// Follows runs of set pixels in `mask` (row by row from the top, the size of
+// `bitmap`) into strokes. Runs shorter than `min_length` pixels are dropped,
+// the rest are simplified by `tolerance` pixels and stretched over `area`
+// with the top row at its top.
+pub(crate) fn trace(
+    mut left: Vec<bool>,
+    bitmap: &Bitmap,
+    min_length: usize,
+    tolerance: f32,
+    (min, max): ((f32, f32), (f32, f32)),
+) -> Vec<Path> {
+    let (w, h) = (bitmap.width as usize, bitmap.height as usize);
+    let neighbors = |left: &[bool], i: usize| -> Vec<usize> {
+        let (x, y) = ((i % w) as i64, (i / w) as i64);
+        NEIGHBORS
+            .iter()
+            .map(|(dx, dy)| (x + dx, y + dy))
+            .filter(|&(nx, ny)| nx >= 0 && ny >= 0 && nx < w as i64 && ny < h as i64)
+            .map(|(nx, ny)| ny as usize * w + nx as usize)
+            .filter(|&n| left[n])
+            .collect()
+    };
+
+    // Start at the loose ends first, whatever is left after that is loops
+    let ends: Vec<usize> = (0..w * h)
+        .filter(|&i| left[i] && neighbors(&left, i).len() <= 1)
+        .collect();
+    let rest = 0..w * h;
+    let mut traces: Vec<Vec<usize>> = Vec::new();
+    for start in ends.into_iter().chain(rest) {
+        if !left[start] {
+            continue;
+        }
+        left[start] = false;
+        let mut trace = vec![start];
+        while let Some(&next) = neighbors(&left, *trace.last().unwrap()).first() {
+            left[next] = false;
+            trace.push(next);
+        }
+        // Close loops that came back next to where they started
+        let (first, last) = (trace[0], *trace.last().unwrap());
+        let (dx, dy) = (
+            (first % w) as i64 - (last % w) as i64,
+            (first / w) as i64 - (last / w) as i64,
+        );
+        if trace.len() > 2 && dx.abs() <= 1 && dy.abs() <= 1 {
+            trace.push(first);
+        }
+        traces.push(trace);
+    }
+
+    let sx = (max.0 - min.0) / w as f32;
+    let sy = (max.1 - min.1) / h as f32;
+    traces
+        .into_iter()
+        .filter(|t| t.len() >= min_length.max(2))
+        .map(|trace| {
+            let pixels: Path = trace
+                .iter()
+                .map(|&i| ((i % w) as f32 + 0.5, (i / w) as f32 + 0.5))
+                .collect();
+            path::simplify(&pixels, tolerance)
+                .into_iter()
+                .map(|(x, y)| (min.0 + x * sx, max.1 - y * sy))
+                .collect()
+        })
+        .collect()
+}
+
+impl EdgeDetect {
+    pub fn new() -> Self {
+        EdgeDetect {
+            blur: 1.4,
+            low: 0.1,
+            high: 0.3,
+            min_length: 5,
+            simplify: 0.5,
+        }
+    }
+
+    // Which pixels are on an edge, row by row from the top
+    pub fn edges(&self, bitmap: &Bitmap) -> Vec<bool> {
+        let (w, h) = (bitmap.width as usize, bitmap.height as usize);
+        let gray: Vec<f32> = (0..h as i64)
+            .flat_map(|y| (0..w as i64).map(move |x| (x, y)))
+            .map(|(x, y)| bitmap.brightness(x, y))
+            .collect();
+        let gray = blur(&gray, w, h, self.blur);
+        let at = |x: i64, y: i64| {
+            gray[y.clamp(0, h as i64 - 1) as usize * w + x.clamp(0, w as i64 - 1) as usize]
+        };
+
+        // Sobel gradients
+        let mut magnitude = vec![0.0; w * h];
+        let mut direction = vec![(0, 0); w * h];
+        for y in 0..h as i64 {
+            for x in 0..w as i64 {
+                let gx = at(x + 1, y - 1) + 2.0 * at(x + 1, y) + at(x + 1, y + 1)
+                    - at(x - 1, y - 1)
+                    - 2.0 * at(x - 1, y)
+                    - at(x - 1, y + 1);
+                let gy = at(x - 1, y + 1) + 2.0 * at(x, y + 1) + at(x + 1, y + 1)
+                    - at(x - 1, y - 1)
+                    - 2.0 * at(x, y - 1)
+                    - at(x + 1, y - 1);
+                let i = y as usize * w + x as usize;
+                magnitude[i] = (gx * gx + gy * gy).sqrt();
+                // Gradient direction rounded to one of four neighbors
+                let angle = gy.atan2(gx).to_degrees().rem_euclid(180.0);
+                direction[i] = match angle {
+                    a if !(22.5..157.5).contains(&a) => (1, 0),
+                    a if a < 67.5 => (1, 1),
+                    a if a < 112.5 => (0, 1),
+                    _ => (-1, 1),
+                };
+            }
+        }
+        let strongest = magnitude.iter().copied().fold(0.0, f32::max);
+        if strongest <= f32::EPSILON {
+            return vec![false; w * h];
+        }
+
+        // Thin the edges to the local maxima across them. Ties keep one side
+        // so a sharp step gives a single line of pixels.
+        let mag = |x: i64, y: i64| {
+            if x < 0 || y < 0 || x >= w as i64 || y >= h as i64 {
+                0.0
+            } else {
+                magnitude[y as usize * w + x as usize]
+            }
+        };
+        let mut thin = vec![0.0; w * h];
+        for y in 0..h as i64 {
+            for x in 0..w as i64 {
+                let i = y as usize * w + x as usize;
+                let (dx, dy) = direction[i];
+                let m = magnitude[i];
+                if m >= mag(x + dx, y + dy) && m > mag(x - dx, y - dy) {
+                    thin[i] = m / strongest;
+                }
+            }
+        }
+
+        // Hysteresis: grow from the strong pixels through the weak ones
+        let mut edge = vec![false; w * h];
+        let mut stack: Vec<usize> = (0..w * h).filter(|&i| thin[i] >= self.high).collect();
+        for &i in &stack {
+            edge[i] = true;
+        }
+        while let Some(i) = stack.pop() {
+            let (x, y) = ((i % w) as i64, (i / w) as i64);
+            for (dx, dy) in NEIGHBORS {
+                let (nx, ny) = (x + dx, y + dy);
+                if nx < 0 || ny < 0 || nx >= w as i64 || ny >= h as i64 {
+                    continue;
+                }
+                let n = ny as usize * w + nx as usize;
+                if !edge[n] && thin[n] >= self.low {
+                    edge[n] = true;
+                    stack.push(n);
+                }
+            }
+        }
+        edge
+    }
+
+    // Edges traced into strokes, with the bitmap stretched over min..max and
+    // its top row at max.1
+    pub fn strokes(&self, bitmap: &Bitmap, min: (f32, f32), max: (f32, f32)) -> Vec<Path> {
+        let edges = self.edges(bitmap);
+        trace(edges, bitmap, self.min_length, self.simplify, (min, max))
+    }
+
+    // Draws the strokes with the bitmap over min..max in drawing coordinates
+    pub fn draw(&self, printer: &mut Printer, bitmap: &Bitmap, min: (f32, f32), max: (f32, f32)) {
+        for stroke in self.strokes(bitmap, min, max) {
+            printer.draw_polyline(&stroke);
+        }
+    }
+}
+
+impl Default for EdgeDetect {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn outlines_a_square() {
+        let mut bitmap = Bitmap::new(40, 40, [255, 255, 255]);
+        for y in 10..30 {
+            for x in 10..30 {
+                bitmap.set(x, y, [0, 0, 0]);
+            }
+        }
+        let strokes = EdgeDetect::new().strokes(&bitmap, (0.0, 0.0), (40.0, 40.0));
+        assert!(!strokes.is_empty() && strokes.len() <= 4, "{:?}", strokes);
+
+        // Everything is on the outline of the square, and goes all round it
+        let off = |(x, y): (f32, f32)| {
+            let dx = (x - 10.0).abs().min((x - 30.0).abs());
+            let dy = (y - 10.0).abs().min((y - 30.0).abs());
+            dx.min(dy)
+        };
+        assert!(strokes.iter().flatten().all(|&p| off(p) <= 1.5));
+        let length: f32 = strokes.iter().map(|s| path::length(s)).sum();
+        assert!((length - 80.0).abs() < 10.0, "{}", length);
+
+        // Nothing in a flat image
+        let flat = Bitmap::new(10, 10, [128, 128, 128]);
+        assert!(EdgeDetect::new().edges(&flat).iter().all(|e| !e));
+    }
+}
@@ -0,0 +1,188 @@
+use crate::sim::{self, Segment};
+
+// Output for EiBotBoard machines (EggBot, SphereBot and friends). These don't
+// run G-code: the artwork rectangle is wrapped around the egg, with x turning
+// the egg (motor 1) and y swinging the pen arm (motor 2), and the pen is
+// lifted by a servo.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct EggBot {
+    // Motor 1 steps for one full turn of the egg
+    pub steps_per_turn: u32,
+    // Motor 2 steps for the pen arm to cover the height of the artwork
+    pub arm_steps: u32,
+    // Servo positions in EBB units (1/12 µs), see the SC,4 and SC,5 commands
+    pub servo_up: u32,
+    pub servo_down: u32,
+    // Time for the servo to settle after raising or lowering the pen, in ms
+    pub servo_delay: u32,
+}
+
+impl Default for EggBot {
+    // 1/16 microstepping of 200 step motors, which is what the EBB enables
+    // with EM,1,1
+    fn default() -> Self {
+        EggBot {
+            steps_per_turn: 3200,
+            arm_steps: 800,
+            servo_up: 19000,
+            servo_down: 14000,
+            servo_delay: 200,
+        }
+    }
+}
+
+// Position of the motors, with the egg allowed to turn more than once
+struct Steps {
+    x: i64,
+    y: i64,
+}
+
+impl EggBot {
+    // Motor steps for a point of the artwork. The arm starts on the middle of
+    // the artwork, which is where the user lines it up.
+    fn steps(&self, (x, y): (f32, f32), size: (f32, f32)) -> (i64, i64) {
+        (
+            (x / size.0 * self.steps_per_turn as f32).round() as i64,
+            ((y - size.1 / 2.0) / size.1 * self.arm_steps as f32).round() as i64,
+        )
+    }
+
+    fn pen(&self, down: bool, out: &mut Vec<String>) {
+        out.push(format!(
+            "SP,{},{}",
+            if down { 0 } else { 1 },
+            self.servo_delay
+        ));
+    }
+
+    // Relative move, at least 1ms long as the board requires
+    fn step_move(&self, at: &mut Steps, to: (i64, i64), seconds: f32, out: &mut Vec<String>) {
+        let (dx, dy) = (to.0 - at.x, to.1 - at.y);
+        if dx == 0 && dy == 0 {
+            return;
+        }
+        let ms = (seconds * 1000.0).round().max(1.0) as u32;
+        out.push(format!("SM,{},{},{}", ms, dx, dy));
+        at.x = to.0;
+        at.y = to.1;
+    }
+
+    // EBB commands for a simulated program drawn on an artwork of `size` mm
+    pub fn program(&self, segments: &[Segment], size: (f32, f32)) -> Vec<String> {
+        let turn = self.steps_per_turn as i64;
+        let mut out = vec![
+            "EM,1,1".to_string(),
+            format!("SC,4,{}", self.servo_up),
+            format!("SC,5,{}", self.servo_down),
+        ];
+        self.pen(false, &mut out);
+
+        let mut at = Steps { x: 0, y: 0 };
+        // Whole turns added to x, travel takes the short way round the egg
+        let mut turns = 0;
+        let mut down = false;
+
+        for (i, s) in segments.iter().enumerate() {
+            if s.from_xy() == s.to_xy() {
+                if sim::is_dot(s, segments.get(i + 1)) {
+                    self.pen(true, &mut out);
+                    self.pen(false, &mut out);
+                }
+                continue;
+            }
+
+            if s.pen_down != down {
+                down = s.pen_down;
+                self.pen(down, &mut out);
+            }
+
+            let (x, y) = self.steps(s.to_xy(), size);
+            if !down {
+                turns = ((at.x - x) as f32 / turn as f32).round() as i64;
+            }
+            // Timed by the distance actually covered, which is shorter than
+            // the segment when travel wraps around
+            let to = (x + turns * turn, y);
+            let mm = (
+                (to.0 - at.x) as f32 / turn as f32 * size.0,
+                (to.1 - at.y) as f32 / self.arm_steps as f32 * size.1,
+            );
+            let seconds = (mm.0 * mm.0 + mm.1 * mm.1).sqrt() / (s.feed / 60.0);
+            self.step_move(&mut at, to, seconds, &mut out);
+        }
+
+        if down {
+            self.pen(false, &mut out);
+        }
+        // Back to where the arm was lined up, then release the motors
+        let home = (at.x as f32 / turn as f32).round() as i64 * turn;
+        self.step_move(&mut at, (home, 0), 1.0, &mut out);
+        out.push("EM,0,0".to_string());
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn seg(from: (f32, f32, f32), to: (f32, f32, f32), pen_down: bool) -> Segment {
+        Segment {
+            from,
+            to,
+            feed: 600.0,
+            pen_down,
+            cornering: 0.0,
+        }
+    }
+
+    #[test]
+    fn strokes_and_dots() {
+        let bot = EggBot::default();
+        let size = (320.0, 80.0);
+        let segments = vec![
+            seg((0.0, 40.0, 6.5), (10.0, 40.0, 6.5), false),
+            seg((10.0, 40.0, 6.5), (10.0, 40.0, 4.0), false),
+            seg((10.0, 40.0, 4.0), (20.0, 50.0, 4.0), true),
+            seg((20.0, 50.0, 4.0), (20.0, 50.0, 6.5), false),
+            seg((20.0, 50.0, 6.5), (30.0, 50.0, 6.5), false),
+            seg((30.0, 50.0, 6.5), (30.0, 50.0, 4.0), false),
+            seg((30.0, 50.0, 4.0), (30.0, 50.0, 6.5), false),
+        ];
+        let program = bot.program(&segments, size);
+
+        assert_eq!(
+            &program[..9],
+            &[
+                "EM,1,1",
+                "SC,4,19000",
+                "SC,5,14000",
+                "SP,1,200",
+                // 10mm at 10mm/s
+                "SM,1000,100,0",
+                "SP,0,200",
+                "SM,1414,100,100",
+                "SP,1,200",
+                "SM,1000,100,0",
+            ]
+        );
+        // The dot
+        assert_eq!(&program[9..11], &["SP,0,200", "SP,1,200"]);
+        assert_eq!(program.last().unwrap(), "EM,0,0");
+    }
+
+    #[test]
+    fn travel_wraps_around() {
+        let bot = EggBot::default();
+        let size = (320.0, 80.0);
+        let segments = vec![
+            seg((0.0, 40.0, 6.5), (310.0, 40.0, 6.5), false),
+            seg((310.0, 40.0, 6.5), (310.0, 40.0, 4.0), false),
+            seg((310.0, 40.0, 4.0), (300.0, 40.0, 4.0), true),
+        ];
+        let program = bot.program(&segments, size);
+        // -100 steps instead of +3100
+        assert_eq!(program[4], "SM,1000,-100,0");
+        assert!(program[6].ends_with(",-100,0"));
+    }
+}
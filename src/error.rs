@@ -0,0 +1,83 @@
+use std::fmt;
+use std::io;
+
+use crate::config::ConfigError;
+
+// What can go wrong loading, checking and writing plots
+#[derive(Debug)]
+pub enum Error {
+    // Reading or writing failed
+    Io(io::Error),
+    // A file or text that isn't what it should be, e.g. an SVG, an image or
+    // a program
+    Parse(String),
+    // A config that can't work, e.g. a pen that goes up to draw
+    Config(ConfigError),
+    // Sizes that don't work out: drawing outside the work area, tiles
+    // larger than the bed, sheets smaller than their overlap or an empty
+    // preview
+    Bounds(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "{}", e),
+            Error::Config(e) => write!(f, "{}", e),
+            Error::Parse(message) | Error::Bounds(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(e) => Some(e),
+            Error::Config(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+impl From<ConfigError> for Error {
+    fn from(e: ConfigError) -> Self {
+        Error::Config(e)
+    }
+}
+
+// For code that deals in io::Error, like streams and the command line
+impl From<Error> for io::Error {
+    fn from(e: Error) -> Self {
+        match e {
+            Error::Io(e) => e,
+            Error::Parse(message) => io::Error::new(io::ErrorKind::InvalidData, message),
+            Error::Config(e) => io::Error::new(io::ErrorKind::InvalidInput, e),
+            Error::Bounds(message) => io::Error::new(io::ErrorKind::InvalidInput, message),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn conversions() {
+        let e = Error::from(io::Error::new(io::ErrorKind::NotFound, "gone"));
+        assert!(matches!(e, Error::Io(_)));
+        assert_eq!(e.to_string(), "gone");
+        assert!(std::error::Error::source(&e).is_some());
+
+        let e = io::Error::from(Error::Bounds("too wide".to_string()));
+        assert_eq!(e.kind(), io::ErrorKind::InvalidInput);
+        assert_eq!(e.to_string(), "too wide");
+        let e = io::Error::from(Error::Parse("not a WAV file".to_string()));
+        assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+    }
+}
@@ -0,0 +1,197 @@
+use std::f32::consts::FRAC_PI_2;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::path;
+
+// A feed rate, for the speeds of the config and overrides, e.g.
+// `config.xy_speed = Feed::mm_per_s(40.0)`. Codes and the simulation carry
+// feeds as plain mm/min.
+#[derive(Debug, Copy, Clone, PartialEq, PartialOrd)]
+pub struct Feed(f32);
+
+impl Feed {
+    pub fn mm_per_min(feed: f32) -> Self {
+        Feed(feed)
+    }
+
+    pub fn mm_per_s(feed: f32) -> Self {
+        Feed(feed * 60.0)
+    }
+
+    pub fn per_min(self) -> f32 {
+        self.0
+    }
+
+    pub fn per_s(self) -> f32 {
+        self.0 / 60.0
+    }
+
+    // The F word of a move, the feed in mm/min with `decimals` places
+    pub fn word(self, decimals: usize) -> String {
+        format!("F{:.*}", decimals, self.0)
+    }
+}
+
+impl From<Feed> for f32 {
+    fn from(feed: Feed) -> f32 {
+        feed.per_min()
+    }
+}
+
+// The number in mm/min, as the config files have it
+impl fmt::Display for Feed {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+// A number with an optional unit, "1000", "1000 mm/min" or "40mm/s"
+impl FromStr for Feed {
+    type Err = std::num::ParseFloatError;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let text = text.trim();
+        if let Some(v) = text.strip_suffix("mm/s") {
+            return Ok(Feed::mm_per_s(v.trim().parse()?));
+        }
+        let v = text.strip_suffix("mm/min").unwrap_or(text);
+        Ok(Feed::mm_per_min(v.trim().parse()?))
+    }
+}
+
+// Draws straight runs fast and slows down on tight curves, where flexible
+// pens lag behind and the machine shakes. The radius of curvature at every
+// vertex is estimated from how far the path turns there.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct AdaptiveFeed {
+    // Feed on the tightest curves and at sharp corners
+    pub min: Feed,
+    // Feed on straight runs
+    pub max: Feed,
+    // Radius of curvature in mm at which the feed is halfway between the two.
+    // Larger slows down on gentler curves already.
+    pub radius: f32,
+}
+
+impl AdaptiveFeed {
+    pub fn new(min: Feed, max: Feed) -> Self {
+        AdaptiveFeed {
+            min,
+            max,
+            radius: 5.0,
+        }
+    }
+
+    // Feed in mm/min for a radius of curvature
+    pub fn feed(&self, radius: f32) -> f32 {
+        let (min, max) = (self.min.per_min(), self.max.per_min());
+        if radius.is_infinite() {
+            return max;
+        }
+        let r = radius.max(0.0);
+        min + (max - min) * r / (r + self.radius.max(1e-3))
+    }
+
+    // Feed in mm/min for each segment of `path`, the slower of the feeds at
+    // its ends
+    pub fn feeds(&self, path: &[(f32, f32)]) -> Vec<f32> {
+        let (min, max) = (self.min.per_min(), self.max.per_min());
+        let n = path.len();
+        let at_vertex: Vec<f32> = (0..n)
+            .map(|i| {
+                if i == 0 || i == n - 1 {
+                    return max;
+                }
+                let (a, b, c) = (path[i - 1], path[i], path[i + 1]);
+                let (d0, d1) = (path::dist(a, b), path::dist(b, c));
+                if d0 <= f32::EPSILON || d1 <= f32::EPSILON {
+                    return max;
+                }
+                let turn = ((b.0 - a.0) * (c.1 - b.1) - (b.1 - a.1) * (c.0 - b.0))
+                    .atan2((b.0 - a.0) * (c.0 - b.0) + (b.1 - a.1) * (c.1 - b.1))
+                    .abs();
+                if turn <= 1e-4 {
+                    max
+                } else if turn >= FRAC_PI_2 {
+                    min
+                } else {
+                    self.feed(d0.min(d1) / turn)
+                }
+            })
+            .collect();
+        at_vertex.windows(2).map(|w| w[0].min(w[1])).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::test_config;
+    use crate::{Overrides, Printer};
+
+    #[test]
+    fn units() {
+        assert_eq!(f32::from(Feed::mm_per_s(40.0)), 2400.0);
+        assert_eq!(Feed::mm_per_min(1500.0).per_s(), 25.0);
+        assert_eq!("1000".parse(), Ok(Feed::mm_per_min(1000.0)));
+        assert_eq!("1000 mm/min".parse(), Ok(Feed::mm_per_min(1000.0)));
+        assert_eq!("40mm/s".parse(), Ok(Feed::mm_per_s(40.0)));
+        assert!("fast".parse::<Feed>().is_err());
+
+        assert_eq!(Feed::mm_per_s(25.0).word(0), "F1500");
+        assert_eq!(Feed::mm_per_min(812.5).word(2), "F812.50");
+        assert_eq!(Feed::mm_per_s(40.0).to_string(), "2400");
+    }
+
+    #[test]
+    fn slower_on_curves() {
+        let feed = AdaptiveFeed::new(Feed::mm_per_min(300.0), Feed::mm_per_min(3000.0));
+        assert_eq!(feed.feed(f32::INFINITY), 3000.0);
+        assert_eq!(feed.feed(5.0), 1650.0);
+
+        // Straight, then around a corner
+        let feeds = feed.feeds(&[(0.0, 0.0), (10.0, 0.0), (20.0, 0.0), (20.0, 10.0)]);
+        assert_eq!(feeds, [3000.0, 300.0, 300.0]);
+
+        // Circles of 2 and 20mm radius in 32 segments
+        let circle = |r: f32| -> Vec<(f32, f32)> {
+            (0..=32)
+                .map(|i| {
+                    let a = i as f32 / 32.0 * 2.0 * std::f32::consts::PI;
+                    (r * a.cos(), r * a.sin())
+                })
+                .collect()
+        };
+        let small = feed.feeds(&circle(2.0))[10];
+        let large = feed.feeds(&circle(20.0))[10];
+        assert!(small < large && large < 3000.0);
+        assert!((small - feed.feed(2.0)).abs() < 50.0, "{}", small);
+    }
+
+    #[test]
+    fn adaptive_feed() {
+        let mut config = test_config();
+        config.adaptive_feed = Some(AdaptiveFeed::new(
+            Feed::mm_per_min(300.0),
+            Feed::mm_per_min(3000.0),
+        ));
+        let mut printer = Printer::new(config);
+        printer.draw_polyline(&[(0.0, 0.0), (10.0, 0.0), (20.0, 0.0), (20.0, 10.0)]);
+        printer.set_overrides(Overrides {
+            draw_speed: Some(Feed::mm_per_min(700.0)),
+            ..Overrides::default()
+        });
+        printer.draw_polyline(&[(0.0, 0.0), (10.0, 0.0), (10.0, 10.0)]);
+
+        let code: Vec<String> = printer.codes().iter().map(|c| c.to_string()).collect();
+        for line in [
+            "G1 X10.0 Y0.0 F3000.0",
+            "G1 X20.0 Y0.0 F300.0",
+            "G1 X20.0 Y10.0 F300.0",
+            "G1 X10.0 Y10.0 F700.0",
+        ] {
+            assert!(code.iter().any(|l| l == line), "missing {}", line);
+        }
+    }
+}
@@ -0,0 +1,221 @@
+use std::rc::Rc;
+
+use crate::canvas::Canvas;
+use crate::curve;
+use crate::knife::DragKnife;
+use crate::noise::{Displace, Jitter};
+use crate::path::{self, Path};
+use crate::transform::AffineTransform;
+
+// A transformation over a single path. Filters may split a path into several
+// pieces (clipping) or drop it entirely, so they return a list of paths.
+// A single dot is passed through as a path with one vertex.
+pub trait PathFilter {
+    fn apply(&self, path: &[(f32, f32)]) -> Vec<Path>;
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct Resample {
+    pub spacing: f32,
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct Smooth {
+    pub iterations: usize,
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct Simplify {
+    pub tolerance: f32,
+}
+
+// Rounds sharp corners with arcs of `radius` mm, so the pen doesn't stop
+// dead and blob at every corner
+#[derive(Debug, Copy, Clone)]
+pub struct Fillet {
+    pub radius: f32,
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct ClipRect {
+    pub min: (f32, f32),
+    pub max: (f32, f32),
+}
+
+impl PathFilter for Resample {
+    fn apply(&self, path: &[(f32, f32)]) -> Vec<Path> {
+        vec![path::resample(path, self.spacing)]
+    }
+}
+
+impl PathFilter for Smooth {
+    fn apply(&self, path: &[(f32, f32)]) -> Vec<Path> {
+        vec![path::chaikin(path, self.iterations)]
+    }
+}
+
+impl PathFilter for Simplify {
+    fn apply(&self, path: &[(f32, f32)]) -> Vec<Path> {
+        vec![path::simplify(path, self.tolerance)]
+    }
+}
+
+impl PathFilter for Fillet {
+    fn apply(&self, path: &[(f32, f32)]) -> Vec<Path> {
+        vec![path::fillet(path, self.radius, curve::TOLERANCE)]
+    }
+}
+
+impl PathFilter for ClipRect {
+    fn apply(&self, path: &[(f32, f32)]) -> Vec<Path> {
+        path::clip_rect(path, self.min, self.max)
+    }
+}
+
+impl PathFilter for Canvas {
+    fn apply(&self, path: &[(f32, f32)]) -> Vec<Path> {
+        self.clip(path)
+    }
+}
+
+impl PathFilter for Jitter {
+    fn apply(&self, path: &[(f32, f32)]) -> Vec<Path> {
+        vec![Jitter::apply(self, path)]
+    }
+}
+
+impl PathFilter for Displace {
+    fn apply(&self, path: &[(f32, f32)]) -> Vec<Path> {
+        vec![self.apply_path(path)]
+    }
+}
+
+impl PathFilter for DragKnife {
+    fn apply(&self, path: &[(f32, f32)]) -> Vec<Path> {
+        vec![DragKnife::apply(self, path)]
+    }
+}
+
+impl PathFilter for AffineTransform {
+    fn apply(&self, path: &[(f32, f32)]) -> Vec<Path> {
+        vec![self.apply_path(path)]
+    }
+}
+
+// Filters applied one after another, in the order they were added
+#[derive(Default, Clone)]
+pub struct Pipeline {
+    filters: Vec<Rc<dyn PathFilter>>,
+}
+
+impl Pipeline {
+    pub fn new() -> Self {
+        Pipeline::default()
+    }
+
+    pub fn then<F: PathFilter + 'static>(mut self, filter: F) -> Self {
+        self.filters.push(Rc::new(filter));
+        self
+    }
+
+    pub fn push<F: PathFilter + 'static>(&mut self, filter: F) {
+        self.filters.push(Rc::new(filter));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.filters.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.filters.len()
+    }
+
+    pub fn apply_all(&self, paths: Vec<Path>) -> Vec<Path> {
+        self.filters.iter().fold(paths, |paths, filter| {
+            paths
+                .iter()
+                .flat_map(|p| filter.apply(p))
+                .filter(|p| !p.is_empty())
+                .collect()
+        })
+    }
+}
+
+impl PathFilter for Pipeline {
+    fn apply(&self, path: &[(f32, f32)]) -> Vec<Path> {
+        self.apply_all(vec![path.to_vec()])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::test_config;
+    use crate::{Code, Printer};
+
+    #[test]
+    fn empty_pipeline_is_identity() {
+        let path = vec![(0.0, 0.0), (1.0, 1.0)];
+        assert_eq!(Pipeline::new().apply(&path), vec![path]);
+    }
+
+    #[test]
+    fn filters_run_in_order() {
+        let path = vec![(-10.0, 0.0), (10.0, 0.0)];
+
+        // Clip first, then move: the piece ends up at 5..15
+        let a = Pipeline::new()
+            .then(ClipRect {
+                min: (0.0, -1.0),
+                max: (10.0, 1.0),
+            })
+            .then(AffineTransform::translate(5.0, 0.0));
+        assert_eq!(a.apply(&path), vec![vec![(5.0, 0.0), (15.0, 0.0)]]);
+
+        // Move first, then clip: the piece ends up at 0..10
+        let b = Pipeline::new()
+            .then(AffineTransform::translate(5.0, 0.0))
+            .then(ClipRect {
+                min: (0.0, -1.0),
+                max: (10.0, 1.0),
+            });
+        assert_eq!(b.apply(&path), vec![vec![(0.0, 0.0), (10.0, 0.0)]]);
+    }
+
+    #[test]
+    fn pipelines_compose() {
+        let smooth = Pipeline::new()
+            .then(Resample { spacing: 1.0 })
+            .then(Smooth { iterations: 2 })
+            .then(Simplify { tolerance: 0.01 });
+        let outer = Pipeline::new().then(smooth).then(Jitter::new(0.2, 5.0, 1));
+        assert_eq!(outer.len(), 2);
+
+        let out = outer.apply(&[(0.0, 0.0), (10.0, 0.0), (10.0, 10.0)]);
+        assert_eq!(out.len(), 1);
+        assert!(out[0].len() > 3);
+    }
+
+    #[test]
+    fn filters_at_render_time() {
+        let mut printer = Printer::new(test_config());
+        printer.draw_polyline(&[(0.0, 10.0), (100.0, 10.0)]);
+        printer.draw_point(200.0, 200.0);
+        printer.set_filters(Pipeline::new().then(ClipRect {
+            min: (0.0, 0.0),
+            max: (50.0, 50.0),
+        }));
+
+        // The op list is untouched, only the emitted program is clipped
+        assert_eq!(printer.ops().len(), 2);
+        let code = printer.codes();
+        let xs: Vec<f32> = code
+            .iter()
+            .filter_map(|c| match c {
+                Code::Move(p, _) => p.x,
+                _ => None,
+            })
+            .collect();
+        assert_eq!(xs, vec![0.0, 50.0]);
+    }
+}
@@ -0,0 +1,303 @@
+use crate::Printer;
+use crate::canvas::Canvas;
+use crate::path::{self, Path};
+
+// Parallel lines filling a shape
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Hatch {
+    // Direction of the lines in degrees, counterclockwise from the x axis
+    pub angle: f32,
+    // Distance between the lines
+    pub spacing: f32,
+}
+
+impl Hatch {
+    pub fn new(angle: f32, spacing: f32) -> Self {
+        Hatch { angle, spacing }
+    }
+
+    // Lines filling `shape`, every other one running backwards to save travel
+    pub fn fill(&self, shape: &Canvas) -> Vec<Path> {
+        let outline = shape.outline();
+        let Some(first) = outline.first() else {
+            return Vec::new();
+        };
+        let (min, max) = outline.iter().fold((*first, *first), |(min, max), p| {
+            (
+                (min.0.min(p.0), min.1.min(p.1)),
+                (max.0.max(p.0), max.1.max(p.1)),
+            )
+        });
+        let center = ((min.0 + max.0) / 2.0, (min.1 + max.1) / 2.0);
+        let reach = path::dist(min, max) / 2.0 + self.spacing;
+        let angle = self.angle.to_radians();
+        let (dir, normal) = ((angle.cos(), angle.sin()), (-angle.sin(), angle.cos()));
+        let at = |t: f32, o: f32| {
+            (
+                center.0 + t * dir.0 + o * normal.0,
+                center.1 + t * dir.1 + o * normal.1,
+            )
+        };
+
+        let mut lines = Vec::new();
+        let count = (reach / self.spacing.max(1e-3)).ceil() as i64;
+        for (row, i) in (-count..=count).enumerate() {
+            let o = i as f32 * self.spacing;
+            let mut pieces = shape.clip(&[at(-reach, o), at(reach, o)]);
+            if row % 2 == 1 {
+                pieces.reverse();
+                pieces.iter_mut().for_each(|p| p.reverse());
+            }
+            lines.extend(pieces);
+        }
+        lines
+    }
+}
+
+// How a closed shape is filled in
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum FillStyle {
+    Hatch(Hatch),
+    // The hatch and the same again at right angles
+    CrossHatch(Hatch),
+    // Rings following the outline inwards, `spacing` apart
+    Concentric { spacing: f32 },
+}
+
+impl FillStyle {
+    pub fn fill(&self, shape: &Canvas) -> Vec<Path> {
+        match *self {
+            FillStyle::Hatch(hatch) => hatch.fill(shape),
+            FillStyle::CrossHatch(hatch) => {
+                let mut lines = hatch.fill(shape);
+                lines.extend(
+                    Hatch {
+                        angle: hatch.angle + 90.0,
+                        ..hatch
+                    }
+                    .fill(shape),
+                );
+                lines
+            }
+            FillStyle::Concentric { spacing } => {
+                let spacing = spacing.max(1e-3);
+                let outline = shape.outline();
+                let mut lines = Vec::new();
+                // Half a spacing in from the edge, so the rings are even
+                let mut ring = path::inset(&outline, spacing / 2.0);
+                while !ring.is_empty() {
+                    // Notches can push a ring over the edge
+                    lines.extend(shape.clip(&ring));
+                    ring = path::inset(&ring, spacing);
+                }
+                lines
+            }
+        }
+    }
+}
+
+// Shape filled with its own hatch, or one picked by the Fill it's in
+#[derive(Debug, Clone, PartialEq)]
+pub struct Region {
+    pub shape: Canvas,
+    pub hatch: Option<Hatch>,
+}
+
+// Composite fill of several regions. Regions without a hatch of their own
+// get the default spacing and an angle that differs from the regions they
+// touch, turning by `turn` degrees at a time, so neighbors stay apart.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Fill {
+    pub default: Hatch,
+    pub turn: f32,
+    // Regions closer than this are neighbors, in mm. Defaults to the spacing.
+    pub touch: f32,
+    pub regions: Vec<Region>,
+}
+
+impl Fill {
+    pub fn new(default: Hatch) -> Self {
+        Fill {
+            default,
+            turn: 90.0,
+            touch: default.spacing,
+            regions: Vec::new(),
+        }
+    }
+
+    // Region hatched with whatever angle sets it apart from its neighbors
+    pub fn add(&mut self, shape: Canvas) -> &mut Self {
+        self.regions.push(Region { shape, hatch: None });
+        self
+    }
+
+    pub fn add_hatched(&mut self, shape: Canvas, hatch: Hatch) -> &mut Self {
+        self.regions.push(Region {
+            shape,
+            hatch: Some(hatch),
+        });
+        self
+    }
+
+    fn touching(&self, a: &Canvas, b: &Canvas) -> bool {
+        let (a, b) = (a.outline(), b.outline());
+        let near = |p: (f32, f32), outline: &Path| {
+            outline
+                .windows(2)
+                .any(|w| path::dist_to_segment(p, w[0], w[1]) <= self.touch)
+        };
+        a.iter().any(|&p| near(p, &b)) || b.iter().any(|&p| near(p, &a))
+    }
+
+    // The hatch of every region, in order
+    pub fn hatches(&self) -> Vec<Hatch> {
+        let n = self.regions.len();
+        let neighbors: Vec<Vec<usize>> = (0..n)
+            .map(|i| {
+                (0..n)
+                    .filter(|&j| {
+                        j != i && self.touching(&self.regions[i].shape, &self.regions[j].shape)
+                    })
+                    .collect()
+            })
+            .collect();
+        // Lines at a and b look the same if they're parallel
+        let clash = |a: f32, b: f32| {
+            let d = (a - b).rem_euclid(180.0);
+            d.min(180.0 - d) < 1.0
+        };
+        let choices = ((180.0 / self.turn.abs().max(1.0)).round() as usize).max(1);
+
+        let mut hatches: Vec<Option<Hatch>> = self.regions.iter().map(|r| r.hatch).collect();
+        for i in 0..n {
+            if hatches[i].is_some() {
+                continue;
+            }
+            // The angle clashing with the fewest neighbors, the earliest on a tie
+            let angle = (0..choices)
+                .map(|k| self.default.angle + k as f32 * self.turn)
+                .min_by_key(|&angle| {
+                    neighbors[i]
+                        .iter()
+                        .filter_map(|&j| hatches[j])
+                        .filter(|h| clash(h.angle, angle))
+                        .count()
+                })
+                .unwrap_or(self.default.angle);
+            hatches[i] = Some(Hatch {
+                angle,
+                ..self.default
+            });
+        }
+        hatches.into_iter().flatten().collect()
+    }
+
+    // Hatch lines of every region, in order
+    pub fn lines(&self) -> Vec<Vec<Path>> {
+        self.regions
+            .iter()
+            .zip(self.hatches())
+            .map(|(region, hatch)| hatch.fill(&region.shape))
+            .collect()
+    }
+
+    // Draws the fill with the regions in drawing coordinates
+    pub fn draw(&self, printer: &mut Printer) {
+        for line in self.lines().iter().flatten() {
+            printer.draw_polyline(line);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::test_config;
+
+    fn square(x: f32, y: f32, size: f32) -> Canvas {
+        Canvas::Rect {
+            min: (x, y),
+            max: (x + size, y + size),
+        }
+    }
+
+    #[test]
+    fn fills_the_shape() {
+        let lines = Hatch::new(0.0, 1.0).fill(&square(0.0, 0.0, 10.0));
+        // Horizontal lines 1mm apart, back and forth
+        assert!((10..=11).contains(&lines.len()), "{}", lines.len());
+        assert!(lines.iter().all(|l| (l[0].1 - l[1].1).abs() < 1e-4));
+        assert!(
+            lines
+                .iter()
+                .flatten()
+                .all(|p| p.0 >= -1e-4 && p.0 <= 10.0 + 1e-4)
+        );
+        assert!(lines[0][0].0 < lines[0][1].0 && lines[1][0].0 > lines[1][1].0);
+
+        let diagonal = Hatch::new(45.0, 1.0).fill(&Canvas::circle((0.0, 0.0), 5.0));
+        assert!(
+            diagonal
+                .iter()
+                .flatten()
+                .all(|&p| path::dist(p, (0.0, 0.0)) <= 5.0 + 1e-3)
+        );
+        let length: f32 = diagonal.iter().map(|l| path::length(l)).sum();
+        // About the area of the circle over the spacing
+        assert!((length - 78.5).abs() < 8.0, "{}", length);
+    }
+
+    #[test]
+    fn fill_styles() {
+        let shape = square(0.0, 0.0, 10.0);
+        let hatch = Hatch::new(0.0, 1.0);
+        let single = FillStyle::Hatch(hatch).fill(&shape);
+        let cross = FillStyle::CrossHatch(hatch).fill(&shape);
+        assert_eq!(cross.len(), 2 * single.len());
+        assert!(
+            cross[single.len()..]
+                .iter()
+                .all(|l| (l[0].0 - l[1].0).abs() < 1e-4)
+        );
+
+        // Rings at 0.5, 1.5, ... 4.5mm from the edge
+        let rings = FillStyle::Concentric { spacing: 1.0 }.fill(&shape);
+        assert_eq!(rings.len(), 5);
+        let sizes: Vec<f32> = rings.iter().map(|r| path::length(r) / 4.0).collect();
+        for (size, expected) in sizes.iter().zip([9.0, 7.0, 5.0, 3.0, 1.0]) {
+            assert!((size - expected).abs() < 1e-3, "{:?}", sizes);
+        }
+    }
+
+    #[test]
+    fn neighbors_alternate() {
+        let mut fill = Fill::new(Hatch::new(45.0, 1.0));
+        fill.add(square(0.0, 0.0, 10.0))
+            .add(square(10.0, 0.0, 10.0))
+            .add(square(20.0, 0.0, 10.0))
+            .add(square(50.0, 0.0, 10.0))
+            .add_hatched(square(60.0, 0.0, 10.0), Hatch::new(45.0, 0.5));
+        let angles: Vec<f32> = fill.hatches().iter().map(|h| h.angle).collect();
+        // A row of three alternates, the one on its own touches a region set
+        // to the default angle so it turns
+        assert_eq!(angles, [45.0, 135.0, 45.0, 135.0, 45.0]);
+        assert_eq!(fill.hatches()[4].spacing, 0.5);
+    }
+
+    #[test]
+    fn filled_polygon() {
+        let mut printer = Printer::new(test_config());
+        let triangle = [(0.0, 0.0), (20.0, 0.0), (10.0, 15.0)];
+        printer.draw_polygon_filled(&triangle, FillStyle::Concentric { spacing: 1.0 });
+        let rings = printer.ops().len();
+        assert!(rings > 3);
+        assert_eq!(
+            printer.ops()[0].label.as_deref(),
+            Some("draw_polygon_filled(3 points)")
+        );
+
+        printer.draw_polygon_filled(&triangle, FillStyle::CrossHatch(Hatch::new(0.0, 1.0)));
+        assert!(printer.ops().len() > rings + 20);
+        assert!(printer.out_of_bounds().is_empty());
+    }
+}
@@ -0,0 +1,245 @@
+use crate::raster::Bitmap;
+use crate::sampling;
+use crate::{DotWeight, Error, Printer};
+
+fn invalid(message: &str) -> Error {
+    Error::Parse(message.to_string())
+}
+
+// Reads a netpbm image, PBM, PGM or PPM in plain or binary form. Photos in
+// other formats can be converted with any image tool.
+pub fn parse(data: &[u8]) -> Result<Bitmap, Error> {
+    let mut at = 0;
+    // Header fields are separated by whitespace and comments
+    let mut token = || -> Option<&[u8]> {
+        loop {
+            match data.get(at)? {
+                b'#' => {
+                    while data.get(at).is_some_and(|&b| b != b'\n') {
+                        at += 1;
+                    }
+                }
+                b if b.is_ascii_whitespace() => at += 1,
+                _ => break,
+            }
+        }
+        let start = at;
+        while data.get(at).is_some_and(|b| !b.is_ascii_whitespace()) {
+            at += 1;
+        }
+        Some(&data[start..at])
+    };
+    let number = |t: Option<&[u8]>| -> Result<u32, Error> {
+        t.and_then(|t| std::str::from_utf8(t).ok()?.parse().ok())
+            .ok_or_else(|| invalid("invalid netpbm header"))
+    };
+
+    let magic = token().ok_or_else(|| invalid("empty image"))?;
+    let kind = match magic {
+        b"P1" | b"P2" | b"P3" | b"P4" | b"P5" | b"P6" => magic[1] - b'0',
+        _ => return Err(invalid("not a netpbm image")),
+    };
+    let width = number(token())?;
+    let height = number(token())?;
+    let bitmap = kind == 1 || kind == 4;
+    let max = if bitmap { 1 } else { number(token())? };
+    if max == 0 || max > 65535 {
+        return Err(invalid("invalid netpbm maximum value"));
+    }
+    let channels = if kind == 3 || kind == 6 { 3 } else { 1 };
+    let count = (width * height) as usize * channels;
+
+    let samples: Vec<u32> = if kind <= 3 {
+        (0..count)
+            .map(|_| match token() {
+                // Plain bitmaps may leave out the spaces between pixels
+                Some(t) if bitmap && t.len() > 1 => Err(invalid("invalid netpbm pixel")),
+                t => number(t),
+            })
+            .collect::<Result<_, _>>()?
+    } else {
+        // A single whitespace byte separates the header from the raster
+        let start = at + 1;
+        let raster = data.get(start..).unwrap_or_default();
+        if kind == 4 {
+            // Rows of bits, padded to whole bytes
+            let row = width.div_ceil(8) as usize;
+            if raster.len() < row * height as usize {
+                return Err(invalid("truncated image"));
+            }
+            (0..height as usize)
+                .flat_map(|y| (0..width as usize).map(move |x| (x, y)))
+                .map(|(x, y)| ((raster[y * row + x / 8] >> (7 - x % 8)) & 1) as u32)
+                .collect()
+        } else if max < 256 {
+            if raster.len() < count {
+                return Err(invalid("truncated image"));
+            }
+            raster[..count].iter().map(|&b| b as u32).collect()
+        } else {
+            if raster.len() < count * 2 {
+                return Err(invalid("truncated image"));
+            }
+            raster[..count * 2]
+                .chunks(2)
+                .map(|b| u16::from_be_bytes([b[0], b[1]]) as u32)
+                .collect()
+        }
+    };
+
+    let level = |v: u32| {
+        let v = (v.min(max) * 255 / max) as u8;
+        // In bitmaps 1 is black
+        if bitmap { 255 - v } else { v }
+    };
+    let mut image = Bitmap::new(width, height, [255, 255, 255]);
+    for (i, pixel) in samples.chunks(channels).enumerate() {
+        image.pixels[i] = match pixel {
+            [r, g, b] => [level(*r), level(*g), level(*b)],
+            [v] => [level(*v); 3],
+            _ => unreachable!(),
+        };
+    }
+    Ok(image)
+}
+
+pub fn load(filename: &str) -> Result<Bitmap, Error> {
+    parse(&std::fs::read(filename)?)
+}
+
+// Halftone screen as in print: a dot in every cell of a grid turned by
+// `angle`, heavier the darker the image is over the cell. Unlike the
+// dithers and stippling the dots stay on a regular grid and only their
+// weight changes.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Screen {
+    // Distance between dots, in mm
+    pub cell: f32,
+    // Turn of the grid in degrees, 45 hides the rows best
+    pub angle: f32,
+    // Cells lighter than this darkness (0..=1) get no dot
+    pub threshold: f32,
+    pub weight: DotWeight,
+}
+
+impl Screen {
+    pub fn new(cell: f32, weight: DotWeight) -> Self {
+        Screen {
+            cell,
+            angle: 45.0,
+            threshold: 0.05,
+            weight,
+        }
+    }
+
+    // Dots with their weights, with the bitmap stretched over min..max and
+    // its top row at max.1. Every other row runs backwards to save travel.
+    pub fn dots(
+        &self,
+        bitmap: &Bitmap,
+        min: (f32, f32),
+        max: (f32, f32),
+    ) -> Vec<((f32, f32), f32)> {
+        let darkness = sampling::darkness(bitmap, min, max);
+        let center = ((min.0 + max.0) / 2.0, (min.1 + max.1) / 2.0);
+        let (sin, cos) = self.angle.to_radians().sin_cos();
+        let cell = self.cell.max(1e-3);
+        // Enough rows and columns to cover the corners at any angle
+        let n = ((max.0 - min.0).hypot(max.1 - min.1) / cell / 2.0).ceil() as i32 + 1;
+
+        let mut dots = Vec::new();
+        for (row, j) in (-n..=n).enumerate() {
+            let mut line = Vec::new();
+            for i in -n..=n {
+                let (u, v) = (i as f32 * cell, j as f32 * cell);
+                let p = (center.0 + u * cos - v * sin, center.1 + u * sin + v * cos);
+                if p.0 < min.0 || p.0 > max.0 || p.1 < min.1 || p.1 > max.1 {
+                    continue;
+                }
+                // Average over the cell rather than the pixel under the dot
+                let mut sum = 0.0;
+                for k in 0..9 {
+                    let (du, dv) = ((k % 3 - 1) as f32 / 3.0, (k / 3 - 1) as f32 / 3.0);
+                    let q = (
+                        p.0 + (du * cos - dv * sin) * cell,
+                        p.1 + (du * sin + dv * cos) * cell,
+                    );
+                    sum += darkness(q);
+                }
+                let weight = sum / 9.0;
+                if weight >= self.threshold {
+                    line.push((p, weight));
+                }
+            }
+            if row % 2 == 1 {
+                line.reverse();
+            }
+            dots.extend(line);
+        }
+        dots
+    }
+
+    // Draws the dots with the bitmap over min..max in drawing coordinates
+    pub fn draw(&self, printer: &mut Printer, bitmap: &Bitmap, min: (f32, f32), max: (f32, f32)) {
+        for ((x, y), weight) in self.dots(bitmap, min, max) {
+            printer.draw_weighted_point(x, y, weight, self.weight);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn netpbm() {
+        let plain = parse(b"P2\n# gray\n3 1\n255\n0 128 255\n").unwrap();
+        assert_eq!((plain.width, plain.height), (3, 1));
+        assert_eq!(plain.pixels, [[0; 3], [128; 3], [255; 3]]);
+
+        let mut binary = b"P6 2 1 255\n".to_vec();
+        binary.extend([255, 0, 0, 0, 0, 255]);
+        assert_eq!(parse(&binary).unwrap().pixels, [[255, 0, 0], [0, 0, 255]]);
+
+        // Bits with 1 for black, rows padded to whole bytes
+        let bits = parse(&[b"P4 3 2\n".as_slice(), &[0b1010_0000, 0b0100_0000]].concat()).unwrap();
+        let black: Vec<bool> = bits.pixels.iter().map(|p| p[0] == 0).collect();
+        assert_eq!(black, [true, false, true, false, true, false]);
+
+        let wide = parse(&[b"P5 1 1 65535\n".as_slice(), &[0x80, 0x00]].concat()).unwrap();
+        assert_eq!(wide.pixels, [[127; 3]]);
+
+        assert!(parse(b"P5 2 2 255\n\x00").is_err());
+        assert!(parse(b"GIF89a").is_err());
+    }
+
+    #[test]
+    fn screen_dots() {
+        // Black on the left half, white on the right
+        let mut bitmap = Bitmap::new(2, 1, [255, 255, 255]);
+        bitmap.set(0, 0, [0, 0, 0]);
+        let screen = Screen {
+            angle: 0.0,
+            ..Screen::new(2.0, DotWeight::Dwell(0.5))
+        };
+        let dots = screen.dots(&bitmap, (0.0, 0.0), (20.0, 10.0));
+        assert!(dots.iter().all(|&((x, _), _)| x <= 10.0));
+        // The column on the edge is only a third over the black half
+        let edge: Vec<f32> = dots.iter().filter(|d| d.0.0 == 10.0).map(|d| d.1).collect();
+        assert!(!edge.is_empty() && edge.iter().all(|&w| (w - 1.0 / 3.0).abs() < 1e-3));
+        // Rows alternate direction
+        let second = dots.iter().position(|d| d.0.1 != dots[0].0.1).unwrap();
+        let forward = |i: usize| dots[i].0.0 < dots[i + 1].0.0;
+        assert_ne!(forward(0), forward(second));
+
+        // Turned, the grid still stays inside
+        let turned =
+            Screen::new(2.0, DotWeight::Dwell(0.5)).dots(&bitmap, (0.0, 0.0), (20.0, 10.0));
+        assert!(turned.len() > 10);
+        assert!(
+            turned
+                .iter()
+                .all(|&((x, y), _)| (0.0..=20.0).contains(&x) && (0.0..=10.0).contains(&y))
+        );
+    }
+}
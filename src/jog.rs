@@ -0,0 +1,169 @@
+use crate::{ABS_COORD, Code, Point, PrinterConfig, REL_COORD};
+
+// Places to move to when lining up the paper
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Corner {
+    BottomLeft,
+    BottomRight,
+    TopLeft,
+    TopRight,
+    Center,
+}
+
+// Manual moves for setting up the machine, in machine coordinates as the
+// config's min and max are. Once the position is known from a go_to, jogs
+// stay inside the drawing area, before that they are relative moves.
+// The codes can be saved or sent with live::Sender::send_codes.
+#[derive(Debug, Clone)]
+pub struct Jog<'a> {
+    config: &'a PrinterConfig,
+    pos: Option<(f32, f32)>,
+}
+
+fn xy(x: f32, y: f32, feed: f32) -> Code {
+    Code::Move(
+        Point {
+            x: Some(x),
+            y: Some(y),
+            z: None,
+        },
+        feed,
+    )
+}
+
+impl<'a> Jog<'a> {
+    pub fn new(config: &'a PrinterConfig) -> Self {
+        Jog { config, pos: None }
+    }
+
+    pub fn position(&self) -> Option<(f32, f32)> {
+        self.pos
+    }
+
+    pub fn home(&mut self) -> Vec<Code> {
+        self.pos = None;
+        let mut code = vec![self.config.dialect.home()];
+        code.extend(self.pen_up());
+        code
+    }
+
+    pub fn pen_up(&self) -> Vec<Code> {
+        let c = self.config;
+        c.actuator.up(c.z0, c.up_speed.per_min())
+    }
+
+    pub fn pen_down(&self) -> Vec<Code> {
+        let c = self.config;
+        c.actuator.down(c.z_draw, c.down_speed.per_min())
+    }
+
+    pub fn corner(&self, corner: Corner) -> (f32, f32) {
+        let (min, max) = (self.config.min, self.config.max);
+        match corner {
+            Corner::BottomLeft => min,
+            Corner::BottomRight => (max.0, min.1),
+            Corner::TopLeft => (min.0, max.1),
+            Corner::TopRight => max,
+            Corner::Center => ((min.0 + max.0) / 2.0, (min.1 + max.1) / 2.0),
+        }
+    }
+
+    // Raises the pen and moves there
+    pub fn go_to(&mut self, corner: Corner) -> Vec<Code> {
+        let (x, y) = self.corner(corner);
+        self.pos = Some((x, y));
+        let mut code = self.pen_up();
+        code.extend([ABS_COORD, xy(x, y, self.config.xy_speed.per_min())]);
+        code
+    }
+
+    // Moves by (dx, dy), with the pen wherever it is
+    pub fn step(&mut self, dx: f32, dy: f32) -> Vec<Code> {
+        let (min, max) = (self.config.min, self.config.max);
+        match self.pos {
+            Some((x, y)) => {
+                let to = ((x + dx).clamp(min.0, max.0), (y + dy).clamp(min.1, max.1));
+                self.pos = Some(to);
+                vec![ABS_COORD, xy(to.0, to.1, self.config.xy_speed.per_min())]
+            }
+            None => vec![
+                REL_COORD,
+                xy(dx, dy, self.config.xy_speed.per_min()),
+                ABS_COORD,
+            ],
+        }
+    }
+
+    // Traces the drawing area with the pen up, to check the paper is under it
+    pub fn frame(&mut self) -> Vec<Code> {
+        let mut code = self.go_to(Corner::BottomLeft);
+        for corner in [
+            Corner::BottomRight,
+            Corner::TopRight,
+            Corner::TopLeft,
+            Corner::BottomLeft,
+        ] {
+            let (x, y) = self.corner(corner);
+            code.push(xy(x, y, self.config.xy_speed.per_min()));
+        }
+        code
+    }
+
+    // Short line from where the pen is, to check it's inked and at the right
+    // height
+    pub fn test_stroke(&mut self, length: f32) -> Vec<Code> {
+        let mut code = self.pen_down();
+        code.extend(self.step(length, 0.0));
+        code.extend(self.pen_up());
+        code
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::test_config;
+
+    #[test]
+    fn jog_commands() {
+        let config = test_config();
+        let mut jog = Jog::new(&config);
+        let lines =
+            |code: Vec<Code>| -> Vec<String> { code.iter().map(|c| c.to_string()).collect() };
+
+        assert_eq!(
+            lines(jog.home()),
+            ["G28 W ; Home all without mesh bed level", "G1 Z6.5 F800.0"]
+        );
+        // Position unknown, so relative
+        assert_eq!(
+            lines(jog.step(5.0, -2.0)),
+            [
+                "G91 ; Use relative coordinates",
+                "G1 X5.0 Y-2.0 F1000.0",
+                "G90 ; Use absolute coordinates"
+            ]
+        );
+
+        assert_eq!(
+            lines(jog.go_to(Corner::TopRight))[2],
+            "G1 X254.0 Y212.0 F1000.0"
+        );
+        // Kept inside the drawing area
+        assert_eq!(lines(jog.step(10.0, -12.0))[1], "G1 X254.0 Y200.0 F1000.0");
+        assert_eq!(jog.position(), Some((254.0, 200.0)));
+
+        assert_eq!(
+            lines(jog.test_stroke(-4.0)),
+            [
+                "G1 Z4.0 F500.0",
+                "G90 ; Use absolute coordinates",
+                "G1 X250.0 Y200.0 F1000.0",
+                "G1 Z6.5 F800.0"
+            ]
+        );
+        let frame = lines(jog.frame());
+        assert_eq!(frame.last().unwrap(), "G1 X50.0 Y35.0 F1000.0");
+        assert_eq!(frame.len(), 7);
+    }
+}
@@ -0,0 +1,164 @@
+use std::f32::consts::PI;
+
+use crate::path::{self, Path};
+
+// Angle between two arc points when swiveling around a corner, in radians
+const SWIVEL_STEP: f32 = PI / 18.0;
+
+// Drag-knife compensation. The blade tip trails the tool axis by `offset`,
+// so the tool is moved `offset` ahead of the path in the direction of travel.
+// At corners sharper than `threshold` the tool swivels around the corner on
+// an arc, which turns the blade in place instead of rounding the corner off.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct DragKnife {
+    // Distance from the tool axis to the blade tip, in mm
+    pub offset: f32,
+    // Smallest change of direction that gets a swivel, in degrees
+    pub threshold: f32,
+}
+
+fn direction(a: (f32, f32), b: (f32, f32)) -> Option<(f32, f32)> {
+    let len = path::dist(a, b);
+    if len <= f32::EPSILON {
+        None
+    } else {
+        Some(((b.0 - a.0) / len, (b.1 - a.1) / len))
+    }
+}
+
+impl DragKnife {
+    pub fn new(offset: f32) -> Self {
+        DragKnife {
+            offset,
+            threshold: 15.0,
+        }
+    }
+
+    fn ahead(&self, p: (f32, f32), dir: (f32, f32)) -> (f32, f32) {
+        (p.0 + dir.0 * self.offset, p.1 + dir.1 * self.offset)
+    }
+
+    // Arc around `corner` turning the blade from `from` to `to`, without the
+    // starting point
+    fn swivel(&self, corner: (f32, f32), from: (f32, f32), to: (f32, f32), out: &mut Path) {
+        let a0 = from.1.atan2(from.0);
+        let mut turn = to.1.atan2(to.0) - a0;
+        if turn > PI {
+            turn -= 2.0 * PI;
+        } else if turn < -PI {
+            turn += 2.0 * PI;
+        }
+
+        if turn.abs().to_degrees() < self.threshold {
+            out.push(self.ahead(corner, to));
+            return;
+        }
+
+        let steps = (turn.abs() / SWIVEL_STEP).ceil().max(1.0) as usize;
+        for i in 1..=steps {
+            let a = a0 + turn * i as f32 / steps as f32;
+            out.push(self.ahead(corner, (a.cos(), a.sin())));
+        }
+    }
+
+    // Tool path that makes the blade tip follow `input`. The cut starts with a
+    // short lead-in from the first vertex that lines up the blade, closed
+    // paths also swivel back into the starting direction at the end.
+    pub fn apply(&self, input: &[(f32, f32)]) -> Path {
+        // Segments without a direction can't steer the blade
+        let mut pts: Path = Vec::with_capacity(input.len());
+        for &p in input {
+            if pts.last().is_none_or(|&q| path::dist(p, q) > f32::EPSILON) {
+                pts.push(p);
+            }
+        }
+        if pts.len() < 2 {
+            return pts;
+        }
+
+        let dirs: Vec<(f32, f32)> = pts
+            .windows(2)
+            .filter_map(|w| direction(w[0], w[1]))
+            .collect();
+
+        let mut out = vec![pts[0], self.ahead(pts[0], dirs[0])];
+        for i in 1..dirs.len() {
+            out.push(self.ahead(pts[i], dirs[i - 1]));
+            self.swivel(pts[i], dirs[i - 1], dirs[i], &mut out);
+        }
+        let last = dirs[dirs.len() - 1];
+        out.push(self.ahead(pts[pts.len() - 1], last));
+
+        if path::is_closed(input) {
+            self.swivel(pts[0], last, dirs[0], &mut out);
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Printer;
+    use crate::tests::test_config;
+
+    fn close(a: (f32, f32), b: (f32, f32)) -> bool {
+        path::dist(a, b) < 1e-4
+    }
+
+    #[test]
+    fn straight_cut() {
+        let knife = DragKnife::new(0.5);
+        let out = knife.apply(&[(0.0, 0.0), (10.0, 0.0)]);
+        assert_eq!(out, vec![(0.0, 0.0), (0.5, 0.0), (10.5, 0.0)]);
+    }
+
+    #[test]
+    fn corner_swivel() {
+        let knife = DragKnife::new(1.0);
+        let out = knife.apply(&[(0.0, 0.0), (10.0, 0.0), (10.0, 10.0)]);
+
+        // Overshoot the corner, swivel around it and continue upwards
+        assert!(close(out[2], (11.0, 0.0)));
+        assert!(close(*out.last().unwrap(), (10.0, 11.0)));
+        let arc = &out[3..out.len() - 1];
+        assert!(arc.len() >= 9);
+        assert!(
+            arc.iter()
+                .all(|&p| (path::dist(p, (10.0, 0.0)) - 1.0).abs() < 1e-4)
+        );
+        assert!(close(arc[arc.len() - 1], (10.0, 1.0)));
+
+        // Gentle bends don't swivel
+        let out = knife.apply(&[(0.0, 0.0), (10.0, 0.0), (20.0, 1.0)]);
+        assert_eq!(out.len(), 5);
+    }
+
+    #[test]
+    fn closed_paths_swivel_back() {
+        let knife = DragKnife::new(1.0);
+        let square = [
+            (0.0, 0.0),
+            (10.0, 0.0),
+            (10.0, 10.0),
+            (0.0, 10.0),
+            (0.0, 0.0),
+        ];
+        let out = knife.apply(&square);
+        assert!(close(*out.last().unwrap(), (1.0, 0.0)));
+    }
+
+    #[test]
+    fn drag_knife_output() {
+        let mut config = test_config();
+        config.drag_knife = Some(DragKnife::new(0.5));
+        let mut printer = Printer::new(config);
+        printer.draw_polyline(&[(0.0, 0.0), (10.0, 0.0), (10.0, 10.0)]);
+        printer.draw_point(20.0, 20.0);
+
+        let code: Vec<String> = printer.codes().iter().map(|c| c.to_string()).collect();
+        assert!(code.contains(&"G1 X10.5 Y0.0 F1000.0".to_string()));
+        assert!(code.contains(&"G1 X10.0 Y10.5 F1000.0".to_string()));
+        assert!(code.contains(&"G1 X20.0 Y20.0 F1000.0".to_string()));
+    }
+}
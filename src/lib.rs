@@ -1,19 +1,117 @@
-use std::cmp;
+use std::borrow::Cow;
 use std::fmt;
 use std::fs::File;
 use std::io;
 use std::io::prelude::*;
+use std::sync::Arc;
+use std::time::Duration;
 
-const G_MODE: u32 = 0;
+pub mod actuator;
+pub mod art;
+#[cfg(feature = "audio")]
+pub mod audio;
+pub mod backend;
+pub mod border;
+pub mod calibration;
+pub mod canvas;
+pub mod centerline;
+pub mod compact;
+pub mod compose;
+pub mod config;
+pub mod curve;
+pub mod diff;
+pub mod dither;
+pub mod dry_run;
+pub mod drying;
+pub mod edges;
+pub mod eggbot;
+pub mod error;
+pub mod feed;
+pub mod filter;
+pub mod hatch;
+pub mod image;
+pub mod jog;
+pub mod knife;
+pub mod lint;
+pub mod live;
+pub mod noise;
+pub mod order;
+pub mod overlap;
+pub mod parse;
+pub mod path;
+pub mod png;
+pub mod posterize;
+pub mod prelude;
+pub mod preview;
+pub mod progress;
+#[cfg(feature = "python")]
+pub mod python;
+pub mod raster;
+pub mod recovery;
+pub mod rng;
+pub mod ruler;
+pub mod sampling;
+#[cfg(feature = "script")]
+pub mod script;
+#[cfg(feature = "serial")]
+pub mod serial;
+pub mod sheets;
+pub mod signature;
+pub mod sim;
+pub mod sketch;
+pub mod stipple;
+pub mod stream;
+pub mod svg;
+pub mod symmetry;
+pub mod text;
+pub mod tiling;
+pub mod transform;
+pub mod turtle;
+
+pub use error::Error;
+pub use feed::Feed;
+pub use transform::AffineTransform;
+
+// Moves are G1: GRBL runs G0 at its rapid rate and ignores F, while Prusa
+// and Marlin firmware treat the two the same
+const G_MODE: u32 = 1;
 const Z_RESET: f32 = 80.0;
 
-const SPEED: f32 = 10.0;
-const _MAX_FEED: f32 = 1000.0;
+// Comments around the codes of a section
+const SECTION_BEGIN: &str = "Section begins:";
+const SECTION_END: &str = "Section ends:";
 
+// A command with an optional comment, fixed for the built-in ones and built
+// at run time for the others, e.g. those inserted with Printer::emit_raw or
+// read by parse::codes
 #[derive(Debug, Clone)]
 pub struct Source {
-    code: &'static str,
-    comment: Option<&'static str>,
+    code: Cow<'static, str>,
+    comment: Option<Cow<'static, str>>,
+}
+
+impl Source {
+    pub fn new(code: impl Into<String>, comment: Option<String>) -> Self {
+        Source {
+            code: Cow::Owned(code.into()),
+            comment: comment.map(Cow::Owned),
+        }
+    }
+}
+
+impl From<&'static str> for Source {
+    fn from(code: &'static str) -> Self {
+        Source {
+            code: Cow::Borrowed(code),
+            comment: None,
+        }
+    }
+}
+
+impl From<String> for Source {
+    fn from(code: String) -> Self {
+        Source::new(code, None)
+    }
 }
 
 #[derive(Debug, Copy, Clone)]
@@ -23,16 +121,195 @@ pub struct Point {
     z: Option<f32>,
 }
 
+impl Point {
+    // A move in the plane, Z stays where it is
+    pub fn xy(x: f32, y: f32) -> Self {
+        Point {
+            x: Some(x),
+            y: Some(y),
+            z: None,
+        }
+    }
+
+    // A move of Z alone
+    pub fn z(z: f32) -> Self {
+        Point {
+            x: None,
+            y: None,
+            z: Some(z),
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub enum Code {
     Comment(String),
     Model(String),
     Message(String),
     Move(Point, f32),
+    // G0 move read from another program, written back as G0. The feed is
+    // the one in effect, for the simulation.
+    Rapid(Point, f32),
+    // G5 cubic Bézier from the current position, p0 is only informational
+    Bezier(curve::CubicBezier, f32),
+    // G2/G3 arc from the current position, which should be the arc's start
+    Arc(curve::CircularArc, f32),
     Raw(Source),
+    // Wait for the given number of seconds
+    Dwell(f32),
+    // Lowers (true) or raises the pen by other means than a Z move
+    Pen(bool, String),
+    // Waits for the user to resume, showing the message if there is one
+    Pause(String),
+    Beep,
     NOP,
 }
 
+macro_rules! raw {
+    ($a: expr, $b: expr) => {{
+        Code::Raw(Source {
+            code: Cow::Borrowed($a),
+            comment: Some(Cow::Borrowed($b)),
+        })
+    }};
+    ($a: expr) => {{
+        Code::Raw(Source {
+            code: Cow::Borrowed($a),
+            comment: None,
+        })
+    }};
+}
+
+// G-code flavour understood by the firmware
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub enum Dialect {
+    #[default]
+    Prusa,
+    Marlin,
+    // GRBL 1.1, with the pen on Z or a servo on the spindle output
+    Grbl,
+}
+
+impl Dialect {
+    // G5 needs Marlin built with BEZIER_CURVE_SUPPORT
+    pub fn supports_g5(&self) -> bool {
+        matches!(self, Dialect::Marlin)
+    }
+
+    pub fn home(&self) -> Code {
+        match self {
+            Dialect::Prusa => HOME,
+            Dialect::Marlin => raw!("G28", "Home all axes"),
+            Dialect::Grbl => raw!("$H", "Run homing cycle"),
+        }
+    }
+
+    // GRBL keeps its motors powered as set by $1
+    pub fn motors_off(&self) -> Option<Code> {
+        match self {
+            Dialect::Prusa | Dialect::Marlin => Some(OFF),
+            Dialect::Grbl => None,
+        }
+    }
+
+    pub fn pause(&self) -> Code {
+        match self {
+            Dialect::Prusa | Dialect::Marlin => PAUSE,
+            Dialect::Grbl => raw!("M0", "Pause program"),
+        }
+    }
+
+    // M862.3 is Prusa's own, other firmware would reject it
+    pub fn checks_model(&self) -> bool {
+        matches!(self, Dialect::Prusa)
+    }
+
+    // GRBL only has the accelerations stored in $120 and $121
+    pub fn acceleration(&self, acceleration: Option<f32>) -> Option<Code> {
+        match (self, acceleration) {
+            (Dialect::Grbl, _) => None,
+            (_, Some(a)) => Some(Code::Raw(format!("M204 S{}", a).into())),
+            // Back to the settings stored on the machine
+            (_, None) => Some(raw!("M501")),
+        }
+    }
+
+    // The code as this firmware expects it. Codes are built the Marlin way
+    // and only rewritten on the way out, so the simulation still
+    // understands them.
+    pub fn translate(&self, code: Code) -> Code {
+        match (self, code) {
+            // GRBL waits in seconds rather than milliseconds
+            (Dialect::Grbl, Code::Dwell(s)) => Code::Raw(format!("G4 P{:.3}", s).into()),
+            // and shows messages written as comments
+            (Dialect::Grbl, Code::Message(m)) => Code::Raw(format!("(MSG,{})", m).into()),
+            (Dialect::Grbl, Code::Pause(m)) if m.is_empty() => raw!("M0"),
+            (Dialect::Grbl, Code::Pause(m)) => Code::Raw(format!("(MSG,{})\nM0", m).into()),
+            // and has nothing to beep with
+            (Dialect::Grbl, Code::Beep) => Code::NOP,
+            (_, code) => code,
+        }
+    }
+
+    // Marlin's junction deviation is the closest it has to G61/G64. Prusa
+    // firmware only has classic jerk, which doesn't map onto a path
+    // tolerance, so it keeps its own settings.
+    pub fn blending(&self, blending: Blending) -> Option<Code> {
+        match (self, blending) {
+            (Dialect::Prusa | Dialect::Grbl, _) => None,
+            (Dialect::Marlin, Blending::Exact) => Some(raw!("M205 J0.01")),
+            (Dialect::Marlin, Blending::Continuous(tolerance)) => {
+                Some(Code::Raw(format!("M205 J{}", tolerance.max(0.01)).into()))
+            }
+        }
+    }
+}
+
+// How the machine moves through the corners between moves
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Blending {
+    // Slows down almost to a stop at every corner, for fine lettering
+    Exact,
+    // Rounds corners off by up to this many mm to keep moving smoothly,
+    // for background texture
+    Continuous(f32),
+}
+
+// What happens to draws that reach outside min..max, where the head could
+// run into the frame
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum OutOfBounds {
+    // Points are moved onto the edge of the work area
+    Clamp,
+    // The whole draw is left out
+    Skip,
+    // Saving fails
+    Error,
+}
+
+// How much the program comments on the drawing
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Annotations {
+    // Only the draws, for the smallest files
+    None,
+    // A comment naming every draw call and an empty line after every stroke
+    PerShape,
+    // As PerShape, with comments on the travel and the pen going down and up
+    Verbose,
+}
+
+// Work coordinate systems as found on CNC controllers. Their offsets are set
+// up on the machine, e.g. one per paper station on the bed.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Wcs {
+    G54,
+    G55,
+    G56,
+    G57,
+    G58,
+    G59,
+}
+
 #[derive(Debug, Clone)]
 pub struct PrinterConfig {
     pub model: Option<Code>,
@@ -41,131 +318,675 @@ pub struct PrinterConfig {
     pub scale: Option<(f32, f32)>,
     pub z0: f32,
     pub z_draw: f32,
-    pub xy_speed: f32,
-    pub down_speed: f32,
-    pub up_speed: f32,
+    pub xy_speed: Feed,
+    pub down_speed: Feed,
+    pub up_speed: Feed,
+    pub pen_wear_limit: Option<f32>,
+    pub stats_comments: bool,
+    pub thumbnails: Vec<(u32, u32)>,
+    pub metadata: bool,
+    pub dialect: Dialect,
+    // Emit curves as G5 moves instead of flattening them, if the dialect
+    // supports it
+    pub native_curves: bool,
+    // Draw in this work coordinate system instead of setting the origin to
+    // `min` with G92
+    pub wcs: Option<Wcs>,
+    pub engrave: Option<Engrave>,
+    // Compensate for the trailing blade of a drag knife instead of a pen
+    pub drag_knife: Option<knife::DragKnife>,
+    // Mechanism that lifts and lowers the pen
+    pub actuator: Arc<dyn actuator::PenActuator>,
+    pub drying: Option<drying::Drying>,
+    // Write this config and the generator parameters into the file, so the
+    // plot can be made again from the G-code alone (see config::load)
+    pub embed_config: bool,
+    // Shape of the paper in drawing coordinates, everything is clipped to it
+    pub canvas: Option<canvas::Canvas>,
+    pub taper: Option<Taper>,
+    // Slower on tight curves and faster on straight runs instead of drawing
+    // at xy_speed throughout
+    pub adaptive_feed: Option<feed::AdaptiveFeed>,
+    // Acceleration in mm/s² set with M204, restored after sections that
+    // override it. The firmware's stored setting is used when not set.
+    pub acceleration: Option<f32>,
+    // Seconds of plotting between recovery points (see recovery::resume)
+    pub recovery_interval: Option<f32>,
+    // Draws outside the work area are kept with a warning if not set
+    pub out_of_bounds: Option<OutOfBounds>,
+    pub progress: progress::ProgressConfig,
+    // Message for a pause wherever the pen changes, with {pen} filled in.
+    // Without it pens are swapped between separate programs.
+    pub pen_change: Option<String>,
+    // Largest distance in mm between a curve and the moves it's drawn with
+    pub tolerance: f32,
+    // Decimal places of the coordinates and feed rates of moves
+    pub xy_decimals: usize,
+    pub z_decimals: usize,
+    pub feed_decimals: usize,
+    // Shorter moves in the file, every move is written in full if not set
+    pub compact: Option<compact::Compaction>,
+    // Short hops between nearby strokes, every travel is at z0 if not set
+    pub z_hop: Option<ZHop>,
+    // Limits Printer::validate checks the moves against: the highest feed
+    // in mm/min and the longest single pen-down move in mm
+    pub max_feed: Option<Feed>,
+    pub max_draw_move: Option<f32>,
+    pub annotations: Annotations,
+}
+
+impl PrinterConfig {
+    // A pen taped to the extruder of a Prusa MK3S, drawing on the whole bed
+    pub fn prusa_mk3s() -> Self {
+        PrinterConfig {
+            model: Some(Code::Model("MK3S".to_string())),
+            min: (50.0, 35.0),
+            max: (254.0, 212.0),
+            scale: None,
+            z0: 6.5,
+            z_draw: 4.0,
+            xy_speed: Feed::mm_per_min(1000.0),
+            down_speed: Feed::mm_per_min(500.0),
+            up_speed: Feed::mm_per_min(800.0),
+            pen_wear_limit: None,
+            stats_comments: false,
+            thumbnails: Vec::new(),
+            metadata: false,
+            dialect: Dialect::Prusa,
+            native_curves: false,
+            wcs: None,
+            engrave: None,
+            drag_knife: None,
+            actuator: Arc::new(actuator::ZAxis),
+            drying: None,
+            embed_config: false,
+            canvas: None,
+            taper: None,
+            adaptive_feed: None,
+            acceleration: None,
+            recovery_interval: None,
+            out_of_bounds: None,
+            progress: progress::ProgressConfig::default(),
+            pen_change: None,
+            tolerance: curve::TOLERANCE,
+            xy_decimals: 1,
+            z_decimals: 1,
+            feed_decimals: 1,
+            compact: None,
+            z_hop: None,
+            max_feed: None,
+            max_draw_move: None,
+            annotations: Annotations::PerShape,
+        }
+    }
+
+    // Checks the values that would make a plot go wrong, e.g. a pen that
+    // goes up to draw
+    pub fn validate(&self) -> Result<(), config::ConfigError> {
+        use config::ConfigError;
+        if !(self.min.0 < self.max.0 && self.min.1 < self.max.1) {
+            return Err(ConfigError::WorkArea {
+                min: self.min,
+                max: self.max,
+            });
+        }
+        if let Some((w, h)) = self.scale
+            && !(w > 0.0 && h > 0.0)
+        {
+            return Err(ConfigError::NonPositiveScale((w, h)));
+        }
+        let positive = [
+            ("xy_speed", Some(self.xy_speed.per_min())),
+            ("down_speed", Some(self.down_speed.per_min())),
+            ("up_speed", Some(self.up_speed.per_min())),
+            ("pen_wear_limit", self.pen_wear_limit),
+            ("acceleration", self.acceleration),
+            ("recovery_interval", self.recovery_interval),
+            ("tolerance", Some(self.tolerance)),
+            ("z_hop.height", self.z_hop.map(|h| h.height)),
+            ("z_hop.distance", self.z_hop.map(|h| h.distance)),
+            ("max_feed", self.max_feed.map(Feed::per_min)),
+            ("max_draw_move", self.max_draw_move),
+        ];
+        for (key, value) in positive {
+            if let Some(value) = value
+                && (value.is_nan() || value <= 0.0)
+            {
+                return Err(ConfigError::NonPositive { key, value });
+            }
+        }
+        // Other actuators lift the pen without Z
+        if self.actuator.ramps_z() && (self.z_draw.is_nan() || self.z_draw >= self.z0) {
+            return Err(ConfigError::PenHeights {
+                z0: self.z0,
+                z_draw: self.z_draw,
+            });
+        }
+        if !self.actuator.supports(self.dialect) {
+            return Err(ConfigError::UnsupportedActuator {
+                actuator: self.actuator.describe(),
+                dialect: self.dialect,
+            });
+        }
+        if let Some(&size) = self.thumbnails.iter().find(|(w, h)| *w == 0 || *h == 0) {
+            return Err(ConfigError::EmptyThumbnail(size));
+        }
+        Ok(())
+    }
+}
+
+impl Default for PrinterConfig {
+    fn default() -> Self {
+        PrinterConfig::prusa_mk3s()
+    }
+}
+
+// State carried through emission of the op list
+#[derive(Debug, Default)]
+struct EmitState {
+    // Distance drawn since the pen was last replaced
+    pen_wear: f32,
+    // Ink put down since the last drying pause
+    ink: drying::Ink,
+    // How far up the pen stays when it goes down for the next stroke, for
+    // actuators that taper with actuator::PenActuator::ramp
+    start_lift: f32,
+    // Cornering last set on the machine
+    blending: Option<Blending>,
+    // Acceleration last set on the machine, None for the stored setting
+    acceleration: Option<f32>,
+    // Where the next stroke starts when nothing else comes before it, so
+    // the pen may hop there
+    next_start: Option<(f32, f32)>,
+    // End of the last stroke if the pen only hopped up from it
+    hopped: Option<(f32, f32)>,
+}
+
+#[derive(Debug, Clone)]
+pub enum Shape {
+    Point((f32, f32)),
+    Stroke(Vec<(f32, f32)>),
+    Curve(Vec<curve::CubicBezier>),
+    Arc(curve::CircularArc),
+}
+
+// Values that replace the config for single ops, e.g. to try out settings
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub struct Overrides {
+    // Feed for the pen-down moves instead of xy_speed
+    pub draw_speed: Option<Feed>,
+    // Pen-down height instead of z_draw
+    pub z_draw: Option<f32>,
+    // Seconds to hold the pen down before it moves, e.g. for heavier dots
+    pub dwell: Option<f32>,
+    // Cornering for this op and the following ones that don't set their own,
+    // where the dialect supports it
+    pub blending: Option<Blending>,
+    // Feed for the pen-up moves to the start of a stroke instead of xy_speed
+    pub travel_speed: Option<Feed>,
+    // Acceleration instead of the config's, in mm/s²
+    pub acceleration: Option<f32>,
+}
+
+impl Overrides {
+    // These overrides, with the ones they leave unset taken from `other`
+    pub fn or(self, other: Overrides) -> Overrides {
+        Overrides {
+            draw_speed: self.draw_speed.or(other.draw_speed),
+            z_draw: self.z_draw.or(other.z_draw),
+            dwell: self.dwell.or(other.dwell),
+            blending: self.blending.or(other.blending),
+            travel_speed: self.travel_speed.or(other.travel_speed),
+            acceleration: self.acceleration.or(other.acceleration),
+        }
+    }
+}
+
+// Named run of ops drawn with their own settings, e.g. fine lettering at a
+// lower speed and acceleration in the middle of fast background texture
+#[derive(Debug, Clone, PartialEq)]
+pub struct Section {
+    pub name: String,
+    pub overrides: Overrides,
+}
+
+// How draw_weighted_point makes a dot's weight visible
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum DotWeight {
+    // Holds the pen down for up to this many seconds so the ink spreads
+    Dwell(f32),
+    // Lowers the pen up to this many mm below z_draw, spreading the nib
+    Depth(f32),
+    // Scribbles a spiral of up to this radius in mm
+    Scribble(f32),
+}
+
+// Lifts the pen only a little between strokes that are close together
+// instead of all the way to z0, which saves a lot of time on dense plots
+// like stipples. Only for pens on a Z axis.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct ZHop {
+    // Above the pen-down height, in mm
+    pub height: f32,
+    // Longest travel that only hops, in mm
+    pub distance: f32,
+}
+
+// Brush-pen strokes that start and end with the pen partly raised, so the
+// line thins out towards both ends
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Taper {
+    // Distance over which the pen comes down at the start of a stroke and
+    // goes back up at its end, in mm
+    pub length: f32,
+    // How far up the pen is at the very ends, as a fraction of the way from
+    // pen down to pen up
+    pub lift: f32,
+}
+
+impl Taper {
+    // The points of `path` with how far the pen is lifted at each, with
+    // extra points where the lift changes. Strokes shorter than twice the
+    // taper length taper over half their length at each end.
+    pub fn profile(&self, path: &[(f32, f32)]) -> Vec<((f32, f32), f32)> {
+        let total = path::length(path);
+        let ramp = self.length.min(total / 2.0);
+        let lift = |s: f32| {
+            if ramp <= 0.0 {
+                return 0.0;
+            }
+            let end = (1.0 - s / ramp).max(1.0 - (total - s) / ramp);
+            self.lift.clamp(0.0, 1.0) * end.clamp(0.0, 1.0)
+        };
+
+        let Some(&first) = path.first() else {
+            return Vec::new();
+        };
+        let step = ramp / 8.0;
+        let mut out = vec![(first, lift(0.0))];
+        let mut s0 = 0.0;
+        for w in path.windows(2) {
+            let d = path::dist(w[0], w[1]);
+            let s1 = s0 + d;
+            if step > 0.0 {
+                // Subdivide where the segment runs through either ramp
+                let mut s = (s0 / step).floor() * step + step;
+                while s < s1 - 1e-4 {
+                    if s <= ramp + 1e-4 || s >= total - ramp - 1e-4 {
+                        out.push((path::lerp(w[0], w[1], (s - s0) / d), lift(s)));
+                    }
+                    s += step;
+                }
+            }
+            out.push((w[1], lift(s1)));
+            s0 = s1;
+        }
+        out
+    }
+
+    // Pen height when lifted by `lift` between `z_draw` and `z0`
+    fn height(z_draw: f32, z0: f32, lift: f32) -> f32 {
+        z_draw + lift * (z0 - z_draw)
+    }
+}
+
+// Cut strokes into the material in several passes instead of drawing them,
+// for V-bit engraving on the same machine. z_draw is the material surface.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Engrave {
+    // Final depth below the surface, in mm
+    pub depth: f32,
+    // Deepest cut of a single pass, in mm
+    pub step_down: f32,
+    // Feed when plunging into the material, instead of down_speed
+    pub plunge_speed: Feed,
+}
+
+impl Engrave {
+    // Heights of the passes from the first to the last, in equal steps
+    pub fn passes(&self, surface: f32) -> Vec<f32> {
+        let n = (self.depth / self.step_down.max(1e-3)).ceil().max(1.0) as usize;
+        (1..=n)
+            .map(|i| surface - self.depth * i as f32 / n as f32)
+            .collect()
+    }
 }
 
+// The pen used for a layer. Holders differ, so every pen can have its own
+// offsets, which are applied when the program is emitted.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Pen {
+    pub name: String,
+    // Position of the pen tip relative to the nominal tool position, in mm
+    pub offset: (f32, f32),
+    // Added to the pen-down height, positive for pens that sit higher
+    pub z_offset: f32,
+}
+
+impl Pen {
+    pub fn new(name: &str) -> Self {
+        Pen {
+            name: name.to_string(),
+            ..Default::default()
+        }
+    }
+}
+
+// Drawing operations as queued by the draw_* calls, in bed coordinates.
+// They are only turned into `Code`s when the program is emitted so that
+// whole-composition passes can still work on the geometry.
+#[derive(Debug, Clone)]
+pub struct Op {
+    pub shape: Shape,
+    pub label: Option<String>,
+    // Index into the printer's layer names
+    pub layer: usize,
+    pub overrides: Overrides,
+    // Index into the printer's sections
+    pub section: Option<usize>,
+    // Emitted before the op, e.g. a pause to swap the paper
+    pub before: Vec<Code>,
+}
+
+impl Shape {
+    pub fn path(&self) -> Vec<(f32, f32)> {
+        self.flatten(curve::TOLERANCE)
+    }
+
+    // Curves and arcs as polylines within `tolerance` mm
+    pub fn flatten(&self, tolerance: f32) -> Vec<(f32, f32)> {
+        match self {
+            Shape::Point(at) => vec![*at],
+            Shape::Stroke(path) => path.clone(),
+            Shape::Curve(curves) => curve::flatten(curves, tolerance),
+            Shape::Arc(arc) => arc.flatten(tolerance),
+        }
+    }
+
+    fn transformed(&self, t: &AffineTransform) -> Shape {
+        match self {
+            Shape::Point(at) => Shape::Point(t.apply(*at)),
+            Shape::Stroke(path) => Shape::Stroke(t.apply_path(path)),
+            Shape::Curve(curves) => Shape::Curve(
+                curves
+                    .iter()
+                    .map(|c| {
+                        curve::CubicBezier::new(
+                            t.apply(c.p0),
+                            t.apply(c.c0),
+                            t.apply(c.c1),
+                            t.apply(c.p1),
+                        )
+                    })
+                    .collect(),
+            ),
+            // Stretched unevenly an arc isn't round anymore
+            Shape::Arc(arc) => match arc.transformed(t) {
+                Some(arc) => Shape::Arc(arc),
+                None => Shape::Curve(arc.beziers()).transformed(t),
+            },
+        }
+    }
+
+    // Where the pen goes down and where it comes up again
+    fn ends(&self) -> order::Ends {
+        match self {
+            Shape::Point(at) => (*at, *at),
+            Shape::Stroke(path) => (path[0], path[path.len() - 1]),
+            Shape::Curve(curves) => (curves[0].p0, curves[curves.len() - 1].p1),
+            Shape::Arc(arc) => (arc.point(0.0), arc.point(1.0)),
+        }
+    }
+
+    // The same shape drawn the other way around
+    fn reversed(&self) -> Shape {
+        match self {
+            Shape::Point(at) => Shape::Point(*at),
+            Shape::Stroke(path) => Shape::Stroke(path.iter().rev().copied().collect()),
+            Shape::Curve(curves) => Shape::Curve(
+                curves
+                    .iter()
+                    .rev()
+                    .map(|c| curve::CubicBezier::new(c.p1, c.c1, c.c0, c.p0))
+                    .collect(),
+            ),
+            Shape::Arc(arc) => Shape::Arc(curve::CircularArc {
+                start: arc.start + arc.sweep,
+                sweep: -arc.sweep,
+                ..*arc
+            }),
+        }
+    }
+
+    fn from_path(path: Vec<(f32, f32)>) -> Shape {
+        if path.len() == 1 {
+            Shape::Point(path[0])
+        } else {
+            Shape::Stroke(path)
+        }
+    }
+}
+
+impl Op {
+    pub fn path(&self) -> Vec<(f32, f32)> {
+        self.shape.path()
+    }
+}
+
+#[derive(Clone)]
 pub struct Printer {
     config: PrinterConfig,
-    code: Vec<Code>,
+    ops: Vec<Op>,
+    filters: filter::Pipeline,
+    layers: Vec<String>,
+    // Pen for every layer, same indices as `layers`
+    pens: Vec<Pen>,
+    layer: usize,
+    overrides: Overrides,
+    sections: Vec<Section>,
+    symmetry: Option<symmetry::Symmetry>,
+    // Composed transforms of push_transform, innermost last
+    transforms: Vec<AffineTransform>,
+    // Section that ops are drawn into
+    section: Option<usize>,
+    // Part of a job split into several sessions, as (number, count)
+    session: Option<(usize, usize)>,
+    // Settings of the generator, embedded with the config
+    parameters: Vec<(String, String)>,
+    // Inserted codes waiting for the next op
+    pending: Vec<Code>,
     pub width: f32,
     pub height: f32,
 }
 
+// Move to (x, y) at a feed, as a Code
+#[macro_export]
 macro_rules! xy {
-    ($a: expr, $b: expr, $c: expr) => {{
-        Code::Move(
-            Point {
-                x: Some($a),
-                y: Some($b),
-                z: None,
-            },
-            $c,
-        )
-    }};
+    ($a: expr, $b: expr, $c: expr) => {{ $crate::Code::Move($crate::Point::xy($a, $b), $c) }};
 }
 
+// Move Z to a height at a feed, as a Code
+#[macro_export]
 macro_rules! z {
-    ($a: expr, $b: expr) => {{
-        Code::Move(
-            Point {
-                x: None,
-                y: None,
-                z: Some($a),
-            },
-            $b,
-        )
-    }};
-}
-
-macro_rules! raw {
-    ($a: expr, $b: expr) => {{
-        Code::Raw(Source {
-            code: $a,
-            comment: Some($b),
-        })
-    }};
-    ($a: expr) => {{
-        Code::Raw(Source {
-            code: $a,
-            comment: None,
-        })
-    }};
+    ($a: expr, $b: expr) => {{ $crate::Code::Move($crate::Point::z($a), $b) }};
 }
 
 const HOME: Code = raw!("G28 W", "Home all without mesh bed level");
 const UNITS_MM: Code = raw!("G21", "Set units to millimeters");
 const ABS_COORD: Code = raw!("G90", "Use absolute coordinates");
+const REL_COORD: Code = raw!("G91", "Use relative coordinates");
 const SET_ORIGIN: Code = raw!("G92 X0 Y0", "Set current position to origin");
 const OFF: Code = raw!("M84", "Disable motors");
+const PAUSE: Code = raw!("M601", "Pause print");
+
+impl Wcs {
+    fn code(&self) -> Code {
+        match self {
+            Wcs::G54 => raw!("G54", "Use work coordinate system 1"),
+            Wcs::G55 => raw!("G55", "Use work coordinate system 2"),
+            Wcs::G56 => raw!("G56", "Use work coordinate system 3"),
+            Wcs::G57 => raw!("G57", "Use work coordinate system 4"),
+            Wcs::G58 => raw!("G58", "Use work coordinate system 5"),
+            Wcs::G59 => raw!("G59", "Use work coordinate system 6"),
+        }
+    }
+}
+
+// Where the pen-down moves of a stroke starting at `start` end up
+fn stroke_end(start: (f32, f32), body: &[Code]) -> (f32, f32) {
+    body.iter().fold(start, |at, c| match c {
+        Code::Move(p, _) | Code::Rapid(p, _) => (p.x.unwrap_or(at.0), p.y.unwrap_or(at.1)),
+        Code::Bezier(curve, _) => curve.p1,
+        Code::Arc(arc, _) => arc.point(1.0),
+        _ => at,
+    })
+}
+
+// `filename` with "-suffix" added before the extension
+fn numbered(filename: &str, suffix: &str) -> String {
+    match filename.rsplit_once('.') {
+        Some((stem, ext)) => format!("{}-{}.{}", stem, suffix, ext),
+        None => format!("{}-{}", filename, suffix),
+    }
+}
 
 fn rescale(m: f32, rmin: f32, rmax: f32, tmin: f32, tmax: f32) -> f32 {
     ((m - rmin) / (rmax - rmin)) * (tmax - tmin) + tmin
 }
 
-fn render_coord(axis: char, v: Option<f32>) -> String {
+// Decimal places of the numbers in moves
+#[derive(Debug, Copy, Clone, PartialEq)]
+struct Precision {
+    xy: usize,
+    z: usize,
+    feed: usize,
+}
+
+const PRECISION: Precision = Precision {
+    xy: 1,
+    z: 1,
+    feed: 1,
+};
+
+fn render_coord(axis: char, v: Option<f32>, decimals: usize) -> String {
     if let Some(value) = v {
-        format!("{}{:.1}", axis, value)
+        format!("{}{:.*}", axis, decimals, value)
     } else {
         "".to_string()
     }
 }
 
-fn write_code(f: &mut File, c: Code) -> Result<(), io::Error> {
+// Lines that `c` takes up in the file
+fn lines(c: &Code) -> usize {
+    c.to_string().lines().count().max(1)
+}
+
+fn write_code(f: &mut impl Write, c: Code) -> Result<(), io::Error> {
     f.write_all(c.to_string().as_bytes())?;
     f.write_all("\n".as_bytes())?;
     Ok(())
 }
 
-fn render_move(point: &Point, feed: &f32) -> String {
-    let point_str = point.to_string();
+// `g` is G_MODE, or 0 for a rapid
+fn render_move(g: u32, point: &Point, feed: &f32, precision: &Precision) -> String {
+    let point_str = render_point(point, precision);
 
     if point_str.is_empty() {
         Code::Comment("[WARNING] Move without coordinates!".to_string()).to_string()
     } else {
-        format!("G{} {} F{:.1}", G_MODE, point_str, feed)
+        format!(
+            "G{} {} {}",
+            g,
+            point_str,
+            Feed::mm_per_min(*feed).word(precision.feed)
+        )
     }
 }
 
-impl Point {
-    fn dist(&self, other: &Point) -> f32 {
-        let diff = |a: f32, b: f32| -> f32 { a - b };
+// G5 takes the control points relative to the start and end of the curve
+fn render_bezier(c: &curve::CubicBezier, feed: &f32, precision: &Precision) -> String {
+    let n = precision.xy;
+    format!(
+        "G5 I{:.*} J{:.*} P{:.*} Q{:.*} X{:.*} Y{:.*} {}",
+        n,
+        c.c0.0 - c.p0.0,
+        n,
+        c.c0.1 - c.p0.1,
+        n,
+        c.c1.0 - c.p1.0,
+        n,
+        c.c1.1 - c.p1.1,
+        n,
+        c.p1.0,
+        n,
+        c.p1.1,
+        Feed::mm_per_min(*feed).word(precision.feed)
+    )
+}
+
+// G2 turns clockwise, G3 counterclockwise, around the center at I/J from the
+// start. A full circle ends where it starts.
+fn render_arc(arc: &curve::CircularArc, feed: &f32, precision: &Precision) -> String {
+    let start = arc.point(0.0);
+    let end = arc.point(1.0);
+    let n = precision.xy;
+    format!(
+        "G{} X{:.*} Y{:.*} I{:.*} J{:.*} {}",
+        if arc.clockwise() { 2 } else { 3 },
+        n,
+        end.0,
+        n,
+        end.1,
+        n,
+        arc.center.0 - start.0,
+        n,
+        arc.center.1 - start.1,
+        Feed::mm_per_min(*feed).word(precision.feed)
+    )
+}
 
-        // TODO: How to fold over entire Point?
-        let delta_x = self.x.zip(other.x).map(|(x, y)| diff(x, y)).unwrap_or(0.0);
-        let delta_y = self.y.zip(other.y).map(|(x, y)| diff(x, y)).unwrap_or(0.0);
-        let delta_z = self.z.zip(other.z).map(|(x, y)| diff(x, y)).unwrap_or(0.0);
+fn render_point(point: &Point, precision: &Precision) -> String {
+    let x = render_coord('X', point.x, precision.xy);
+    let y = render_coord('Y', point.y, precision.xy);
+    let z = render_coord('Z', point.z, precision.z);
 
-        ((delta_x).powf(2.0) + (delta_y).powf(2.0) + (delta_z).powf(2.0)).sqrt()
-    }
+    let x_space = if !x.is_empty() && (!y.is_empty() || !z.is_empty()) {
+        " "
+    } else {
+        ""
+    };
+
+    let y_space = if !y.is_empty() && !z.is_empty() {
+        " "
+    } else {
+        ""
+    };
+
+    format!("{}{}{}{}{}", x, x_space, y, y_space, z)
 }
 
 impl fmt::Display for Point {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let x = render_coord('X', self.x);
-        let y = render_coord('Y', self.y);
-        let z = render_coord('Z', self.z);
-
-        let x_space = if !x.is_empty() && (!y.is_empty() || !z.is_empty()) {
-            " "
-        } else {
-            ""
-        };
-
-        let y_space = if !y.is_empty() && !z.is_empty() {
-            " "
-        } else {
-            ""
-        };
+        write!(f, "{}", render_point(self, &PRECISION))
+    }
+}
 
-        write!(f, "{}{}{}{}{}", x, x_space, y, y_space, z)
+impl Code {
+    // The code with the moves written to `precision`
+    fn render(&self, precision: &Precision) -> String {
+        match self {
+            Code::Move(p, s) => render_move(G_MODE, p, s, precision),
+            Code::Rapid(p, s) => render_move(0, p, s, precision),
+            Code::Bezier(c, s) => render_bezier(c, s, precision),
+            Code::Arc(a, s) => render_arc(a, s, precision),
+            code => code.to_string(),
+        }
     }
 }
 
 impl fmt::Display for Source {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        if let Some(comment) = self.comment {
+        if let Some(comment) = &self.comment {
             write!(f, "{} ; {}", self.code, comment)
         } else {
             write!(f, "{}", self.code)
@@ -179,8 +1000,16 @@ impl fmt::Display for Code {
             Code::Comment(c) => write!(f, "; {}", c),
             Code::Model(m) => write!(f, "M862.3 P \"{}\" ; printer model check", m),
             Code::Message(m) => write!(f, "M117 {}", m),
-            Code::Move(p, s) => write!(f, "{}", render_move(p, s)),
+            Code::Move(p, s) => write!(f, "{}", render_move(G_MODE, p, s, &PRECISION)),
+            Code::Rapid(p, s) => write!(f, "{}", render_move(0, p, s, &PRECISION)),
+            Code::Bezier(c, s) => write!(f, "{}", render_bezier(c, s, &PRECISION)),
+            Code::Arc(a, s) => write!(f, "{}", render_arc(a, s, &PRECISION)),
             Code::Raw(src) => write!(f, "{}", src),
+            Code::Pen(_, line) => write!(f, "{}", line),
+            Code::Dwell(s) => write!(f, "G4 P{:.0}", s * 1000.0),
+            Code::Pause(m) if m.is_empty() => write!(f, "M601"),
+            Code::Pause(m) => write!(f, "M117 {}\nM601", m),
+            Code::Beep => write!(f, "M300 S440 P200"),
             Code::NOP => write!(f, ""),
         }
     }
@@ -193,141 +1022,1789 @@ impl fmt::Display for Printer {
 }
 
 impl Printer {
+    // Takes the config as it is, try_new checks it first
     pub fn new(config: PrinterConfig) -> Self {
-        // TODO: Check if the config is valid?
-        // - is z0 > z_draw?
         Printer {
-            // TODO: Can we remove this clone?
-            config: config.clone(),
-            code: Vec::new(),
             width: config.max.0 - config.min.0,
             height: config.max.1 - config.min.1,
+            config,
+            ops: Vec::new(),
+            filters: filter::Pipeline::new(),
+            layers: vec!["default".to_string()],
+            pens: vec![Pen::default()],
+            layer: 0,
+            overrides: Overrides::default(),
+            sections: Vec::new(),
+            symmetry: None,
+            transforms: Vec::new(),
+            section: None,
+            session: None,
+            parameters: Vec::new(),
+            pending: Vec::new(),
         }
     }
 
-    pub fn draw_point(&mut self, xp: f32, yp: f32) {
-        let x: f32;
-        let y: f32;
+    pub fn try_new(config: PrinterConfig) -> Result<Self, config::ConfigError> {
+        config.validate()?;
+        Ok(Printer::new(config))
+    }
 
+    fn to_bed(&self, xp: f32, yp: f32) -> (f32, f32) {
+        let (xp, yp) = self.transform().apply((xp, yp));
         if let Some((ow, oh)) = self.config.scale {
-            x = rescale(xp, 0.0, ow, 0.0, self.width);
-            y = rescale(yp, 0.0, oh, 0.0, self.height);
+            (
+                rescale(xp, 0.0, ow, 0.0, self.width),
+                rescale(yp, 0.0, oh, 0.0, self.height),
+            )
         } else {
-            x = xp;
-            y = yp;
+            (xp, yp)
         }
+    }
 
-        // TODO: What to do if x, y are outside the defined print area?
+    pub fn draw_point(&mut self, xp: f32, yp: f32) {
+        self.push_drawn(
+            Shape::Point(self.to_bed(xp, yp)),
+            Some(format!("draw_point({:.1}, {:.1})", xp, yp)),
+        );
+    }
 
-        self.code
-            .push(Code::Comment(format!("draw_point({:.1}, {:.1})", xp, yp)));
-        // -> (x, y)
-        self.code.push(xy!(x, y, self.config.xy_speed));
-        // pen down
-        self.code
-            .push(z!(self.config.z_draw, self.config.down_speed));
-        // pen up
-        self.code.push(z!(self.config.z0, self.config.up_speed));
-        self.code.push(Code::NOP);
+    pub fn draw_point_labeled(&mut self, xp: f32, yp: f32, label: &str) {
+        self.labeled(label, |p| p.draw_point(xp, yp));
     }
 
-    fn total_dist(&self) -> f32 {
-        let mut total_dist = 0.0;
+    // The draw calls made by `draw` are commented with `label` instead of
+    // their names, e.g. to find a part of the drawing in the G-code
+    pub fn labeled(&mut self, label: &str, draw: impl FnOnce(&mut Self)) {
+        let start = self.ops.len();
+        draw(self);
+        for op in self.ops.iter_mut().skip(start) {
+            op.label = Some(label.to_string());
+        }
+    }
 
-        let mut curr_point = Point {
-            x: Some(0.0),
-            y: Some(0.0),
-            z: Some(self.config.z0),
+    // Dot that looks heavier the closer `weight` is to 1, e.g. for the
+    // darkness of an image under a stipple
+    pub fn draw_weighted_point(&mut self, xp: f32, yp: f32, weight: f32, style: DotWeight) {
+        let weight = weight.clamp(0.0, 1.0);
+        let (x, y) = self.to_bed(xp, yp);
+        let label = Some(format!(
+            "draw_weighted_point({:.1}, {:.1}, {:.2})",
+            xp, yp, weight
+        ));
+        let overrides = self.overrides;
+        let shape = match style {
+            DotWeight::Dwell(max) => {
+                self.overrides.dwell = Some(max * weight);
+                Shape::Point((x, y))
+            }
+            DotWeight::Depth(max) => {
+                let z_draw = overrides.z_draw.unwrap_or(self.config.z_draw);
+                self.overrides.z_draw = Some(z_draw - max * weight);
+                Shape::Point((x, y))
+            }
+            DotWeight::Scribble(max) => {
+                let radius = max * weight;
+                if radius < 0.05 {
+                    Shape::Point((x, y))
+                } else {
+                    // Two turns out from the center
+                    let n = ((radius * 16.0).ceil() as usize).clamp(8, 64);
+                    Shape::Stroke(
+                        (0..=n)
+                            .map(|i| {
+                                let t = i as f32 / n as f32;
+                                let a = t * 4.0 * std::f32::consts::PI;
+                                (x + radius * t * a.cos(), y + radius * t * a.sin())
+                            })
+                            .collect(),
+                    )
+                }
+            }
         };
+        self.push_drawn(shape, label);
+        self.overrides = overrides;
+    }
 
-        for c in &self.code {
-            if let Code::Move(p, _) = c {
-                total_dist += curr_point.dist(p);
+    // Single straight stroke from (x0, y0) to (x1, y1)
+    pub fn draw_line(&mut self, x0: f32, y0: f32, x1: f32, y1: f32) {
+        self.push_drawn(
+            Shape::Stroke(vec![self.to_bed(x0, y0), self.to_bed(x1, y1)]),
+            Some(format!(
+                "draw_line({:.1}, {:.1}, {:.1}, {:.1})",
+                x0, y0, x1, y1
+            )),
+        );
+    }
 
-                curr_point.x = p.x.or(curr_point.x);
-                curr_point.y = p.y.or(curr_point.y);
-                curr_point.z = p.z.or(curr_point.z);
-            }
+    // Plunge once, trace all of the points and only lift at the end
+    pub fn draw_polyline(&mut self, points: &[(f32, f32)]) {
+        if points.is_empty() {
+            return;
         }
 
-        total_dist
+        self.push_drawn(
+            Shape::Stroke(points.iter().map(|&(x, y)| self.to_bed(x, y)).collect()),
+            Some(format!("draw_polyline({} points)", points.len())),
+        );
     }
 
-    pub fn save(&self, filename: &str) -> Result<(), io::Error> {
-        // TODO: Need to return actual Result
-        // TODO: Return error if self.code.len() == 0?
-        let mut file = File::create(filename)?;
-        let mut header: Vec<Code> = Vec::new();
-        let mut footer: Vec<Code> = Vec::new();
+    // Fills the polygon through `points`, closing edge implied, without
+    // drawing its outline
+    pub fn draw_polygon_filled(&mut self, points: &[(f32, f32)], style: hatch::FillStyle) {
+        let mut label = Some(format!("draw_polygon_filled({} points)", points.len()));
+        let shape = canvas::Canvas::Polygon(points.to_vec());
+        for line in style.fill(&shape) {
+            let line = line.iter().map(|&(x, y)| self.to_bed(x, y)).collect();
+            self.push_drawn(Shape::from_path(line), label.take());
+        }
+    }
 
-        header.push(Code::Comment("Start of generated code".to_string()));
-        if let Some(model) = &self.config.model {
-            // TODO: Can we remove this clone?
-            header.push(model.clone());
+    // Lettering in the single-stroke font with the baseline of the first
+    // line starting at (x, y). Capitals are `height` tall, further lines go
+    // downwards.
+    pub fn draw_text(&mut self, x: f32, y: f32, height: f32, text: &str) {
+        let mut label = Some(format!("draw_text({:?})", text));
+        for stroke in text::layout(text, height) {
+            let stroke = stroke
+                .iter()
+                .map(|&(px, py)| self.to_bed(x + px, y + py))
+                .collect();
+            self.push_drawn(Shape::from_path(stroke), label.take());
         }
-        header.push(UNITS_MM);
-        header.push(ABS_COORD);
-        header.push(HOME);
-        header.push(Code::NOP);
+    }
 
-        // Move z first so we don't scrape the print area!
-        header.push(z!(self.config.z0, self.config.xy_speed));
-        header.push(xy!(
-            self.config.min.0,
-            self.config.min.1,
-            self.config.xy_speed
-        ));
-        header.push(SET_ORIGIN);
-        header.push(Code::Message("0.0%".to_string()));
-        header.push(Code::NOP);
+    // Chain of Bézier segments, drawn in one stroke. Kept as curves until
+    // emission so dialects with G5 can output them as is.
+    pub fn draw_curve(&mut self, curves: &[curve::CubicBezier]) {
+        if curves.is_empty() {
+            return;
+        }
 
-        footer.push(Code::Comment(
-            "Lift the head up before turning off".to_string(),
-        ));
-        footer.push(z!(Z_RESET, self.config.xy_speed));
-        footer.push(OFF);
-        footer.push(Code::NOP);
+        let label = format!("draw_curve({} segments)", curves.len());
+        let curves = curves
+            .iter()
+            .map(|c| {
+                curve::CubicBezier::new(
+                    self.to_bed(c.p0.0, c.p0.1),
+                    self.to_bed(c.c0.0, c.c0.1),
+                    self.to_bed(c.c1.0, c.c1.1),
+                    self.to_bed(c.p1.0, c.p1.1),
+                )
+            })
+            .collect();
+        self.push_drawn(Shape::Curve(curves), Some(label));
+    }
+
+    pub fn draw_cubic_bezier(
+        &mut self,
+        p0: (f32, f32),
+        c0: (f32, f32),
+        c1: (f32, f32),
+        p1: (f32, f32),
+    ) {
+        self.draw_curve(&[curve::CubicBezier::new(p0, c0, c1, p1)]);
+    }
+
+    // Smooth stroke through all of `points` (see curve::catmull_rom)
+    pub fn draw_spline(&mut self, points: &[(f32, f32)]) {
+        match points.len() {
+            0 => (),
+            1 => self.draw_point(points[0].0, points[0].1),
+            _ => self.draw_curve(&curve::catmull_rom(points)),
+        }
+    }
+
+    // Arc around (cx, cy) from `start_angle` to `end_angle` in degrees,
+    // counterclockwise if the end angle is the larger one. Goes out as a
+    // single G2/G3 move where nothing has to change the path on the way.
+    pub fn draw_arc(&mut self, cx: f32, cy: f32, radius: f32, start_angle: f32, end_angle: f32) {
+        let arc = curve::CircularArc::new(
+            (cx, cy),
+            radius,
+            start_angle.to_radians(),
+            (end_angle - start_angle).to_radians(),
+        );
+        let label = format!(
+            "draw_arc({:.1}, {:.1}, {:.1}, {:.1}, {:.1})",
+            cx, cy, radius, start_angle, end_angle
+        );
+        self.push_drawn(
+            Shape::Arc(arc).transformed(&self.bed_transform()),
+            Some(label),
+        );
+    }
+
+    pub fn draw_circle(&mut self, cx: f32, cy: f32, r: f32) {
+        let circle = curve::CircularArc::new((cx, cy), r, 0.0, 2.0 * std::f32::consts::PI);
+        self.push_drawn(
+            Shape::Arc(circle).transformed(&self.bed_transform()),
+            Some(format!("draw_circle({:.1}, {:.1}, {:.1})", cx, cy, r)),
+        );
+    }
+
+    // Applies `t` to everything drawn until the matching pop_transform,
+    // inside the transforms pushed before it, e.g. a translate to place a
+    // motif and then a rotate to turn it around its own origin
+    pub fn push_transform(&mut self, t: AffineTransform) {
+        let t = t.then(&self.transform());
+        self.transforms.push(t);
+    }
+
+    pub fn pop_transform(&mut self) {
+        self.transforms.pop();
+    }
+
+    // All pushed transforms in one
+    pub fn transform(&self) -> AffineTransform {
+        self.transforms
+            .last()
+            .copied()
+            .unwrap_or(AffineTransform::IDENTITY)
+    }
+
+    // Drawing coordinates to the bed, which may be scaled differently along
+    // x and y
+    fn scale_transform(&self) -> AffineTransform {
+        match self.config.scale {
+            Some((ow, oh)) => AffineTransform::scale(self.width / ow, self.height / oh),
+            None => AffineTransform::IDENTITY,
+        }
+    }
+
+    // What draw calls go through: the pushed transforms, then the scale to
+    // the bed
+    fn bed_transform(&self) -> AffineTransform {
+        self.transform().then(&self.scale_transform())
+    }
+
+    // Op of the drawing, with its copies when drawing with symmetry
+    fn push_drawn(&mut self, shape: Shape, label: Option<String>) {
+        let Some(symmetry) = self.symmetry else {
+            return self.push_op(shape, label);
+        };
+        // The symmetry is in drawing coordinates, around the same center
+        // whatever transforms are pushed
+        let to_bed = self.scale_transform();
+        let Some(to_drawing) = to_bed.invert() else {
+            return self.push_op(shape, label);
+        };
+        for t in symmetry.transforms() {
+            let t = to_drawing.then(&t).then(&to_bed);
+            self.push_op(shape.transformed(&t), label.clone());
+        }
+    }
+
+    fn push_op(&mut self, shape: Shape, label: Option<String>) {
+        let shape = match self.config.out_of_bounds {
+            Some(OutOfBounds::Skip) if !self.in_bounds(&shape) => return,
+            Some(OutOfBounds::Clamp) if !self.in_bounds(&shape) => Shape::from_path(
+                shape
+                    .path()
+                    .iter()
+                    .map(|&(x, y)| (x.clamp(0.0, self.width), y.clamp(0.0, self.height)))
+                    .collect(),
+            ),
+            _ => shape,
+        };
+        self.ops.push(Op {
+            shape,
+            label,
+            layer: self.layer,
+            overrides: match self.section {
+                Some(i) => self.overrides.or(self.sections[i].overrides),
+                None => self.overrides,
+            },
+            section: self.section,
+            before: std::mem::take(&mut self.pending),
+        });
+    }
+
+    // Paths already in bed coordinates, e.g. decorations sized in mm on paper
+    // that shouldn't be scaled with the drawing
+    fn draw_bed_paths(&mut self, paths: &[path::Path], label: Option<&str>) {
+        for path in paths.iter().filter(|p| !p.is_empty()) {
+            self.push_op(Shape::from_path(path.clone()), label.map(str::to_string));
+        }
+    }
+
+    // Emits `code` between what was drawn before and what's drawn next.
+    // It stays in front of the next draw call when the ops are optimized.
+    pub fn insert(&mut self, code: Code) {
+        self.pending.push(code);
+    }
+
+    // Any command, e.g. a feed override built at run time like "M220 S150",
+    // inserted like the codes of `insert`
+    pub fn emit_raw(&mut self, code: impl Into<String>, comment: Option<String>) {
+        self.insert(Code::Raw(Source::new(code, comment)));
+    }
+
+    // Waits for the user, e.g. to swap the paper or refill a brush
+    pub fn pause(&mut self, message: &str) {
+        self.insert(Code::Pause(message.to_string()));
+    }
+
+    pub fn dwell(&mut self, seconds: f32) {
+        self.insert(Code::Dwell(seconds));
+    }
+
+    pub fn beep(&mut self) {
+        self.insert(Code::Beep);
+    }
+
+    // Everything drawn from now on goes to the layer called `name`, which is
+    // created if it doesn't exist yet. Drawing starts on the "default" layer.
+    pub fn set_layer(&mut self, name: &str) {
+        self.layer = self.layer_index(name);
+    }
+
+    fn layer_index(&mut self, name: &str) -> usize {
+        match self.layers.iter().position(|l| l == name) {
+            Some(i) => i,
+            None => {
+                self.layers.push(name.to_string());
+                self.pens.push(Pen::default());
+                self.layers.len() - 1
+            }
+        }
+    }
+
+    // Draw the layer called `name` with `pen`, creating the layer if needed
+    pub fn set_layer_pen(&mut self, name: &str, pen: Pen) {
+        let i = self.layer_index(name);
+        self.pens[i] = pen;
+    }
+
+    // Everything drawn from now on goes to a layer named after `pen` and is
+    // drawn with it
+    pub fn set_pen(&mut self, pen: Pen) {
+        let name = pen.name.clone();
+        self.set_layer_pen(&name, pen);
+        self.set_layer(&name);
+    }
+
+    pub fn pens(&self) -> &[Pen] {
+        &self.pens
+    }
+
+    // Settings for everything drawn from now on, `Overrides::default()`
+    // goes back to the config values
+    pub fn set_overrides(&mut self, overrides: Overrides) {
+        self.overrides = overrides;
+    }
+
+    // Everything drawn until end_section belongs to a section called `name`
+    // and uses `overrides` where set_overrides leaves a setting unset. The
+    // machine goes back to the config after the section. Beginning a section
+    // ends the one before.
+    pub fn begin_section(&mut self, name: &str, overrides: Overrides) {
+        self.sections.push(Section {
+            name: name.to_string(),
+            overrides,
+        });
+        self.section = Some(self.sections.len() - 1);
+    }
+
+    pub fn end_section(&mut self) {
+        self.section = None;
+    }
+
+    // Repeat everything drawn from now on around a center, e.g. for
+    // mandalas. The copies are ops of their own, so they're clipped, checked
+    // and optimized like the rest of the drawing. None ends the symmetry.
+    pub fn set_symmetry(&mut self, symmetry: Option<symmetry::Symmetry>) {
+        self.symmetry = symmetry;
+    }
+
+    pub fn sections(&self) -> &[Section] {
+        &self.sections
+    }
+
+    // Record a setting of the generator, e.g. its seed. The value replaces
+    // an earlier one of the same name.
+    pub fn set_parameter(&mut self, name: &str, value: impl fmt::Display) {
+        let value = value.to_string();
+        match self.parameters.iter_mut().find(|(n, _)| n == name) {
+            Some(p) => p.1 = value,
+            None => self.parameters.push((name.to_string(), value)),
+        }
+    }
+
+    pub fn parameters(&self) -> &[(String, String)] {
+        &self.parameters
+    }
+
+    // The area that can be drawn on, the whole drawing area unless the config
+    // has a canvas
+    pub fn canvas(&self) -> canvas::Canvas {
+        self.config.canvas.clone().unwrap_or(canvas::Canvas::Rect {
+            min: (0.0, 0.0),
+            max: (self.width, self.height),
+        })
+    }
+
+    pub fn layers(&self) -> &[String] {
+        &self.layers
+    }
+
+    // Filters that every path goes through when the program is emitted
+    pub fn set_filters(&mut self, filters: filter::Pipeline) {
+        self.filters = filters;
+    }
+
+    pub fn ops(&self) -> &[Op] {
+        &self.ops
+    }
+
+    // Indices of the ops that reach outside the work area
+    pub fn out_of_bounds(&self) -> Vec<usize> {
+        (0..self.ops.len())
+            .filter(|&i| !self.in_bounds(&self.ops[i].shape))
+            .collect()
+    }
+
+    fn in_bounds(&self, shape: &Shape) -> bool {
+        let margin = 1e-3;
+        shape.path().iter().all(|&p| {
+            path::inside_rect(
+                p,
+                (-margin, -margin),
+                (self.width + margin, self.height + margin),
+            )
+        })
+    }
+
+    // Displace all queued geometry by a smooth noise field. This works on the
+    // op list, so it should be applied once the composition is complete.
+    pub fn displace(&mut self, field: &noise::Displace) {
+        let tolerance = self.config.tolerance;
+        for op in &mut self.ops {
+            match &mut op.shape {
+                Shape::Point(at) => *at = field.apply(*at),
+                Shape::Stroke(path) => *path = field.apply_path(path),
+                Shape::Curve(curves) => {
+                    op.shape = Shape::Stroke(field.apply_path(&curve::flatten(curves, tolerance)))
+                }
+                Shape::Arc(arc) => {
+                    op.shape = Shape::Stroke(field.apply_path(&arc.flatten(tolerance)))
+                }
+            }
+        }
+    }
+
+    // Drop pen-down segments that retrace earlier ones within `tolerance` mm.
+    // Strokes that are only partly duplicated are split into several ops.
+    pub fn dedupe(&mut self, tolerance: f32) {
+        let paths: Vec<Vec<(f32, f32)>> = self.ops.iter().map(Op::path).collect();
+        let mut ops = std::mem::take(&mut self.ops);
+
+        // Codes of ops that are dropped go with the next op that's left
+        let mut taken = 0;
+        self.ops = overlap::dedupe_indexed(&paths, tolerance)
+            .into_iter()
+            .map(|(i, path)| {
+                let start = taken.min(i + 1);
+                taken = taken.max(i + 1);
+                Op {
+                    shape: Shape::from_path(path),
+                    label: ops[i].label.take(),
+                    layer: ops[i].layer,
+                    overrides: ops[i].overrides,
+                    section: ops[i].section,
+                    before: ops[start..=i]
+                        .iter_mut()
+                        .flat_map(|op| std::mem::take(&mut op.before))
+                        .collect(),
+                }
+            })
+            .collect();
+        self.pending
+            .splice(0..0, ops.into_iter().flat_map(|op| op.before));
+    }
+
+    // Moves the ops of every pen together, in the order the pens are first
+    // used, so each pen only has to be loaded once. The ops of a pen keep
+    // their order.
+    pub fn group_by_pen(&mut self) {
+        let mut pens: Vec<&str> = Vec::new();
+        for op in &self.ops {
+            let name = self.pens[op.layer].name.as_str();
+            if !pens.contains(&name) {
+                pens.push(name);
+            }
+        }
+        let rank: Vec<usize> = self
+            .ops
+            .iter()
+            .map(|op| {
+                let name = self.pens[op.layer].name.as_str();
+                pens.iter().position(|&p| p == name).unwrap()
+            })
+            .collect();
+        let mut order: Vec<usize> = (0..self.ops.len()).collect();
+        order.sort_by_key(|&i| rank[i]);
+        let mut ops: Vec<Option<Op>> = std::mem::take(&mut self.ops)
+            .into_iter()
+            .map(Some)
+            .collect();
+        self.ops = order.into_iter().map(|i| ops[i].take().unwrap()).collect();
+    }
+
+    // Reorder the ops, and turn strokes around, to cut down on travel. Ops
+    // only move within runs of the same layer and section, so pen changes
+    // and sections stay where they were. Like dedupe this works on the op
+    // list, so it should be applied once the composition is complete.
+    pub fn optimize(&mut self) {
+        let mut ops: Vec<Option<Op>> = std::mem::take(&mut self.ops)
+            .into_iter()
+            .map(Some)
+            .collect();
+        let mut at = (0.0, 0.0);
+        let mut start = 0;
+        while start < ops.len() {
+            let key = |op: &Option<Op>| op.as_ref().map(|op| (op.layer, op.section));
+            // Inserted codes start a run of their own
+            let len = 1 + ops[start + 1..]
+                .iter()
+                .take_while(|op| {
+                    key(op) == key(&ops[start])
+                        && op.as_ref().is_some_and(|op| op.before.is_empty())
+                })
+                .count();
+            let run = &mut ops[start..start + len];
+            let ends: Vec<order::Ends> = run
+                .iter()
+                .map(|op| op.as_ref().unwrap().shape.ends())
+                .collect();
+            for (i, reversed) in order::optimize(&ends, at) {
+                let mut op = run[i].take().unwrap();
+                if reversed {
+                    op.shape = op.shape.reversed();
+                }
+                at = op.shape.ends().1;
+                self.ops.push(op);
+            }
+            start += len;
+        }
+    }
+
+    fn emit_op(&self, op: &Op, state: &mut EmitState, code: &mut Vec<Code>) {
+        // Move the tool so that the tip of this layer's pen lands on the path
+        let pen = &self.pens[op.layer];
+        let shift = |(x, y): (f32, f32)| (x - pen.offset.0, y - pen.offset.1);
+        let overrides = Overrides {
+            z_draw: Some(self.z_draw(op)),
+            ..op.overrides
+        };
+        if let Some(blending) = op.overrides.blending
+            && state.blending != Some(blending)
+        {
+            code.extend(self.config.dialect.blending(blending));
+            state.blending = Some(blending);
+        }
+        self.emit_acceleration(op.overrides.acceleration, state, code);
+
+        if let Shape::Curve(curves) = &op.shape
+            && self.keeps_shapes()
+            && self.config.native_curves
+            && self.config.dialect.supports_g5()
+        {
+            self.emit_label(op, code);
+            let curves: Vec<curve::CubicBezier> = curves
+                .iter()
+                .map(|c| {
+                    curve::CubicBezier::new(shift(c.p0), shift(c.c0), shift(c.c1), shift(c.p1))
+                })
+                .collect();
+            self.emit_curve(&curves, &overrides, state, code);
+            return;
+        }
+        if let Shape::Arc(arc) = &op.shape
+            && self.keeps_shapes()
+        {
+            self.emit_label(op, code);
+            let arc = curve::CircularArc {
+                center: shift(arc.center),
+                ..*arc
+            };
+            let draw_speed = overrides
+                .draw_speed
+                .unwrap_or(self.config.xy_speed)
+                .per_min();
+            let body = vec![Code::Arc(arc, draw_speed)];
+            self.emit_stroke(arc.point(0.0), arc.length(), body, &overrides, state, code);
+            return;
+        }
+
+        let path = op.shape.flatten(self.config.tolerance);
+        let mut paths = if self.filters.is_empty() {
+            vec![path]
+        } else {
+            self.filters.apply_all(vec![path])
+        };
+        if let Some(canvas) = &self.config.canvas {
+            paths = paths.iter().flat_map(|p| canvas.clip(p)).collect();
+        }
+
+        if !paths.is_empty() {
+            self.emit_label(op, code);
+        }
+        // Every path but the last hops to the next one
+        let next = state.next_start.take();
+        let starts: Vec<Option<(f32, f32)>> = paths
+            .iter()
+            .skip(1)
+            .map(|p| p.first().map(|&p| shift(p)))
+            .chain([next])
+            .collect();
+        for (mut path, next) in paths.into_iter().zip(starts) {
+            if let Some(knife) = &self.config.drag_knife {
+                path = knife.apply(&path);
+            }
+            let path: Vec<(f32, f32)> = path.into_iter().map(shift).collect();
+            state.next_start = next;
+            self.emit_path(&path, &overrides, state, code);
+        }
+    }
+
+    fn emit_label(&self, op: &Op, code: &mut Vec<Code>) {
+        if let Some(label) = &op.label
+            && self.config.annotations != Annotations::None
+        {
+            code.push(Code::Comment(label.clone()));
+        }
+    }
+
+    // A comment on what the machine does next, when the config wants them
+    fn emit_note(&self, note: impl FnOnce() -> String, code: &mut Vec<Code>) {
+        if self.config.annotations == Annotations::Verbose {
+            code.push(Code::Comment(note()));
+        }
+    }
+
+    // Curves and arcs can only go out as they are when nothing changes the
+    // path on the way
+    fn keeps_shapes(&self) -> bool {
+        self.filters.is_empty()
+            && self.config.drag_knife.is_none()
+            && self.config.canvas.is_none()
+            && self.config.taper.is_none()
+            && self.config.adaptive_feed.is_none()
+    }
+
+    fn emit_path(
+        &self,
+        path: &[(f32, f32)],
+        overrides: &Overrides,
+        state: &mut EmitState,
+        code: &mut Vec<Code>,
+    ) {
+        let Some(&start) = path.first() else {
+            return;
+        };
+
+        if let Some(taper) = &self.config.taper
+            && self.config.engrave.is_none()
+            && path.len() > 1
+        {
+            self.emit_tapered(path, taper, overrides, state, code);
+            return;
+        }
+        let feeds = self.draw_feeds(path, overrides);
+        let body = path[1..]
+            .iter()
+            .zip(feeds)
+            .map(|(&(x, y), feed)| xy!(x, y, feed))
+            .collect();
+        self.emit_stroke(start, path::length(path), body, overrides, state, code);
+    }
+
+    // Z follows the taper along the moves where the pen is on a Z axis,
+    // other actuators get their partial lift set before every move
+    fn emit_tapered(
+        &self,
+        path: &[(f32, f32)],
+        taper: &Taper,
+        overrides: &Overrides,
+        state: &mut EmitState,
+        code: &mut Vec<Code>,
+    ) {
+        let z_draw = overrides.z_draw.unwrap_or(self.config.z_draw);
+        let actuator = &self.config.actuator;
+        let profile = taper.profile(path);
+        let (start, start_lift) = profile[0];
+        let points: Vec<(f32, f32)> = profile.iter().map(|&(p, _)| p).collect();
+        let feeds = self.draw_feeds(&points, overrides);
+
+        let mut body = Vec::new();
+        let mut overrides = *overrides;
+        if actuator.ramps_z() {
+            overrides.z_draw = Some(Taper::height(z_draw, self.config.z0, start_lift));
+            for (&((x, y), lift), &feed) in profile[1..].iter().zip(&feeds) {
+                let z = Taper::height(z_draw, self.config.z0, lift);
+                body.push(Code::Move(
+                    Point {
+                        x: Some(x),
+                        y: Some(y),
+                        z: Some(z),
+                    },
+                    feed,
+                ));
+            }
+        } else {
+            state.start_lift = start_lift;
+            let mut last = start_lift;
+            for (&((x, y), lift), &feed) in profile[1..].iter().zip(&feeds) {
+                if lift != last {
+                    body.extend(actuator.ramp(lift));
+                    last = lift;
+                }
+                body.push(xy!(x, y, feed));
+            }
+        }
+        self.emit_stroke(start, path::length(path), body, &overrides, state, code);
+    }
+
+    // Feed for every segment of `path`. An op's own draw speed wins over the
+    // adaptive feed, e.g. for feed calibration.
+    fn draw_feeds(&self, path: &[(f32, f32)], overrides: &Overrides) -> Vec<f32> {
+        let segments = path.len().saturating_sub(1);
+        match (overrides.draw_speed, &self.config.adaptive_feed) {
+            (Some(speed), _) => vec![speed.per_min(); segments],
+            (None, Some(adaptive)) => adaptive.feeds(path),
+            (None, None) => vec![self.config.xy_speed.per_min(); segments],
+        }
+    }
+
+    // Native G5 moves, with straight moves bridging any gaps in the chain
+    fn emit_curve(
+        &self,
+        curves: &[curve::CubicBezier],
+        overrides: &Overrides,
+        state: &mut EmitState,
+        code: &mut Vec<Code>,
+    ) {
+        let Some(first) = curves.first() else {
+            return;
+        };
+
+        let draw_speed = overrides
+            .draw_speed
+            .unwrap_or(self.config.xy_speed)
+            .per_min();
+        let mut body = Vec::new();
+        let mut at = first.p0;
+        for c in curves {
+            if c.p0 != at {
+                body.push(xy!(c.p0.0, c.p0.1, draw_speed));
+            }
+            body.push(Code::Bezier(*c, draw_speed));
+            at = c.p1;
+        }
+
+        let length = path::length(&curve::flatten(curves, self.config.tolerance));
+        self.emit_stroke(first.p0, length, body, overrides, state, code);
+    }
+
+    // Travel to `start`, lower the pen, run the pen-down `body` and lift the
+    // pen again
+    fn emit_stroke(
+        &self,
+        (x, y): (f32, f32),
+        length: f32,
+        body: Vec<Code>,
+        overrides: &Overrides,
+        state: &mut EmitState,
+        code: &mut Vec<Code>,
+    ) {
+        // Travel at hop height only as far as the hop allows, and never to
+        // swap pens
+        let worn = self
+            .config
+            .pen_wear_limit
+            .is_some_and(|limit| state.pen_wear >= limit);
+        if worn
+            || state.hopped.is_some_and(|from| {
+                self.config
+                    .z_hop
+                    .is_none_or(|hop| path::dist(from, (x, y)) > hop.distance)
+            })
+        {
+            self.emit_retract(state, code);
+        }
+        state.hopped = None;
+
+        // Only swap pens between strokes, never in the middle of one
+        if worn {
+            code.push(Code::Comment(format!(
+                "Pen wear limit reached after {:.0}mm",
+                state.pen_wear
+            )));
+            code.push(Code::Message("Replace pen".to_string()));
+            code.push(self.config.dialect.pause());
+            code.push(Code::NOP);
+            state.pen_wear = 0.0;
+        }
+        state.pen_wear += length;
+
+        let z_draw = overrides.z_draw.unwrap_or(self.config.z_draw);
+        let end = stroke_end((x, y), &body);
+
+        // -> (x, y)
+        self.emit_note(|| format!("Travel to X{:.1} Y{:.1}", x, y), code);
+        code.push(xy!(
+            x,
+            y,
+            overrides
+                .travel_speed
+                .unwrap_or(self.config.xy_speed)
+                .per_min()
+        ));
+        // pen down, only partly at the start of a taper so the pen doesn't
+        // press down fully first
+        self.emit_note(|| format!("Pen down for {:.1}mm", length.abs()), code);
+        let lift = std::mem::take(&mut state.start_lift);
+        let partly = if lift > 0.0 {
+            self.config.actuator.ramp(lift)
+        } else {
+            Vec::new()
+        };
+        if partly.is_empty() {
+            code.extend(
+                self.config
+                    .actuator
+                    .down(z_draw, self.config.down_speed.per_min()),
+            );
+        } else {
+            code.extend(partly);
+        }
+        if let Some(seconds) = overrides.dwell.filter(|&s| s > 0.0) {
+            code.push(Code::Dwell(seconds));
+        }
+        if let Some(engrave) = &self.config.engrave {
+            // Closed paths go straight into the next pass, open ones return
+            // to the start at safe height
+            let closed = stroke_end((x, y), &body) == (x, y);
+            for (i, z) in engrave.passes(z_draw).into_iter().enumerate() {
+                if i > 0 && !closed {
+                    code.extend(
+                        self.config
+                            .actuator
+                            .up(self.config.z0, self.config.up_speed.per_min()),
+                    );
+                    code.push(xy!(x, y, self.config.xy_speed.per_min()));
+                    code.extend(
+                        self.config
+                            .actuator
+                            .down(z_draw, self.config.down_speed.per_min()),
+                    );
+                }
+                code.push(z!(z, engrave.plunge_speed.per_min()));
+                code.extend(body.iter().cloned());
+            }
+        } else {
+            code.extend(body);
+        }
+        // pen up
+        self.emit_note(|| "Pen up".to_string(), code);
+        let up = self.up_height(end, z_draw, state);
+        code.extend(self.config.actuator.up(up, self.config.up_speed.per_min()));
+        if self.config.annotations != Annotations::None {
+            code.push(Code::NOP);
+        }
+    }
+
+    // Where the pen goes up to after a stroke ending at `end`: just above
+    // the paper if the next stroke is close enough, z0 otherwise
+    fn up_height(&self, end: (f32, f32), z_draw: f32, state: &mut EmitState) -> f32 {
+        let next = state.next_start.take();
+        let Some(hop) = self.config.z_hop.filter(|_| self.config.actuator.ramps_z()) else {
+            return self.config.z0;
+        };
+        let z = z_draw + hop.height;
+        if z >= self.config.z0 || next.is_none_or(|next| path::dist(end, next) > hop.distance) {
+            return self.config.z0;
+        }
+        state.hopped = Some(end);
+        z
+    }
+
+    // All the way up after a hop, before anything that isn't the next stroke
+    fn emit_retract(&self, state: &mut EmitState, code: &mut Vec<Code>) {
+        if state.hopped.take().is_some() {
+            code.extend(
+                self.config
+                    .actuator
+                    .up(self.config.z0, self.config.up_speed.per_min()),
+            );
+        }
+    }
+
+    // The drawing body, every code tagged with the layer it belongs to
+    fn layered_codes(&self) -> Vec<(usize, Code)> {
+        self.op_codes()
+            .into_iter()
+            .map(|(i, c)| (self.ops[i].layer, c))
+            .collect()
+    }
+
+    // The drawing body, every code tagged with the index of its op
+    fn op_codes(&self) -> Vec<(usize, Code)> {
+        let mut state = EmitState::default();
+        let mut code = self.emit_ops(0..self.ops.len(), &mut state);
+        // Don't leave a section's acceleration behind for the next job
+        let mut restore = Vec::new();
+        self.emit_acceleration(None, &mut state, &mut restore);
+        let last = self.ops.len().saturating_sub(1);
+        code.extend(restore.into_iter().map(|c| (last, c)));
+        // Inserted after the last draw call, there's nothing to pause
+        // between without one
+        if !self.ops.is_empty() {
+            code.extend(self.pending.iter().map(|c| (last, c.clone())));
+        }
+        code
+    }
+
+    // Sets the acceleration of an op, `acceleration` or else the config's,
+    // if the machine isn't at it already
+    fn emit_acceleration(
+        &self,
+        acceleration: Option<f32>,
+        state: &mut EmitState,
+        code: &mut Vec<Code>,
+    ) {
+        let acceleration = acceleration.or(self.config.acceleration);
+        if acceleration == state.acceleration {
+            return;
+        }
+        code.extend(self.config.dialect.acceleration(acceleration));
+        state.acceleration = acceleration;
+    }
+
+    // Pause for loading the pen of `next` if it's another than that of `op`
+    fn emit_pen_change(&self, op: &Op, next: &Op, code: &mut Vec<Code>) {
+        let Some(message) = &self.config.pen_change else {
+            return;
+        };
+        let pen = &self.pens[next.layer].name;
+        if *pen == self.pens[op.layer].name {
+            return;
+        }
+        code.push(Code::Comment(format!("Pen change: {}", pen)));
+        code.push(Code::Message(message.replace("{pen}", pen)));
+        code.push(self.config.dialect.pause());
+        code.push(Code::NOP);
+    }
+
+    // Codes for some of the ops, with a drying pause before each of them
+    // counting as part of the op before and a pen change as part of the op
+    // after
+    fn emit_ops(&self, ops: std::ops::Range<usize>, state: &mut EmitState) -> Vec<(usize, Code)> {
+        let mut code = Vec::new();
+        for i in ops.clone() {
+            let mut buffer = Vec::new();
+            let mut between = Vec::new();
+            if i > 0 {
+                self.emit_drying(&self.ops[i - 1], &self.ops[i], state, &mut between);
+                if !between.is_empty() {
+                    self.emit_retract(state, &mut buffer);
+                }
+                buffer.append(&mut between);
+                code.extend(buffer.drain(..).map(|c| (i - 1, c)));
+                self.emit_pen_change(&self.ops[i - 1], &self.ops[i], &mut between);
+            }
+            between.extend(self.ops[i].before.iter().cloned());
+            if !between.is_empty() {
+                self.emit_retract(state, &mut buffer);
+            }
+            buffer.append(&mut between);
+            // Not worth a hop when a pen change or inserted codes come next
+            let hops = self.config.z_hop.is_some() && i + 1 < ops.end;
+            let next = self
+                .ops
+                .get(i + 1)
+                .filter(|next| hops && next.layer == self.ops[i].layer && next.before.is_empty());
+            state.next_start = next.and_then(|next| {
+                let offset = self.pens[next.layer].offset;
+                let (x, y) = *next.path().first()?;
+                Some((x - offset.0, y - offset.1))
+            });
+            self.emit_op(&self.ops[i], state, &mut buffer);
+            code.extend(buffer.into_iter().map(|c| (i, c)));
+        }
+        // Nothing follows in this run, e.g. the last draw call of a live plot
+        let mut buffer = Vec::new();
+        self.emit_retract(state, &mut buffer);
+        code.extend(buffer.into_iter().map(|c| (ops.end.saturating_sub(1), c)));
+        code
+    }
+
+    // Estimated time of every op in seconds, including the travel to it
+    fn op_times(&self) -> Vec<f32> {
+        let mut times = vec![0.0; self.ops.len()];
+        let mut machine = self.machine();
+        for (i, c) in self.op_codes() {
+            times[i] += machine.step(&c).iter().map(|s| s.time()).sum::<f32>();
+            times[i] += sim::wait_time(&c);
+        }
+        times
+    }
+
+    // Ranges of ops that each take at most `max_seconds`. Jobs are only cut
+    // between ops, so an op that takes longer on its own gets a session to
+    // itself. The estimate doesn't include the header of every session.
+    pub fn sessions(&self, max_seconds: f32) -> Vec<std::ops::Range<usize>> {
+        let mut sessions = Vec::new();
+        let mut start = 0;
+        let mut time = 0.0;
+        for (i, t) in self.op_times().into_iter().enumerate() {
+            if i > start && time + t > max_seconds {
+                sessions.push(start..i);
+                start = i;
+                time = 0.0;
+            }
+            time += t;
+        }
+        if start < self.ops.len() {
+            sessions.push(start..self.ops.len());
+        }
+        sessions
+    }
+
+    // Write the job as several files of at most `max_minutes` each, named
+    // like `filename` with the session number added ("plot-1.gcode", ...).
+    // Every file homes and sets the origin the same way, so the sessions
+    // line up on paper that stays in place.
+    pub fn save_sessions(&self, filename: &str, max_minutes: f32) -> Result<Vec<String>, Error> {
+        let sessions = self.sessions(max_minutes * 60.0);
+        let mut names = Vec::new();
+        for (i, range) in sessions.iter().enumerate() {
+            let mut part = self.clone();
+            part.ops = self.ops[range.clone()].to_vec();
+            part.session = Some((i + 1, sessions.len()));
+
+            let name = numbered(filename, &(i + 1).to_string());
+            part.save(&name)?;
+            names.push(name);
+        }
+        Ok(names)
+    }
+
+    // Splits a drawing larger than a sheet across several sheets. Every sheet
+    // gets the part of the drawing on it moved to the origin, with the
+    // alignment crosshairs on an "alignment" layer.
+    pub fn split_sheets(&self, sheets: &sheets::Sheets) -> Vec<(sheets::Sheet, Printer)> {
+        self.split_plan(sheets, sheets.plan(self.drawing_size()))
+    }
+
+    // Width and height from the origin to the farthest point drawn
+    fn drawing_size(&self) -> (f32, f32) {
+        self.ops
+            .iter()
+            .flat_map(|op| op.path())
+            .fold((0.0, 0.0), |(w, h): (f32, f32), (x, y)| {
+                (w.max(x), h.max(y))
+            })
+    }
+
+    fn split_plan(
+        &self,
+        sheets: &sheets::Sheets,
+        plan: Vec<sheets::Sheet>,
+    ) -> Vec<(sheets::Sheet, Printer)> {
+        let marks = sheets.marks(&plan);
+
+        plan.into_iter()
+            .map(|sheet| {
+                let (x0, y0) = sheet.origin;
+                let area = canvas::Canvas::Rect {
+                    min: sheet.origin,
+                    max: (x0 + sheets.sheet.0, y0 + sheets.sheet.1),
+                };
+                let local = |p: &path::Path| p.iter().map(|&(x, y)| (x - x0, y - y0)).collect();
+
+                let mut part = self.clone();
+                part.ops = self
+                    .ops
+                    .iter()
+                    .flat_map(|op| {
+                        area.clip(&op.path())
+                            .into_iter()
+                            .enumerate()
+                            .map(|(i, piece)| Op {
+                                shape: Shape::from_path(local(&piece)),
+                                before: if i == 0 {
+                                    op.before.clone()
+                                } else {
+                                    Vec::new()
+                                },
+                                ..op.clone()
+                            })
+                    })
+                    .collect();
+
+                part.layer = part.layer_index("alignment");
+                part.overrides = Overrides::default();
+                for mark in marks.iter().filter(|m| sheets.on_sheet(&sheet, m)) {
+                    part.push_op(
+                        Shape::Stroke(local(mark)),
+                        Some("alignment mark".to_string()),
+                    );
+                }
+                (sheet, part)
+            })
+            .collect()
+    }
+
+    // Write every sheet of `split_sheets` to its own file, named like
+    // `filename` with the row and column added ("mural-r1c2.gcode", ...)
+    pub fn save_sheets(
+        &self,
+        filename: &str,
+        sheets: &sheets::Sheets,
+    ) -> Result<Vec<String>, Error> {
+        let mut names = Vec::new();
+        for (sheet, part) in self.split_sheets(sheets) {
+            let name = numbered(
+                filename,
+                &format!("r{}c{}", sheet.row + 1, sheet.column + 1),
+            );
+            part.save(&name)?;
+            names.push(name);
+        }
+        Ok(names)
+    }
+
+    // Splits the drawing into `columns` by `rows` tiles that overlap by
+    // `overlap` mm, like save_sheets but with the tiles sized to the
+    // drawing. Fails if the tiles would be larger than the bed.
+    pub fn save_tiled(
+        &self,
+        columns: usize,
+        rows: usize,
+        overlap: f32,
+        filename: &str,
+    ) -> Result<Vec<String>, Error> {
+        let size = self.drawing_size();
+        let tile = |size: f32, n: usize| (size + (n.max(1) - 1) as f32 * overlap) / n.max(1) as f32;
+        let tile = (tile(size.0, columns), tile(size.1, rows));
+        // Only happens with an overlap at least as large as the drawing
+        if overlap >= tile.0 || overlap >= tile.1 {
+            return Err(Error::Bounds(format!(
+                "an overlap of {:.1}mm doesn't leave anything of the {:.1} x {:.1}mm tiles",
+                overlap, tile.0, tile.1
+            )));
+        }
+        if tile.0 > self.width + 1e-3 || tile.1 > self.height + 1e-3 {
+            return Err(Error::Bounds(format!(
+                "tiles of {:.1} x {:.1}mm don't fit the bed, use more columns or rows",
+                tile.0, tile.1
+            )));
+        }
+        let sheets = sheets::Sheets::new(tile, overlap)?;
+        let mut names = Vec::new();
+        for (sheet, part) in self.split_plan(&sheets, sheets.grid(columns, rows)) {
+            let name = numbered(
+                filename,
+                &format!("r{}c{}", sheet.row + 1, sheet.column + 1),
+            );
+            part.save(&name)?;
+            names.push(name);
+        }
+        Ok(names)
+    }
+
+    // Signs the drawing on its own "signature" layer. Returns the ops that
+    // reached into the signature zone, which are cut back unless the
+    // signature only warns about them. Sign after everything else is drawn,
+    // later ops aren't checked.
+    pub fn sign(&mut self, signature: &signature::Signature) -> Vec<usize> {
+        let size = (self.width, self.height);
+        let (min, max) = signature.zone(size);
+        let intruders: Vec<usize> = self
+            .ops
+            .iter()
+            .enumerate()
+            .filter(|(_, op)| path::cut_rect(&op.path(), min, max) != [op.path()])
+            .map(|(i, _)| i)
+            .collect();
+
+        if signature.intrusions == signature::Intrusions::Clip && !intruders.is_empty() {
+            self.ops = std::mem::take(&mut self.ops)
+                .into_iter()
+                .flat_map(|op| {
+                    path::cut_rect(&op.path(), min, max)
+                        .into_iter()
+                        .enumerate()
+                        .map(move |(i, piece)| Op {
+                            shape: Shape::from_path(piece),
+                            before: if i == 0 {
+                                op.before.clone()
+                            } else {
+                                Vec::new()
+                            },
+                            ..op.clone()
+                        })
+                })
+                .collect();
+        }
+
+        let (layer, overrides) = (self.layer, self.overrides);
+        self.layer = self.layer_index("signature");
+        self.overrides = Overrides::default();
+        self.draw_bed_paths(&signature.paths(size), Some("signature"));
+        (self.layer, self.overrides) = (layer, overrides);
+        intruders
+    }
+
+    // Pause between `op` and `next` if the ink needs time to dry
+    fn emit_drying(&self, op: &Op, next: &Op, state: &mut EmitState, code: &mut Vec<Code>) {
+        let Some(drying) = &self.config.drying else {
+            return;
+        };
+
+        if drying.density.is_some() {
+            state.ink.add(&op.path(), drying.region);
+        }
+
+        let reason = if drying.after_layer && op.layer != next.layer {
+            format!("after layer {}", self.layers[op.layer])
+        } else if drying
+            .density
+            .is_some_and(|limit| state.ink.max_density(drying.region) > limit)
+        {
+            "ink density reached".to_string()
+        } else {
+            return;
+        };
+
+        code.push(Code::Comment(format!(
+            "Let the ink dry for {:.0}s, {}",
+            drying.seconds, reason
+        )));
+        if let Some((x, y)) = drying.park {
+            code.push(xy!(x, y, self.config.xy_speed.per_min()));
+        }
+        code.push(Code::Dwell(drying.seconds));
+        code.push(Code::NOP);
+        state.ink.clear();
+    }
+
+    fn codes(&self) -> Vec<Code> {
+        self.layered_codes().into_iter().map(|(_, c)| c).collect()
+    }
+
+    fn origin(&self) -> (f32, f32, f32) {
+        (0.0, 0.0, self.config.z0)
+    }
+
+    // Highest z at which the pen touches the paper, taking overrides into
+    // account so the simulation doesn't mistake shallow strokes for travel
+    fn pen_z(&self) -> f32 {
+        let lifted = |z: f32| match self.config.taper {
+            Some(t) if self.config.actuator.ramps_z() => Taper::height(z, self.config.z0, t.lift),
+            _ => z,
+        };
+        self.ops
+            .iter()
+            .map(|op| lifted(self.z_draw(op)))
+            .fold(lifted(self.config.z_draw), f32::max)
+    }
+
+    // Pen-down height for `op`, including the offset of its layer's pen
+    fn z_draw(&self, op: &Op) -> f32 {
+        op.overrides.z_draw.unwrap_or(self.config.z_draw) + self.pens[op.layer].z_offset
+    }
+
+    // Every move of the drawing body as the machine will execute it, starting
+    // from the origin set up by the header
+    pub fn simulate(&self) -> Vec<sim::Segment> {
+        sim::replay(&self.codes(), self.machine())
+    }
+
+    // Machine at the origin, ramping speeds at the config's acceleration
+    fn machine(&self) -> sim::Machine {
+        sim::Machine::new(self.origin(), self.pen_z()).with_acceleration(self.config.acceleration)
+    }
+
+    // Estimated time of the whole drawing, from the feed of every move and
+    // the acceleration if the config sets it
+    pub fn estimated_duration(&self) -> Duration {
+        Duration::from_secs_f32(self.stats().total.time.max(0.0))
+    }
+
+    // Distances, estimated time and command counts, overall and per layer
+    pub fn stats(&self) -> sim::PlotStats {
+        let mut stats = sim::PlotStats::new(&self.layers);
+        let mut machine = self.machine();
+        for (layer, c) in self.layered_codes() {
+            let segments = machine.step(&c);
+            stats.record(layer, &c, &segments);
+        }
+        stats
+    }
+
+    fn layered_segments(&self) -> Vec<(usize, sim::Segment)> {
+        let mut machine = self.machine();
+        self.layered_codes()
+            .iter()
+            .flat_map(|(layer, c)| machine.step(c).into_iter().map(|s| (*layer, s)))
+            .collect()
+    }
+
+    // Interactive HTML page to review the plot before running it
+    pub fn preview_html(&self, filename: &str) -> Result<(), Error> {
+        let page = preview::html(
+            &self.layered_segments(),
+            &self.layers,
+            (self.width, self.height),
+        );
+        Ok(std::fs::write(filename, page)?)
+    }
+
+    // The toolpath as an SVG drawing of the bed, travel dashed
+    pub fn preview_svg(&self, filename: &str) -> Result<(), Error> {
+        let svg = preview::svg(
+            &self.layered_segments(),
+            &self.layers,
+            (self.width, self.height),
+        );
+        Ok(std::fs::write(filename, svg)?)
+    }
+
+    // What ends up on paper as a `width` x `height` PNG
+    pub fn preview_png(&self, filename: &str, width: u32, height: u32) -> Result<(), Error> {
+        if width == 0 || height == 0 {
+            return Err(Error::Bounds(format!(
+                "empty preview size {}x{}",
+                width, height
+            )));
+        }
+        let bitmap = raster::render(&self.simulate(), (self.width, self.height), width, height);
+        Ok(std::fs::write(filename, bitmap.to_png())?)
+    }
+
+    // PrusaSlicer-style thumbnail comment blocks, one per configured size
+    fn thumbnails(&self) -> Vec<Code> {
+        let segments = self.simulate();
+        let mut code = Vec::new();
+        for &(w, h) in &self.config.thumbnails {
+            let bitmap = raster::render(&segments, (self.width, self.height), w, h);
+            code.push(Code::NOP);
+            code.extend(
+                raster::thumbnail_block(&bitmap)
+                    .into_iter()
+                    .map(Code::Comment),
+            );
+        }
+        code.push(Code::NOP);
+        code
+    }
+
+    // Job information in the "; key = value" form slicers write at the end of
+    // the file, which is what PrusaLink and OctoPrint read for their UIs
+    fn metadata(&self) -> Vec<Code> {
+        let segments = self.simulate();
+        let stats = self.stats();
+        let mut code = vec![
+            Code::Comment(format!(
+                "estimated printing time (normal mode) = {}",
+                sim::format_time_words(stats.total.time)
+            )),
+            Code::Comment(format!("drawn distance [mm] = {:.1}", stats.total.drawn)),
+            Code::Comment(format!("travel distance [mm] = {:.1}", stats.total.travel)),
+        ];
+        if let Some(((x0, y0), (x1, y1))) = sim::drawn_extents(&segments) {
+            code.push(Code::Comment(format!(
+                "bed area used = {:.1}x{:.1} at {:.1},{:.1}",
+                x1 - x0,
+                y1 - y0,
+                x0,
+                y0
+            )));
+        }
+        code.push(Code::Comment(format!("layers = {}", self.layers.join(";"))));
+        if let Some(Code::Model(model)) = &self.config.model {
+            code.push(Code::Comment(format!("printer_model = {}", model)));
+        }
+        code.push(Code::Comment(format!(
+            "generator = {} {}",
+            env!("CARGO_PKG_NAME"),
+            env!("CARGO_PKG_VERSION")
+        )));
+        code
+    }
+
+    // EiBotBoard command file for an EggBot, with the bed area wrapped around
+    // the egg
+    pub fn save_eggbot(&self, filename: &str, bot: &eggbot::EggBot) -> Result<(), Error> {
+        let mut program = bot.program(&self.simulate(), (self.width, self.height));
+        program.push(String::new());
+        Ok(std::fs::write(filename, program.join("\r"))?)
+    }
+
+    // Where pen-down strokes cross or retrace each other
+    pub fn intersections(&self, tolerance: f32) -> overlap::Report {
+        overlap::report(&self.simulate(), tolerance)
+    }
+
+    fn header(&self) -> Vec<Code> {
+        let mut header: Vec<Code> = Vec::new();
+
+        header.push(Code::Comment("Start of generated code".to_string()));
+        let outside = self.out_of_bounds().len();
+        if outside > 0 {
+            header.push(Code::Comment(format!(
+                "[WARNING] {} draws reach outside the work area!",
+                outside
+            )));
+        }
+        if let Some((n, count)) = self.session {
+            header.push(Code::Comment(format!("Session {} of {}", n, count)));
+        }
+        if self.config.metadata {
+            header.push(Code::Comment(format!(
+                "generated by {} {}",
+                env!("CARGO_PKG_NAME"),
+                env!("CARGO_PKG_VERSION")
+            )));
+        }
+        if self.config.embed_config {
+            header.extend(config::to_comments(&self.config, &self.parameters));
+        }
+        if !self.config.thumbnails.is_empty() {
+            header.extend(self.thumbnails());
+        }
+        if self.config.stats_comments {
+            header.extend(self.stats().to_comments());
+        }
+        if let Some(model) = &self.config.model
+            && self.config.dialect.checks_model()
+        {
+            // TODO: Can we remove this clone?
+            header.push(model.clone());
+        }
+        header.push(UNITS_MM);
+        header.push(ABS_COORD);
+        header.push(self.config.dialect.home());
+        header.push(Code::NOP);
+
+        if let Some(wcs) = self.config.wcs {
+            // The origin comes from the WCS offsets, so G92 isn't needed
+            header.push(wcs.code());
+            header.extend(
+                self.config
+                    .actuator
+                    .up(self.config.z0, self.config.xy_speed.per_min()),
+            );
+            header.push(xy!(0.0, 0.0, self.config.xy_speed.per_min()));
+        } else {
+            // Move z first so we don't scrape the print area!
+            header.extend(
+                self.config
+                    .actuator
+                    .up(self.config.z0, self.config.xy_speed.per_min()),
+            );
+            header.push(xy!(
+                self.config.min.0,
+                self.config.min.1,
+                self.config.xy_speed.per_min()
+            ));
+            header.push(SET_ORIGIN);
+        }
+        if self.config.progress.messages {
+            header.push(Code::Message("0.0%".to_string()));
+        }
+        header.push(Code::NOP);
+        header
+    }
+
+    // The drawing with progress messages, section markers and recovery
+    // points. Progress is
+    // based on the estimated time of the moves, so the countdown stays
+    // accurate no matter how long individual moves take.
+    fn body(&self, on_progress: &mut dyn FnMut(progress::ProgressEvent)) -> Vec<Code> {
+        let mut tracker = progress::Tracker::new(&self.config.progress, self.stats().total.time);
+        let mut machine = self.machine();
+        let mut elapsed = 0.0;
+        let pen_z = self.pen_z();
+        let mut section = None;
+        let mut op = None;
+        let mut recovered: Option<f32> = None;
+        let mut body = Vec::new();
+        for (i, c) in self.op_codes() {
+            if self.ops[i].section != section {
+                body.extend(self.section_markers(section, self.ops[i].section, elapsed));
+                section = self.ops[i].section;
+            }
+            // Before the first code of an op, where the pen is up
+            if op != Some(i)
+                && let Some(interval) = self.config.recovery_interval
+                && recovered.is_none_or(|t| elapsed - t >= interval)
+            {
+                let position = machine.position();
+                body.push(recovery::Marker::comment(
+                    i,
+                    position,
+                    position.2 <= pen_z + 1e-4,
+                ));
+                recovered = Some(elapsed);
+            }
+            op = Some(i);
+            let seconds =
+                machine.step(&c).iter().map(|s| s.time()).sum::<f32>() + sim::wait_time(&c);
+            elapsed += seconds;
+            let event = tracker.record(&c, seconds);
+            body.push(c);
+            if let Some(event) = event {
+                body.extend(tracker.message(&event));
+                on_progress(event);
+            }
+        }
+        body.extend(self.section_markers(section, None, elapsed));
+        body
+    }
+
+    fn section_markers(&self, from: Option<usize>, to: Option<usize>, time: f32) -> Vec<Code> {
+        let mut markers = Vec::new();
+        if let Some(i) = from {
+            markers.push(Code::Comment(format!(
+                "{} \"{}\"",
+                SECTION_END, self.sections[i].name
+            )));
+        }
+        if let Some(i) = to {
+            markers.push(Code::Comment(format!(
+                "{} \"{}\" at {}",
+                SECTION_BEGIN,
+                self.sections[i].name,
+                sim::format_time(time)
+            )));
+        }
+        markers
+    }
+
+    // Table of contents listing where every section begins, with the body
+    // starting at line `first`. Empty without sections.
+    fn contents(&self, body: &[Code], first: usize) -> Vec<Code> {
+        let mut line = first;
+        let mut entries = Vec::new();
+        for c in body {
+            if let Code::Comment(comment) = c
+                && let Some(section) = comment.strip_prefix(SECTION_BEGIN)
+            {
+                entries.push((section.trim_start().to_string(), line));
+            }
+            line += lines(c);
+        }
+        if entries.is_empty() {
+            return Vec::new();
+        }
+
+        // The contents themselves push the body down
+        let offset = entries.len() + 2;
+        let mut contents = vec![Code::Comment("Contents".to_string())];
+        contents.extend(entries.into_iter().map(|(section, line)| {
+            Code::Comment(format!("  {}, line {}", section, line + offset))
+        }));
+        contents.push(Code::NOP);
+        contents
+    }
+
+    fn footer(&self) -> Vec<Code> {
+        let mut footer: Vec<Code> = Vec::new();
+
+        footer.push(Code::Comment(
+            "Lift the head up before turning off".to_string(),
+        ));
+        // Other actuators have already lifted the pen, and there may be no
+        // Z axis at all
+        if self.config.actuator.ramps_z() {
+            footer.push(z!(Z_RESET, self.config.xy_speed.per_min()));
+        }
+        footer.extend(self.config.dialect.motors_off());
+        footer.push(Code::NOP);
+        if self.config.metadata {
+            footer.extend(self.metadata());
+        }
+        footer
+    }
+
+    // Codes rewritten for the config's dialect, as they go to the machine
+    fn translated(&self, code: impl IntoIterator<Item = Code>) -> Vec<Code> {
+        code.into_iter().map(|c| self.output(c)).collect()
+    }
+
+    fn precision(&self) -> Precision {
+        Precision {
+            xy: self.config.xy_decimals,
+            z: self.config.z_decimals,
+            feed: self.config.feed_decimals,
+        }
+    }
+
+    // The code as it's written out, for the dialect and with moves at the
+    // config's precision
+    fn output(&self, c: Code) -> Code {
+        let precision = self.precision();
+        match self.config.dialect.translate(c) {
+            c @ (Code::Move(..) | Code::Rapid(..) | Code::Bezier(..) | Code::Arc(..))
+                if precision != PRECISION =>
+            {
+                Code::Raw(c.render(&precision).into())
+            }
+            c => c,
+        }
+    }
+
+    // Follows the written program to shorten its moves, if the config says so
+    fn compactor(&self) -> Option<compact::Compactor> {
+        self.config
+            .compact
+            .map(|mode| compact::Compactor::new(mode, self.config.dialect, self.precision()))
+    }
+
+    // Writes `c` as it goes out, through the compactor if there is one
+    fn write_output(
+        &self,
+        w: &mut impl Write,
+        c: Code,
+        compactor: &mut Option<compact::Compactor>,
+    ) -> Result<(), io::Error> {
+        match compactor {
+            Some(compactor) => compactor
+                .code(c)
+                .into_iter()
+                .try_for_each(|c| write_code(w, c)),
+            None => write_code(w, self.output(c)),
+        }
+    }
+
+    pub fn save(&self, filename: &str) -> Result<(), Error> {
+        // TODO: Return error if self.ops.len() == 0?
+        let mut file = io::BufWriter::new(File::create(filename)?);
+        self.write_to(&mut file)
+    }
+
+    // The program from the `n`th recovery point on (counting from 0), to
+    // pick up a plot that was cut short. It homes and sets the origin as the
+    // full program does, see recovery::resume.
+    pub fn save_from_checkpoint(&self, n: usize, filename: &str) -> Result<(), Error> {
+        let mut program = Vec::new();
+        self.write_to(&mut program)?;
+        let program = String::from_utf8_lossy(&program);
+        let markers = recovery::markers(&program);
+        let marker = markers.get(n).ok_or_else(|| {
+            Error::Bounds(format!(
+                "no recovery point {}, the program has {} (see recovery_interval)",
+                n,
+                markers.len()
+            ))
+        })?;
+        Ok(std::fs::write(
+            filename,
+            recovery::resume(&program, marker)?,
+        )?)
+    }
+
+    // Starts writing the program right away, see stream::Stream
+    pub fn begin<W: Write>(self, writer: W) -> Result<stream::Stream<W>, Error> {
+        stream::Stream::begin(self, writer)
+    }
+
+    // The whole program as saved, e.g. to stdout, a socket or memory
+    pub fn write_to<W: Write>(&self, w: &mut W) -> Result<(), Error> {
+        self.write_with_progress(w, |_| ())
+    }
+
+    // Like write_to, calling `on_progress` wherever the config's progress
+    // interval falls, e.g. to drive a progress bar
+    pub fn write_with_progress<W: Write>(
+        &self,
+        w: &mut W,
+        mut on_progress: impl FnMut(progress::ProgressEvent),
+    ) -> Result<(), Error> {
+        self.write_program(w, &mut on_progress, None)
+    }
+
+    // The program with the same moves but the pen never touching the paper,
+    // see dry_run::DryRun. The header, stats included, is that of the real
+    // plot.
+    pub fn save_dry_run(&self, filename: &str, dry: &dry_run::DryRun) -> Result<(), Error> {
+        let mut file = io::BufWriter::new(File::create(filename)?);
+        self.write_program(&mut file, &mut |_| (), Some(dry))
+    }
+
+    // The drawing in the language of `backend`, one line per code, between
+    // what the backend starts and finishes with. The G-code header and
+    // footer are left out, write_to writes the full program.
+    pub fn write_backend<W: Write>(
+        &self,
+        w: &mut W,
+        backend: &mut dyn backend::Backend,
+    ) -> Result<(), Error> {
+        let mut lines = vec![backend.start()];
+        for c in self.codes() {
+            lines.push(backend.render(&c));
+        }
+        lines.push(backend.finish());
+        for line in lines.iter().filter(|l| !l.is_empty()) {
+            writeln!(w, "{}", line)?;
+        }
+        Ok(w.flush()?)
+    }
+
+    // The drawing as HP-GL, for pen plotters and vinyl cutters
+    pub fn save_hpgl(&self, filename: &str) -> Result<(), Error> {
+        let mut file = io::BufWriter::new(File::create(filename)?);
+        let mut hpgl = backend::Hpgl::new(self.origin(), self.pen_z());
+        self.write_backend(&mut file, &mut hpgl)
+    }
 
-        for c in header {
-            write_code(&mut file, c)?;
+    fn write_program<W: Write>(
+        &self,
+        w: &mut W,
+        on_progress: &mut dyn FnMut(progress::ProgressEvent),
+        dry: Option<&dry_run::DryRun>,
+    ) -> Result<(), Error> {
+        if self.config.out_of_bounds == Some(OutOfBounds::Error)
+            && let Some(&i) = self.out_of_bounds().first()
+        {
+            let op = &self.ops[i];
+            return Err(Error::Bounds(format!(
+                "{} reaches outside the work area",
+                op.label.clone().unwrap_or(format!("op {}", i))
+            )));
+        }
+        let header = self.header();
+        let mut body = self.body(on_progress);
+        if let Some(dry) = dry {
+            let pen_z = self.pen_z();
+            body = body
+                .into_iter()
+                .map(|c| dry.apply(c, pen_z, self.config.z0))
+                .collect();
         }
+        let footer = self.footer();
 
-        // TODO: Can we skip based on time instead?
-        let mut count = 1;
-        let skip = cmp::max(((self.code.len() as f32) * 0.015) as u32, 5); // 5 number of commands
-        // in draw_point
-        let total_time = (Self::total_dist(self) / SPEED) as u32;
-        for c in &self.code {
-            // TODO: Can we remove this clone?
-            write_code(&mut file, c.clone())?;
-
-            if count % skip == 0 {
-                let percent: f32 = (count as f32) / (self.code.len() as f32);
-                let total_seconds = ((1.0 - percent) * total_time as f32) as u32;
-                let hours = total_seconds / 3600;
-                let minutes = (total_seconds % 3600) / 60;
-                let seconds = total_seconds % 60;
-
-                write_code(
-                    &mut file,
-                    Code::Message(format!(
-                        "{:.1}% R{:02}:{:02}:{:02}",
-                        percent * 100.0,
-                        hours,
-                        minutes,
-                        seconds
-                    )),
-                )?;
-            }
+        // Line numbers of the sections only settle once the contents, which
+        // come between the header and the body, are known in length
+        let first = header.iter().map(lines).sum::<usize>() + 1;
+        let contents = self.contents(&body, first);
 
-            count += 1;
+        let mut compactor = self.compactor();
+        for c in header.into_iter().chain(contents).chain(body) {
+            self.write_output(w, c, &mut compactor)?;
         }
 
         for c in footer {
-            write_code(&mut file, c)?;
+            self.write_output(w, c, &mut compactor)?;
+        }
+        for c in compactor.iter_mut().flat_map(|c| c.finish()) {
+            write_code(w, c)?;
         }
 
-        file.flush()?;
+        w.flush()?;
         Ok(())
     }
 }
@@ -336,21 +2813,37 @@ impl Printer {
 mod tests {
     use super::*;
 
-    fn test_config() -> PrinterConfig {
+    // The MK3S preset, with the settings the expected programs depend on
+    // spelled out so changes to the preset don't change the tests
+    pub(crate) fn test_config() -> PrinterConfig {
         PrinterConfig {
-            model: Some(Code::Model("MK3S".to_string())), // Printer model check
-            min: (50.0, 35.0),   // Smallest possible printer (x, y) position
-            max: (254.0, 212.0), // Largest possible printer (x, y) position
-            scale: None,         // Original scale to resize based on min and max
-            z0: 6.5,             // z position where the printer can freely move along xy-axis
-            z_draw: 4.0,         // z position where pen meets paper
-            xy_speed: 1000.0,    // Speed when moving through the 2D xy-plane with pen up
-            down_speed: 500.0,   // Speed when lowering the pen (z0 -> z_draw)
-            up_speed: 800.0,     // Speed when raising the pen (z_draw -> z0)
+            min: (50.0, 35.0),
+            max: (254.0, 212.0),
+            z0: 6.5,
+            z_draw: 4.0,
+            xy_speed: Feed::mm_per_min(1000.0),
+            down_speed: Feed::mm_per_min(500.0),
+            up_speed: Feed::mm_per_min(800.0),
+            dialect: Dialect::Prusa,
+            xy_decimals: 1,
+            z_decimals: 1,
+            feed_decimals: 1,
+            annotations: Annotations::PerShape,
+            ..PrinterConfig::prusa_mk3s()
         }
     }
 
-    fn assert_within(a: f32, b: f32, n: f32) {
+    // Length of every move, drawn or not
+    pub(crate) fn total_dist(printer: &Printer) -> f32 {
+        printer.simulate().iter().map(|s| s.length()).sum()
+    }
+
+    // A file in the temp dir that no other test run writes at the same time
+    pub(crate) fn temp_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("artful_gcode_{}_{}", std::process::id(), name))
+    }
+
+    pub(crate) fn assert_within(a: f32, b: f32, n: f32) {
         if (a - b).abs() >= n {
             panic!("The difference between {} and {} is more than {}!", a, b, n);
         }
@@ -365,6 +2858,46 @@ mod tests {
         assert_eq!(c.to_string(), "M117 50.3%");
     }
 
+    #[test]
+    fn move_precision() {
+        let precision = Precision {
+            xy: 3,
+            z: 2,
+            feed: 0,
+        };
+        let c = Code::Move(
+            Point {
+                x: Some(50.0504),
+                y: Some(35.0496),
+                z: Some(4.125),
+            },
+            1000.4,
+        );
+        assert_eq!(c.render(&precision), "G1 X50.050 Y35.050 Z4.12 F1000");
+        // One decimal as before, so 0.05mm steps get lost
+        assert_eq!(c.to_string(), "G1 X50.1 Y35.0 Z4.1 F1000.4");
+        let arc = Code::Arc(
+            curve::CircularArc::new((1.0, 0.0), 1.0, std::f32::consts::PI, -std::f32::consts::PI),
+            500.0,
+        );
+        assert_eq!(
+            arc.render(&precision),
+            "G2 X2.000 Y0.000 I1.000 J0.000 F500"
+        );
+
+        // Stipples 0.05mm apart stay apart in the file
+        let mut config = test_config();
+        config.xy_decimals = 2;
+        let mut printer = Printer::new(config);
+        printer.draw_point(0.0, 0.0);
+        printer.draw_point(0.05, 0.0);
+        let mut out = Vec::new();
+        printer.write_to(&mut out).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        assert!(text.contains("G1 X0.00 Y0.00 F1000.0"));
+        assert!(text.contains("G1 X0.05 Y0.00 F1000.0"));
+    }
+
     #[test]
     fn code_model() {
         let c: Code = Code::Model("MK3S".to_owned());
@@ -430,12 +2963,117 @@ mod tests {
         assert_eq!(c.to_string(), format!("G{} Y1.0 F1000.0", G_MODE));
     }
 
+    fn program(printer: &Printer) -> String {
+        let mut out = Vec::new();
+        printer.write_to(&mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    // Compares `text` with the golden file src/snapshots/`name`. Run the
+    // tests with UPDATE_SNAPSHOTS=1 to write the files instead, and review
+    // the changes to them before committing.
+    fn assert_snapshot(name: &str, text: &str) {
+        let path = format!("{}/src/snapshots/{}", env!("CARGO_MANIFEST_DIR"), name);
+        if std::env::var_os("UPDATE_SNAPSHOTS").is_some() {
+            std::fs::write(&path, text).unwrap();
+        }
+        let golden = std::fs::read_to_string(&path)
+            .unwrap_or_else(|e| panic!("{}: {}, run with UPDATE_SNAPSHOTS=1", path, e));
+        for (i, (line, expected)) in text.lines().zip(golden.lines()).enumerate() {
+            assert_eq!(line, expected, "{} differs at line {}", name, i + 1);
+        }
+        assert_eq!(text.lines().count(), golden.lines().count(), "{}", name);
+    }
+
+    // Points, lines, circles and curves all over the work area
+    fn random_drawing(seed: u64) -> Printer {
+        let mut rng = rng::Rng::new(seed);
+        let mut printer = Printer::new(test_config());
+        let (w, h) = (printer.width, printer.height);
+        let point = |rng: &mut rng::Rng| (rng.range(0.0, w), rng.range(0.0, h));
+        for _ in 0..20 {
+            match rng.below(4) {
+                0 => {
+                    let (x, y) = point(&mut rng);
+                    printer.draw_point(x, y);
+                }
+                1 => {
+                    let n = 2 + rng.below(5);
+                    let points: Vec<_> = (0..n).map(|_| point(&mut rng)).collect();
+                    printer.draw_polyline(&points);
+                }
+                2 => {
+                    let (x, y) = point(&mut rng);
+                    let r = rng.range(0.5, 20.0).min(x).min(y).min(w - x).min(h - y);
+                    printer.draw_circle(x, y, r.max(0.1));
+                }
+                _ => {
+                    let p: Vec<_> = (0..4).map(|_| point(&mut rng)).collect();
+                    printer.draw_cubic_bezier(p[0], p[1], p[2], p[3]);
+                }
+            }
+        }
+        printer
+    }
+
+    // The moves of a program written by `printer`, read back from its text
+    // and replayed from the origin the header sets up
+    fn replayed(printer: &Printer, text: &str) -> Vec<sim::Segment> {
+        let body: String = text
+            .lines()
+            .skip_while(|l| !l.starts_with("G92"))
+            .skip(1)
+            .map(|l| format!("{}\n", l))
+            .collect();
+        sim::replay(&parse::codes(&body), printer.machine())
+    }
+
     #[test]
     fn simple_example() {
         let mut printer = Printer::new(test_config());
         printer.draw_point(50.0, 50.0);
-        if let Err(e) = printer.save("simple.gcode") {
-            panic!("Unable to generate GCODE: {}", e);
+        assert_snapshot("simple.gcode", &program(&printer));
+    }
+
+    #[test]
+    fn shapes_example() {
+        let mut config = test_config();
+        config.stats_comments = true;
+        let mut printer = Printer::new(config);
+        printer.draw_polyline(&[(10.0, 10.0), (40.0, 10.0), (40.0, 40.0), (10.0, 40.0)]);
+        printer.set_layer("circles");
+        printer.draw_circle(80.0, 60.0, 15.0);
+        assert_snapshot("shapes.gcode", &program(&printer));
+    }
+
+    #[test]
+    fn grbl_example() {
+        let mut config = test_config();
+        config.dialect = Dialect::Grbl;
+        config.xy_speed = Feed::mm_per_s(20.0);
+        let mut printer = Printer::new(config.clone());
+        printer.draw_polyline(&[(10.0, 10.0), (40.0, 10.0), (40.0, 40.0)]);
+        printer.draw_circle(80.0, 60.0, 5.0);
+        let text = program(&printer);
+        assert_snapshot("grbl.gcode", &text);
+
+        // GRBL runs G0 at its rapid rate whatever F says, so strokes have to
+        // be G1, compacted or not
+        config.compact = Some(compact::Compaction::Modal);
+        let mut compacted = Printer::new(config);
+        compacted.draw_line(0.0, 0.0, 9.0, 9.0);
+        let compacted = program(&compacted);
+        for text in [text, compacted] {
+            let mut down = false;
+            for line in text.lines() {
+                assert!(!line.starts_with("G0"), "{}", line);
+                if line.starts_with("G1 Z") {
+                    down = line.starts_with("G1 Z4.0");
+                } else if down && line.contains('X') {
+                    let feed_move = ["G1 X", "G2 ", "G3 "].iter().any(|g| line.starts_with(g));
+                    assert!(feed_move, "{}", line);
+                }
+            }
         }
     }
 
@@ -445,9 +3083,22 @@ mod tests {
         for _i in 0..10000 {
             printer.draw_point(50.0, 50.0);
         }
-        if let Err(e) = printer.save("progress.gcode") {
-            panic!("Unable to generate GCODE: {}", e);
-        }
+        let text = program(&printer);
+        let percents: Vec<f32> = text
+            .lines()
+            .filter_map(|l| l.strip_prefix("M117 "))
+            .map(|m| m.split('%').next().unwrap().parse().unwrap())
+            .collect();
+        // A message every 1.5% of the time, the last one within that of the
+        // end
+        assert_eq!(percents.first(), Some(&0.0));
+        assert!(percents.last().is_some_and(|&p| p >= 98.5));
+        assert!(percents.len() >= 66);
+        assert!(
+            percents
+                .windows(2)
+                .all(|w| w[0] < w[1] && w[1] - w[0] < 3.0)
+        );
     }
 
     #[test]
@@ -456,9 +3107,1078 @@ mod tests {
         for i in 0..1000 {
             printer.draw_point(i as f32, i as f32);
         }
-        if let Err(e) = printer.save("speed.gcode") {
-            panic!("Unable to generate GCODE: {}", e);
+        let text = program(&printer);
+        assert!(text.contains("; [WARNING] 822 draws reach outside the work area!"));
+        assert_eq!(text.matches("G1 Z4.0 F500.0").count(), 1000);
+        assert!(text.contains("G1 X999.0 Y999.0 F1000.0"));
+    }
+
+    #[test]
+    fn random_drawings_stay_in_bounds() {
+        for seed in 0..20 {
+            let printer = random_drawing(seed);
+            let text = program(&printer);
+            let (w, h) = (printer.width, printer.height);
+            for s in replayed(&printer, &text) {
+                let (x, y, z) = s.to;
+                // Written to 0.1mm
+                let margin = 0.05 + 1e-3;
+                assert!(
+                    (-margin..=w + margin).contains(&x) && (-margin..=h + margin).contains(&y),
+                    "seed {}: move to ({}, {}) outside the envelope",
+                    seed,
+                    x,
+                    y
+                );
+                assert!(
+                    z >= printer.config.z_draw,
+                    "seed {}: Z{} below the pen",
+                    seed,
+                    z
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn random_drawings_retract_before_travel() {
+        for seed in 0..20 {
+            let printer = random_drawing(seed);
+            let text = program(&printer);
+            let (z_draw, z0) = (printer.config.z_draw, printer.config.z0);
+            let mut down = false;
+            for s in replayed(&printer, &text) {
+                if s.from_xy() == s.to_xy() {
+                    if s.to.2 <= z_draw + 1e-4 {
+                        down = true;
+                    } else if s.to.2 >= z0 - 1e-4 {
+                        down = false;
+                    }
+                } else {
+                    // Either drawing on the paper or travelling at the full
+                    // height, never dragging the pen or travelling low
+                    assert!(
+                        s.pen_down && down || !down && s.from.2 >= z0 - 1e-4,
+                        "seed {}: travel to {:?} without a retract",
+                        seed,
+                        s.to
+                    );
+                }
+            }
+            assert!(!down, "seed {}: ends with the pen down", seed);
+        }
+    }
+
+    #[test]
+    fn random_drawings_round_trip() {
+        for seed in 0..20 {
+            let printer = random_drawing(seed);
+            let text = program(&printer);
+            // The text replays to the moves the printer meant: the pen goes
+            // down at the same places, written to 0.1mm, and draws as much.
+            // Arcs are flattened from rounded centers, so the drawn lengths
+            // only come close.
+            let written = replayed(&printer, &text);
+            let meant = printer.simulate();
+            let z_draw = printer.config.z_draw;
+            let plunges = |segments: &[sim::Segment]| -> Vec<(f32, f32)> {
+                segments
+                    .iter()
+                    .filter(|s| {
+                        s.from_xy() == s.to_xy() && s.to.2 <= z_draw + 1e-4 && s.from.2 > s.to.2
+                    })
+                    .map(|s| s.to_xy())
+                    .collect()
+            };
+            let (a, b) = (plunges(&written), plunges(&meant));
+            assert!(!b.is_empty());
+            assert_eq!(a.len(), b.len(), "seed {}", seed);
+            for (a, b) in a.iter().zip(&b) {
+                assert!(
+                    path::dist(*a, *b) < 0.071,
+                    "seed {}: {:?} != {:?}",
+                    seed,
+                    a,
+                    b
+                );
+            }
+            let drawn = |segments: &[sim::Segment]| -> f32 {
+                segments
+                    .iter()
+                    .filter(|s| s.pen_down)
+                    .map(|s| path::dist(s.from_xy(), s.to_xy()))
+                    .sum()
+            };
+            assert_within(drawn(&written) / drawn(&meant), 1.0, 0.01);
+
+            // And reads back into codes that write the same text
+            let again: Vec<String> = parse::codes(&text).iter().map(|c| c.to_string()).collect();
+            let lines: Vec<&str> = text.lines().collect();
+            for (a, b) in again.iter().zip(&lines) {
+                assert_eq!(a, b, "seed {}", seed);
+            }
+            assert_eq!(again.len(), lines.len(), "seed {}", seed);
+        }
+    }
+
+    #[test]
+    fn polyline_single_plunge() {
+        let mut printer = Printer::new(test_config());
+        printer.draw_polyline(&[(10.0, 10.0), (20.0, 10.0), (20.0, 20.0)]);
+        let code = printer.codes();
+        let plunges = code
+            .iter()
+            .filter(|c| matches!(c, Code::Move(p, _) if p.z == Some(4.0)))
+            .count();
+        assert_eq!(plunges, 1);
+
+        // 10mm travel at z0, plunge and retract, 20mm of drawing
+        let expected = (10.0_f32.powi(2) * 2.0).sqrt() + 20.0 + 2.0 * 2.5;
+        assert_within(total_dist(&printer), expected, 0.01);
+    }
+
+    #[test]
+    fn line_at_draw_speed() {
+        let mut printer = Printer::new(test_config());
+        printer.set_overrides(Overrides {
+            draw_speed: Some(Feed::mm_per_min(900.0)),
+            ..Overrides::default()
+        });
+        printer.draw_line(10.0, 10.0, 30.0, 10.0);
+        assert_eq!(printer.ops().len(), 1);
+        let code: Vec<String> = printer.codes().iter().map(|c| c.to_string()).collect();
+        assert!(
+            code.iter().any(|l| l == "G1 X30.0 Y10.0 F900.0"),
+            "{:?}",
+            code
+        );
+        let plunges = code.iter().filter(|l| l.contains("Z4.0")).count();
+        assert_eq!(plunges, 1);
+    }
+
+    #[test]
+    fn arcs_as_g2_g3() {
+        let mut printer = Printer::new(test_config());
+        printer.draw_circle(50.0, 50.0, 10.0);
+        printer.draw_arc(50.0, 50.0, 5.0, 90.0, 0.0);
+        let code: Vec<String> = printer.codes().iter().map(|c| c.to_string()).collect();
+        let arcs: Vec<&String> = code
+            .iter()
+            .filter(|l| l.starts_with("G2") || l.starts_with("G3"))
+            .collect();
+        assert_eq!(
+            arcs,
+            [
+                "G3 X60.0 Y50.0 I-10.0 J0.0 F1000.0",
+                "G2 X55.0 Y50.0 I0.0 J-5.0 F1000.0"
+            ]
+        );
+        assert!(code.contains(&"G1 X50.0 Y55.0 F1000.0".to_string()));
+        let circumference = 2.0 * std::f32::consts::PI * 10.0;
+        let drawn: f32 = printer
+            .simulate()
+            .iter()
+            .filter(|s| s.pen_down)
+            .map(|s| path::dist(s.from_xy(), s.to_xy()))
+            .sum();
+        assert_within(drawn, circumference + circumference / 8.0, 0.2);
+
+        // Stretched drawings and anything that changes the path get line
+        // segments instead
+        let mut config = test_config();
+        config.scale = Some((408.0, 177.0));
+        let mut stretched = Printer::new(config);
+        stretched.draw_circle(50.0, 50.0, 10.0);
+        assert!(matches!(stretched.ops()[0].shape, Shape::Curve(_)));
+        printer.set_filters(filter::Pipeline::new().then(filter::ClipRect {
+            min: (0.0, 0.0),
+            max: (200.0, 200.0),
+        }));
+        for p in [printer, stretched] {
+            let code = p.codes();
+            assert!(!code.iter().any(|c| matches!(c, Code::Arc(..))));
+        }
+    }
+
+    #[test]
+    fn optimize_travel() {
+        let mut printer = Printer::new(test_config());
+        let mut rng = rng::Rng::new(2);
+        let mut random = |max: u64| (rng.next_u64() % max) as f32;
+        for layer in ["black", "red"] {
+            printer.set_layer(layer);
+            for _ in 0..100 {
+                printer.draw_point(random(200), random(170));
+            }
+        }
+        printer.draw_polyline(&[(150.0, 0.0), (0.0, 0.0)]);
+        printer.draw_arc(50.0, 50.0, 10.0, 0.0, 90.0);
+        let before = total_dist(&printer);
+        let drawn = printer.stats().total.drawn;
+        printer.optimize();
+
+        assert!(total_dist(&printer) < before * 0.5);
+        assert_within(printer.stats().total.drawn, drawn, 1e-2);
+        // Still all black first, and the red strokes after them
+        let layers: Vec<usize> = printer.ops().iter().map(|op| op.layer).collect();
+        assert!(layers[..100].iter().all(|&l| l == 1));
+        assert!(layers[100..].iter().all(|&l| l == 2));
+        assert_eq!(layers.len(), 202);
+    }
+
+    #[test]
+    fn dedupe_ops() {
+        let mut printer = Printer::new(test_config());
+        printer.draw_polyline(&[(0.0, 0.0), (10.0, 0.0), (10.0, 10.0)]);
+        printer.draw_polyline(&[(10.0, 10.0), (10.0, 0.0), (20.0, 0.0)]);
+        printer.draw_point(5.0, 5.0);
+        printer.draw_point(5.0, 5.0);
+        printer.dedupe(0.05);
+
+        let paths: Vec<Vec<(f32, f32)>> = printer.ops().iter().map(Op::path).collect();
+        assert_eq!(
+            paths,
+            vec![
+                vec![(0.0, 0.0), (10.0, 0.0), (10.0, 10.0)],
+                vec![(10.0, 0.0), (20.0, 0.0)],
+                vec![(5.0, 5.0)],
+            ]
+        );
+    }
+
+    #[test]
+    fn pen_wear_pauses() {
+        let mut config = test_config();
+        config.pen_wear_limit = Some(25.0);
+        let mut printer = Printer::new(config);
+        for i in 0..5 {
+            let y = i as f32;
+            printer.draw_polyline(&[(0.0, y), (10.0, y)]);
+        }
+
+        // 10mm per stroke: the pen is swapped before the 4th stroke only
+        let code = printer.codes();
+        let pauses: Vec<usize> = code
+            .iter()
+            .enumerate()
+            .filter(|(_, c)| c.to_string().starts_with("M601"))
+            .map(|(i, _)| i)
+            .collect();
+        assert_eq!(pauses.len(), 1);
+        assert_eq!(code[pauses[0] - 1].to_string(), "M117 Replace pen");
+    }
+
+    #[test]
+    fn pen_changes() {
+        let mut config = test_config();
+        config.pen_change = Some("Load {pen} pen".to_string());
+        let mut printer = Printer::new(config);
+        for (i, pen) in ["black", "red", "black"].into_iter().enumerate() {
+            printer.set_pen(Pen::new(pen));
+            let y = i as f32;
+            printer.draw_polyline(&[(0.0, y), (10.0, y)]);
+        }
+        assert_eq!(printer.layers(), &["default", "black", "red"]);
+
+        let messages = |printer: &Printer| -> Vec<String> {
+            let code = printer.codes();
+            (0..code.len())
+                .filter(|&i| code[i].to_string().starts_with("M601"))
+                .map(|i| code[i - 1].to_string())
+                .collect()
+        };
+        assert_eq!(
+            messages(&printer),
+            ["M117 Load red pen", "M117 Load black pen"]
+        );
+
+        // Grouped, the black strokes go first and red is loaded once
+        printer.group_by_pen();
+        assert_eq!(messages(&printer), ["M117 Load red pen"]);
+        let ys: Vec<f32> = printer.ops().iter().map(|op| op.path()[0].1).collect();
+        assert_eq!(ys, [0.0, 2.0, 1.0]);
+    }
+
+    #[test]
+    fn z_hops() {
+        let mut config = test_config();
+        config.z_hop = Some(ZHop {
+            height: 0.5,
+            distance: 5.0,
+        });
+        let mut printer = Printer::new(config);
+        for x in [0.0, 2.0, 4.0, 50.0] {
+            printer.draw_point(x, 0.0);
+        }
+        printer.pause("Check");
+        printer.draw_point(52.0, 0.0);
+        let lifts: Vec<String> = printer
+            .codes()
+            .iter()
+            .map(|c| c.to_string())
+            .filter(|c| c.starts_with("G1 Z") && !c.starts_with("G1 Z4.0"))
+            .collect();
+        // Hops to the close dots, then all the way up for the long travel,
+        // the pause and the end
+        assert_eq!(
+            lifts,
+            [
+                "G1 Z4.5 F800.0",
+                "G1 Z4.5 F800.0",
+                "G1 Z6.5 F800.0",
+                "G1 Z6.5 F800.0",
+                "G1 Z6.5 F800.0"
+            ]
+        );
+
+        // Clipping moves the start of the next stroke out of reach
+        let mut config = test_config();
+        config.z_hop = Some(ZHop {
+            height: 0.5,
+            distance: 5.0,
+        });
+        config.canvas = Some(canvas::Canvas::Rect {
+            min: (0.0, 0.0),
+            max: (40.0, 40.0),
+        });
+        let mut printer = Printer::new(config);
+        printer.draw_point(0.0, 0.0);
+        printer.draw_polyline(&[(0.0, -3.0), (30.0, -3.0), (30.0, 10.0)]);
+        let travel: Vec<String> = printer
+            .codes()
+            .iter()
+            .map(|c| c.to_string())
+            .filter(|c| c.starts_with("G1"))
+            .skip_while(|c| c != "G1 Z4.5 F800.0")
+            .take(3)
+            .collect();
+        assert_eq!(
+            travel,
+            ["G1 Z4.5 F800.0", "G1 Z6.5 F800.0", "G1 X30.0 Y0.0 F1000.0"]
+        );
+    }
+
+    #[test]
+    fn annotations() {
+        let lines = |annotations: Annotations| -> Vec<String> {
+            let mut config = test_config();
+            config.annotations = annotations;
+            let mut printer = Printer::new(config);
+            printer.draw_point_labeled(1.0, 2.0, "eye highlight");
+            printer.labeled("mouth", |p| {
+                p.draw_line(0.0, 0.0, 5.0, 0.0);
+            });
+            printer.codes().iter().map(|c| c.to_string()).collect()
+        };
+
+        assert_eq!(
+            lines(Annotations::PerShape),
+            [
+                "; eye highlight",
+                "G1 X1.0 Y2.0 F1000.0",
+                "G1 Z4.0 F500.0",
+                "G1 Z6.5 F800.0",
+                "",
+                "; mouth",
+                "G1 X0.0 Y0.0 F1000.0",
+                "G1 Z4.0 F500.0",
+                "G1 X5.0 Y0.0 F1000.0",
+                "G1 Z6.5 F800.0",
+                "",
+            ]
+        );
+        assert_eq!(
+            lines(Annotations::None)[..4],
+            [
+                "G1 X1.0 Y2.0 F1000.0",
+                "G1 Z4.0 F500.0",
+                "G1 Z6.5 F800.0",
+                "G1 X0.0 Y0.0 F1000.0"
+            ]
+        );
+        assert_eq!(
+            lines(Annotations::Verbose)[..6],
+            [
+                "; eye highlight",
+                "; Travel to X1.0 Y2.0",
+                "G1 X1.0 Y2.0 F1000.0",
+                "; Pen down for 0.0mm",
+                "G1 Z4.0 F500.0",
+                "; Pen up"
+            ]
+        );
+    }
+
+    #[test]
+    fn inserted_codes() {
+        let lines = |printer: &Printer| -> Vec<String> {
+            let mut out = Vec::new();
+            printer.write_to(&mut out).unwrap();
+            let text = String::from_utf8(out).unwrap();
+            text.lines()
+                .filter(|l| {
+                    ["M117 S", "M601", "M0", "(MSG,S", "G4", "M300"]
+                        .iter()
+                        .any(|c| l.starts_with(c))
+                })
+                .map(str::to_string)
+                .collect()
+        };
+
+        let mut printer = Printer::new(test_config());
+        printer.draw_line(0.0, 0.0, 10.0, 0.0);
+        printer.pause("Swap paper");
+        printer.draw_line(20.0, 0.0, 30.0, 0.0);
+        printer.dwell(1.5);
+        printer.draw_line(10.0, 1.0, 0.0, 1.0);
+        printer.beep();
+        // The stroke next to the first stays after the pause
+        printer.optimize();
+        let ys: Vec<f32> = printer.ops().iter().map(|op| op.path()[0].0).collect();
+        assert_eq!(ys[1], printer.to_bed(20.0, 0.0).0);
+        assert_eq!(
+            lines(&printer),
+            ["M117 Swap paper", "M601", "G4 P1500", "M300 S440 P200"]
+        );
+
+        let mut config = test_config();
+        config.dialect = Dialect::Grbl;
+        let mut grbl = Printer::new(config);
+        grbl.draw_line(0.0, 0.0, 10.0, 0.0);
+        grbl.pause("Swap paper");
+        grbl.draw_line(20.0, 0.0, 30.0, 0.0);
+        grbl.beep();
+        assert_eq!(lines(&grbl), ["(MSG,Swap paper)", "M0"]);
+
+        // A pause before a stroke that's deduplicated away isn't lost
+        let mut printer = Printer::new(test_config());
+        printer.draw_line(0.0, 0.0, 10.0, 0.0);
+        printer.pause("");
+        printer.draw_line(0.0, 0.0, 10.0, 0.0);
+        printer.dedupe(0.01);
+        assert_eq!(printer.ops().len(), 1);
+        assert_eq!(lines(&printer), ["M601"]);
+    }
+
+    #[test]
+    fn raw_commands() {
+        let mut printer = Printer::new(test_config());
+        printer.draw_line(0.0, 0.0, 10.0, 0.0);
+        let percent = 150;
+        printer.emit_raw(
+            format!("M220 S{}", percent),
+            Some("Faster from here".to_string()),
+        );
+        printer.draw_line(0.0, 1.0, 10.0, 1.0);
+        printer.emit_raw("M220 S100", None);
+
+        let code: Vec<String> = printer.codes().iter().map(|c| c.to_string()).collect();
+        let at = |line: &str| code.iter().position(|l| l == line).unwrap();
+        let faster = at("M220 S150 ; Faster from here");
+        assert!(faster < at("; draw_line(0.0, 1.0, 10.0, 1.0)"));
+        assert_eq!(at("M220 S100"), code.len() - 1);
+    }
+
+    #[test]
+    fn layer_stats() {
+        let mut printer = Printer::new(test_config());
+        printer.draw_polyline(&[(0.0, 0.0), (10.0, 0.0)]);
+        printer.set_layer("dots");
+        printer.draw_point(10.0, 10.0);
+        printer.draw_point(10.0, 20.0);
+        printer.set_layer("default");
+        printer.draw_polyline(&[(10.0, 20.0), (10.0, 40.0)]);
+
+        let stats = printer.stats();
+        assert_eq!(printer.layers(), &["default", "dots"]);
+        assert_eq!(stats.layers.len(), 2);
+
+        let (name, default) = &stats.layers[0];
+        assert_eq!(name, "default");
+        assert_within(default.drawn, 30.0, 1e-4);
+        assert_eq!(default.commands, 4 + 4);
+
+        let (_, dots) = &stats.layers[1];
+        assert_within(dots.drawn, 0.0, 1e-4);
+        assert_within(dots.travel, 20.0 + 4.0 * 2.5, 1e-4);
+        assert_eq!(dots.commands, 6);
+
+        assert_within(
+            stats.total.drawn + stats.total.travel,
+            total_dist(&printer),
+            1e-3,
+        );
+        assert_eq!(stats.to_comments().len(), 4);
+    }
+
+    #[test]
+    fn thumbnail_comments() {
+        let mut config = test_config();
+        config.thumbnails = vec![(16, 16), (220, 124)];
+        let mut printer = Printer::new(config);
+        printer.draw_polyline(&[(0.0, 0.0), (100.0, 100.0)]);
+
+        let code: Vec<String> = printer.thumbnails().iter().map(|c| c.to_string()).collect();
+        let begins: Vec<&String> = code
+            .iter()
+            .filter(|l| l.starts_with("; thumbnail begin"))
+            .collect();
+        assert_eq!(begins.len(), 2);
+        assert!(begins[0].starts_with("; thumbnail begin 16x16 "));
+        assert!(begins[1].starts_with("; thumbnail begin 220x124 "));
+        assert_eq!(code.iter().filter(|l| *l == "; thumbnail end").count(), 2);
+    }
+
+    #[test]
+    fn metadata_comments() {
+        let mut printer = Printer::new(test_config());
+        printer.draw_polyline(&[(10.0, 10.0), (110.0, 10.0), (110.0, 60.0)]);
+        printer.set_layer("red");
+        printer.draw_point(20.0, 20.0);
+
+        let lines: Vec<String> = printer.metadata().iter().map(|c| c.to_string()).collect();
+        let time = sim::format_time_words(printer.stats().total.time);
+        assert!(lines.contains(&format!(
+            "; estimated printing time (normal mode) = {}",
+            time
+        )));
+        assert!(lines.contains(&"; drawn distance [mm] = 150.0".to_string()));
+        assert!(lines.contains(&"; bed area used = 100.0x50.0 at 10.0,10.0".to_string()));
+        assert!(lines.contains(&"; layers = default;red".to_string()));
+        assert!(lines.contains(&"; printer_model = MK3S".to_string()));
+    }
+
+    #[test]
+    fn weighted_points() {
+        let mut printer = Printer::new(test_config());
+        printer.draw_weighted_point(10.0, 10.0, 0.5, DotWeight::Dwell(1.0));
+        printer.draw_weighted_point(20.0, 10.0, 1.0, DotWeight::Depth(0.4));
+        printer.draw_weighted_point(30.0, 10.0, 1.0, DotWeight::Scribble(1.0));
+        printer.draw_point(40.0, 10.0);
+
+        let code: Vec<String> = printer.codes().iter().map(|c| c.to_string()).collect();
+        let dwell = code.iter().position(|l| l == "G4 P500").unwrap();
+        assert_eq!(code[dwell - 1], "G1 Z4.0 F500.0");
+        assert_eq!(code.iter().filter(|l| l.starts_with("G4")).count(), 1);
+        assert!(code.iter().any(|l| l == "G1 Z3.6 F500.0"));
+
+        // Only the dot with its own depth goes deeper, the scribble stays
+        // within its radius
+        assert!(matches!(printer.ops()[2].shape, Shape::Stroke(_)));
+        assert!(
+            printer.ops()[2]
+                .path()
+                .iter()
+                .all(|&p| path::dist(p, (30.0, 10.0)) <= 1.0 + 1e-4)
+        );
+        assert_eq!(printer.z_draw(&printer.ops()[3]), 4.0);
+    }
+
+    #[test]
+    fn tapered_strokes() {
+        let taper = Taper {
+            length: 2.0,
+            lift: 0.5,
+        };
+        let profile = taper.profile(&[(0.0, 0.0), (10.0, 0.0)]);
+        assert_eq!(profile[0], ((0.0, 0.0), 0.5));
+        assert_eq!(*profile.last().unwrap(), ((10.0, 0.0), 0.5));
+        // Fully down in the middle, and ramping in even steps
+        assert!(
+            profile
+                .iter()
+                .all(|&(p, lift)| lift == 0.0 || p.0 < 2.0 || p.0 > 8.0)
+        );
+        assert_eq!(profile.len(), 2 + 2 * 8);
+        assert!(
+            profile
+                .windows(2)
+                .all(|w| (w[0].1 - w[1].1).abs() <= 0.5 / 8.0 + 1e-4)
+        );
+
+        // Z follows the ramp, from halfway up down to z_draw and back
+        let mut config = test_config();
+        config.taper = Some(taper);
+        let mut printer = Printer::new(config.clone());
+        printer.draw_polyline(&[(0.0, 0.0), (10.0, 0.0)]);
+        let code: Vec<String> = printer.codes().iter().map(|c| c.to_string()).collect();
+        assert!(code.iter().any(|l| l == "G1 Z5.2 F500.0"));
+        assert!(code.iter().any(|l| l == "G1 X2.0 Y0.0 Z4.0 F1000.0"));
+        // Still drawn all the way, tapered ends included, which also count
+        // the Z they move
+        assert!(printer.stats().total.drawn > 10.0);
+
+        // Servos step through the angles in between
+        config.actuator = Arc::new(actuator::Servo {
+            index: 0,
+            up_angle: 90,
+            down_angle: 30,
+            dwell_ms: 0,
+        });
+        let mut printer = Printer::new(config);
+        printer.draw_polyline(&[(0.0, 0.0), (10.0, 0.0)]);
+        let code: Vec<String> = printer.codes().iter().map(|c| c.to_string()).collect();
+        let angles: Vec<&String> = code.iter().filter(|l| l.starts_with("M280")).collect();
+        // Straight to the taper's angle, never fully down first
+        assert_eq!(angles.first().unwrap().as_str(), "M280 P0 S60");
+        assert!(angles.iter().any(|l| l.as_str() == "M280 P0 S60"));
+        assert!(angles.len() > 10);
+    }
+
+    #[test]
+    fn blending_per_section() {
+        let mut config = test_config();
+        config.dialect = Dialect::Marlin;
+        let mut printer = Printer::new(config);
+        printer.set_overrides(Overrides {
+            blending: Some(Blending::Continuous(0.2)),
+            ..Overrides::default()
+        });
+        printer.draw_polyline(&[(0.0, 0.0), (10.0, 0.0)]);
+        printer.draw_polyline(&[(0.0, 5.0), (10.0, 5.0)]);
+        printer.set_overrides(Overrides {
+            blending: Some(Blending::Exact),
+            ..Overrides::default()
+        });
+        printer.draw_polyline(&[(0.0, 10.0), (10.0, 10.0)]);
+
+        let code: Vec<String> = printer.codes().iter().map(|c| c.to_string()).collect();
+        let modes: Vec<&str> = code
+            .iter()
+            .filter(|l| l.starts_with("M205"))
+            .map(|l| l.as_str())
+            .collect();
+        // Only emitted when the mode changes
+        assert_eq!(modes, ["M205 J0.2", "M205 J0.01"]);
+
+        // Prusa firmware keeps its own settings
+        let mut printer = Printer::new(test_config());
+        printer.set_overrides(Overrides {
+            blending: Some(Blending::Exact),
+            ..Overrides::default()
+        });
+        printer.draw_polyline(&[(0.0, 0.0), (10.0, 0.0)]);
+        assert!(
+            printer
+                .codes()
+                .iter()
+                .all(|c| !c.to_string().starts_with("M205"))
+        );
+    }
+
+    #[test]
+    fn section_overrides() {
+        let mut config = test_config();
+        config.acceleration = Some(1000.0);
+        let mut printer = Printer::new(config);
+        printer.draw_polyline(&[(0.0, 0.0), (10.0, 0.0)]);
+        printer.begin_section(
+            "lettering",
+            Overrides {
+                draw_speed: Some(Feed::mm_per_min(300.0)),
+                travel_speed: Some(Feed::mm_per_min(2000.0)),
+                acceleration: Some(250.0),
+                ..Overrides::default()
+            },
+        );
+        // Set explicitly, so the section's speed doesn't apply
+        printer.set_overrides(Overrides {
+            draw_speed: Some(Feed::mm_per_min(400.0)),
+            ..Overrides::default()
+        });
+        printer.draw_polyline(&[(0.0, 5.0), (10.0, 5.0)]);
+        printer.set_overrides(Overrides::default());
+        printer.draw_polyline(&[(0.0, 10.0), (10.0, 10.0)]);
+        printer.end_section();
+        printer.draw_polyline(&[(0.0, 15.0), (10.0, 15.0)]);
+
+        assert_eq!(printer.sections().len(), 1);
+        let sections: Vec<_> = printer.ops().iter().map(|op| op.section).collect();
+        assert_eq!(sections, [None, Some(0), Some(0), None]);
+
+        let code: Vec<String> = printer.codes().iter().map(|c| c.to_string()).collect();
+        let accelerations: Vec<&str> = code
+            .iter()
+            .filter(|l| l.starts_with("M204"))
+            .map(|l| l.as_str())
+            .collect();
+        assert_eq!(accelerations, ["M204 S1000", "M204 S250", "M204 S1000"]);
+        for line in [
+            "G1 X0.0 Y5.0 F2000.0",
+            "G1 X10.0 Y5.0 F400.0",
+            "G1 X10.0 Y10.0 F300.0",
+            "G1 X0.0 Y15.0 F1000.0",
+            "G1 X10.0 Y15.0 F1000.0",
+        ] {
+            assert!(code.iter().any(|l| l == line), "missing {}", line);
+        }
+
+        // Without a configured acceleration the stored one is restored
+        let mut printer = Printer::new(test_config());
+        printer.begin_section(
+            "slow",
+            Overrides {
+                acceleration: Some(250.0),
+                ..Overrides::default()
+            },
+        );
+        printer.draw_polyline(&[(0.0, 0.0), (10.0, 0.0)]);
+        let code: Vec<String> = printer.codes().iter().map(|c| c.to_string()).collect();
+        let m204 = code.iter().position(|l| l == "M204 S250").unwrap();
+        let m501 = code.iter().position(|l| l == "M501").unwrap();
+        assert!(m204 < m501);
+    }
+
+    #[test]
+    fn section_contents() {
+        let mut printer = Printer::new(test_config());
+        printer.draw_polyline(&[(0.0, 0.0), (10.0, 0.0)]);
+        printer.begin_section("border", Overrides::default());
+        printer.draw_polyline(&[(0.0, 5.0), (100.0, 5.0)]);
+        printer.begin_section("lettering", Overrides::default());
+        printer.draw_polyline(&[(0.0, 10.0), (10.0, 10.0)]);
+
+        let filename = temp_path("sections.gcode");
+        let filename = filename.to_str().unwrap();
+        printer.save(filename).unwrap();
+        let text = std::fs::read_to_string(filename).unwrap();
+        let file: Vec<&str> = text.lines().collect();
+
+        let contents = file.iter().position(|&l| l == "; Contents").unwrap();
+        let entries = &file[contents + 1..contents + 3];
+        let mut starts = Vec::new();
+        for (entry, name) in entries.iter().zip(["border", "lettering"]) {
+            let (begin, line) = entry.rsplit_once(", line ").unwrap();
+            let line: usize = line.parse().unwrap();
+            // Every entry points at the marker it was made from
+            let begin = begin.trim_start_matches(';').trim();
+            assert_eq!(file[line - 1], format!("; Section begins: {}", begin));
+            assert!(begin.starts_with(&format!("\"{}\" at ", name)));
+            starts.push(line);
+        }
+        assert!(contents < starts[0] && starts[0] < starts[1]);
+
+        // The first section ends where the second begins, the last one with
+        // the drawing
+        assert_eq!(file[starts[1] - 2], "; Section ends: \"border\"");
+        assert!(file.contains(&"; Section ends: \"lettering\""));
+        // Starting times follow the drawing
+        assert!(
+            file[starts[0] - 1].ends_with("at 00:00:01"),
+            "{}",
+            file[starts[0] - 1]
+        );
+        assert!(
+            file[starts[1] - 1].ends_with("at 00:00:08"),
+            "{}",
+            file[starts[1] - 1]
+        );
+
+        // Nothing without sections
+        let mut plain = Printer::new(test_config());
+        plain.draw_point(1.0, 1.0);
+        plain.save(filename).unwrap();
+        let text = std::fs::read_to_string(filename).unwrap();
+        assert!(!text.contains("Contents") && !text.contains("Section"));
+    }
+
+    #[test]
+    fn transform_stack() {
+        let mut printer = Printer::new(test_config());
+        printer.push_transform(AffineTransform::translate(100.0, 50.0));
+        printer.push_transform(AffineTransform::rotate_deg(90.0));
+        printer.draw_point(10.0, 0.0);
+        printer.push_transform(AffineTransform::mirror_x());
+        printer.draw_arc(0.0, 0.0, 10.0, 0.0, 90.0);
+        printer.pop_transform();
+        printer.pop_transform();
+        printer.draw_point(10.0, 0.0);
+        printer.pop_transform();
+        printer.pop_transform();
+        printer.draw_point(10.0, 0.0);
+
+        let starts: Vec<(f32, f32)> = printer.ops().iter().map(|op| op.path()[0]).collect();
+        let expected = [(100.0, 60.0), (100.0, 40.0), (110.0, 50.0), (10.0, 0.0)];
+        for (p, e) in starts.iter().zip(expected) {
+            assert!(path::dist(*p, e) < 1e-3, "{:?}", starts);
+        }
+        // Mirrored, the arc turns the other way
+        let Shape::Arc(arc) = printer.ops()[1].shape else {
+            panic!("not an arc");
+        };
+        assert!(arc.clockwise());
+        assert!(path::dist(arc.point(1.0), (90.0, 50.0)) < 1e-3);
+    }
+
+    #[test]
+    fn native_curves() {
+        let curve = curve::CubicBezier::new((0.0, 0.0), (0.0, 10.0), (10.0, 10.0), (10.0, 0.0));
+
+        // Flattened by default
+        let mut printer = Printer::new(test_config());
+        printer.draw_curve(&[curve]);
+        let flat = printer.codes();
+        assert!(!flat.iter().any(|c| matches!(c, Code::Bezier(..))));
+
+        let mut config = test_config();
+        config.dialect = Dialect::Marlin;
+        config.native_curves = true;
+        let mut printer = Printer::new(config);
+        printer.draw_curve(&[curve]);
+        let code: Vec<String> = printer.codes().iter().map(|c| c.to_string()).collect();
+        assert!(code.contains(&"G5 I0.0 J10.0 P0.0 Q10.0 X10.0 Y0.0 F1000.0".to_string()));
+        assert!(code.len() < flat.len());
+
+        // The G5 moves simulate to the same drawing as the flattened curve
+        let length = path::length(&curve.flatten(curve::TOLERANCE));
+        assert_within(printer.stats().total.drawn, length, 1e-3);
+    }
+
+    #[test]
+    fn splines_and_tolerance() {
+        let moves = |tolerance: f32| {
+            let mut config = test_config();
+            config.tolerance = tolerance;
+            let mut printer = Printer::new(config);
+            printer.draw_cubic_bezier((0.0, 0.0), (0.0, 10.0), (10.0, 10.0), (10.0, 0.0));
+            printer.codes().len()
+        };
+        assert!(moves(0.01) > moves(0.5));
+
+        // Through every point, in one stroke
+        let mut printer = Printer::new(test_config());
+        let points = [(0.0, 0.0), (10.0, 5.0), (20.0, 0.0), (30.0, 5.0)];
+        printer.draw_spline(&points);
+        assert_eq!(printer.ops().len(), 1);
+        let path = printer.ops()[0].path();
+        for p in points {
+            let p = printer.to_bed(p.0, p.1);
+            assert!(path.iter().any(|&q| (p.0 - q.0).hypot(p.1 - q.1) < 1e-4));
+        }
+    }
+
+    #[test]
+    fn wcs_origin() {
+        let lines = |printer: &Printer| -> Vec<String> {
+            printer.header().iter().map(|c| c.to_string()).collect()
+        };
+
+        let printer = Printer::new(test_config());
+        assert!(lines(&printer).iter().any(|l| l.starts_with("G92 X0 Y0")));
+
+        let mut config = test_config();
+        config.wcs = Some(Wcs::G55);
+        let header = lines(&Printer::new(config));
+        assert!(header.contains(&"G55 ; Use work coordinate system 2".to_string()));
+        assert!(header.contains(&"G1 X0.0 Y0.0 F1000.0".to_string()));
+        assert!(!header.iter().any(|l| l.starts_with("G92")));
+    }
+
+    #[test]
+    fn pen_offsets() {
+        let mut printer = Printer::new(test_config());
+        printer.set_layer_pen(
+            "red",
+            Pen {
+                name: "red".to_string(),
+                offset: (1.5, -2.0),
+                z_offset: 0.5,
+            },
+        );
+        printer.draw_point(10.0, 10.0);
+        printer.set_layer("red");
+        printer.draw_point(10.0, 10.0);
+
+        let code: Vec<String> = printer.codes().iter().map(|c| c.to_string()).collect();
+        assert!(code.contains(&"G1 X10.0 Y10.0 F1000.0".to_string()));
+        assert!(code.contains(&"G1 Z4.0 F500.0".to_string()));
+        assert!(code.contains(&"G1 X8.5 Y12.0 F1000.0".to_string()));
+        assert!(code.contains(&"G1 Z4.5 F500.0".to_string()));
+        assert_eq!(printer.pens()[1].name, "red");
+        // The offset only applies to the layer's own ops
+        assert_eq!(printer.pens()[0], Pen::default());
+    }
+
+    #[test]
+    fn engrave_passes() {
+        let mut config = test_config();
+        config.engrave = Some(Engrave {
+            depth: 1.2,
+            step_down: 0.5,
+            plunge_speed: Feed::mm_per_min(100.0),
+        });
+        let triangle = [(0.0, 0.0), (10.0, 0.0), (10.0, 10.0), (0.0, 0.0)];
+
+        let mut printer = Printer::new(config.clone());
+        printer.draw_polyline(&triangle);
+        let code: Vec<String> = printer.codes().iter().map(|c| c.to_string()).collect();
+        let plunges: Vec<&String> = code.iter().filter(|l| l.ends_with("F100.0")).collect();
+        assert_eq!(
+            plunges,
+            ["G1 Z3.6 F100.0", "G1 Z3.2 F100.0", "G1 Z2.8 F100.0"]
+        );
+        // A closed path is cut without lifting in between
+        assert_eq!(code.iter().filter(|l| l.starts_with("G1 Z6.5")).count(), 1);
+        // Plunging into the material counts as cutting
+        let cut = 3.0 * path::length(&triangle) + 1.2;
+        assert_within(printer.stats().total.drawn, cut, 1e-3);
+
+        let mut printer = Printer::new(config);
+        printer.draw_polyline(&triangle[..3]);
+        let code: Vec<String> = printer.codes().iter().map(|c| c.to_string()).collect();
+        assert_eq!(code.iter().filter(|l| l.starts_with("G1 Z6.5")).count(), 3);
+    }
+
+    #[test]
+    fn session_splitting() {
+        let mut printer = Printer::new(test_config());
+        // 100mm at 1000mm/min is 6s per stroke, plus travel and lifts
+        for i in 0..10 {
+            let y = i as f32;
+            printer.draw_polyline(&[(0.0, y), (100.0, y)]);
+        }
+
+        let sessions = printer.sessions(20.0);
+        assert_eq!(sessions.first().unwrap().start, 0);
+        assert_eq!(sessions.last().unwrap().end, 10);
+        assert!(sessions.windows(2).all(|w| w[0].end == w[1].start));
+        assert!(sessions.len() >= 4);
+        assert_eq!(printer.sessions(1e6), vec![0..10]);
+
+        let filename = temp_path("session.gcode");
+        let names = printer
+            .save_sessions(filename.to_str().unwrap(), 0.5)
+            .unwrap();
+        assert_eq!(names.len(), printer.sessions(30.0).len());
+        assert_eq!(names[0], temp_path("session-1.gcode").to_str().unwrap());
+        let first = std::fs::read_to_string(&names[0]).unwrap();
+        assert!(first.contains(&format!("; Session 1 of {}", names.len())));
+        assert!(first.contains("G92 X0 Y0"));
+    }
+
+    #[test]
+    fn io_errors_are_returned() {
+        let mut printer = Printer::new(test_config());
+        printer.draw_point(1.0, 1.0);
+        let missing = std::env::temp_dir()
+            .join("artful_gcode_missing")
+            .join("plot.gcode");
+        let missing = missing.to_string_lossy();
+        let err = printer.save(&missing).unwrap_err();
+        assert!(matches!(err, Error::Io(e) if e.kind() == io::ErrorKind::NotFound));
+        assert!(printer.preview_html(&missing).is_err());
+        assert!(printer.save_sessions(&missing, 60.0).is_err());
+    }
+
+    #[test]
+    fn write_to_memory() {
+        let mut printer = Printer::new(test_config());
+        printer.draw_line(0.0, 0.0, 10.0, 10.0);
+        let mut memory = Vec::new();
+        printer.write_to(&mut memory).unwrap();
+
+        let filename = temp_path("write_to.gcode");
+        let filename = filename.to_string_lossy();
+        printer.save(&filename).unwrap();
+        assert_eq!(memory, std::fs::read(&*filename).unwrap());
+        assert!(
+            String::from_utf8(memory)
+                .unwrap()
+                .contains("G1 X10.0 Y10.0")
+        );
+    }
+
+    #[test]
+    fn grbl_dialect() {
+        let mut config = test_config();
+        config.dialect = Dialect::Grbl;
+        config.actuator = Arc::new(actuator::Spindle {
+            up: 0,
+            down: 1000,
+            dwell_ms: 250,
+        });
+        assert!(config.validate().is_ok());
+        let mut printer = Printer::new(config.clone());
+        printer.draw_line(0.0, 0.0, 10.0, 10.0);
+        let mut memory = Vec::new();
+        printer.write_to(&mut memory).unwrap();
+        let program = String::from_utf8(memory).unwrap();
+        let lines: Vec<&str> = program.lines().collect();
+
+        for line in [
+            "$H ; Run homing cycle",
+            "M3 S1000",
+            "M3 S0",
+            "G4 P0.250",
+            "(MSG,0.0%)",
+        ] {
+            assert!(lines.contains(&line), "missing {}", line);
+        }
+        for prusa in ["G28", "M862.3", "M117", "M84", "M204"] {
+            assert!(!lines.iter().any(|l| l.starts_with(prusa)), "has {}", prusa);
         }
+        // The simulation still sees the pauses
+        assert!(printer.stats().total.time > 0.5);
+
+        config.actuator = Arc::new(actuator::Servo {
+            index: 0,
+            up_angle: 90,
+            down_angle: 30,
+            dwell_ms: 0,
+        });
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn out_of_bounds_policies() {
+        let draw = |policy| {
+            let mut printer = Printer::new(PrinterConfig {
+                out_of_bounds: policy,
+                ..test_config()
+            });
+            printer.draw_point(10.0, 10.0);
+            printer.draw_point(300.0, 10.0);
+            printer.draw_line(100.0, -20.0, 100.0, 20.0);
+            printer
+        };
+
+        let kept = draw(None);
+        assert_eq!(kept.ops().len(), 3);
+        assert_eq!(kept.out_of_bounds(), [1, 2]);
+        let header: Vec<String> = kept.header().iter().map(|c| c.to_string()).collect();
+        assert_eq!(
+            header[1],
+            "; [WARNING] 2 draws reach outside the work area!"
+        );
+
+        let clamped = draw(Some(OutOfBounds::Clamp));
+        assert!(clamped.out_of_bounds().is_empty());
+        assert_eq!(clamped.ops()[1].path(), [(204.0, 10.0)]);
+        assert_eq!(clamped.ops()[2].path(), [(100.0, 0.0), (100.0, 20.0)]);
+
+        let skipped = draw(Some(OutOfBounds::Skip));
+        assert_eq!(skipped.ops().len(), 1);
+
+        let strict = draw(Some(OutOfBounds::Error));
+        let err = strict.write_to(&mut Vec::new()).unwrap_err();
+        assert!(matches!(err, Error::Bounds(_)));
+        assert_eq!(
+            err.to_string(),
+            "draw_point(300.0, 10.0) reaches outside the work area"
+        );
+    }
+
+    #[test]
+    fn lifts_and_extents() {
+        let mut printer = Printer::new(test_config());
+        printer.draw_polyline(&[(10.0, 10.0), (40.0, 10.0)]);
+        printer.draw_point(60.0, 20.0);
+        printer.draw_point(5.0, 50.0);
+        let stats = printer.stats().total;
+        assert_eq!(stats.pen_lifts, 3);
+        assert_eq!(stats.drawn, 30.0);
+        // Travel starts at the origin
+        assert_eq!(stats.extents, Some(((0.0, 0.0), (60.0, 50.0))));
     }
 
     #[test]
@@ -471,7 +4191,7 @@ mod tests {
         //  2 * (z0 - z_draw)
         // We drew two points, so the total formula is:
         let expected = 99.0 + (2.0 * (2.0 * (6.5 - 4.0)));
-        let actual = printer.total_dist();
+        let actual = total_dist(&printer);
         assert_within(actual, expected, 0.01);
     }
 }
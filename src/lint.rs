@@ -0,0 +1,181 @@
+use std::fmt;
+
+use crate::{Code, Feed, Printer};
+
+// What can be wrong with the moves of a program
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Problem {
+    // Outside the work area between min and max
+    OutOfBounds,
+    // Below the bed, Z under 0
+    BelowBed,
+    // Faster than the config's max_feed
+    FeedTooHigh,
+    // A single pen-down move longer than the config's max_draw_move, e.g.
+    // a stray line across the sheet
+    LongDrawMove,
+    // A pen-down move in the plane that doesn't go anywhere, e.g. from a
+    // point given twice. Travel that is already where it's going is fine.
+    ZeroLengthMove,
+}
+
+// A problem of one op, found in the moves it's drawn with
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub problem: Problem,
+    pub op: usize,
+    pub message: String,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl Printer {
+    // Checks the moves of the drawing as they will be written, after the
+    // filters, clipping and the rest of the config. Every problem is
+    // reported once per op.
+    pub fn validate(&self) -> Vec<Diagnostic> {
+        let mut diagnostics: Vec<Diagnostic> = Vec::new();
+        let mut report = |problem: Problem, op: usize, what: String| {
+            if diagnostics
+                .iter()
+                .any(|d| d.problem == problem && d.op == op)
+            {
+                return;
+            }
+            let name = self.ops[op].label.clone().unwrap_or(format!("op {}", op));
+            diagnostics.push(Diagnostic {
+                problem,
+                op,
+                message: format!("{} {}", name, what),
+            });
+        };
+
+        let margin = 1e-3;
+        let mut machine = self.machine();
+        for (i, c) in self.op_codes() {
+            let feed = match &c {
+                Code::Move(_, f) | Code::Bezier(_, f) | Code::Arc(_, f) => Some(*f),
+                _ => None,
+            };
+            if let (Some(feed), Some(max)) = (feed, self.config.max_feed.map(Feed::per_min))
+                && feed > max
+            {
+                report(
+                    Problem::FeedTooHigh,
+                    i,
+                    format!("moves at F{}, above the limit of F{}", feed, max),
+                );
+            }
+
+            let segments = machine.step(&c);
+            if let Code::Move(p, _) = &c
+                && (p.x.is_some() || p.y.is_some())
+                && segments.iter().all(|s| s.pen_down && s.length() < 1e-6)
+            {
+                report(
+                    Problem::ZeroLengthMove,
+                    i,
+                    "has a move that goes nowhere".to_string(),
+                );
+            }
+            for s in &segments {
+                let (x, y, z) = s.to;
+                if x < -margin || y < -margin || x > self.width + margin || y > self.height + margin
+                {
+                    report(
+                        Problem::OutOfBounds,
+                        i,
+                        format!("moves outside the work area to ({:.1}, {:.1})", x, y),
+                    );
+                }
+                if z < 0.0 {
+                    report(
+                        Problem::BelowBed,
+                        i,
+                        format!("goes below the bed to Z{:.2}", z),
+                    );
+                }
+                let length = (s.to.0 - s.from.0).hypot(s.to.1 - s.from.1);
+                if let Some(max) = self.config.max_draw_move
+                    && s.pen_down
+                    && length > max
+                {
+                    report(
+                        Problem::LongDrawMove,
+                        i,
+                        format!("draws a {:.1}mm line, longer than {}mm", length, max),
+                    );
+                }
+            }
+        }
+        diagnostics
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Overrides;
+    use crate::tests::test_config;
+
+    #[test]
+    fn validation() {
+        let mut config = test_config();
+        config.max_feed = Some(Feed::mm_per_min(1500.0));
+        config.max_draw_move = Some(100.0);
+        let mut printer = Printer::new(config);
+        printer.draw_line(10.0, 10.0, 20.0, 10.0);
+        assert_eq!(printer.validate(), []);
+
+        printer.draw_line(0.0, 20.0, 150.0, 20.0);
+        printer.draw_polyline(&[(-5.0, 30.0), (10.0, 30.0), (12.0, 30.0)]);
+        printer.draw_polyline(&[(5.0, 40.0), (5.0, 40.0), (6.0, 40.0)]);
+        printer.set_overrides(Overrides {
+            draw_speed: Some(Feed::mm_per_min(3000.0)),
+            z_draw: Some(-0.5),
+            ..Overrides::default()
+        });
+        printer.draw_line(10.0, 50.0, 20.0, 50.0);
+
+        let found: Vec<(Problem, usize)> = printer
+            .validate()
+            .iter()
+            .map(|d| (d.problem, d.op))
+            .collect();
+        assert_eq!(
+            found,
+            [
+                (Problem::LongDrawMove, 1),
+                (Problem::OutOfBounds, 2),
+                (Problem::ZeroLengthMove, 3),
+                (Problem::BelowBed, 4),
+                (Problem::FeedTooHigh, 4),
+            ]
+        );
+        assert_eq!(
+            printer.validate()[1].to_string(),
+            "draw_polyline(3 points) moves outside the work area to (-5.0, 30.0)"
+        );
+    }
+
+    #[test]
+    fn clean_drawing_validates() {
+        let mut printer = Printer::new(test_config());
+        // Starting at the origin and strokes that start where the last one
+        // ended travel nowhere, which is fine
+        printer.draw_point(0.0, 0.0);
+        printer.draw_line(0.0, 0.0, 50.0, 0.0);
+        printer.draw_polyline(&[(50.0, 0.0), (50.0, 50.0), (0.0, 50.0)]);
+        printer.draw_circle(100.0, 100.0, 20.0);
+        printer.draw_point(100.0, 100.0);
+        printer.draw_text(10.0, 150.0, 5.0, "clean");
+        assert_eq!(printer.validate(), []);
+
+        printer.optimize();
+        assert_eq!(printer.validate(), []);
+    }
+}
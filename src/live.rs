@@ -0,0 +1,303 @@
+#[cfg(feature = "serial")]
+use std::fs::File;
+#[cfg(feature = "serial")]
+use std::io::BufReader;
+use std::io::{self, BufRead, Write};
+
+use crate::{Code, EmitState, Printer, curve};
+
+// Line-by-line connection to the firmware. Every line waits for the "ok"
+// that Marlin-style firmware sends once it has room for the next command,
+// which keeps its buffer from overflowing.
+pub struct Sender<R: BufRead, W: Write> {
+    reader: R,
+    writer: W,
+    pub lines_sent: usize,
+    // Number of the next line when lines go out with numbers and checksums
+    numbered: Option<usize>,
+}
+
+// Times a line is sent again on request before giving up
+const MAX_RESENDS: usize = 5;
+
+// Marlin's checksum: every byte of the line XORed together
+fn checksum(line: &str) -> u8 {
+    line.bytes().fold(0, |sum, b| sum ^ b)
+}
+
+// The command part of a code, without comments
+fn command(code: &Code) -> Option<String> {
+    let line = code.to_string();
+    let line = line.split(';').next().unwrap_or("").trim();
+    (!line.is_empty()).then(|| line.to_string())
+}
+
+// Without the serial feature, set the port up first (e.g. `stty -F
+// /dev/ttyACM0 115200 raw -echo`) and hand the opened device to new
+#[cfg(feature = "serial")]
+impl Sender<BufReader<File>, File> {
+    // Opens a serial device like /dev/ttyACM0 at `baud`, see
+    // serial::configure
+    pub fn open(path: &str, baud: u32) -> Result<Self, io::Error> {
+        let writer = File::options().read(true).write(true).open(path)?;
+        crate::serial::configure(&writer, baud)?;
+        let reader = BufReader::new(writer.try_clone()?);
+        Ok(Sender::new(reader, writer))
+    }
+}
+
+impl<R: BufRead, W: Write> Sender<R, W> {
+    pub fn new(reader: R, writer: W) -> Self {
+        Sender {
+            reader,
+            writer,
+            lines_sent: 0,
+            numbered: None,
+        }
+    }
+
+    // Sends every line with a line number and checksum from now on, so the
+    // firmware can ask for lines that got garbled on the way. The numbering
+    // starts over with M110.
+    pub fn number_lines(&mut self) -> Result<(), io::Error> {
+        self.numbered = Some(0);
+        self.send("M110 N0")
+    }
+
+    // Sends a line and waits for it to be acknowledged. Other output like
+    // temperature reports, echo: and busy: messages is skipped. With
+    // numbered lines, lines the firmware asks for again are resent.
+    pub fn send(&mut self, line: &str) -> Result<(), io::Error> {
+        let framed = match self.numbered {
+            Some(n) => {
+                self.numbered = Some(n + 1);
+                let numbered = format!("N{} {}", n, line);
+                format!("{}*{}", numbered, checksum(&numbered))
+            }
+            None => line.to_string(),
+        };
+        self.writer.write_all(framed.as_bytes())?;
+        self.writer.write_all(b"\n")?;
+        self.writer.flush()?;
+        self.lines_sent += 1;
+
+        let mut error: Option<String> = None;
+        let mut resend = false;
+        let mut resends = 0;
+        let mut reply = String::new();
+        loop {
+            reply.clear();
+            if self.reader.read_line(&mut reply)? == 0 {
+                return Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    format!("connection closed while waiting for \"{}\"", line),
+                ));
+            }
+            let reply = reply.trim();
+            if reply.starts_with("Resend:") || reply.starts_with("rs ") {
+                resend = self.numbered.is_some();
+                continue;
+            }
+            if reply.starts_with("ok") {
+                if resend && resends < MAX_RESENDS {
+                    self.writer.write_all(framed.as_bytes())?;
+                    self.writer.write_all(b"\n")?;
+                    self.writer.flush()?;
+                    (error, resend, resends) = (None, false, resends + 1);
+                    continue;
+                }
+                return match error {
+                    Some(e) => Err(io::Error::other(format!("{} after \"{}\"", e, line))),
+                    None => Ok(()),
+                };
+            }
+            if ["Error", "error:", "!!", "ALARM"]
+                .iter()
+                .any(|e| reply.starts_with(e))
+            {
+                // A numbered line may just have been garbled, which the
+                // firmware follows up with a resend request
+                if self.numbered.is_none() {
+                    return Err(io::Error::other(format!("{} after \"{}\"", reply, line)));
+                }
+                error = Some(reply.to_string());
+            }
+        }
+    }
+
+    // Sends the whole program of `printer` as it would be saved, calling
+    // `on_progress` with the lines sent so far and the total after each
+    pub fn send_program(
+        &mut self,
+        printer: &Printer,
+        mut on_progress: impl FnMut(usize, usize),
+    ) -> Result<(), io::Error> {
+        let mut program = Vec::new();
+        printer.write_to(&mut program)?;
+        let program = String::from_utf8_lossy(&program);
+        let lines: Vec<&str> = program
+            .lines()
+            .map(|l| l.split(';').next().unwrap_or("").trim())
+            .filter(|l| !l.is_empty())
+            .collect();
+        for (i, line) in lines.iter().enumerate() {
+            self.send(line)?;
+            on_progress(i + 1, lines.len());
+        }
+        Ok(())
+    }
+
+    // Sends the commands of the codes, comments and empty lines are skipped
+    pub fn send_codes<'a>(
+        &mut self,
+        codes: impl IntoIterator<Item = &'a Code>,
+    ) -> Result<(), io::Error> {
+        for c in codes {
+            if let Some(line) = command(c) {
+                self.send(&line)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+// Draws on the machine while the drawing is being made: every draw call is
+// sent as soon as it's made, e.g. for live-coded performances. Filters and
+// the rest of the config apply as they do for saved files, but passes over
+// the whole composition (ordering, dedupe, stats) can't.
+pub struct LivePlot<R: BufRead, W: Write> {
+    printer: Printer,
+    sender: Sender<R, W>,
+    state: EmitState,
+    // Ops of the printer already on their way
+    sent: usize,
+}
+
+impl<R: BufRead, W: Write> LivePlot<R, W> {
+    // Homes the machine and sets the origin, ready for the first stroke
+    pub fn start(printer: Printer, sender: Sender<R, W>) -> Result<Self, io::Error> {
+        let mut live = LivePlot {
+            sent: printer.ops.len(),
+            printer,
+            sender,
+            state: EmitState::default(),
+        };
+        let header = live.printer.translated(live.printer.header());
+        live.sender.send_codes(&header)?;
+        Ok(live)
+    }
+
+    // For changing layers, overrides and filters between draw calls
+    pub fn printer(&mut self) -> &mut Printer {
+        &mut self.printer
+    }
+
+    pub fn sender(&mut self) -> &mut Sender<R, W> {
+        &mut self.sender
+    }
+
+    // Sends everything drawn since the last time
+    pub fn flush(&mut self) -> Result<(), io::Error> {
+        let end = self.printer.ops.len();
+        let code = self.printer.emit_ops(self.sent..end, &mut self.state);
+        self.sent = end;
+        let code = self.printer.translated(code.into_iter().map(|(_, c)| c));
+        self.sender.send_codes(&code)
+    }
+
+    pub fn draw_point(&mut self, xp: f32, yp: f32) -> Result<(), io::Error> {
+        self.printer.draw_point(xp, yp);
+        self.flush()
+    }
+
+    pub fn draw_polyline(&mut self, points: &[(f32, f32)]) -> Result<(), io::Error> {
+        self.printer.draw_polyline(points);
+        self.flush()
+    }
+
+    pub fn draw_curve(&mut self, curves: &[curve::CubicBezier]) -> Result<(), io::Error> {
+        self.printer.draw_curve(curves);
+        self.flush()
+    }
+
+    // Lifts the head and turns the motors off. The printer keeps the whole
+    // drawing, so it can still be saved.
+    pub fn finish(mut self) -> Result<Printer, io::Error> {
+        self.flush()?;
+        let pending = std::mem::take(&mut self.printer.pending);
+        let footer = self
+            .printer
+            .translated(pending.into_iter().chain(self.printer.footer()));
+        self.sender.send_codes(&footer)?;
+        Ok(self.printer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::test_config;
+
+    fn firmware(replies: &str) -> Sender<io::Cursor<Vec<u8>>, Vec<u8>> {
+        Sender::new(io::Cursor::new(replies.as_bytes().to_vec()), Vec::new())
+    }
+
+    #[test]
+    fn waits_for_ok() {
+        let mut sender =
+            firmware("start\necho:busy: processing\nok\nT:21.0 /0.0\nok\nError:Unknown command\n");
+        sender.send("G28 W").unwrap();
+        sender.send("G0 X1.0 F1000.0").unwrap();
+        let err = sender.send("G999").unwrap_err();
+        assert_eq!(err.to_string(), "Error:Unknown command after \"G999\"");
+        assert!(sender.send("G0 X2.0").is_err());
+
+        let sent = String::from_utf8(sender.writer).unwrap();
+        assert_eq!(sent, "G28 W\nG0 X1.0 F1000.0\nG999\nG0 X2.0\n");
+    }
+
+    #[test]
+    fn numbered_lines() {
+        let mut sender = firmware(
+            "ok\nok\nError:checksum mismatch, Last Line: 0\nResend: 2\nok\nok\nError:Unknown\nok\n",
+        );
+        sender.number_lines().unwrap();
+        sender.send("G28").unwrap();
+        // Garbled on the way, sent again
+        sender.send("G0 X1.0").unwrap();
+        assert!(sender.send("G999").is_err());
+
+        let sent = String::from_utf8(sender.writer).unwrap();
+        assert_eq!(
+            sent,
+            "N0 M110 N0*125\nN1 G28*18\nN2 G0 X1.0*124\nN2 G0 X1.0*124\nN3 G999*35\n"
+        );
+        assert_eq!(checksum("N1 G28"), 18);
+    }
+
+    #[test]
+    fn live_plot() {
+        let replies = "ok\n".repeat(100);
+        let sender = Sender::new(io::Cursor::new(replies.into_bytes()), Vec::new());
+        let mut plot = LivePlot::start(Printer::new(test_config()), sender).unwrap();
+        let header = plot.sender().lines_sent;
+        assert!(header > 0);
+
+        plot.draw_polyline(&[(10.0, 10.0), (20.0, 10.0)]).unwrap();
+        // Stroke: travel, pen down, draw, pen up
+        assert_eq!(plot.sender().lines_sent, header + 4);
+        plot.printer().set_layer("red");
+        plot.draw_point(30.0, 30.0).unwrap();
+        let sent = plot.sender().lines_sent;
+        let printer = plot.finish().unwrap();
+
+        // Same commands as the saved program, without progress messages
+        let lines = |code: Vec<Code>| {
+            code.iter()
+                .filter(|c| !c.to_string().split(';').next().unwrap().trim().is_empty())
+                .count()
+        };
+        assert_eq!(sent, lines(printer.header()) + lines(printer.codes()));
+        assert_eq!(printer.ops().len(), 2);
+    }
+}
@@ -0,0 +1,300 @@
+use std::io;
+use std::process::ExitCode;
+
+use artful_gcode::prelude::*;
+use artful_gcode::sim;
+
+const USAGE: &str = "\
+usage: artful-gcode plot INPUT -o OUTPUT [options]
+       artful-gcode run SCRIPT -o OUTPUT [options]
+
+INPUT is an SVG file or a text file of x,y points in mm, one point per
+line, with empty lines between polylines. SCRIPT is a drawing script, see
+src/script.rs; run needs artful-gcode built with the script feature.
+
+options:
+  -o, --output FILE     G-code to write, or HP-GL for a .hpgl or .plt file
+  -s, --scale FACTOR    scales the drawing, after fitting an SVG to the bed
+  -O, --optimize        orders the strokes to shorten travel
+  --dry-run             keeps the pen above the paper, to check the placement
+  --resume N            writes the program from recovery point N on, for a
+                        plot that was cut short (see recovery_interval);
+                        --dry-run, --resume and HP-GL output exclude each
+                        other
+  --preview FILE        writes a preview as .svg, .html or .png as well
+  --seed N              seeds random() in scripts (default: 0)
+  -h, --help            prints this help";
+
+#[derive(Debug, Default, PartialEq)]
+struct Options {
+    input: String,
+    output: String,
+    scale: Option<f32>,
+    optimize: bool,
+    dry_run: bool,
+    resume: Option<usize>,
+    preview: Option<String>,
+    // Run the input as a script
+    script: bool,
+    seed: u64,
+}
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, message)
+}
+
+fn parse_args(args: &[String]) -> Result<Options, io::Error> {
+    let mut args = args.iter();
+    let mut options = Options::default();
+    match args.next().map(String::as_str) {
+        Some("plot") => {}
+        Some("run") => options.script = true,
+        Some(other) => return Err(invalid(format!("unknown command: {}", other))),
+        None => return Err(invalid("no command given".to_string())),
+    }
+
+    let mut input = None;
+    let mut output = None;
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .cloned()
+                .ok_or_else(|| invalid(format!("{} needs a value", arg)))
+        };
+        match arg.as_str() {
+            "-o" | "--output" => output = Some(value()?),
+            "-s" | "--scale" => {
+                let text = value()?;
+                let scale = text
+                    .parse()
+                    .ok()
+                    .filter(|s: &f32| *s > 0.0)
+                    .ok_or_else(|| invalid(format!("invalid scale: {}", text)))?;
+                options.scale = Some(scale);
+            }
+            "-O" | "--optimize" => options.optimize = true,
+            "--dry-run" => options.dry_run = true,
+            "--resume" => {
+                let text = value()?;
+                let n = text
+                    .parse()
+                    .map_err(|_| invalid(format!("invalid recovery point: {}", text)))?;
+                options.resume = Some(n);
+            }
+            "--preview" => options.preview = Some(value()?),
+            "--seed" => {
+                let text = value()?;
+                let seed = text
+                    .parse()
+                    .map_err(|_| invalid(format!("invalid seed: {}", text)))?;
+                options.seed = seed;
+            }
+            other if other.starts_with('-') => {
+                return Err(invalid(format!("unknown option: {}", other)));
+            }
+            _ if input.is_none() => input = Some(arg.clone()),
+            other => return Err(invalid(format!("unexpected argument: {}", other))),
+        }
+    }
+    options.input = input.ok_or_else(|| invalid("no input given".to_string()))?;
+    options.output = output.ok_or_else(|| invalid("no output given (-o)".to_string()))?;
+    // Each of these writes a different program, so only one can apply
+    let chosen: Vec<&str> = [
+        options.resume.map(|_| "--resume"),
+        options.dry_run.then_some("--dry-run"),
+        hpgl(&options.output).then_some("HP-GL output"),
+    ]
+    .into_iter()
+    .flatten()
+    .collect();
+    if let [a, b, ..] = chosen[..] {
+        return Err(invalid(format!("{} can't be used with {}", a, b)));
+    }
+    Ok(options)
+}
+
+// Polylines of a points file: "x,y" or "x y" per line, an empty line ends
+// a polyline and # starts a comment
+fn parse_points(text: &str) -> Result<Vec<Path>, io::Error> {
+    let mut paths = vec![Vec::new()];
+    for (number, line) in text.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or("").trim();
+        if line.is_empty() {
+            paths.push(Vec::new());
+            continue;
+        }
+        let numbers: Vec<f32> = line
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|w| !w.is_empty())
+            .map(|w| w.parse().ok())
+            .collect::<Option<_>>()
+            .filter(|n: &Vec<f32>| n.len() == 2)
+            .ok_or_else(|| invalid(format!("line {}: expected x,y: {}", number + 1, line)))?;
+        paths.last_mut().unwrap().push((numbers[0], numbers[1]));
+    }
+    paths.retain(|p| !p.is_empty());
+    Ok(paths)
+}
+
+#[cfg(feature = "script")]
+fn run_script(printer: &mut Printer, options: &Options) -> Result<(), io::Error> {
+    let source = std::fs::read_to_string(&options.input)?;
+    artful_gcode::script::run(printer, &source, options.seed, |line| println!("{}", line)).map_err(
+        |e| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{}: {}", options.input, e),
+            )
+        },
+    )
+}
+
+#[cfg(not(feature = "script"))]
+fn run_script(_: &mut Printer, _: &Options) -> Result<(), io::Error> {
+    Err(invalid(
+        "run needs artful-gcode built with --features script".to_string(),
+    ))
+}
+
+// HP-GL rather than G-code, by the extension of `output`
+fn hpgl(output: &str) -> bool {
+    let output = output.to_lowercase();
+    output.ends_with(".hpgl") || output.ends_with(".plt")
+}
+
+fn plot(options: &Options) -> Result<(), io::Error> {
+    let mut printer = Printer::new(PrinterConfig::default());
+    if let Some(s) = options.scale {
+        printer.push_transform(AffineTransform::scale(s, s));
+    }
+    if options.script {
+        run_script(&mut printer, options)?;
+    } else if options.input.to_lowercase().ends_with(".svg") {
+        Svg::load(&options.input)?.draw(&mut printer);
+    } else {
+        for path in parse_points(&std::fs::read_to_string(&options.input)?)? {
+            match path.as_slice() {
+                [(x, y)] => printer.draw_point(*x, *y),
+                _ => printer.draw_polyline(&path),
+            }
+        }
+    }
+    if options.optimize {
+        printer.optimize();
+    }
+    for diagnostic in printer.validate() {
+        eprintln!("warning: {}", diagnostic);
+    }
+
+    if let Some(n) = options.resume {
+        printer.save_from_checkpoint(n, &options.output)?;
+    } else if hpgl(&options.output) {
+        printer.save_hpgl(&options.output)?;
+    } else if options.dry_run {
+        printer.save_dry_run(&options.output, &DryRun::default())?;
+    } else {
+        printer.save(&options.output)?;
+    }
+    if let Some(preview) = &options.preview {
+        match preview.rsplit_once('.').map(|(_, e)| e.to_lowercase()) {
+            Some(e) if e == "svg" => printer.preview_svg(preview)?,
+            Some(e) if e == "html" => printer.preview_html(preview)?,
+            Some(e) if e == "png" => printer.preview_png(preview, 800, 800)?,
+            _ => return Err(invalid(format!("unknown preview format: {}", preview))),
+        }
+    }
+
+    let stats = printer.stats().total;
+    eprintln!(
+        "{}: {:.0} mm drawn, {:.0} mm travel, about {}",
+        options.output,
+        stats.drawn,
+        stats.travel,
+        sim::format_time(stats.time)
+    );
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.is_empty() || args.iter().any(|a| a == "-h" || a == "--help") {
+        println!("{}", USAGE);
+        return ExitCode::SUCCESS;
+    }
+    match parse_args(&args).and_then(|options| plot(&options)) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("artful-gcode: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(line: &str) -> Vec<String> {
+        line.split_whitespace().map(str::to_string).collect()
+    }
+
+    #[test]
+    fn arguments() {
+        let options = parse_args(&args(
+            "plot in.svg -o out.gcode -s 0.5 -O --preview out.png",
+        ))
+        .unwrap();
+        assert_eq!(
+            options,
+            Options {
+                input: "in.svg".to_string(),
+                output: "out.gcode".to_string(),
+                scale: Some(0.5),
+                optimize: true,
+                dry_run: false,
+                resume: None,
+                preview: Some("out.png".to_string()),
+                script: false,
+                seed: 0,
+            }
+        );
+        let options = parse_args(&args("run ring.txt -o out.gcode --seed 7")).unwrap();
+        assert!(options.script);
+        assert_eq!(options.seed, 7);
+
+        let err = parse_args(&args("plot in.svg")).unwrap_err();
+        assert_eq!(err.to_string(), "no output given (-o)");
+        let err = parse_args(&args("plot in.svg -o")).unwrap_err();
+        assert_eq!(err.to_string(), "-o needs a value");
+        let err = parse_args(&args("plot in.svg -o out.gcode --scale -1")).unwrap_err();
+        assert_eq!(err.to_string(), "invalid scale: -1");
+    }
+
+    #[test]
+    fn conflicting_outputs() {
+        let err = parse_args(&args("plot in.svg -o out.gcode --resume 2 --dry-run")).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        assert_eq!(err.to_string(), "--resume can't be used with --dry-run");
+        let err = parse_args(&args("plot in.svg -o out.hpgl --resume 2")).unwrap_err();
+        assert_eq!(err.to_string(), "--resume can't be used with HP-GL output");
+        let err = parse_args(&args("plot in.svg -o OUT.PLT --dry-run")).unwrap_err();
+        assert_eq!(err.to_string(), "--dry-run can't be used with HP-GL output");
+        assert!(parse_args(&args("plot in.svg -o out.gcode --resume 2")).is_ok());
+        assert!(parse_args(&args("plot in.svg -o out.gcode --dry-run")).is_ok());
+    }
+
+    #[test]
+    fn points() {
+        let text = "# a triangle\n0,0\n10, 0\n5 8\n\n\n20,20\n";
+        let paths = parse_points(text).unwrap();
+        assert_eq!(
+            paths,
+            [
+                vec![(0.0, 0.0), (10.0, 0.0), (5.0, 8.0)],
+                vec![(20.0, 20.0)]
+            ]
+        );
+        let err = parse_points("1,2\n3;4\n").unwrap_err();
+        assert_eq!(err.to_string(), "line 2: expected x,y: 3;4");
+    }
+}
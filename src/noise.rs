@@ -0,0 +1,214 @@
+use crate::path::{self, Path};
+use crate::rng::{Rng, mix};
+
+// Seeded value noise: random values on an integer lattice, smoothly
+// interpolated in between. Output is in [-1, 1].
+#[derive(Debug, Copy, Clone)]
+pub struct ValueNoise {
+    key: u64,
+}
+
+fn smoothstep(t: f32) -> f32 {
+    t * t * (3.0 - 2.0 * t)
+}
+
+impl ValueNoise {
+    pub fn new(seed: u64) -> Self {
+        ValueNoise {
+            key: Rng::new(seed).next_u64(),
+        }
+    }
+
+    fn lattice(&self, ix: i64, iy: i64) -> f32 {
+        let h = mix(self.key ^ mix((ix as u64) ^ mix(iy as u64).rotate_left(17)));
+        (h >> 40) as f32 / (1u64 << 23) as f32 - 1.0
+    }
+
+    pub fn get(&self, x: f32) -> f32 {
+        self.get2(x, 0.0)
+    }
+
+    pub fn get2(&self, x: f32, y: f32) -> f32 {
+        let (fx, fy) = (x.floor(), y.floor());
+        let (ix, iy) = (fx as i64, fy as i64);
+        let (tx, ty) = (smoothstep(x - fx), smoothstep(y - fy));
+
+        let top = self.lattice(ix, iy) + (self.lattice(ix + 1, iy) - self.lattice(ix, iy)) * tx;
+        let bottom = self.lattice(ix, iy + 1)
+            + (self.lattice(ix + 1, iy + 1) - self.lattice(ix, iy + 1)) * tx;
+
+        top + (bottom - top) * ty
+    }
+}
+
+// Hand-drawn wobble: every vertex is pushed around by low-frequency noise
+// sampled along the arc length, so neighbouring vertices move together.
+#[derive(Debug, Copy, Clone)]
+pub struct Jitter {
+    // Maximum displacement in mm
+    pub amplitude: f32,
+    // Distance along the path over which the wobble changes direction, in mm
+    pub wavelength: f32,
+    // Straight runs longer than this are split so they can wobble too
+    pub max_segment: Option<f32>,
+    pub seed: u64,
+}
+
+impl Jitter {
+    pub fn new(amplitude: f32, wavelength: f32, seed: u64) -> Self {
+        Jitter {
+            amplitude,
+            wavelength,
+            max_segment: Some(wavelength / 4.0),
+            seed,
+        }
+    }
+
+    pub fn apply(&self, input: &[(f32, f32)]) -> Path {
+        let noise_x = ValueNoise::new(self.seed);
+        let noise_y = ValueNoise::new(self.seed.wrapping_add(1));
+        let wavelength = self.wavelength.max(1e-3);
+
+        let pts = match self.max_segment {
+            Some(max) if max > 0.0 => split_long(input, max),
+            _ => input.to_vec(),
+        };
+
+        let mut s = 0.0;
+        let mut prev = pts.first().copied();
+        pts.iter()
+            .map(|&p| {
+                s += prev.map(|q| path::dist(q, p)).unwrap_or(0.0);
+                prev = Some(p);
+                let t = s / wavelength;
+                (
+                    p.0 + self.amplitude * noise_x.get(t),
+                    p.1 + self.amplitude * noise_y.get(t),
+                )
+            })
+            .collect()
+    }
+}
+
+// Smooth 2D displacement field for distorting a whole composition
+#[derive(Debug, Copy, Clone)]
+pub struct Displace {
+    // Maximum displacement in mm
+    pub amplitude: f32,
+    // Features per mm, e.g. 0.02 for blobs roughly 50mm across
+    pub frequency: f32,
+    pub seed: u64,
+}
+
+impl Displace {
+    pub fn new(amplitude: f32, frequency: f32, seed: u64) -> Self {
+        Displace {
+            amplitude,
+            frequency,
+            seed,
+        }
+    }
+
+    pub fn apply(&self, (x, y): (f32, f32)) -> (f32, f32) {
+        let noise_x = ValueNoise::new(self.seed);
+        let noise_y = ValueNoise::new(self.seed.wrapping_add(1));
+        let (u, v) = (x * self.frequency, y * self.frequency);
+        (
+            x + self.amplitude * noise_x.get2(u, v),
+            y + self.amplitude * noise_y.get2(u, v),
+        )
+    }
+
+    pub fn apply_path(&self, input: &[(f32, f32)]) -> Path {
+        input.iter().map(|p| self.apply(*p)).collect()
+    }
+}
+
+// Insert evenly spaced vertices into segments longer than `max_len`,
+// keeping all of the original vertices
+pub fn split_long(input: &[(f32, f32)], max_len: f32) -> Path {
+    let mut out = Vec::with_capacity(input.len());
+    for w in input.windows(2) {
+        out.push(w[0]);
+        let n = (path::dist(w[0], w[1]) / max_len).ceil() as usize;
+        for i in 1..n {
+            out.push(path::lerp(w[0], w[1], i as f32 / n as f32));
+        }
+    }
+    if let Some(last) = input.last() {
+        out.push(*last);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::test_config;
+    use crate::{Printer, Shape};
+
+    #[test]
+    fn noise_is_smooth_and_bounded() {
+        let noise = ValueNoise::new(1);
+        let mut prev = noise.get(0.0);
+        for i in 1..1000 {
+            let v = noise.get(i as f32 * 0.01);
+            assert!((-1.0..=1.0).contains(&v));
+            assert!((v - prev).abs() < 0.1);
+            prev = v;
+        }
+        assert_ne!(ValueNoise::new(1).get(0.5), ValueNoise::new(2).get(0.5));
+    }
+
+    #[test]
+    fn jitter_is_deterministic() {
+        let line = vec![(0.0, 0.0), (100.0, 0.0)];
+        let a = Jitter::new(0.5, 20.0, 3).apply(&line);
+        let b = Jitter::new(0.5, 20.0, 3).apply(&line);
+        let c = Jitter::new(0.5, 20.0, 4).apply(&line);
+        assert_eq!(a, b);
+        assert_ne!(a, c);
+
+        // Long runs are split so the line actually wobbles
+        assert!(a.len() >= 21);
+        for (p, q) in a.iter().zip(split_long(&line, 5.0).iter()) {
+            assert!(path::dist(*p, *q) <= 0.5 * 2.0_f32.sqrt() + 1e-4);
+        }
+    }
+
+    #[test]
+    fn displacement_is_continuous() {
+        let field = Displace::new(2.0, 0.05, 9);
+        let a = field.apply((10.0, 10.0));
+        let b = field.apply((10.1, 10.0));
+        assert!(path::dist(a, (10.0, 10.0)) <= 2.0 * 2.0_f32.sqrt());
+        assert!(path::dist(a, b) < 0.2);
+        assert_eq!(a, Displace::new(2.0, 0.05, 9).apply((10.0, 10.0)));
+    }
+
+    #[test]
+    fn split_keeps_vertices() {
+        let out = split_long(&[(0.0, 0.0), (10.0, 0.0), (10.0, 1.0)], 4.0);
+        assert_eq!(out.len(), 5);
+        assert_eq!(out[0], (0.0, 0.0));
+        assert_eq!(out[3], (10.0, 0.0));
+        assert_eq!(out[4], (10.0, 1.0));
+        assert!((out[1].0 - 10.0 / 3.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn displace_ops() {
+        let mut printer = Printer::new(test_config());
+        printer.draw_point(10.0, 10.0);
+        printer.draw_point(20.0, 10.0);
+        printer.displace(&Displace::new(1.0, 0.1, 5));
+
+        for (op, orig) in printer.ops().iter().zip([(10.0, 10.0), (20.0, 10.0)]) {
+            let Shape::Point(at) = &op.shape else {
+                panic!("Expected a point!");
+            };
+            assert_ne!(*at, orig);
+            assert!(path::dist(*at, orig) <= 2.0_f32.sqrt());
+        }
+    }
+}
@@ -0,0 +1,196 @@
+use std::collections::HashMap;
+
+use crate::path;
+
+// How far apart in the order two strokes can be for 2-opt to try swapping
+// the run between them, keeps large point clouds fast
+const WINDOW: usize = 100;
+const PASSES: usize = 8;
+
+// Where a stroke starts and where it ends
+pub type Ends = ((f32, f32), (f32, f32));
+
+// Order in which to draw strokes with the given (start, end) points to keep
+// the travel between them short, starting at `from`. Every entry is the
+// index of a stroke and whether to draw it backwards.
+pub fn optimize(ends: &[Ends], from: (f32, f32)) -> Vec<(usize, bool)> {
+    let mut order = nearest_neighbor(ends, from);
+    two_opt(ends, from, &mut order);
+    order
+}
+
+// Length of all travel moves when drawing in `order`
+pub fn travel(ends: &[Ends], from: (f32, f32), order: &[(usize, bool)]) -> f32 {
+    let mut at = from;
+    let mut total = 0.0;
+    for &(i, reversed) in order {
+        let (start, end) = oriented(ends[i], reversed);
+        total += path::dist(at, start);
+        at = end;
+    }
+    total
+}
+
+fn oriented((start, end): Ends, reversed: bool) -> Ends {
+    if reversed { (end, start) } else { (start, end) }
+}
+
+// Greedy: always the closest stroke not drawn yet, from either end. Both
+// ends go into a grid so the search stays near where the pen is.
+fn nearest_neighbor(ends: &[Ends], from: (f32, f32)) -> Vec<(usize, bool)> {
+    let n = ends.len();
+    if n == 0 {
+        return Vec::new();
+    }
+    let points = ends.iter().flat_map(|&(s, e)| [s, e]);
+    let (min, max) = points.fold(
+        ((f32::MAX, f32::MAX), (f32::MIN, f32::MIN)),
+        |(min, max), p| {
+            (
+                (min.0.min(p.0), min.1.min(p.1)),
+                (max.0.max(p.0), max.1.max(p.1)),
+            )
+        },
+    );
+    // About two ends per cell
+    let area = ((max.0 - min.0) * (max.1 - min.1)).max(1e-6);
+    // Strokes all in a line would otherwise make for countless tiny cells
+    let size = (max.0 - min.0).max(max.1 - min.1);
+    let cell = (area / n as f32).sqrt().max(size / n as f32).max(1e-3);
+    let key = |p: (f32, f32)| {
+        (
+            ((p.0 - min.0) / cell).floor() as i32,
+            ((p.1 - min.1) / cell).floor() as i32,
+        )
+    };
+    let mut grid: HashMap<(i32, i32), Vec<(usize, bool)>> = HashMap::new();
+    for (i, &(start, end)) in ends.iter().enumerate() {
+        grid.entry(key(start)).or_default().push((i, false));
+        grid.entry(key(end)).or_default().push((i, true));
+    }
+    let span = key(max).0.max(key(max).1) + 1;
+
+    let mut done = vec![false; n];
+    let mut order = Vec::with_capacity(n);
+    let mut at = from;
+    for _ in 0..n {
+        let center = key(at);
+        let mut best: Option<(f32, usize, bool)> = None;
+        // Rings of cells outwards until nothing closer can be left
+        for ring in 0.. {
+            if let Some((d, _, _)) = best
+                && d < (ring - 1) as f32 * cell
+            {
+                break;
+            }
+            if ring > span + center.0.abs().max(center.1.abs()) {
+                break;
+            }
+            for dx in -ring..=ring {
+                for dy in -ring..=ring {
+                    if dx.abs() != ring && dy.abs() != ring {
+                        continue;
+                    }
+                    let Some(cell) = grid.get_mut(&(center.0 + dx, center.1 + dy)) else {
+                        continue;
+                    };
+                    cell.retain(|&(i, _)| !done[i]);
+                    for &(i, reversed) in cell.iter() {
+                        let (start, _) = oriented(ends[i], reversed);
+                        let d = path::dist(at, start);
+                        if best.is_none_or(|(b, _, _)| d < b) {
+                            best = Some((d, i, reversed));
+                        }
+                    }
+                }
+            }
+        }
+        let Some((_, i, reversed)) = best else {
+            break;
+        };
+        done[i] = true;
+        order.push((i, reversed));
+        at = oriented(ends[i], reversed).1;
+    }
+    order
+}
+
+// Turns runs of the order around, and every stroke in them, as long as that
+// makes the travel shorter
+fn two_opt(ends: &[Ends], from: (f32, f32), order: &mut [(usize, bool)]) {
+    let n = order.len();
+    for _ in 0..PASSES {
+        let mut improved = false;
+        for i in 0..n {
+            for j in i + 1..n.min(i + WINDOW) {
+                let before = if i == 0 {
+                    from
+                } else {
+                    let (k, r) = order[i - 1];
+                    oriented(ends[k], r).1
+                };
+                let (first_start, _) = oriented(ends[order[i].0], order[i].1);
+                let (_, last_end) = oriented(ends[order[j].0], order[j].1);
+                let after = order.get(j + 1).map(|&(k, r)| oriented(ends[k], r).0);
+
+                let mut old = path::dist(before, first_start);
+                let mut new = path::dist(before, last_end);
+                if let Some(after) = after {
+                    old += path::dist(last_end, after);
+                    new += path::dist(first_start, after);
+                }
+                if new < old - 1e-4 {
+                    order[i..=j].reverse();
+                    for entry in &mut order[i..=j] {
+                        entry.1 = !entry.1;
+                    }
+                    improved = true;
+                }
+            }
+        }
+        if !improved {
+            break;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rng::Rng;
+
+    #[test]
+    fn shorter_travel() {
+        // Scattered dots
+        let mut rng = Rng::new(5);
+        let mut random = || (rng.next_u64() % 1000) as f32 / 10.0;
+        let dots: Vec<Ends> = (0..500)
+            .map(|_| {
+                let p = (random(), random());
+                (p, p)
+            })
+            .collect();
+        let identity: Vec<(usize, bool)> = (0..dots.len()).map(|i| (i, false)).collect();
+        let order = optimize(&dots, (0.0, 0.0));
+        let mut seen: Vec<usize> = order.iter().map(|&(i, _)| i).collect();
+        seen.sort();
+        assert_eq!(seen, (0..dots.len()).collect::<Vec<_>>());
+        let greedy = nearest_neighbor(&dots, (0.0, 0.0));
+        let before = travel(&dots, (0.0, 0.0), &identity);
+        let after = travel(&dots, (0.0, 0.0), &order);
+        assert!(after < before / 5.0, "{} {}", before, after);
+        assert!(after <= travel(&dots, (0.0, 0.0), &greedy));
+    }
+
+    #[test]
+    fn strokes_turned_around() {
+        // Lines drawn alternately in opposite directions are best drawn as a
+        // zigzag
+        let lines: Vec<Ends> = (0..4)
+            .map(|i| ((0.0, i as f32), (10.0, i as f32)))
+            .collect();
+        let order = optimize(&lines, (0.0, 0.0));
+        assert_eq!(order, [(0, false), (1, true), (2, false), (3, true)]);
+        assert_eq!(travel(&lines, (0.0, 0.0), &order), 3.0);
+    }
+}
@@ -0,0 +1,318 @@
+use std::collections::HashMap;
+
+use crate::path::{self, Path};
+use crate::sim;
+
+type Segment = ((f32, f32), (f32, f32));
+
+// Spatial hash of everything that has been drawn so far
+struct Drawn {
+    cell: f32,
+    tolerance: f32,
+    grid: HashMap<(i32, i32), Vec<Segment>>,
+}
+
+impl Drawn {
+    fn new(tolerance: f32) -> Self {
+        Drawn {
+            cell: (tolerance * 8.0).max(1.0),
+            tolerance,
+            grid: HashMap::new(),
+        }
+    }
+
+    fn key(&self, p: (f32, f32)) -> (i32, i32) {
+        (
+            (p.0 / self.cell).floor() as i32,
+            (p.1 / self.cell).floor() as i32,
+        )
+    }
+
+    fn insert(&mut self, seg: Segment) {
+        let t = self.tolerance;
+        let lo = self.key((seg.0.0.min(seg.1.0) - t, seg.0.1.min(seg.1.1) - t));
+        let hi = self.key((seg.0.0.max(seg.1.0) + t, seg.0.1.max(seg.1.1) + t));
+        for i in lo.0..=hi.0 {
+            for j in lo.1..=hi.1 {
+                self.grid.entry((i, j)).or_default().push(seg);
+            }
+        }
+    }
+
+    fn near(&self, p: (f32, f32)) -> bool {
+        self.grid.get(&self.key(p)).is_some_and(|segs| {
+            segs.iter()
+                .any(|s| path::dist_to_segment(p, s.0, s.1) <= self.tolerance)
+        })
+    }
+
+    // A segment is covered when every sample along it lies within the
+    // tolerance of something already drawn
+    fn covers(&self, (a, b): Segment) -> bool {
+        let n = (path::dist(a, b) / self.tolerance.max(1e-3)).ceil() as usize;
+        (0..=n.max(1)).all(|i| self.near(path::lerp(a, b, i as f32 / n.max(1) as f32)))
+    }
+}
+
+// Remove pen-down segments that retrace geometry drawn earlier (within
+// `tolerance` mm, in either direction). Paths are split where duplicated
+// segments are removed; dots are dropped if they land on an earlier dot.
+pub fn dedupe(paths: &[Path], tolerance: f32) -> Vec<Path> {
+    dedupe_indexed(paths, tolerance)
+        .into_iter()
+        .map(|(_, p)| p)
+        .collect()
+}
+
+// Same as `dedupe`, but every remaining piece is tagged with the index of the
+// input path it came from
+pub fn dedupe_indexed(paths: &[Path], tolerance: f32) -> Vec<(usize, Path)> {
+    let mut drawn = Drawn::new(tolerance);
+    let mut out = Vec::new();
+
+    for (index, p) in paths.iter().enumerate() {
+        if p.len() == 1 {
+            let dot = (p[0], p[0]);
+            if !drawn.covers(dot) {
+                drawn.insert(dot);
+                out.push((index, p.clone()));
+            }
+            continue;
+        }
+
+        let mut current: Path = Vec::new();
+        for w in p.windows(2) {
+            let seg = (w[0], w[1]);
+            if drawn.covers(seg) {
+                if current.len() > 1 {
+                    out.push((index, std::mem::take(&mut current)));
+                }
+                current.clear();
+            } else {
+                if current.is_empty() {
+                    current.push(w[0]);
+                }
+                current.push(w[1]);
+                drawn.insert(seg);
+            }
+        }
+        if current.len() > 1 {
+            out.push((index, current));
+        }
+    }
+
+    out
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Report {
+    // Points where two pen-down segments cross or touch
+    pub crossings: Vec<(f32, f32)>,
+    // Stretches that are drawn more than once
+    pub overlaps: Vec<((f32, f32), (f32, f32))>,
+}
+
+impl Report {
+    // No stroke crosses or retraces another, e.g. a valid single-line drawing
+    pub fn is_simple(&self) -> bool {
+        self.crossings.is_empty() && self.overlaps.is_empty()
+    }
+
+    pub fn overlap_length(&self) -> f32 {
+        self.overlaps.iter().map(|(a, b)| path::dist(*a, *b)).sum()
+    }
+}
+
+fn cross((ax, ay): (f32, f32), (bx, by): (f32, f32)) -> f32 {
+    ax * by - ay * bx
+}
+
+fn sub(a: (f32, f32), b: (f32, f32)) -> (f32, f32) {
+    (a.0 - b.0, a.1 - b.1)
+}
+
+fn compare(s: Segment, t: Segment, tolerance: f32, report: &mut Report) {
+    let r = sub(s.1, s.0);
+    let q = sub(t.1, t.0);
+    let denom = cross(r, q);
+    let len_r = path::dist(s.0, s.1);
+    let len_q = path::dist(t.0, t.1);
+    if len_r <= f32::EPSILON || len_q <= f32::EPSILON {
+        return;
+    }
+
+    if denom.abs() > 1e-6 * len_r * len_q {
+        let d = sub(t.0, s.0);
+        let u = cross(d, q) / denom;
+        let v = cross(d, r) / denom;
+        let eps_u = tolerance / len_r;
+        let eps_v = tolerance / len_q;
+        let inside = |t: f32, eps: f32| t >= -eps && t <= 1.0 + eps;
+        let at_end = |t: f32, eps: f32| t <= eps || t >= 1.0 - eps;
+
+        // Consecutive segments of a stroke share a vertex, that is not a crossing
+        if inside(u, eps_u) && inside(v, eps_v) && !(at_end(u, eps_u) && at_end(v, eps_v)) {
+            report.crossings.push(path::lerp(s.0, s.1, u));
+        }
+    } else if path::dist_to_segment(t.0, s.0, s.1).min(path::dist_to_segment(t.1, s.0, s.1))
+        <= tolerance
+        || path::dist_to_segment(s.0, t.0, t.1) <= tolerance
+    {
+        // Parallel and close: project t onto s to find the shared stretch
+        let dir = (r.0 / len_r, r.1 / len_r);
+        let proj = |p: (f32, f32)| (p.0 - s.0.0) * dir.0 + (p.1 - s.0.1) * dir.1;
+        let (a, b) = (proj(t.0), proj(t.1));
+        let lo = a.min(b).max(0.0);
+        let hi = a.max(b).min(len_r);
+        let offset = cross(dir, sub(t.0, s.0)).abs();
+        if hi - lo > tolerance && offset <= tolerance {
+            report.overlaps.push((
+                path::lerp(s.0, s.1, lo / len_r),
+                path::lerp(s.0, s.1, hi / len_r),
+            ));
+        }
+    }
+}
+
+// Find all crossings and overlaps between the pen-down segments of a
+// simulated program
+pub fn report(segments: &[sim::Segment], tolerance: f32) -> Report {
+    let drawn: Vec<Segment> = segments
+        .iter()
+        .filter(|s| s.pen_down)
+        .map(|s| (s.from_xy(), s.to_xy()))
+        .collect();
+
+    let cell = Drawn::new(tolerance).cell;
+    let key = |p: (f32, f32)| ((p.0 / cell).floor() as i32, (p.1 / cell).floor() as i32);
+    let mut candidates: HashMap<(i32, i32), Vec<usize>> = HashMap::new();
+    let mut report = Report::default();
+
+    for (i, seg) in drawn.iter().enumerate() {
+        let t = tolerance;
+        let lo = key((seg.0.0.min(seg.1.0) - t, seg.0.1.min(seg.1.1) - t));
+        let hi = key((seg.0.0.max(seg.1.0) + t, seg.0.1.max(seg.1.1) + t));
+
+        let mut seen = Vec::new();
+        for x in lo.0..=hi.0 {
+            for y in lo.1..=hi.1 {
+                let cell = candidates.entry((x, y)).or_default();
+                for &j in cell.iter() {
+                    if !seen.contains(&j) {
+                        seen.push(j);
+                    }
+                }
+                cell.push(i);
+            }
+        }
+
+        for j in seen {
+            compare(drawn[j], *seg, tolerance, &mut report);
+        }
+    }
+
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Printer;
+    use crate::tests::test_config;
+
+    #[test]
+    fn removes_retraced_outline() {
+        let square = vec![
+            (0.0, 0.0),
+            (10.0, 0.0),
+            (10.0, 10.0),
+            (0.0, 10.0),
+            (0.0, 0.0),
+        ];
+        let reversed: Path = square.iter().rev().copied().collect();
+        let out = dedupe(&[square.clone(), reversed], 0.05);
+        assert_eq!(out, vec![square]);
+    }
+
+    #[test]
+    fn splits_partially_duplicated_paths() {
+        let a = vec![(0.0, 0.0), (10.0, 0.0)];
+        // Retraces a, then leaves it
+        let b = vec![(10.0, 0.01), (0.0, 0.01), (0.0, 10.0)];
+        let out = dedupe(&[a.clone(), b], 0.05);
+        assert_eq!(out, vec![a, vec![(0.0, 0.01), (0.0, 10.0)]]);
+    }
+
+    fn seg(a: (f32, f32), b: (f32, f32)) -> sim::Segment {
+        sim::Segment {
+            from: (a.0, a.1, 0.0),
+            to: (b.0, b.1, 0.0),
+            feed: 1000.0,
+            pen_down: true,
+            cornering: 0.0,
+        }
+    }
+
+    #[test]
+    fn reports_crossings_and_overlaps() {
+        let segments = vec![
+            seg((0.0, 0.0), (10.0, 0.0)),
+            seg((10.0, 0.0), (10.0, 10.0)),
+            seg((5.0, -5.0), (5.0, 5.0)),
+            seg((8.0, 0.0), (9.5, 0.0)),
+        ];
+        let report = report(&segments, 0.01);
+
+        assert_eq!(report.crossings.len(), 1);
+        let (x, y) = report.crossings[0];
+        assert!((x - 5.0).abs() < 1e-4 && y.abs() < 1e-4);
+        assert_eq!(report.overlaps, vec![((8.0, 0.0), (9.5, 0.0))]);
+        assert!((report.overlap_length() - 1.5).abs() < 1e-4);
+    }
+
+    #[test]
+    fn closed_polygon_is_simple() {
+        let square = [
+            (0.0, 0.0),
+            (10.0, 0.0),
+            (10.0, 10.0),
+            (0.0, 10.0),
+            (0.0, 0.0),
+        ];
+        let segments: Vec<sim::Segment> = square.windows(2).map(|w| seg(w[0], w[1])).collect();
+        assert!(report(&segments, 0.01).is_simple());
+
+        // Travel moves never count
+        let mut travel = seg((0.0, 5.0), (10.0, 5.0));
+        travel.pen_down = false;
+        let mut segments = segments;
+        segments.push(travel);
+        assert!(report(&segments, 0.01).is_simple());
+    }
+
+    #[test]
+    fn dots_and_distinct_lines_survive() {
+        let paths = vec![
+            vec![(5.0, 5.0)],
+            vec![(5.0, 5.01)],
+            vec![(0.0, 1.0), (10.0, 1.0)],
+            vec![(0.0, 1.5), (10.0, 1.5)],
+        ];
+        let out = dedupe(&paths, 0.1);
+        assert_eq!(out.len(), 3);
+        assert_eq!(out[0], vec![(5.0, 5.0)]);
+    }
+
+    #[test]
+    fn crossing_strokes() {
+        let mut printer = Printer::new(test_config());
+        printer.draw_polyline(&[(0.0, 0.0), (10.0, 10.0)]);
+        printer.draw_polyline(&[(0.0, 10.0), (10.0, 0.0)]);
+        printer.draw_point(20.0, 20.0);
+
+        let report = printer.intersections(0.05);
+        assert_eq!(report.crossings.len(), 1);
+        assert!(report.overlaps.is_empty());
+        assert!(!report.is_simple());
+    }
+}
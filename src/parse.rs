@@ -0,0 +1,323 @@
+use std::f32::consts::TAU;
+
+use crate::curve::{CircularArc, CubicBezier};
+use crate::{Code, Error, Point, Printer, diff};
+
+// Letters with their numbers, e.g. "G1X10 y5.5" gives G1, X10, Y5.5
+pub(crate) fn words(line: &str) -> Vec<(char, f32)> {
+    let mut words = Vec::new();
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        if !c.is_ascii_alphabetic() {
+            continue;
+        }
+        while chars.peek() == Some(&' ') {
+            chars.next();
+        }
+        let mut number = String::new();
+        while let Some(&d) = chars.peek() {
+            if d.is_ascii_digit() || d == '.' || d == '-' || d == '+' {
+                number.push(d);
+                chars.next();
+            } else {
+                break;
+            }
+        }
+        if let Ok(value) = number.parse() {
+            words.push((c.to_ascii_uppercase(), value));
+        }
+    }
+    words
+}
+
+// The code part of a line, without comments, line number or checksum
+pub(crate) fn strip(line: &str) -> String {
+    let line = line.split(';').next().unwrap_or("");
+    let line = line.split('*').next().unwrap_or("");
+    let mut out = String::new();
+    let mut depth = 0;
+    for c in line.chars() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth = i32::max(depth - 1, 0),
+            _ if depth == 0 => out.push(c),
+            _ => {}
+        }
+    }
+    let out = out.trim();
+    match out.strip_prefix(['N', 'n']) {
+        Some(rest) => rest
+            .trim_start_matches(|c: char| c.is_ascii_digit())
+            .to_string(),
+        None => out.to_string(),
+    }
+}
+
+// Reads G-code text, e.g. from another tool, into codes. Absolute moves,
+// curves and arcs, dwells, messages and comments become the codes this
+// crate writes itself, so the simulation and statistics understand them.
+// G0 stays G0 and G1 stays G1. Everything else, including all moves in
+// relative mode and moves with words this crate doesn't write, like E or
+// S, is kept as it is. Moves are written back at this crate's precision,
+// without the comments after them.
+pub fn codes(text: &str) -> Vec<Code> {
+    let mut codes = Vec::new();
+    let mut pos: Option<(f32, f32)> = None;
+    let mut feed: Option<f32> = None;
+    let mut relative = false;
+    let mut motion = 0;
+
+    for line in text.lines() {
+        let trimmed = line.trim();
+        if trimmed.is_empty() {
+            codes.push(Code::NOP);
+            continue;
+        }
+        if let Some(comment) = trimmed.strip_prefix(';') {
+            codes.push(Code::Comment(comment.trim_start().to_string()));
+            continue;
+        }
+        // Messages are text, not words
+        if let Some(message) = trimmed.strip_prefix("M117 ") {
+            codes.push(Code::Message(message.to_string()));
+            continue;
+        }
+        if let Some(model) = trimmed
+            .strip_prefix("M862.3 P \"")
+            .and_then(|m| m.split_once('"'))
+        {
+            codes.push(Code::Model(model.0.to_string()));
+            continue;
+        }
+
+        let words = words(&strip(line));
+        let get = |letter| words.iter().find(|(c, _)| *c == letter).map(|w| w.1);
+        let code = |letter| get(letter).map(|v| v.round() as i32);
+        let raw = Code::Raw(trimmed.to_string().into());
+
+        match code('G') {
+            Some(90) => relative = false,
+            Some(91) => relative = true,
+            Some(28) => pos = Some((0.0, 0.0)),
+            Some(92) => {
+                if let Some(p) = pos {
+                    pos = Some((get('X').unwrap_or(p.0), get('Y').unwrap_or(p.1)));
+                }
+            }
+            Some(4) => {
+                let seconds = get('S').or(get('P').map(|ms| ms / 1000.0));
+                codes.push(seconds.map(Code::Dwell).unwrap_or(raw));
+                continue;
+            }
+            Some(g @ (0 | 1 | 2 | 3 | 5)) => motion = g,
+            _ => {}
+        }
+        let moves = code('M').is_none()
+            && matches!(code('G'), None | Some(0 | 1 | 2 | 3 | 5))
+            && words.iter().any(|(c, _)| "XYZ".contains(*c));
+        feed = get('F').or(feed);
+        if !moves || relative {
+            if relative {
+                pos = None;
+            }
+            codes.push(raw);
+            continue;
+        }
+
+        let from = pos;
+        let to = (
+            get('X').or(from.map(|p| p.0)),
+            get('Y').or(from.map(|p| p.1)),
+        );
+        if let (Some(x), Some(y)) = to {
+            pos = Some((x, y));
+        }
+        // Words that would be lost writing the move back, e.g. extrusion
+        // or laser power
+        let known = match motion {
+            2 | 3 => "GXYZFIJ",
+            5 => "GXYZFIJPQ",
+            _ => "GXYZF",
+        };
+        if words.iter().any(|(c, _)| !known.contains(*c)) {
+            codes.push(raw);
+            continue;
+        }
+        let point = Point {
+            x: get('X'),
+            y: get('Y'),
+            z: get('Z'),
+        };
+        let parsed = match (motion, from, to, feed) {
+            (0, _, _, Some(f)) => Some(Code::Rapid(point, f)),
+            (1, _, _, Some(f)) => Some(Code::Move(point, f)),
+            (5, Some(p0), (Some(x), Some(y)), Some(f)) if get('Z').is_none() => {
+                let p1 = (x, y);
+                let c0 = (
+                    p0.0 + get('I').unwrap_or(0.0),
+                    p0.1 + get('J').unwrap_or(0.0),
+                );
+                let c1 = (
+                    p1.0 + get('P').unwrap_or(0.0),
+                    p1.1 + get('Q').unwrap_or(0.0),
+                );
+                Some(Code::Bezier(CubicBezier::new(p0, c0, c1, p1), f))
+            }
+            (2 | 3, Some(start), (Some(x), Some(y)), Some(f))
+                if get('Z').is_none() && get('R').is_none() =>
+            {
+                arc(start, (x, y), get('I'), get('J'), motion == 2).map(|a| Code::Arc(a, f))
+            }
+            _ => None,
+        };
+        codes.push(parsed.unwrap_or(raw));
+    }
+    codes
+}
+
+// The arc from `start` to `end` around the center at i/j from the start
+fn arc(
+    start: (f32, f32),
+    end: (f32, f32),
+    i: Option<f32>,
+    j: Option<f32>,
+    clockwise: bool,
+) -> Option<CircularArc> {
+    let center = (start.0 + i.unwrap_or(0.0), start.1 + j.unwrap_or(0.0));
+    let radius = (start.0 - center.0).hypot(start.1 - center.1);
+    if radius <= 0.0 {
+        return None;
+    }
+    let from = (start.1 - center.1).atan2(start.0 - center.0);
+    let to = (end.1 - center.1).atan2(end.0 - center.0);
+    // A full circle when it ends where it starts
+    let mut sweep = (to - from).rem_euclid(TAU);
+    if sweep < 1e-4 {
+        sweep = TAU;
+    }
+    if clockwise {
+        sweep -= TAU;
+        if sweep > -1e-4 {
+            sweep = -TAU;
+        }
+    }
+    Some(CircularArc::new(center, radius, from, sweep))
+}
+
+pub fn load(filename: &str) -> Result<Vec<Code>, Error> {
+    Ok(codes(&std::fs::read_to_string(filename)?))
+}
+
+// Draws what a program puts on paper into `printer`, where the strokes and
+// dots go through its transforms, filters and ordering like any drawing and
+// get written again with its config
+pub fn draw(printer: &mut Printer, text: &str) {
+    for stroke in diff::strokes(&diff::segments(text)) {
+        match stroke.points.as_slice() {
+            [(x, y)] => printer.draw_point(*x, *y),
+            points => printer.draw_polyline(points),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::test_config;
+
+    fn lines(codes: &[Code]) -> Vec<String> {
+        codes.iter().map(|c| c.to_string()).collect()
+    }
+
+    #[test]
+    fn round_trip() {
+        let text = "; made elsewhere\n\
+                    G21\n\
+                    M117 Hello there\n\
+                    G1 X10 Y5.5 F1200 ; start\n\
+                    G0 Z2\n\
+                    G4 P250\n\
+                    G5 I0 J10 P0 Q10 X20 Y5.5\n\
+                    G3 X30 Y5.5 I5 J0\n\
+                    \n\
+                    G91\n\
+                    G1 X5\n\
+                    M84";
+        let codes = codes(text);
+        assert_eq!(
+            lines(&codes),
+            [
+                "; made elsewhere",
+                "G21",
+                "M117 Hello there",
+                "G1 X10.0 Y5.5 F1200.0",
+                "G0 Z2.0 F1200.0",
+                "G4 P250",
+                "G5 I0.0 J10.0 P0.0 Q10.0 X20.0 Y5.5 F1200.0",
+                "G3 X30.0 Y5.5 I5.0 J0.0 F1200.0",
+                "",
+                "G91",
+                "G1 X5",
+                "M84",
+            ]
+        );
+        let Code::Arc(arc, _) = &codes[7] else {
+            panic!("Expected an arc!");
+        };
+        assert!((arc.sweep - std::f32::consts::PI).abs() < 1e-4);
+
+        // Written by this crate, read back the same
+        assert_eq!(
+            lines(&super::codes(&lines(&codes[..8]).join("\n"))),
+            lines(&codes[..8])
+        );
+    }
+
+    #[test]
+    fn unknown_words() {
+        let text = "G1 X0 Y0 F1200\n\
+                    G1 X10 S500\n\
+                    G1 X20 E0.5\n\
+                    G0 Y10 T1\n\
+                    G2 X10 Y0 I-5 J0 E1.5\n\
+                    G1 X0";
+        let codes = codes(text);
+        assert_eq!(
+            lines(&codes),
+            [
+                "G1 X0.0 Y0.0 F1200.0",
+                "G1 X10 S500",
+                "G1 X20 E0.5",
+                "G0 Y10 T1",
+                "G2 X10 Y0 I-5 J0 E1.5",
+                "G1 X0.0 F1200.0",
+            ]
+        );
+        assert!(matches!(codes[5], Code::Move(..)));
+    }
+
+    #[test]
+    fn clockwise_full_circle() {
+        let arc = arc((10.0, 0.0), (10.0, 0.0), Some(-10.0), None, true).unwrap();
+        assert_eq!(arc.center, (0.0, 0.0));
+        assert!((arc.sweep + TAU).abs() < 1e-4);
+    }
+
+    #[test]
+    fn import_gcode() {
+        let mut printer = Printer::new(test_config());
+        printer.draw_polyline(&[(10.0, 10.0), (40.0, 10.0), (40.0, 30.0)]);
+        printer.draw_point(60.0, 20.0);
+        let mut original = Vec::new();
+        printer.write_to(&mut original).unwrap();
+        let original = String::from_utf8(original).unwrap();
+
+        let mut imported = Printer::new(test_config());
+        draw(&mut imported, &original);
+        assert_eq!(imported.ops().len(), 2);
+        let mut again = Vec::new();
+        imported.write_to(&mut again).unwrap();
+        let again = String::from_utf8(again).unwrap();
+        assert!(diff::diff_text(&original, &again, 1e-3).same_drawing());
+    }
+}
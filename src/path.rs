@@ -0,0 +1,614 @@
+// Utilities for polylines given as lists of (x, y) vertices
+
+pub type Path = Vec<(f32, f32)>;
+
+pub fn dist(a: (f32, f32), b: (f32, f32)) -> f32 {
+    ((b.0 - a.0).powi(2) + (b.1 - a.1).powi(2)).sqrt()
+}
+
+pub fn lerp(a: (f32, f32), b: (f32, f32), t: f32) -> (f32, f32) {
+    (a.0 + (b.0 - a.0) * t, a.1 + (b.1 - a.1) * t)
+}
+
+pub fn length(path: &[(f32, f32)]) -> f32 {
+    path.windows(2).map(|w| dist(w[0], w[1])).sum()
+}
+
+// Point at arc-length `s` along the path, clamped to the endpoints
+pub fn point_at(path: &[(f32, f32)], s: f32) -> Option<(f32, f32)> {
+    let first = *path.first()?;
+    if s <= 0.0 {
+        return Some(first);
+    }
+
+    let mut travelled = 0.0;
+    for w in path.windows(2) {
+        let d = dist(w[0], w[1]);
+        if travelled + d >= s && d > 0.0 {
+            return Some(lerp(w[0], w[1], (s - travelled) / d));
+        }
+        travelled += d;
+    }
+
+    path.last().copied()
+}
+
+// Resample the path so consecutive vertices are evenly spaced along its arc
+// length. The spacing is shrunk slightly so that both endpoints are kept
+// exactly, i.e. the actual spacing is length / ceil(length / spacing).
+pub fn resample(path: &[(f32, f32)], spacing: f32) -> Path {
+    let total = length(path);
+    if path.len() < 2 || spacing <= 0.0 || total <= 0.0 {
+        return path.to_vec();
+    }
+
+    let n = (total / spacing).ceil().max(1.0) as usize;
+    let step = total / n as f32;
+
+    let mut out = Vec::with_capacity(n + 1);
+    out.push(path[0]);
+
+    // Walk the segments once instead of calling point_at for every sample
+    let mut segment = 0;
+    let mut seg_start = 0.0;
+    let mut seg_len = dist(path[0], path[1]);
+    for i in 1..n {
+        let s = step * i as f32;
+        while seg_start + seg_len < s && segment + 2 < path.len() {
+            seg_start += seg_len;
+            segment += 1;
+            seg_len = dist(path[segment], path[segment + 1]);
+        }
+
+        let t = if seg_len > 0.0 {
+            ((s - seg_start) / seg_len).clamp(0.0, 1.0)
+        } else {
+            0.0
+        };
+        out.push(lerp(path[segment], path[segment + 1], t));
+    }
+
+    out.push(path[path.len() - 1]);
+    out
+}
+
+pub fn is_closed(path: &[(f32, f32)]) -> bool {
+    path.len() > 2 && path.first() == path.last()
+}
+
+// Replaces every corner with an arc of `radius`, flattened to chords within
+// `tolerance` of it. Where the segments are too short for the full radius
+// the arc gets smaller, so neighboring arcs never overlap. Closed paths get
+// their closing corner rounded as well.
+pub fn fillet(path: &[(f32, f32)], radius: f32, tolerance: f32) -> Path {
+    let closed = is_closed(path);
+    // Vertices without the repeated one of a closed path
+    let pts = if closed {
+        &path[..path.len() - 1]
+    } else {
+        path
+    };
+    let n = pts.len();
+    if n < 3 || radius <= 0.0 {
+        return path.to_vec();
+    }
+
+    // The arc replacing vertex i, or just the vertex if it's straight
+    let corner = |i: usize| -> Path {
+        let (a, b, c) = (pts[(i + n - 1) % n], pts[i], pts[(i + 1) % n]);
+        let (d0, d1) = (dist(a, b), dist(b, c));
+        if d0 <= f32::EPSILON || d1 <= f32::EPSILON {
+            return vec![b];
+        }
+        let u0 = ((a.0 - b.0) / d0, (a.1 - b.1) / d0);
+        let u1 = ((c.0 - b.0) / d1, (c.1 - b.1) / d1);
+        // Angle between the two segments at b, pi when straight
+        let inner = (u0.0 * u1.0 + u0.1 * u1.1).clamp(-1.0, 1.0).acos();
+        let turn = std::f32::consts::PI - inner;
+        if turn < 1e-3 || inner < 1e-3 {
+            return vec![b];
+        }
+
+        let half = (inner / 2.0).tan();
+        let t = (radius / half).min(d0 / 2.0).min(d1 / 2.0);
+        let r = t * half;
+        let bisector = (u0.0 + u1.0, u0.1 + u1.1);
+        let len = (bisector.0 * bisector.0 + bisector.1 * bisector.1).sqrt();
+        let off = r / (inner / 2.0).sin();
+        let center = (b.0 + bisector.0 / len * off, b.1 + bisector.1 / len * off);
+
+        let p = (b.0 + u0.0 * t, b.1 + u0.1 * t);
+        let start = (p.1 - center.1).atan2(p.0 - center.0);
+        // Left turns go counterclockwise around the center
+        let left = (-u0.0) * u1.1 - (-u0.1) * u1.0 > 0.0;
+        let sweep = if left { turn } else { -turn };
+        let step = 2.0 * (1.0 - tolerance.min(r) / r).acos();
+        let steps = ((turn / step.max(1e-3)).ceil() as usize).max(1);
+        (0..=steps)
+            .map(|k| {
+                let angle = start + sweep * k as f32 / steps as f32;
+                (center.0 + r * angle.cos(), center.1 + r * angle.sin())
+            })
+            .collect()
+    };
+
+    let mut out = Vec::new();
+    if closed {
+        for i in 0..n {
+            out.extend(corner(i));
+        }
+        out.push(out[0]);
+    } else {
+        out.push(pts[0]);
+        for i in 1..n - 1 {
+            out.extend(corner(i));
+        }
+        out.push(pts[n - 1]);
+    }
+    out
+}
+
+// Chaikin corner cutting: every pass replaces each segment with points at 1/4
+// and 3/4 of its length. Open paths keep their endpoints, closed paths
+// (first == last vertex) stay closed.
+pub fn chaikin(path: &[(f32, f32)], iterations: usize) -> Path {
+    let mut current = path.to_vec();
+
+    for _ in 0..iterations {
+        if current.len() < 3 {
+            break;
+        }
+
+        let closed = is_closed(&current);
+        let mut next = Vec::with_capacity(current.len() * 2);
+        if !closed {
+            next.push(current[0]);
+        }
+        for w in current.windows(2) {
+            next.push(lerp(w[0], w[1], 0.25));
+            next.push(lerp(w[0], w[1], 0.75));
+        }
+        if closed {
+            next.push(next[0]);
+        } else {
+            next.push(current[current.len() - 1]);
+        }
+
+        current = next;
+    }
+
+    current
+}
+
+pub fn dist_to_segment(p: (f32, f32), a: (f32, f32), b: (f32, f32)) -> f32 {
+    let len2 = (b.0 - a.0).powi(2) + (b.1 - a.1).powi(2);
+    if len2 <= 0.0 {
+        return dist(p, a);
+    }
+    let t = (((p.0 - a.0) * (b.0 - a.0) + (p.1 - a.1) * (b.1 - a.1)) / len2).clamp(0.0, 1.0);
+    dist(p, lerp(a, b, t))
+}
+
+// Ramer-Douglas-Peucker: drop vertices that deviate less than `tolerance`
+// from the simplified path
+pub fn simplify(path: &[(f32, f32)], tolerance: f32) -> Path {
+    if path.len() < 3 {
+        return path.to_vec();
+    }
+
+    let mut keep = vec![false; path.len()];
+    keep[0] = true;
+    keep[path.len() - 1] = true;
+
+    let mut stack = vec![(0, path.len() - 1)];
+    while let Some((first, last)) = stack.pop() {
+        let mut worst = (0.0, 0);
+        for i in first + 1..last {
+            let d = dist_to_segment(path[i], path[first], path[last]);
+            if d > worst.0 {
+                worst = (d, i);
+            }
+        }
+        if worst.0 > tolerance {
+            keep[worst.1] = true;
+            stack.push((first, worst.1));
+            stack.push((worst.1, last));
+        }
+    }
+
+    path.iter()
+        .zip(keep)
+        .filter(|(_, k)| *k)
+        .map(|(p, _)| *p)
+        .collect()
+}
+
+// Liang-Barsky clip of the segment a -> b, returning the visible part
+fn clip_segment(
+    a: (f32, f32),
+    b: (f32, f32),
+    min: (f32, f32),
+    max: (f32, f32),
+) -> Option<((f32, f32), (f32, f32))> {
+    let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+    let mut t0: f32 = 0.0;
+    let mut t1: f32 = 1.0;
+
+    for (p, q) in [
+        (-dx, a.0 - min.0),
+        (dx, max.0 - a.0),
+        (-dy, a.1 - min.1),
+        (dy, max.1 - a.1),
+    ] {
+        if p == 0.0 {
+            if q < 0.0 {
+                return None;
+            }
+        } else {
+            let r = q / p;
+            if p < 0.0 {
+                t0 = t0.max(r);
+            } else {
+                t1 = t1.min(r);
+            }
+        }
+    }
+
+    if t0 > t1 {
+        None
+    } else {
+        Some((lerp(a, b, t0), lerp(a, b, t1)))
+    }
+}
+
+pub fn inside_rect(p: (f32, f32), min: (f32, f32), max: (f32, f32)) -> bool {
+    p.0 >= min.0 && p.0 <= max.0 && p.1 >= min.1 && p.1 <= max.1
+}
+
+// Clip a path to the rectangle min..max. Every time the path leaves the
+// rectangle it is split, so the result can contain several pieces.
+pub fn clip_rect(path: &[(f32, f32)], min: (f32, f32), max: (f32, f32)) -> Vec<Path> {
+    if path.len() == 1 {
+        return if inside_rect(path[0], min, max) {
+            vec![path.to_vec()]
+        } else {
+            Vec::new()
+        };
+    }
+
+    let mut pieces: Vec<Path> = Vec::new();
+    let mut current: Path = Vec::new();
+    for w in path.windows(2) {
+        match clip_segment(w[0], w[1], min, max) {
+            Some((a, b)) => {
+                if current.last() != Some(&a) {
+                    if current.len() > 1 {
+                        pieces.push(std::mem::take(&mut current));
+                    }
+                    current = vec![a];
+                }
+                current.push(b);
+            }
+            None => {
+                if current.len() > 1 {
+                    pieces.push(std::mem::take(&mut current));
+                }
+                current.clear();
+            }
+        }
+    }
+    if current.len() > 1 {
+        pieces.push(current);
+    }
+
+    pieces
+}
+
+// The opposite of clip_rect: the pieces of the path outside min..max
+pub fn cut_rect(path: &[(f32, f32)], min: (f32, f32), max: (f32, f32)) -> Vec<Path> {
+    if path.len() == 1 {
+        return if inside_rect(path[0], min, max) {
+            Vec::new()
+        } else {
+            vec![path.to_vec()]
+        };
+    }
+
+    let mut pieces: Vec<Path> = Vec::new();
+    let mut current: Path = Vec::new();
+    for w in path.windows(2) {
+        let (a, b) = (w[0], w[1]);
+        // Up to two parts of the segment are outside, before and after the
+        // part inside
+        let outside = match clip_segment(a, b, min, max) {
+            Some((p, q)) => [(a, p), (q, b)],
+            None => [(a, b), (b, b)],
+        };
+        for (p, q) in outside {
+            if p == q {
+                continue;
+            }
+            if current.last() != Some(&p) {
+                if current.len() > 1 {
+                    pieces.push(std::mem::take(&mut current));
+                }
+                current = vec![p];
+            }
+            current.push(q);
+        }
+    }
+    if current.len() > 1 {
+        pieces.push(current);
+    }
+
+    pieces
+}
+
+// Signed area of a polygon, closing edge implied, positive when the vertices
+// run counterclockwise
+pub fn area(polygon: &[(f32, f32)]) -> f32 {
+    let n = polygon.len();
+    (0..n)
+        .map(|i| {
+            let (a, b) = (polygon[i], polygon[(i + 1) % n]);
+            a.0 * b.1 - b.0 * a.1
+        })
+        .sum::<f32>()
+        / 2.0
+}
+
+// The polygon shrunk by `distance`, as a closed path. Edges that would turn
+// around are dropped, so corners merge as the polygon gets thinner, and
+// nothing is left once it's gone. Meant for convex and gently concave
+// shapes, deep notches can make the result cross itself.
+pub fn inset(polygon: &[(f32, f32)], distance: f32) -> Path {
+    let mut points = polygon.to_vec();
+    if is_closed(&points) {
+        points.pop();
+    }
+    points.dedup();
+    if area(&points) < 0.0 {
+        points.reverse();
+    }
+    // Every edge as a point and direction, moved inwards (to the left)
+    let mut lines: Vec<((f32, f32), (f32, f32))> = (0..points.len())
+        .filter_map(|i| {
+            let (a, b) = (points[i], points[(i + 1) % points.len()]);
+            let len = dist(a, b);
+            if len <= 0.0 {
+                return None;
+            }
+            let dir = ((b.0 - a.0) / len, (b.1 - a.1) / len);
+            Some(((a.0 - dir.1 * distance, a.1 + dir.0 * distance), dir))
+        })
+        .collect();
+
+    loop {
+        let n = lines.len();
+        if n < 3 {
+            return Vec::new();
+        }
+        // Corners where neighboring lines meet
+        let corners: Path = (0..n)
+            .map(|i| {
+                let (p, d) = lines[(i + n - 1) % n];
+                let (q, e) = lines[i];
+                let denom = d.0 * e.1 - d.1 * e.0;
+                if denom.abs() <= 1e-6 {
+                    return q;
+                }
+                let t = ((q.0 - p.0) * e.1 - (q.1 - p.1) * e.0) / denom;
+                (p.0 + t * d.0, p.1 + t * d.1)
+            })
+            .collect();
+        let flipped: Vec<usize> = (0..n)
+            .filter(|&i| {
+                let (a, b) = (corners[i], corners[(i + 1) % n]);
+                let d = lines[i].1;
+                (b.0 - a.0) * d.0 + (b.1 - a.1) * d.1 < 0.0
+            })
+            .collect();
+        if flipped.is_empty() {
+            if area(&corners) <= 0.0 {
+                return Vec::new();
+            }
+            let mut ring = corners;
+            ring.push(ring[0]);
+            return ring;
+        }
+        if flipped.len() == n {
+            return Vec::new();
+        }
+        let mut i = 0;
+        lines.retain(|_| {
+            i += 1;
+            !flipped.contains(&(i - 1))
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_within(a: f32, b: f32, n: f32) {
+        if (a - b).abs() >= n {
+            panic!("The difference between {} and {} is more than {}!", a, b, n);
+        }
+    }
+
+    #[test]
+    fn path_length() {
+        assert_within(length(&[(0.0, 0.0), (3.0, 4.0), (3.0, 10.0)]), 11.0, 1e-5);
+        assert_within(length(&[(1.0, 1.0)]), 0.0, 1e-5);
+    }
+
+    #[test]
+    fn resample_spacing() {
+        let path = vec![(0.0, 0.0), (10.0, 0.0), (10.0, 10.0)];
+        let out = resample(&path, 3.0);
+
+        // 20mm at <= 3mm spacing gives 7 intervals
+        assert_eq!(out.len(), 8);
+        assert_eq!(out[0], (0.0, 0.0));
+        assert_eq!(out[7], (10.0, 10.0));
+        // Spacing is along the arc, so only the chord across the corner is shorter
+        assert_within(dist(out[1], out[2]), 20.0 / 7.0, 1e-4);
+        assert_within(dist(out[5], out[6]), 20.0 / 7.0, 1e-4);
+        assert_eq!(out[2].1, 0.0);
+        assert_within(out[4].1, 20.0 * 4.0 / 7.0 - 10.0, 1e-4);
+    }
+
+    #[test]
+    fn resample_degenerate() {
+        assert_eq!(resample(&[(1.0, 2.0)], 1.0), vec![(1.0, 2.0)]);
+        assert_eq!(
+            resample(&[(1.0, 2.0), (1.0, 2.0)], 1.0),
+            vec![(1.0, 2.0), (1.0, 2.0)]
+        );
+    }
+
+    #[test]
+    fn chaikin_smoothing() {
+        let open = vec![(0.0, 0.0), (4.0, 0.0), (4.0, 4.0)];
+        let out = chaikin(&open, 1);
+        assert_eq!(
+            out,
+            vec![
+                (0.0, 0.0),
+                (1.0, 0.0),
+                (3.0, 0.0),
+                (4.0, 1.0),
+                (4.0, 3.0),
+                (4.0, 4.0)
+            ]
+        );
+        assert!(length(&chaikin(&open, 3)) < length(&open));
+
+        let square = vec![(0.0, 0.0), (4.0, 0.0), (4.0, 4.0), (0.0, 4.0), (0.0, 0.0)];
+        let out = chaikin(&square, 2);
+        assert!(is_closed(&out));
+        assert_eq!(out.len(), 17);
+        assert!(!out.contains(&(0.0, 0.0)));
+
+        assert_eq!(
+            chaikin(&[(0.0, 0.0), (1.0, 1.0)], 4),
+            vec![(0.0, 0.0), (1.0, 1.0)]
+        );
+    }
+
+    #[test]
+    fn simplify_straight_runs() {
+        let path = vec![(0.0, 0.0), (1.0, 0.01), (2.0, 0.0), (3.0, 5.0), (4.0, 10.0)];
+        assert_eq!(
+            simplify(&path, 0.1),
+            vec![(0.0, 0.0), (2.0, 0.0), (4.0, 10.0)]
+        );
+        // Collinear vertices are always dropped
+        assert_eq!(simplify(&path, 0.001).len(), 4);
+    }
+
+    #[test]
+    fn clip_to_rect() {
+        let (min, max) = ((0.0, 0.0), (10.0, 10.0));
+        let path = vec![
+            (-5.0, 5.0),
+            (5.0, 5.0),
+            (5.0, 15.0),
+            (8.0, 15.0),
+            (8.0, 5.0),
+        ];
+        let pieces = clip_rect(&path, min, max);
+        assert_eq!(
+            pieces,
+            vec![
+                vec![(0.0, 5.0), (5.0, 5.0), (5.0, 10.0)],
+                vec![(8.0, 10.0), (8.0, 5.0)]
+            ]
+        );
+
+        assert!(clip_rect(&[(20.0, 20.0)], min, max).is_empty());
+        assert_eq!(clip_rect(&[(2.0, 2.0)], min, max), vec![vec![(2.0, 2.0)]]);
+    }
+
+    #[test]
+    fn cut_out_rect() {
+        let (min, max) = ((0.0, 0.0), (10.0, 10.0));
+        // Straight through, and in and out the same side
+        let pieces = cut_rect(&[(-5.0, 5.0), (15.0, 5.0)], min, max);
+        assert_eq!(
+            pieces,
+            vec![
+                vec![(-5.0, 5.0), (0.0, 5.0)],
+                vec![(10.0, 5.0), (15.0, 5.0)]
+            ]
+        );
+        let pieces = cut_rect(
+            &[(5.0, 15.0), (5.0, 5.0), (8.0, 5.0), (8.0, 15.0)],
+            min,
+            max,
+        );
+        assert_eq!(
+            pieces,
+            vec![
+                vec![(5.0, 15.0), (5.0, 10.0)],
+                vec![(8.0, 10.0), (8.0, 15.0)]
+            ]
+        );
+
+        assert!(cut_rect(&[(2.0, 2.0)], min, max).is_empty());
+        assert_eq!(cut_rect(&[(20.0, 2.0)], min, max), vec![vec![(20.0, 2.0)]]);
+    }
+
+    #[test]
+    fn rounded_corners() {
+        // Right angle corner rounded with a 2mm arc
+        let path = fillet(&[(0.0, 0.0), (10.0, 0.0), (10.0, 10.0)], 2.0, 0.01);
+        assert_eq!(path[0], (0.0, 0.0));
+        assert_eq!(*path.last().unwrap(), (10.0, 10.0));
+        assert_within(path[1].0, 8.0, 1e-4);
+        let center = (8.0, 2.0);
+        for &p in &path[1..path.len() - 1] {
+            assert_within(dist(p, center), 2.0, 1e-3);
+        }
+
+        // Short segments shrink the arc, closed paths stay closed
+        let square = [(0.0, 0.0), (2.0, 0.0), (2.0, 2.0), (0.0, 2.0), (0.0, 0.0)];
+        let path = fillet(&square, 5.0, 0.01);
+        assert!(is_closed(&path));
+        assert!(path.iter().all(|&p| dist(p, (1.0, 1.0)) <= 1.0 + 1e-3));
+
+        // Straight lines are left alone
+        let line = [(0.0, 0.0), (1.0, 0.0), (2.0, 0.0)];
+        assert_eq!(fillet(&line, 1.0, 0.01), line);
+    }
+
+    #[test]
+    fn inset_polygons() {
+        // Clockwise, but shrunk all the same
+        let rect = [(0.0, 0.0), (0.0, 4.0), (10.0, 4.0), (10.0, 0.0)];
+        assert_within(area(&rect), -40.0, 1e-4);
+        let ring = inset(&rect, 1.0);
+        assert!(is_closed(&ring));
+        assert_within(area(&ring[1..]), 16.0, 1e-3);
+        assert!(
+            ring.iter()
+                .all(|p| (1.0..=9.0).contains(&p.0) && (1.0..=3.0).contains(&p.1))
+        );
+        assert!(inset(&rect, 2.5).is_empty());
+
+        // The short edge of the triangle-ish shape drops out on the way
+        let shape = [(0.0, 0.0), (10.0, 0.0), (5.2, 8.0), (4.8, 8.0)];
+        let ring = inset(&shape, 1.5);
+        assert_eq!(ring.len(), 4);
+        assert!(area(&ring[1..]) > 0.0);
+    }
+
+    #[test]
+    fn point_along_path() {
+        let path = [(0.0, 0.0), (10.0, 0.0), (10.0, 10.0)];
+        assert_eq!(point_at(&path, 15.0), Some((10.0, 5.0)));
+        assert_eq!(point_at(&path, 100.0), Some((10.0, 10.0)));
+        assert_eq!(point_at(&[], 1.0), None);
+    }
+}
@@ -0,0 +1,200 @@
+// Minimal PNG encoder for 8-bit RGB images, no dependencies. The image data
+// is deflated with the fixed Huffman code and run-length matches only, which
+// is plenty for line art on a flat background.
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xFFFF_FFFFu32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for chunk in data.chunks(5552) {
+        for &byte in chunk {
+            a += byte as u32;
+            b += a;
+        }
+        a %= 65521;
+        b %= 65521;
+    }
+    (b << 16) | a
+}
+
+struct BitWriter {
+    out: Vec<u8>,
+    bits: u32,
+    count: u32,
+}
+
+impl BitWriter {
+    fn new() -> Self {
+        BitWriter {
+            out: Vec::new(),
+            bits: 0,
+            count: 0,
+        }
+    }
+
+    // Writes the lowest `n` bits of `value`, least significant bit first
+    fn write(&mut self, value: u32, n: u32) {
+        self.bits |= value << self.count;
+        self.count += n;
+        while self.count >= 8 {
+            self.out.push(self.bits as u8);
+            self.bits >>= 8;
+            self.count -= 8;
+        }
+    }
+
+    // Huffman codes are packed most significant bit first
+    fn write_code(&mut self, code: u32, n: u32) {
+        let reversed = code.reverse_bits() >> (32 - n);
+        self.write(reversed, n);
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.count > 0 {
+            self.out.push(self.bits as u8);
+        }
+        self.out
+    }
+}
+
+const LENGTH_BASE: [u32; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u32; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+
+fn write_symbol(w: &mut BitWriter, symbol: u32) {
+    match symbol {
+        0..=143 => w.write_code(0x30 + symbol, 8),
+        144..=255 => w.write_code(0x190 + symbol - 144, 9),
+        256..=279 => w.write_code(symbol - 256, 7),
+        _ => w.write_code(0xC0 + symbol - 280, 8),
+    }
+}
+
+fn write_match(w: &mut BitWriter, length: u32, distance: u32) {
+    let i = LENGTH_BASE.iter().rposition(|&b| b <= length).unwrap();
+    write_symbol(w, 257 + i as u32);
+    w.write(length - LENGTH_BASE[i], LENGTH_EXTRA[i]);
+
+    // Only distances 1..=4 are used, which have no extra bits
+    w.write_code(distance - 1, 5);
+}
+
+// zlib stream using one fixed Huffman block. Repeats of the previous `stride`
+// bytes are encoded as matches.
+fn deflate(data: &[u8], stride: usize) -> Vec<u8> {
+    let mut w = BitWriter::new();
+    // CMF/FLG: deflate with a 32K window, no preset dictionary
+    w.write(0x78, 8);
+    w.write(0x01, 8);
+    // Final block, fixed Huffman codes
+    w.write(1, 1);
+    w.write(1, 2);
+
+    let mut i = 0;
+    while i < data.len() {
+        let mut length = 0;
+        if i >= stride {
+            while i + length < data.len()
+                && length < 258
+                && data[i + length] == data[i + length - stride]
+            {
+                length += 1;
+            }
+        }
+
+        if length >= 3 {
+            write_match(&mut w, length as u32, stride as u32);
+            i += length;
+        } else {
+            write_symbol(&mut w, data[i] as u32);
+            i += 1;
+        }
+    }
+    write_symbol(&mut w, 256);
+
+    let mut out = w.finish();
+    out.extend_from_slice(&adler32(data).to_be_bytes());
+    out
+}
+
+fn chunk(out: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    out.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    let start = out.len();
+    out.extend_from_slice(kind);
+    out.extend_from_slice(data);
+    let crc = crc32(&out[start..]);
+    out.extend_from_slice(&crc.to_be_bytes());
+}
+
+// Encodes `rgb` (3 bytes per pixel, rows top to bottom) as a PNG file
+pub fn encode_rgb(width: u32, height: u32, rgb: &[u8]) -> Vec<u8> {
+    let mut out = vec![0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A];
+
+    let mut header = Vec::with_capacity(13);
+    header.extend_from_slice(&width.to_be_bytes());
+    header.extend_from_slice(&height.to_be_bytes());
+    // 8 bit depth, RGB, deflate, adaptive filtering, no interlace
+    header.extend_from_slice(&[8, 2, 0, 0, 0]);
+    chunk(&mut out, b"IHDR", &header);
+
+    // Every scanline starts with its filter type, always 0 (None) here
+    let row = width as usize * 3;
+    let mut raw = Vec::with_capacity((row + 1) * height as usize);
+    for line in rgb.chunks(row).take(height as usize) {
+        raw.push(0);
+        raw.extend_from_slice(line);
+    }
+    chunk(&mut out, b"IDAT", &deflate(&raw, 3));
+    chunk(&mut out, b"IEND", &[]);
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checksums() {
+        assert_eq!(crc32(b"IEND"), 0xAE42_6082);
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+        assert_eq!(adler32(b"Wikipedia"), 0x11E6_0398);
+    }
+
+    #[test]
+    fn deflate_literals() {
+        // Known output of zlib for "a" with fixed Huffman codes
+        assert_eq!(
+            deflate(b"a", 3),
+            vec![0x78, 0x01, 0x4B, 0x04, 0x00, 0x00, 0x62, 0x00, 0x62]
+        );
+    }
+
+    #[test]
+    fn png_structure() {
+        let white = vec![255u8; 16 * 16 * 3];
+        let png = encode_rgb(16, 16, &white);
+        assert_eq!(&png[..8], &[0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A]);
+        assert_eq!(&png[12..16], b"IHDR");
+        assert_eq!(&png[png.len() - 8..png.len() - 4], b"IEND");
+        // Runs of white compress to a handful of bytes
+        assert!(png.len() < 200);
+    }
+}
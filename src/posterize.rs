@@ -0,0 +1,156 @@
+use crate::Printer;
+use crate::path::Path;
+use crate::raster::Bitmap;
+
+// Painterly multi-pass plots: the image is quantized into `levels` tones and
+// every tone but the lightest is hatched on a layer of its own, "tone 1" for
+// the lightest up to "tone N-1" for the darkest. Give the layers darker pens
+// with Printer::set_layer_pen, or let darker tones hatch denser, or both.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Posterize {
+    pub levels: usize,
+    // Distance between hatch lines of the darkest tone, in mm
+    pub spacing: f32,
+    // Hatch angle of the first tone in degrees, later tones turn further so
+    // overlapping layers don't line up
+    pub angle: f32,
+    // Lighter tones get proportionally wider spacing when set, otherwise all
+    // tones use `spacing` and rely on their pens
+    pub denser: bool,
+}
+
+impl Posterize {
+    pub fn new(levels: usize) -> Self {
+        Posterize {
+            levels: levels.max(2),
+            spacing: 0.8,
+            angle: 45.0,
+            denser: true,
+        }
+    }
+
+    // Tone of a brightness 0..=1, 0 for the lightest
+    pub fn level(&self, brightness: f32) -> usize {
+        let darkness = (1.0 - brightness).clamp(0.0, 1.0);
+        ((darkness * self.levels as f32) as usize).min(self.levels - 1)
+    }
+
+    pub fn layer_name(level: usize) -> String {
+        format!("tone {}", level)
+    }
+
+    // Hatch lines for each tone from 1 up, with the bitmap stretched over
+    // min..max and its top row at max.1
+    pub fn hatching(&self, bitmap: &Bitmap, min: (f32, f32), max: (f32, f32)) -> Vec<Vec<Path>> {
+        let (w, h) = (bitmap.width as f32, bitmap.height as f32);
+        let (sx, sy) = ((max.0 - min.0) / w, (max.1 - min.1) / h);
+        let tone = |(x, y): (f32, f32)| {
+            let px = ((x - min.0) / sx).floor() as i64;
+            let py = ((max.1 - y) / sy).floor() as i64;
+            bitmap
+                .get(px, py)
+                .map(|_| self.level(bitmap.brightness(px, py)))
+        };
+        let center = ((min.0 + max.0) / 2.0, (min.1 + max.1) / 2.0);
+        let reach = ((max.0 - min.0).powi(2) + (max.1 - min.1).powi(2)).sqrt() / 2.0;
+        // Half a pixel, so runs end within a pixel of the tone's edge
+        let step = sx.min(sy).max(1e-3) / 2.0;
+
+        (1..self.levels)
+            .map(|level| {
+                let spacing = if self.denser {
+                    self.spacing * (self.levels - 1) as f32 / level as f32
+                } else {
+                    self.spacing
+                };
+                let angle = (self.angle + level as f32 * 180.0 / self.levels as f32).to_radians();
+                let (dir, normal) = ((angle.cos(), angle.sin()), (-angle.sin(), angle.cos()));
+                let at = |t: f32, o: f32| {
+                    (
+                        center.0 + t * dir.0 + o * normal.0,
+                        center.1 + t * dir.1 + o * normal.1,
+                    )
+                };
+
+                let mut lines = Vec::new();
+                let count = (reach / spacing).ceil() as i64;
+                for (row, i) in (-count..=count).enumerate() {
+                    let o = i as f32 * spacing;
+                    let mut runs: Vec<Path> = Vec::new();
+                    let mut start: Option<f32> = None;
+                    let mut t = -reach;
+                    while t <= reach + step {
+                        let inside = tone(at(t, o)) == Some(level);
+                        match (inside, start) {
+                            (true, None) => start = Some(t),
+                            (false, Some(s)) => {
+                                runs.push(vec![at(s, o), at(t - step, o)]);
+                                start = None;
+                            }
+                            _ => {}
+                        }
+                        t += step;
+                    }
+                    // Every other line runs backwards to save travel
+                    if row % 2 == 1 {
+                        runs.reverse();
+                        runs.iter_mut().for_each(|r| r.reverse());
+                    }
+                    lines.extend(runs.into_iter().filter(|r| r[0] != r[1]));
+                }
+                lines
+            })
+            .collect()
+    }
+
+    // Draws every tone on its layer, with the bitmap over min..max in drawing
+    // coordinates. Drawing carries on in the layer it was in before.
+    pub fn draw(&self, printer: &mut Printer, bitmap: &Bitmap, min: (f32, f32), max: (f32, f32)) {
+        let layer = printer.layer;
+        for (i, lines) in self.hatching(bitmap, min, max).iter().enumerate() {
+            printer.set_layer(&Self::layer_name(i + 1));
+            for line in lines {
+                printer.draw_polyline(line);
+            }
+        }
+        printer.layer = layer;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::path;
+
+    #[test]
+    fn tones_hatch_their_own_bands() {
+        // White, gray and black bands 10 pixels wide, 1mm per pixel
+        let mut bitmap = Bitmap::new(30, 10, [255, 255, 255]);
+        for y in 0..10 {
+            for x in 10..20 {
+                bitmap.set(x, y, [128, 128, 128]);
+            }
+            for x in 20..30 {
+                bitmap.set(x, y, [0, 0, 0]);
+            }
+        }
+        let poster = Posterize::new(3);
+        assert_eq!([1.0, 0.5, 0.0].map(|b| poster.level(b)), [0, 1, 2]);
+
+        let tones = poster.hatching(&bitmap, (0.0, 0.0), (30.0, 10.0));
+        assert_eq!(tones.len(), 2);
+        let within = |lines: &[Path], x0: f32, x1: f32| {
+            lines
+                .iter()
+                .flatten()
+                .all(|p| p.0 >= x0 - 0.5 && p.0 <= x1 + 0.5)
+        };
+        assert!(within(&tones[0], 10.0, 20.0));
+        assert!(within(&tones[1], 20.0, 30.0));
+
+        // The darker band is covered with about twice as much ink
+        let length = |lines: &[Path]| lines.iter().map(|l| path::length(l)).sum::<f32>();
+        let ratio = length(&tones[1]) / length(&tones[0]);
+        assert!((ratio - 2.0).abs() < 0.4, "{}", ratio);
+    }
+}
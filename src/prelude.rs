@@ -0,0 +1,25 @@
+// The types most drawings need, for `use artful_gcode::prelude::*`
+pub use crate::compose::Layer;
+pub use crate::curve::{CircularArc, CubicBezier};
+pub use crate::dry_run::DryRun;
+pub use crate::feed::Feed;
+pub use crate::hatch::FillStyle;
+pub use crate::path::Path;
+pub use crate::svg::Svg;
+pub use crate::turtle::Turtle;
+pub use crate::{
+    AffineTransform, Code, Dialect, Overrides, Pen, Point, Printer, PrinterConfig, xy, z,
+};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn moves() {
+        assert_eq!(xy!(1.0, 2.5, 1000.0).to_string(), "G1 X1.0 Y2.5 F1000.0");
+        assert_eq!(z!(4.0, 500.0).to_string(), "G1 Z4.0 F500.0");
+        let code = Code::Move(Point::xy(3.0, 4.0), 800.0);
+        assert_eq!(code.to_string(), "G1 X3.0 Y4.0 F800.0");
+    }
+}
@@ -0,0 +1,417 @@
+use std::fmt::Write;
+
+use crate::path::Path;
+use crate::sim::{self, Segment};
+
+// Layer colors, cycled when there are more layers than colors
+const COLORS: [&str; 8] = [
+    "#1f3a93", "#c0392b", "#27ae60", "#8e44ad", "#d35400", "#16a085", "#2c3e50", "#f39c12",
+];
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+// 0 for travel, 1 for drawing and 2 for a dot
+fn kind(segments: &[(usize, Segment)], i: usize) -> u8 {
+    let s = &segments[i].1;
+    if s.pen_down {
+        1
+    } else if sim::is_dot(s, segments.get(i + 1).map(|(_, n)| n)) {
+        2
+    } else {
+        0
+    }
+}
+
+// Standalone HTML page with a small canvas viewer: zoom with the mouse wheel,
+// pan by dragging, toggle layers and scrub through the plot in time.
+// `segments` are tagged with their layer index and `size` is the bed size in mm.
+pub fn html(segments: &[(usize, Segment)], layers: &[String], size: (f32, f32)) -> String {
+    let mut data = String::new();
+    let mut time = 0.0;
+    for (i, (layer, s)) in segments.iter().enumerate() {
+        time += s.time();
+        if i > 0 {
+            data.push(',');
+        }
+        let _ = write!(
+            data,
+            "[{:.3},{:.3},{:.3},{:.3},{},{},{:.3}]",
+            s.from.0,
+            s.from.1,
+            s.to.0,
+            s.to.1,
+            kind(segments, i),
+            layer,
+            time
+        );
+    }
+
+    let mut toggles = String::new();
+    for (i, name) in layers.iter().enumerate() {
+        let _ = write!(
+            toggles,
+            "<label style=\"color:{}\"><input type=\"checkbox\" data-layer=\"{}\" checked> {}</label> ",
+            COLORS[i % COLORS.len()],
+            i,
+            escape(name)
+        );
+    }
+
+    let colors: Vec<String> = COLORS.iter().map(|c| format!("\"{}\"", c)).collect();
+
+    format!(
+        r##"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>artful_gcode preview</title>
+<style>
+body {{ margin: 0; font-family: sans-serif; background: #eee; }}
+#controls {{ padding: 8px; background: #fff; border-bottom: 1px solid #ccc; }}
+#time {{ width: 60%; vertical-align: middle; }}
+canvas {{ display: block; cursor: grab; }}
+</style>
+</head>
+<body>
+<div id="controls">
+{toggles}
+<label><input type="checkbox" id="travel" checked> travel</label>
+<input type="range" id="time" min="0" max="1000" value="1000">
+<span id="clock"></span>
+<button id="play">play</button>
+</div>
+<canvas id="view"></canvas>
+<script>
+const SEGMENTS = [{data}];
+const COLORS = [{colors}];
+const BED = [{w:.3}, {h:.3}];
+const TOTAL = {time:.3};
+
+const canvas = document.getElementById("view");
+const ctx = canvas.getContext("2d");
+const slider = document.getElementById("time");
+const clock = document.getElementById("clock");
+let view = {{ scale: 1, x: 0, y: 0 }};
+let hidden = new Set();
+let playing = null;
+
+function fit() {{
+  canvas.width = window.innerWidth;
+  canvas.height = window.innerHeight - document.getElementById("controls").offsetHeight;
+  view.scale = 0.9 * Math.min(canvas.width / BED[0], canvas.height / BED[1]);
+  view.x = (canvas.width - BED[0] * view.scale) / 2;
+  view.y = (canvas.height + BED[1] * view.scale) / 2;
+}}
+
+function fmt(t) {{
+  t = Math.round(t);
+  const pad = (n) => String(n).padStart(2, "0");
+  return pad(Math.floor(t / 3600)) + ":" + pad(Math.floor(t / 60) % 60) + ":" + pad(t % 60);
+}}
+
+function draw() {{
+  const until = TOTAL * slider.value / 1000;
+  const showTravel = document.getElementById("travel").checked;
+  const px = (x) => view.x + x * view.scale;
+  const py = (y) => view.y - y * view.scale;
+
+  ctx.setTransform(1, 0, 0, 1, 0, 0);
+  ctx.clearRect(0, 0, canvas.width, canvas.height);
+  ctx.fillStyle = "#fff";
+  ctx.fillRect(px(0), py(BED[1]), BED[0] * view.scale, BED[1] * view.scale);
+
+  let pen = [0, 0];
+  let start = 0;
+  for (const [x0, y0, x1, y1, down, layer, t] of SEGMENTS) {{
+    if (start > until) break;
+    let f = t > start ? Math.min(1, (until - start) / (t - start)) : 1;
+    const x = x0 + (x1 - x0) * f;
+    const y = y0 + (y1 - y0) * f;
+    pen = [x, y];
+    start = t;
+    if (hidden.has(layer)) continue;
+    if (down === 2) {{
+      ctx.fillStyle = COLORS[layer % COLORS.length];
+      ctx.fillRect(px(x0) - 1, py(y0) - 1, 2, 2);
+      continue;
+    }} else if (down === 1) {{
+      ctx.setLineDash([]);
+      ctx.strokeStyle = COLORS[layer % COLORS.length];
+      ctx.lineWidth = 1.5;
+    }} else if (showTravel && (x0 !== x1 || y0 !== y1)) {{
+      ctx.setLineDash([4, 4]);
+      ctx.strokeStyle = "#bbb";
+      ctx.lineWidth = 0.5;
+    }} else {{
+      continue;
+    }}
+    ctx.beginPath();
+    ctx.moveTo(px(x0), py(y0));
+    ctx.lineTo(px(x), py(y));
+    ctx.stroke();
+  }}
+
+  ctx.setLineDash([]);
+  ctx.strokeStyle = "#e00";
+  ctx.beginPath();
+  ctx.arc(px(pen[0]), py(pen[1]), 5, 0, 2 * Math.PI);
+  ctx.stroke();
+  clock.textContent = fmt(until) + " / " + fmt(TOTAL);
+}}
+
+let drag = null;
+canvas.addEventListener("mousedown", (e) => {{ drag = [e.clientX, e.clientY]; }});
+window.addEventListener("mouseup", () => {{ drag = null; }});
+window.addEventListener("mousemove", (e) => {{
+  if (!drag) return;
+  view.x += e.clientX - drag[0];
+  view.y += e.clientY - drag[1];
+  drag = [e.clientX, e.clientY];
+  draw();
+}});
+canvas.addEventListener("wheel", (e) => {{
+  e.preventDefault();
+  const k = e.deltaY < 0 ? 1.2 : 1 / 1.2;
+  const r = canvas.getBoundingClientRect();
+  const mx = e.clientX - r.left, my = e.clientY - r.top;
+  view.x = mx - (mx - view.x) * k;
+  view.y = my - (my - view.y) * k;
+  view.scale *= k;
+  draw();
+}});
+document.querySelectorAll("input[data-layer]").forEach((box) => {{
+  box.addEventListener("change", () => {{
+    const layer = Number(box.dataset.layer);
+    if (box.checked) hidden.delete(layer); else hidden.add(layer);
+    draw();
+  }});
+}});
+document.getElementById("travel").addEventListener("change", draw);
+slider.addEventListener("input", draw);
+document.getElementById("play").addEventListener("click", () => {{
+  if (playing) {{ clearInterval(playing); playing = null; return; }}
+  if (Number(slider.value) >= 1000) slider.value = 0;
+  playing = setInterval(() => {{
+    slider.value = Number(slider.value) + 2;
+    draw();
+    if (Number(slider.value) >= 1000) {{ clearInterval(playing); playing = null; }}
+  }}, 30);
+}});
+window.addEventListener("resize", () => {{ fit(); draw(); }});
+fit();
+draw();
+</script>
+</body>
+</html>
+"##,
+        toggles = toggles,
+        data = data,
+        colors = colors.join(","),
+        w = size.0,
+        h = size.1,
+        time = time,
+    )
+}
+
+// Static SVG of the toolpath in bed millimetres: strokes in their layer's
+// color, dots as small circles and travel as dashed grey lines. Layers are
+// groups, so they can be told apart and hidden in an editor.
+pub fn svg(segments: &[(usize, Segment)], layers: &[String], size: (f32, f32)) -> String {
+    // y points up on the machine and down in SVG
+    let pt = |(x, y): (f32, f32)| format!("{:.2},{:.2}", x, size.1 - y);
+
+    // Connected moves of the same kind and layer, dots on their own
+    let mut runs: Vec<(u8, usize, Path)> = Vec::new();
+    for (i, (layer, s)) in segments.iter().enumerate() {
+        let kind = kind(segments, i);
+        if kind == 2 {
+            runs.push((kind, *layer, vec![s.to_xy()]));
+            continue;
+        }
+        // Vertical moves have nothing to show from above
+        if s.from_xy() == s.to_xy() {
+            continue;
+        }
+        match runs.last_mut() {
+            Some((k, l, points))
+                if *k == kind && l == layer && points.last() == Some(&s.from_xy()) =>
+            {
+                points.push(s.to_xy())
+            }
+            _ => runs.push((kind, *layer, vec![s.from_xy(), s.to_xy()])),
+        }
+    }
+
+    let mut groups = vec![String::new(); layers.len()];
+    let mut travel = String::new();
+    for (kind, layer, points) in runs {
+        let out = if kind == 0 {
+            &mut travel
+        } else {
+            &mut groups[layer]
+        };
+        if let [(x, y)] = points[..] {
+            let _ = writeln!(
+                out,
+                "<circle cx=\"{:.2}\" cy=\"{:.2}\" r=\"0.4\"/>",
+                x,
+                size.1 - y
+            );
+        } else {
+            let points: Vec<String> = points.into_iter().map(pt).collect();
+            let _ = writeln!(
+                out,
+                "<polyline fill=\"none\" points=\"{}\"/>",
+                points.join(" ")
+            );
+        }
+    }
+
+    let mut body = String::new();
+    let _ = writeln!(
+        body,
+        "<g id=\"travel\" fill=\"none\" stroke=\"#999\" stroke-width=\"0.2\" stroke-dasharray=\"1,1\">\n{}</g>",
+        travel
+    );
+    for (i, (name, group)) in layers.iter().zip(&groups).enumerate() {
+        let color = COLORS[i % COLORS.len()];
+        let _ = writeln!(
+            body,
+            "<g id=\"layer-{}\" data-name=\"{}\" fill=\"{}\" stroke=\"{}\" stroke-width=\"0.4\" stroke-linecap=\"round\" stroke-linejoin=\"round\">\n{}</g>",
+            i,
+            escape(name),
+            color,
+            color,
+            group
+        );
+    }
+    format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}mm\" height=\"{h}mm\" viewBox=\"0 0 {w} {h}\">\n\
+         <rect width=\"{w}\" height=\"{h}\" fill=\"#fff\" stroke=\"#ccc\" stroke-width=\"0.5\"/>\n{body}</svg>\n",
+        w = size.0,
+        h = size.1,
+        body = body
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{temp_path, test_config};
+    use crate::{Error, Printer};
+
+    #[test]
+    fn embeds_segments_and_layers() {
+        let segments = vec![
+            (
+                0,
+                Segment {
+                    from: (0.0, 0.0, 6.5),
+                    to: (10.0, 0.0, 6.5),
+                    feed: 600.0,
+                    pen_down: false,
+                    cornering: 0.0,
+                },
+            ),
+            (
+                1,
+                Segment {
+                    from: (10.0, 0.0, 4.0),
+                    to: (10.0, 10.0, 4.0),
+                    feed: 600.0,
+                    pen_down: true,
+                    cornering: 0.0,
+                },
+            ),
+            (
+                1,
+                Segment {
+                    from: (10.0, 10.0, 4.0),
+                    to: (10.0, 10.0, 6.5),
+                    feed: 600.0,
+                    pen_down: false,
+                    cornering: 0.0,
+                },
+            ),
+            (
+                1,
+                Segment {
+                    from: (10.0, 10.0, 6.5),
+                    to: (10.0, 10.0, 4.0),
+                    feed: 600.0,
+                    pen_down: false,
+                    cornering: 0.0,
+                },
+            ),
+            (
+                1,
+                Segment {
+                    from: (10.0, 10.0, 4.0),
+                    to: (10.0, 10.0, 6.5),
+                    feed: 600.0,
+                    pen_down: false,
+                    cornering: 0.0,
+                },
+            ),
+        ];
+        let layers = vec!["default".to_string(), "<red>".to_string()];
+        let page = html(&segments, &layers, (200.0, 150.0));
+
+        assert!(page.contains(
+            "[0.000,0.000,10.000,0.000,0,0,1.000],[10.000,0.000,10.000,10.000,1,1,2.000]"
+        ));
+        assert!(page.contains("const BED = [200.000, 150.000];"));
+        assert!(page.contains("[10.000,10.000,10.000,10.000,2,1,"));
+        assert!(page.contains("const TOTAL = 2.750;"));
+        assert!(page.contains("&lt;red&gt;"));
+        assert!(page.contains("data-layer=\"1\""));
+
+        let drawing = svg(&segments, &layers, (200.0, 150.0));
+        assert!(drawing.contains("viewBox=\"0 0 200 150\""));
+        // y flipped, travel in its own group
+        let travel = drawing.split("<g id=\"layer-0\"").next().unwrap();
+        assert!(travel.contains("<polyline fill=\"none\" points=\"0.00,150.00 10.00,150.00\"/>"));
+        let red = drawing.split("<g id=\"layer-1\"").nth(1).unwrap();
+        assert!(red.contains("points=\"10.00,150.00 10.00,140.00\""));
+        assert!(red.contains("<circle cx=\"10.00\" cy=\"140.00\" r=\"0.4\"/>"));
+        assert!(drawing.contains("data-name=\"&lt;red&gt;\""));
+    }
+
+    #[test]
+    fn html_preview() {
+        let mut printer = Printer::new(test_config());
+        printer.draw_polyline(&[(0.0, 0.0), (10.0, 0.0)]);
+        printer.set_layer("dots");
+        printer.draw_point(10.0, 10.0);
+
+        let filename = temp_path("preview.html");
+        let filename = filename.to_str().unwrap();
+        if let Err(e) = printer.preview_html(filename) {
+            panic!("Unable to write preview: {}", e);
+        }
+        let page = std::fs::read_to_string(filename).unwrap();
+        assert!(page.contains("data-layer=\"1\""));
+        assert!(page.contains("[0.000,0.000,10.000,0.000,1,0,"));
+
+        let filename = temp_path("preview.svg");
+        let filename = filename.to_str().unwrap();
+        printer.preview_svg(filename).unwrap();
+        let svg = std::fs::read_to_string(filename).unwrap();
+        assert!(svg.contains("<g id=\"layer-1\" data-name=\"dots\""));
+
+        let filename = temp_path("preview.png");
+        let filename = filename.to_str().unwrap();
+        printer.preview_png(filename, 64, 48).unwrap();
+        assert!(std::fs::read(filename).unwrap().starts_with(b"\x89PNG"));
+        for (w, h) in [(0, 0), (64, 0), (0, 48)] {
+            let err = printer.preview_png(filename, w, h).unwrap_err();
+            assert!(matches!(err, Error::Bounds(_)));
+            assert_eq!(err.to_string(), format!("empty preview size {}x{}", w, h));
+        }
+    }
+}
@@ -0,0 +1,250 @@
+use crate::{Code, sim};
+
+// Fewest commands between two reports by percent, so short programs don't
+// get a message for every move
+const MIN_COMMANDS: usize = 5;
+
+// How often progress is reported
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Interval {
+    // Every this many percent of the estimated time
+    Percent(f32),
+    // Every this many commands
+    Commands(usize),
+}
+
+// Progress messages (M117 or the dialect's equivalent) in the program
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProgressConfig {
+    pub interval: Interval,
+    // Text of the messages, with {percent}, {elapsed} and {remaining}
+    // filled in
+    pub template: String,
+    // Without messages the callbacks of Printer::write_with_progress are
+    // still called
+    pub messages: bool,
+}
+
+impl Default for ProgressConfig {
+    fn default() -> Self {
+        ProgressConfig {
+            interval: Interval::Percent(1.5),
+            template: "{percent}% R{remaining}".to_string(),
+            messages: true,
+        }
+    }
+}
+
+impl ProgressConfig {
+    pub fn message(&self, event: &ProgressEvent) -> String {
+        self.template
+            .replace("{percent}", &format!("{:.1}", event.percent))
+            // Whole seconds, cut off rather than rounded
+            .replace("{elapsed}", &sim::format_time(event.elapsed.floor()))
+            .replace("{remaining}", &sim::format_time(event.remaining.floor()))
+    }
+}
+
+// Where the program is when progress is reported
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct ProgressEvent {
+    // Of the estimated time, 0 to 100
+    pub percent: f32,
+    // Estimated seconds of drawing so far and still to go
+    pub elapsed: f32,
+    pub remaining: f32,
+    // Commands of the drawing so far
+    pub commands: usize,
+}
+
+// Follows the program as it's emitted and says when progress is due
+#[derive(Debug, Clone)]
+pub(crate) struct Tracker {
+    config: ProgressConfig,
+    total: f32,
+    elapsed: f32,
+    commands: usize,
+    // Commands at the last report
+    reported: usize,
+    // Percent or command count of the next report
+    next: f32,
+}
+
+impl Tracker {
+    // `total` is the estimated time of the drawing in seconds
+    pub(crate) fn new(config: &ProgressConfig, total: f32) -> Self {
+        let mut tracker = Tracker {
+            config: config.clone(),
+            total,
+            elapsed: 0.0,
+            commands: 0,
+            reported: 0,
+            next: 0.0,
+        };
+        tracker.next = tracker.step();
+        tracker
+    }
+
+    fn step(&self) -> f32 {
+        match self.config.interval {
+            Interval::Percent(p) => p.max(1e-3),
+            Interval::Commands(n) => n.max(1) as f32,
+        }
+    }
+
+    // Counts `code` after it took `seconds`, returning an event when the
+    // next report is due
+    pub(crate) fn record(&mut self, code: &Code, seconds: f32) -> Option<ProgressEvent> {
+        self.elapsed += seconds;
+        if !matches!(code, Code::Comment(_) | Code::NOP) {
+            self.commands += 1;
+        }
+        if self.total <= 0.0 {
+            return None;
+        }
+        let percent = (self.elapsed / self.total * 100.0).min(100.0);
+        let step = self.step();
+        let at = match self.config.interval {
+            Interval::Percent(_) => percent,
+            Interval::Commands(_) => self.commands as f32,
+        };
+        // Nothing more once the next report would be past the end
+        let finished = matches!(self.config.interval, Interval::Percent(_)) && self.next > 100.0;
+        if at < self.next || finished {
+            return None;
+        }
+        if matches!(self.config.interval, Interval::Percent(_))
+            && self.commands - self.reported < MIN_COMMANDS
+            && percent < 100.0
+        {
+            return None;
+        }
+        self.reported = self.commands;
+        self.next = ((at / step).floor() + 1.0) * step;
+        Some(ProgressEvent {
+            percent,
+            elapsed: self.elapsed,
+            remaining: self.total - self.elapsed,
+            commands: self.commands,
+        })
+    }
+
+    // The message for `event` if the program should have one
+    pub(crate) fn message(&self, event: &ProgressEvent) -> Option<Code> {
+        self.config
+            .messages
+            .then(|| Code::Message(self.config.message(event)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Printer;
+    use crate::tests::{temp_path, test_config};
+
+    fn events(config: &ProgressConfig, codes: usize) -> Vec<ProgressEvent> {
+        // Every code takes a second
+        let mut tracker = Tracker::new(config, codes as f32);
+        (0..codes)
+            .filter_map(|_| tracker.record(&Code::Raw("G0 X1".into()), 1.0))
+            .collect()
+    }
+
+    #[test]
+    fn intervals() {
+        let config = ProgressConfig {
+            interval: Interval::Percent(10.0),
+            ..ProgressConfig::default()
+        };
+        let percents: Vec<f32> = events(&config, 100)
+            .iter()
+            .map(|e| e.percent.round())
+            .collect();
+        assert_eq!(
+            percents,
+            [10.0, 20.0, 30.0, 40.0, 50.0, 60.0, 70.0, 80.0, 90.0, 100.0]
+        );
+
+        // Short programs are only reported every few commands, and at the end
+        let percents: Vec<f32> = events(&ProgressConfig::default(), 3)
+            .iter()
+            .map(|e| e.percent.round())
+            .collect();
+        assert_eq!(percents, [100.0]);
+        let commands: Vec<usize> = events(&ProgressConfig::default(), 12)
+            .iter()
+            .map(|e| e.commands)
+            .collect();
+        assert_eq!(commands, [5, 10, 12]);
+
+        let config = ProgressConfig {
+            interval: Interval::Commands(25),
+            ..ProgressConfig::default()
+        };
+        let commands: Vec<usize> = events(&config, 100).iter().map(|e| e.commands).collect();
+        assert_eq!(commands, [25, 50, 75, 100]);
+
+        let event = events(&config, 100)[0];
+        assert_eq!(config.message(&event), "25.0% R00:01:15");
+        let config = ProgressConfig {
+            template: "{elapsed} of the way".to_string(),
+            ..config
+        };
+        assert_eq!(config.message(&event), "00:00:25 of the way");
+    }
+
+    #[test]
+    fn progress_by_time() {
+        let mut printer = Printer::new(test_config());
+        // One long stroke followed by lots of short dots
+        printer.draw_polyline(&[(0.0, 0.0), (200.0, 0.0), (200.0, 170.0)]);
+        for i in 0..100 {
+            printer.draw_point(i as f32, 10.0);
+        }
+
+        let filename = temp_path("progress.gcode");
+        let filename = filename.to_str().unwrap();
+        if let Err(e) = printer.save(filename) {
+            panic!("Unable to generate GCODE: {}", e);
+        }
+        let text = std::fs::read_to_string(filename).unwrap();
+        let percents: Vec<f32> = text
+            .lines()
+            .filter_map(|l| l.strip_prefix("M117 "))
+            .filter_map(|m| m.split('%').next()?.parse().ok())
+            .collect();
+
+        // The long stroke takes a large share of the time, so progress
+        // jumps while it's drawn instead of advancing per command
+        assert!(percents.len() > 2);
+        assert!(percents.windows(2).all(|w| w[0] <= w[1]));
+        assert!(percents.windows(2).any(|w| w[1] - w[0] > 10.0));
+        assert!(*percents.last().unwrap() <= 100.0);
+    }
+
+    #[test]
+    fn progress_callback() {
+        let mut config = test_config();
+        config.progress = ProgressConfig {
+            interval: Interval::Commands(10),
+            messages: false,
+            ..ProgressConfig::default()
+        };
+        let mut printer = Printer::new(config);
+        for i in 0..20 {
+            printer.draw_point(i as f32, 10.0);
+        }
+        let mut events = Vec::new();
+        let mut program = Vec::new();
+        printer
+            .write_with_progress(&mut program, |e| events.push(e))
+            .unwrap();
+
+        // Points are three commands each
+        let commands: Vec<usize> = events.iter().map(|e| e.commands).collect();
+        assert_eq!(commands, (1..=6).map(|n| n * 10).collect::<Vec<_>>());
+        assert!(events.windows(2).all(|w| w[0].percent < w[1].percent));
+        assert!(!String::from_utf8(program).unwrap().contains("M117"));
+    }
+}
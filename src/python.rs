@@ -0,0 +1,333 @@
+// C functions behind the Python module in python/artful_gcode.py, which
+// loads them with ctypes. Build the shared library with
+//
+//     cargo rustc --release --features python --lib --crate-type cdylib
+//
+// and test the module against it with
+//
+//     python3 -m unittest discover python
+//
+// Configs and printers are handed out as pointers that the caller owns and
+// frees exactly once, with ag_config_free and ag_printer_free. Strings
+// handed out are owned by the caller too and freed with ag_string_free,
+// except ag_last_error's. Calls that can fail return null or -1 and leave
+// the reason for ag_last_error. Every pointer passed in must come from the
+// matching ag_ function and not have been freed, strings must be valid C
+// strings and arrays as long as the function says. Pointers passed in stay
+// owned by the caller.
+#![allow(clippy::missing_safety_doc)]
+
+use std::cell::RefCell;
+use std::ffi::{CStr, CString, c_char, c_int};
+
+use crate::{AffineTransform, Printer, PrinterConfig, config};
+
+thread_local! {
+    static LAST_ERROR: RefCell<CString> = RefCell::new(CString::default());
+}
+
+fn fail(error: impl std::fmt::Display) {
+    let message = CString::new(error.to_string().replace('\0', " ")).unwrap_or_default();
+    LAST_ERROR.with(|e| *e.borrow_mut() = message);
+}
+
+fn status<T, E: std::fmt::Display>(result: Result<T, E>) -> c_int {
+    match result {
+        Ok(_) => 0,
+        Err(e) => {
+            fail(e);
+            -1
+        }
+    }
+}
+
+fn owned(text: String) -> *mut c_char {
+    CString::new(text.replace('\0', " "))
+        .unwrap_or_default()
+        .into_raw()
+}
+
+// SAFETY: `text` is a valid C string for the length of the call
+unsafe fn text<'a>(text: *const c_char) -> &'a str {
+    if text.is_null() {
+        return "";
+    }
+    unsafe { CStr::from_ptr(text) }.to_str().unwrap_or("")
+}
+
+// SAFETY: `printer` came from ag_printer_new and wasn't freed
+unsafe fn printer<'a>(printer: *mut Printer) -> &'a mut Printer {
+    unsafe { &mut *printer }
+}
+
+// The reason the last call on this thread failed. Owned by the library and
+// valid until the next failing call on this thread, don't free it.
+#[unsafe(no_mangle)]
+pub extern "C" fn ag_last_error() -> *const c_char {
+    LAST_ERROR.with(|e| e.borrow().as_ptr())
+}
+
+// Frees a string from ag_config_get or ag_to_string, null is ignored
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ag_string_free(text: *mut c_char) {
+    if !text.is_null() {
+        drop(unsafe { CString::from_raw(text) });
+    }
+}
+
+// A new default config, to free with ag_config_free
+#[unsafe(no_mangle)]
+pub extern "C" fn ag_config_default() -> *mut PrinterConfig {
+    Box::into_raw(Box::new(PrinterConfig::default()))
+}
+
+// A new MK3S config, to free with ag_config_free
+#[unsafe(no_mangle)]
+pub extern "C" fn ag_config_prusa_mk3s() -> *mut PrinterConfig {
+    Box::into_raw(Box::new(PrinterConfig::prusa_mk3s()))
+}
+
+// Changes one entry of `config` in place, 0 if the value is valid and -1
+// with the config unchanged if not
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ag_config_set(
+    config: *mut PrinterConfig,
+    key: *const c_char,
+    value: *const c_char,
+) -> c_int {
+    let config = unsafe { &mut *config };
+    let changed = config::with_entry(config, unsafe { text(key) }, unsafe { text(value) });
+    status(changed.map(|c| *config = c))
+}
+
+// The value of `key` as the config entries write it, to free with
+// ag_string_free, or null for a key the config doesn't have
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ag_config_get(
+    config: *const PrinterConfig,
+    key: *const c_char,
+) -> *mut c_char {
+    let key = unsafe { text(key) };
+    match config::entries(unsafe { &*config })
+        .into_iter()
+        .find(|(k, _)| k == key)
+    {
+        Some((_, value)) => owned(value),
+        None => {
+            fail(format!("no config entry {}", key));
+            std::ptr::null_mut()
+        }
+    }
+}
+
+// Frees a config from ag_config_default or ag_config_prusa_mk3s, null is
+// ignored
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ag_config_free(config: *mut PrinterConfig) {
+    if !config.is_null() {
+        drop(unsafe { Box::from_raw(config) });
+    }
+}
+
+// A new printer with a copy of `config`, to free with ag_printer_free, or
+// null if the config doesn't validate. The config can be freed right away.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ag_printer_new(config: *const PrinterConfig) -> *mut Printer {
+    let config = unsafe { &*config }.clone();
+    match Printer::try_new(config) {
+        Ok(printer) => Box::into_raw(Box::new(printer)),
+        Err(e) => {
+            fail(e);
+            std::ptr::null_mut()
+        }
+    }
+}
+
+// Frees a printer from ag_printer_new, null is ignored
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ag_printer_free(printer: *mut Printer) {
+    if !printer.is_null() {
+        drop(unsafe { Box::from_raw(printer) });
+    }
+}
+
+// Writes the width and height of the bed to `size`, which holds 2 floats
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ag_printer_size(printer: *mut Printer, size: *mut f32) {
+    let printer = unsafe { self::printer(printer) };
+    unsafe { std::ptr::copy_nonoverlapping([printer.width, printer.height].as_ptr(), size, 2) };
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ag_draw_point(printer: *mut Printer, x: f32, y: f32) {
+    unsafe { self::printer(printer) }.draw_point(x, y);
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ag_draw_line(printer: *mut Printer, x0: f32, y0: f32, x1: f32, y1: f32) {
+    unsafe { self::printer(printer) }.draw_line(x0, y0, x1, y1);
+}
+
+// `xy` holds `n` points as x0, y0, x1, y1, ... and is only read during the
+// call
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ag_draw_polyline(printer: *mut Printer, xy: *const f32, n: usize) {
+    if xy.is_null() {
+        return;
+    }
+    let xy = unsafe { std::slice::from_raw_parts(xy, 2 * n) };
+    let points: Vec<(f32, f32)> = xy.chunks(2).map(|p| (p[0], p[1])).collect();
+    unsafe { self::printer(printer) }.draw_polyline(&points);
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ag_draw_circle(printer: *mut Printer, cx: f32, cy: f32, r: f32) {
+    unsafe { self::printer(printer) }.draw_circle(cx, cy, r);
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ag_draw_arc(
+    printer: *mut Printer,
+    cx: f32,
+    cy: f32,
+    radius: f32,
+    start_angle: f32,
+    end_angle: f32,
+) {
+    unsafe { self::printer(printer) }.draw_arc(cx, cy, radius, start_angle, end_angle);
+}
+
+// `xy` holds the start, the two control points and the end, 8 floats
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ag_draw_cubic_bezier(printer: *mut Printer, xy: *const f32) {
+    let p = unsafe { std::slice::from_raw_parts(xy, 8) };
+    unsafe { self::printer(printer) }.draw_cubic_bezier(
+        (p[0], p[1]),
+        (p[2], p[3]),
+        (p[4], p[5]),
+        (p[6], p[7]),
+    );
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ag_draw_text(
+    printer: *mut Printer,
+    x: f32,
+    y: f32,
+    height: f32,
+    text: *const c_char,
+) {
+    let text = unsafe { self::text(text) };
+    unsafe { self::printer(printer) }.draw_text(x, y, height, text);
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ag_set_layer(printer: *mut Printer, name: *const c_char) {
+    let name = unsafe { text(name) };
+    unsafe { self::printer(printer) }.set_layer(name);
+}
+
+// The transform x' = a x + c y + e, y' = b x + d y + f, from the 6 floats
+// at `t`
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ag_push_transform(printer: *mut Printer, t: *const f32) {
+    let t = unsafe { std::slice::from_raw_parts(t, 6) };
+    let t = AffineTransform::new(t[0], t[1], t[2], t[3], t[4], t[5]);
+    unsafe { self::printer(printer) }.push_transform(t);
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ag_pop_transform(printer: *mut Printer) {
+    unsafe { self::printer(printer) }.pop_transform();
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ag_optimize(printer: *mut Printer) {
+    unsafe { self::printer(printer) }.optimize();
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ag_dedupe(printer: *mut Printer, tolerance: f32) {
+    unsafe { self::printer(printer) }.dedupe(tolerance);
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ag_estimated_duration(printer: *mut Printer) -> f32 {
+    unsafe { self::printer(printer) }
+        .estimated_duration()
+        .as_secs_f32()
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ag_save(printer: *mut Printer, filename: *const c_char) -> c_int {
+    let filename = unsafe { text(filename) };
+    status(unsafe { self::printer(printer) }.save(filename))
+}
+
+// The program as text, to free with ag_string_free, or null if it can't be
+// written
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ag_to_string(printer: *mut Printer) -> *mut c_char {
+    let mut program = Vec::new();
+    match unsafe { self::printer(printer) }.write_to(&mut program) {
+        Ok(()) => owned(String::from_utf8_lossy(&program).into_owned()),
+        Err(e) => {
+            fail(e);
+            std::ptr::null_mut()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn c(text: &str) -> CString {
+        CString::new(text).unwrap()
+    }
+
+    fn last_error() -> String {
+        unsafe { CStr::from_ptr(ag_last_error()) }
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    #[test]
+    fn drawing_through_the_c_api() {
+        unsafe {
+            let config = ag_config_prusa_mk3s();
+            assert_eq!(
+                ag_config_set(config, c("xy_speed").as_ptr(), c("1500").as_ptr()),
+                0
+            );
+            assert_eq!(
+                ag_config_set(config, c("z0").as_ptr(), c("high").as_ptr()),
+                -1
+            );
+            assert!(last_error().contains("z0"));
+            let speed = ag_config_get(config, c("xy_speed").as_ptr());
+            assert_eq!(CStr::from_ptr(speed).to_str(), Ok("1500"));
+            ag_string_free(speed);
+            assert!(ag_config_get(config, c("nonsense").as_ptr()).is_null());
+
+            let printer = ag_printer_new(config);
+            ag_config_free(config);
+            ag_draw_polyline(printer, [0.0, 0.0, 10.0, 0.0, 10.0, 10.0].as_ptr(), 3);
+            ag_push_transform(printer, [1.0, 0.0, 0.0, 1.0, 20.0, 0.0].as_ptr());
+            ag_draw_point(printer, 5.0, 5.0);
+            ag_pop_transform(printer);
+            assert!(ag_estimated_duration(printer) > 0.0);
+
+            let program = ag_to_string(printer);
+            let text = CStr::from_ptr(program).to_string_lossy().into_owned();
+            ag_string_free(program);
+            assert!(text.contains("G1 X10.0 Y10.0 F1500.0"));
+            assert!(text.contains("G1 X25.0 Y5.0 F1500.0"));
+
+            let missing = c("/no/such/dir/out.gcode");
+            assert_eq!(ag_save(printer, missing.as_ptr()), -1);
+            assert!(!last_error().is_empty());
+            ag_printer_free(printer);
+        }
+    }
+}
@@ -0,0 +1,204 @@
+use crate::png;
+use crate::sim::{self, Segment};
+
+// Simple RGB canvas used for preview images and thumbnails
+#[derive(Debug, Clone, PartialEq)]
+pub struct Bitmap {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<[u8; 3]>,
+}
+
+impl Bitmap {
+    pub fn new(width: u32, height: u32, background: [u8; 3]) -> Self {
+        Bitmap {
+            width,
+            height,
+            pixels: vec![background; (width * height) as usize],
+        }
+    }
+
+    pub fn get(&self, x: i64, y: i64) -> Option<[u8; 3]> {
+        if x < 0 || y < 0 || x >= self.width as i64 || y >= self.height as i64 {
+            None
+        } else {
+            Some(self.pixels[(y as u32 * self.width + x as u32) as usize])
+        }
+    }
+
+    pub fn set(&mut self, x: i64, y: i64, color: [u8; 3]) {
+        if x >= 0 && y >= 0 && x < self.width as i64 && y < self.height as i64 {
+            self.pixels[(y as u32 * self.width + x as u32) as usize] = color;
+        }
+    }
+
+    // Perceived brightness 0..=1 of a pixel, 0 outside the bitmap
+    pub fn brightness(&self, x: i64, y: i64) -> f32 {
+        self.get(x, y).map_or(0.0, |[r, g, b]| {
+            (0.299 * r as f32 + 0.587 * g as f32 + 0.114 * b as f32) / 255.0
+        })
+    }
+
+    // Bresenham line between pixel centers
+    pub fn line(&mut self, (x0, y0): (f32, f32), (x1, y1): (f32, f32), color: [u8; 3]) {
+        let (mut x, mut y) = (x0.round() as i64, y0.round() as i64);
+        let (x1, y1) = (x1.round() as i64, y1.round() as i64);
+        let dx = (x1 - x).abs();
+        let dy = -(y1 - y).abs();
+        let sx = if x < x1 { 1 } else { -1 };
+        let sy = if y < y1 { 1 } else { -1 };
+        let mut err = dx + dy;
+
+        loop {
+            self.set(x, y, color);
+            if x == x1 && y == y1 {
+                break;
+            }
+            let e2 = 2 * err;
+            if e2 >= dy {
+                err += dy;
+                x += sx;
+            }
+            if e2 <= dx {
+                err += dx;
+                y += sy;
+            }
+        }
+    }
+
+    pub fn to_png(&self) -> Vec<u8> {
+        let rgb: Vec<u8> = self.pixels.iter().flatten().copied().collect();
+        png::encode_rgb(self.width, self.height, &rgb)
+    }
+}
+
+// Renders the pen-down strokes and dots of a simulated program onto a
+// `width` x `height` bitmap. The bed (`size` in mm) is scaled to fit and
+// centered, with y pointing up like on the machine.
+pub fn render(segments: &[Segment], size: (f32, f32), width: u32, height: u32) -> Bitmap {
+    let mut bitmap = Bitmap::new(width, height, [255, 255, 255]);
+    let scale = ((width as f32 - 1.0) / size.0).min((height as f32 - 1.0) / size.1);
+    let ox = (width as f32 - 1.0 - size.0 * scale) / 2.0;
+    let oy = (height as f32 - 1.0 + size.1 * scale) / 2.0;
+    let px = |(x, y): (f32, f32)| (ox + x * scale, oy - y * scale);
+    let ink = [0, 0, 0];
+
+    for (i, s) in segments.iter().enumerate() {
+        if s.pen_down {
+            bitmap.line(px(s.from_xy()), px(s.to_xy()), ink);
+        } else if sim::is_dot(s, segments.get(i + 1)) {
+            let p = px(s.to_xy());
+            bitmap.line(p, p, ink);
+        }
+    }
+
+    bitmap
+}
+
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+pub fn base64(data: &[u8]) -> String {
+    let mut out = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let b = [
+            chunk[0],
+            chunk.get(1).copied().unwrap_or(0),
+            chunk.get(2).copied().unwrap_or(0),
+        ];
+        let n = ((b[0] as u32) << 16) | ((b[1] as u32) << 8) | b[2] as u32;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(BASE64[((n >> (18 - 6 * i)) & 63) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+// Comment lines (without the leading "; ") embedding `bitmap` as a PNG
+// thumbnail the way PrusaSlicer does, which Prusa and Klipper UIs pick up
+pub fn thumbnail_block(bitmap: &Bitmap) -> Vec<String> {
+    let encoded = base64(&bitmap.to_png());
+    let mut lines = vec![format!(
+        "thumbnail begin {}x{} {}",
+        bitmap.width,
+        bitmap.height,
+        encoded.len()
+    )];
+    for chunk in encoded.as_bytes().chunks(78) {
+        lines.push(String::from_utf8_lossy(chunk).into_owned());
+    }
+    lines.push("thumbnail end".to_string());
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn draw_lines() {
+        let mut bitmap = Bitmap::new(5, 5, [255, 255, 255]);
+        bitmap.line((0.0, 0.0), (4.0, 4.0), [0, 0, 0]);
+        for i in 0..5 {
+            assert_eq!(bitmap.get(i, i), Some([0, 0, 0]));
+        }
+        assert_eq!(bitmap.get(1, 0), Some([255, 255, 255]));
+        assert_eq!(bitmap.get(5, 5), None);
+
+        // Drawing outside the canvas is ignored
+        bitmap.line((-10.0, 2.0), (10.0, 2.0), [1, 2, 3]);
+        assert_eq!(bitmap.get(0, 2), Some([1, 2, 3]));
+    }
+
+    #[test]
+    fn base64_encoding() {
+        assert_eq!(base64(b""), "");
+        assert_eq!(base64(b"f"), "Zg==");
+        assert_eq!(base64(b"fo"), "Zm8=");
+        assert_eq!(base64(b"foo"), "Zm9v");
+        assert_eq!(base64(b"foobar"), "Zm9vYmFy");
+    }
+
+    #[test]
+    fn thumbnail_framing() {
+        let lines = thumbnail_block(&Bitmap::new(100, 80, [255, 255, 255]));
+        assert!(lines[0].starts_with("thumbnail begin 100x80 "));
+        assert_eq!(lines.last().unwrap(), "thumbnail end");
+
+        let len: usize = lines[0].rsplit(' ').next().unwrap().parse().unwrap();
+        let body = &lines[1..lines.len() - 1];
+        assert_eq!(body.iter().map(|l| l.len()).sum::<usize>(), len);
+        assert!(body.iter().all(|l| l.len() <= 78));
+    }
+
+    #[test]
+    fn render_strokes_and_dots() {
+        let seg = |from: (f32, f32, f32), to: (f32, f32, f32), pen_down: bool| Segment {
+            from,
+            to,
+            feed: 1000.0,
+            pen_down,
+            cornering: 0.0,
+        };
+        let segments = vec![
+            // A horizontal line along the bottom of the bed
+            seg((0.0, 0.0, 4.0), (10.0, 0.0, 4.0), true),
+            seg((10.0, 0.0, 4.0), (10.0, 0.0, 6.0), false),
+            // A dot in the top left corner
+            seg((10.0, 0.0, 6.0), (0.0, 10.0, 6.0), false),
+            seg((0.0, 10.0, 6.0), (0.0, 10.0, 4.0), false),
+            seg((0.0, 10.0, 4.0), (0.0, 10.0, 6.0), false),
+        ];
+        let bitmap = render(&segments, (10.0, 10.0), 11, 11);
+
+        for x in 0..11 {
+            assert_eq!(bitmap.get(x, 10), Some([0, 0, 0]));
+        }
+        assert_eq!(bitmap.get(0, 0), Some([0, 0, 0]));
+        // Travel moves are not drawn
+        assert_eq!(bitmap.get(5, 5), Some([255, 255, 255]));
+    }
+}
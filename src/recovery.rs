@@ -0,0 +1,185 @@
+use crate::{Code, Error};
+
+// Start of the comments marking recovery points
+pub(crate) const TAG: &str = "Recovery point:";
+
+// Place in a program where plotting can pick up again after the machine lost
+// power. Points are only placed between ops, where the pen is up.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Marker {
+    // Index of the op that starts right after the marker
+    pub op: usize,
+    // Where the tool is, relative to the origin of the drawing
+    pub position: (f32, f32, f32),
+    pub pen_down: bool,
+    // Line of the marker in the file, counting from 1
+    pub line: usize,
+}
+
+impl Marker {
+    pub(crate) fn comment(op: usize, (x, y, z): (f32, f32, f32), pen_down: bool) -> Code {
+        Code::Comment(format!(
+            "{} op {}, X{:.1} Y{:.1} Z{:.1}, pen {}",
+            TAG,
+            op,
+            x,
+            y,
+            z,
+            if pen_down { "down" } else { "up" }
+        ))
+    }
+
+    fn parse(line: &str, number: usize) -> Option<Marker> {
+        let rest = line.strip_prefix("; ")?.strip_prefix(TAG)?;
+        let mut fields = rest.split(',').map(str::trim);
+        let op = fields.next()?.strip_prefix("op ")?.parse().ok()?;
+        let mut axes = fields.next()?.split(' ');
+        let mut axis = |name: char| axes.next()?.strip_prefix(name)?.parse().ok();
+        let position = (axis('X')?, axis('Y')?, axis('Z')?);
+        let pen_down = match fields.next()? {
+            "pen down" => true,
+            "pen up" => false,
+            _ => return None,
+        };
+        Some(Marker {
+            op,
+            position,
+            pen_down,
+            line: number,
+        })
+    }
+}
+
+fn invalid(message: &str) -> Error {
+    Error::Parse(message.to_string())
+}
+
+// Every recovery point in the text of a G-code file, in order
+pub fn markers(text: &str) -> Vec<Marker> {
+    text.lines()
+        .enumerate()
+        .filter_map(|(i, line)| Marker::parse(line, i + 1))
+        .collect()
+}
+
+// Program that plots what is left of `text` after `marker`, typically the
+// last one the machine got past before it lost power. It sets the machine up
+// the way the original file does, since that homes and sets the origin
+// anyway, then puts back the acceleration and cornering that were in effect
+// at the marker.
+pub fn resume(text: &str, marker: &Marker) -> Result<String, Error> {
+    let lines: Vec<&str> = text.lines().collect();
+    let first = markers(text)
+        .first()
+        .map(|m| m.line)
+        .ok_or_else(|| invalid("no recovery points found"))?;
+    if marker.line < first
+        || lines
+            .get(marker.line - 1)
+            .and_then(|l| Marker::parse(l, marker.line))
+            != Some(*marker)
+    {
+        return Err(invalid("marker isn't in this file"));
+    }
+    if marker.pen_down {
+        return Err(invalid("can't resume with the pen down"));
+    }
+
+    // The last of every setting made since the drawing started
+    let mut settings: Vec<&str> = Vec::new();
+    for &line in &lines[first..marker.line - 1] {
+        for command in ["M204 ", "M205 "] {
+            if line.starts_with(command) {
+                settings.retain(|s| !s.starts_with(command));
+                settings.push(line);
+            }
+        }
+    }
+
+    let mut out: Vec<String> = lines[..first - 1].iter().map(|l| l.to_string()).collect();
+    out.push(format!("; Resuming at op {}", marker.op));
+    out.extend(settings.iter().map(|l| l.to_string()));
+    out.extend(lines[marker.line - 1..].iter().map(|l| l.to_string()));
+    let mut program = out.join("\n");
+    program.push('\n');
+    Ok(program)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{temp_path, test_config};
+    use crate::{Overrides, Printer};
+
+    #[test]
+    fn round_trip() {
+        let line = Marker::comment(12, (10.0, 20.0, 6.5), false).to_string();
+        assert_eq!(line, "; Recovery point: op 12, X10.0 Y20.0 Z6.5, pen up");
+        assert_eq!(
+            Marker::parse(&line, 3),
+            Some(Marker {
+                op: 12,
+                position: (10.0, 20.0, 6.5),
+                pen_down: false,
+                line: 3
+            })
+        );
+        assert_eq!(Marker::parse("; Recovery point: op x", 1), None);
+        assert!(markers("G28\nG1 X1\n").is_empty());
+    }
+
+    #[test]
+    fn power_loss_recovery() {
+        let mut config = test_config();
+        config.recovery_interval = Some(15.0);
+        let mut printer = Printer::new(config);
+        printer.begin_section(
+            "slow",
+            Overrides {
+                acceleration: Some(250.0),
+                ..Overrides::default()
+            },
+        );
+        for i in 0..10 {
+            let y = i as f32 * 5.0;
+            printer.draw_polyline(&[(0.0, y), (100.0, y)]);
+        }
+
+        let filename = temp_path("recovery.gcode");
+        let filename = filename.to_str().unwrap();
+        printer.save(filename).unwrap();
+        let text = std::fs::read_to_string(filename).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+
+        // One at the start, then whenever 15s have passed
+        let markers = markers(&text);
+        assert!(markers.len() > 2 && markers.len() < 10, "{:?}", markers);
+        assert_eq!(markers[0].op, 0);
+        assert_eq!(markers[0].position, (0.0, 0.0, 6.5));
+        assert!(markers.iter().all(|m| !m.pen_down));
+
+        let marker = markers[2];
+        let resumed = resume(&text, &marker).unwrap();
+        let resumed: Vec<&str> = resumed.lines().collect();
+        // Set up like the original, with the section's acceleration back
+        assert_eq!(resumed[..markers[0].line - 1], lines[..markers[0].line - 1]);
+        let at = resumed
+            .iter()
+            .position(|&l| l.starts_with("; Resuming"))
+            .unwrap();
+        assert_eq!(resumed[at], format!("; Resuming at op {}", marker.op));
+        assert_eq!(resumed[at + 1], "M204 S250");
+        assert_eq!(resumed[at + 2..], lines[marker.line - 1..]);
+
+        let mut other = marker;
+        other.line += 1;
+        assert!(resume(&text, &other).is_err());
+
+        printer.save_from_checkpoint(2, filename).unwrap();
+        let saved = std::fs::read_to_string(filename).unwrap();
+        std::fs::remove_file(filename).unwrap();
+        assert_eq!(saved, resume(&text, &marker).unwrap());
+        let err = printer.save_from_checkpoint(10, filename).unwrap_err();
+        assert!(err.to_string().starts_with("no recovery point 10"));
+    }
+}
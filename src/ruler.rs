@@ -0,0 +1,330 @@
+use crate::path::Path;
+use crate::{Printer, text};
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Units {
+    Metric,
+    Imperial,
+}
+
+impl Units {
+    // Names and sizes in mm, smallest first
+    fn table(&self) -> &'static [(&'static str, f32)] {
+        match self {
+            Units::Metric => &[("mm", 1.0), ("cm", 10.0), ("m", 1e3), ("km", 1e6)],
+            Units::Imperial => &[("in", 25.4), ("ft", 304.8), ("mi", 1_609_344.0)],
+        }
+    }
+
+    // Largest unit that `mm` is at least one of
+    fn fitting(&self, mm: f32) -> (&'static str, f32) {
+        let table = self.table();
+        table
+            .iter()
+            .rev()
+            .find(|(_, size)| mm >= *size)
+            .copied()
+            .unwrap_or(table[0])
+    }
+}
+
+// Largest 1, 2 or 5 times a power of ten that isn't more than `v`
+fn nice_floor(v: f32) -> f32 {
+    let p = 10f32.powf(v.log10().floor());
+    [5.0, 2.0, 1.0]
+        .into_iter()
+        .map(|m| m * p)
+        .find(|&n| n <= v * (1.0 + 1e-5))
+        .unwrap_or(p)
+}
+
+// Number without trailing zeros, e.g. 0.5 or 2
+fn number(v: f32) -> String {
+    let s = format!("{:.2}", v);
+    s.trim_end_matches('0').trim_end_matches('.').to_string()
+}
+
+// Label centered above `x` with its baseline at `y`
+fn label(text: &str, x: f32, y: f32, height: f32) -> Vec<Path> {
+    let x0 = x - text::width(text, height) / 2.0;
+    text::layout(text, height)
+        .into_iter()
+        .map(|p| p.into_iter().map(|(px, py)| (x0 + px, y + py)).collect())
+        .collect()
+}
+
+fn tick(x: f32, height: f32) -> Path {
+    vec![(x, 0.0), (x, height)]
+}
+
+// Draws paths given in mm on paper at `at` in drawing coordinates, so they
+// keep their size whatever the config's scale is
+fn draw(printer: &mut Printer, at: (f32, f32), paths: &[Path], label: &str) {
+    let (x0, y0) = printer.to_bed(at.0, at.1);
+    let paths: Vec<Path> = paths
+        .iter()
+        .map(|p| p.iter().map(|&(x, y)| (x0 + x, y0 + y)).collect())
+        .collect();
+    printer.draw_bed_paths(&paths, Some(label));
+}
+
+// Scale bar for maps: a round real-world distance, e.g. "1 km", with ticks
+// at its divisions
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct ScaleBar {
+    // Real-world distance per distance on paper, 25000 for 1:25000
+    pub ratio: f32,
+    pub units: Units,
+    // The bar is as long as possible up to this, in mm on paper
+    pub max_length: f32,
+    // Tick and label height in mm
+    pub height: f32,
+}
+
+impl ScaleBar {
+    pub fn new(ratio: f32, units: Units) -> Self {
+        ScaleBar {
+            ratio,
+            units,
+            max_length: 50.0,
+            height: 2.0,
+        }
+    }
+
+    // Length on paper and the labeled ticks along it
+    pub fn ticks(&self) -> (f32, Vec<(f32, String)>) {
+        let (name, size) = self.units.fitting(self.max_length * self.ratio);
+        let value = nice_floor(self.max_length * self.ratio / size);
+        let length = value * size / self.ratio;
+
+        let leading = value / 10f32.powf(value.log10().floor());
+        let divisions = if leading.round() as u32 == 2 { 4 } else { 5 };
+        let ticks = (0..=divisions)
+            .map(|i| {
+                let v = value * i as f32 / divisions as f32;
+                let text = if i == divisions {
+                    format!("{} {}", number(v), name)
+                } else {
+                    number(v)
+                };
+                (length * i as f32 / divisions as f32, text)
+            })
+            .collect();
+        (length, ticks)
+    }
+
+    // In mm on paper with the left end of the bar at the origin
+    pub fn paths(&self) -> Vec<Path> {
+        let (length, ticks) = self.ticks();
+        let spacing = length / (ticks.len() - 1) as f32;
+        let mut paths = vec![vec![(0.0, 0.0), (length, 0.0)]];
+
+        for (i, (x, text)) in ticks.iter().enumerate() {
+            let end = i == 0 || i == ticks.len() - 1;
+            let h = if end { self.height * 1.5 } else { self.height };
+            paths.push(tick(*x, h));
+
+            // Only the ends are labeled when the numbers don't fit
+            let fits = text::width(text, self.height) < spacing * 0.8;
+            if end || fits {
+                paths.extend(label(text, *x, self.height * 2.0, self.height));
+            }
+        }
+        paths
+    }
+
+    pub fn draw(&self, printer: &mut Printer, at: (f32, f32)) {
+        draw(printer, at, &self.paths(), "scale bar");
+    }
+}
+
+// Ruler with graduated ticks, numbered at every major tick
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Ruler {
+    // Real-world distance per distance on paper, 1 for actual size
+    pub ratio: f32,
+    pub units: Units,
+    // Length on paper in mm
+    pub length: f32,
+    // Height of the major ticks in mm, the others are shorter
+    pub height: f32,
+}
+
+// Closest major ticks may get on paper, in mm
+const MIN_MAJOR: f32 = 8.0;
+// Closest minor ticks may get on paper, in mm
+const MIN_MINOR: f32 = 0.8;
+
+impl Ruler {
+    pub fn new(length: f32, units: Units) -> Self {
+        Ruler {
+            ratio: 1.0,
+            units,
+            length,
+            height: 4.0,
+        }
+    }
+
+    // Real-world distance between major ticks, and the unit they count in
+    fn major(&self) -> (f32, &'static str, f32) {
+        let min = MIN_MAJOR * self.ratio;
+        let (_, size) = self.units.fitting(min);
+        // Whole units at least, nobody wants a ruler numbered in 0.4 inches
+        let n = nice_floor(min / size).max(1.0);
+        let n = [1.0, 2.0, 5.0, 10.0]
+            .into_iter()
+            .map(|m| m * n)
+            .find(|&m| m * size >= min)
+            .unwrap_or(n);
+        let (name, unit) = self.units.fitting(n * size);
+        (n * size, name, unit)
+    }
+
+    // Positions on paper with the tick heights
+    pub fn ticks(&self) -> Vec<(f32, f32)> {
+        let (major, _, _) = self.major();
+        // Metric rulers split in tenths, imperial ones keep halving
+        let metric = self.units == Units::Metric;
+        let mut levels = 0;
+        let mut minor = major;
+        loop {
+            let next = if metric {
+                if levels == 0 {
+                    major / 2.0
+                } else {
+                    major / 10.0
+                }
+            } else {
+                minor / 2.0
+            };
+            if next / self.ratio < MIN_MINOR || (metric && levels == 2) {
+                break;
+            }
+            minor = next;
+            levels += 1;
+        }
+
+        let count = (self.length * self.ratio / minor + 1e-3).floor() as usize;
+        (0..=count)
+            .map(|i| {
+                let real = minor * i as f32;
+                // How many times the major spacing was split to reach this
+                let depth = (0..=levels)
+                    .find(|&d| {
+                        let step = if metric {
+                            [major, major / 2.0, major / 10.0][d]
+                        } else {
+                            major / 2f32.powi(d as i32)
+                        };
+                        let r = real / step;
+                        (r - r.round()).abs() < 1e-3
+                    })
+                    .unwrap_or(levels);
+                (real / self.ratio, self.height / (1.0 + 0.5 * depth as f32))
+            })
+            .collect()
+    }
+
+    // In mm on paper with the zero end at the origin
+    pub fn paths(&self) -> Vec<Path> {
+        let (major, name, size) = self.major();
+        let ticks = self.ticks();
+        let end = ticks.last().map_or(0.0, |t| t.0);
+        let mut paths = vec![vec![(0.0, 0.0), (end, 0.0)]];
+        paths.extend(ticks.iter().map(|&(x, h)| tick(x, h)));
+
+        let text_height = self.height / 2.0;
+        let majors = (self.length * self.ratio / major + 1e-3).floor() as usize;
+        for i in 0..=majors {
+            let v = major * i as f32 / size;
+            let text = if i == majors {
+                format!("{} {}", number(v), name)
+            } else {
+                number(v)
+            };
+            let x = major * i as f32 / self.ratio;
+            paths.extend(label(
+                &text,
+                x,
+                self.height + text_height * 0.5,
+                text_height,
+            ));
+        }
+        paths
+    }
+
+    pub fn draw(&self, printer: &mut Printer, at: (f32, f32)) {
+        draw(printer, at, &self.paths(), "ruler");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sim;
+    use crate::tests::test_config;
+
+    #[test]
+    fn map_scale_bar() {
+        // 50mm at 1:25000 is 1.25km, rounded down to 1km
+        let bar = ScaleBar::new(25000.0, Units::Metric);
+        let (length, ticks) = bar.ticks();
+        assert!((length - 40.0).abs() < 1e-3);
+        let labels: Vec<&str> = ticks.iter().map(|(_, t)| t.as_str()).collect();
+        assert_eq!(labels, ["0", "0.2", "0.4", "0.6", "0.8", "1 km"]);
+
+        // An inch to the mile
+        let bar = ScaleBar::new(63360.0, Units::Imperial);
+        let (length, ticks) = bar.ticks();
+        assert!((length - 25.4).abs() < 1e-3);
+        assert_eq!(ticks.last().unwrap().1, "1 mi");
+
+        let bar = ScaleBar {
+            max_length: 120.0,
+            ..ScaleBar::new(1000.0, Units::Metric)
+        };
+        let (length, ticks) = bar.ticks();
+        assert!((length - 100.0).abs() < 1e-3);
+        assert_eq!(ticks.len(), 6);
+        assert_eq!(ticks[1].1, "20");
+    }
+
+    #[test]
+    fn ruler_ticks() {
+        // Actual size: cm numbers with mm ticks
+        let ruler = Ruler::new(100.0, Units::Metric);
+        let ticks = ruler.ticks();
+        assert_eq!(ticks.len(), 101);
+        assert!((ticks[10].0 - 10.0).abs() < 1e-3);
+        assert!(ticks[10].1 > ticks[5].1 && ticks[5].1 > ticks[1].1);
+
+        // Two inches down to sixteenths
+        let ruler = Ruler::new(50.8, Units::Imperial);
+        let ticks = ruler.ticks();
+        assert_eq!(ticks.len(), 33);
+        assert!((ticks[16].0 - 25.4).abs() < 1e-3);
+        assert!(ticks[16].1 > ticks[8].1 && ticks[8].1 > ticks[4].1);
+
+        // At 1:100 a major tick is 1m
+        let ruler = Ruler {
+            ratio: 100.0,
+            ..Ruler::new(50.0, Units::Metric)
+        };
+        assert_eq!(ruler.major(), (1000.0, "m", 1000.0));
+        assert_eq!(Ruler::new(50.0, Units::Metric).major(), (10.0, "cm", 10.0));
+    }
+
+    #[test]
+    fn scale_bar_keeps_its_size() {
+        // Drawing units are half a millimetre
+        let mut config = test_config();
+        config.scale = Some((408.0, 354.0));
+        let mut printer = Printer::new(config);
+        ScaleBar::new(25000.0, Units::Metric).draw(&mut printer, (100.0, 100.0));
+
+        let ((x0, _), (x1, _)) = sim::drawn_extents(&printer.simulate()).unwrap();
+        // 40mm bar from x = 50, with the labels sticking out a little
+        assert!(x0 < 50.0 && x0 > 48.0);
+        assert!(x1 > 90.0 && x1 < 100.0);
+    }
+}
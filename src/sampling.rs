@@ -0,0 +1,190 @@
+use std::f32::consts::{PI, SQRT_2};
+
+use crate::path;
+use crate::raster::Bitmap;
+use crate::rng::Rng;
+
+// Blue-noise points over a rectangle with Bridson's algorithm: no two points
+// closer than `radius`, and no gaps much larger than that. Stipples placed
+// this way look even, unlike uniform random ones which clump.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct PoissonDisk {
+    pub min: (f32, f32),
+    pub max: (f32, f32),
+    // Smallest distance between two points, in mm
+    pub radius: f32,
+    // Candidates tried around a point before it's given up on
+    pub attempts: usize,
+    pub seed: u64,
+}
+
+impl PoissonDisk {
+    pub fn new(min: (f32, f32), max: (f32, f32), radius: f32, seed: u64) -> Self {
+        PoissonDisk {
+            min,
+            max,
+            radius,
+            attempts: 30,
+            seed,
+        }
+    }
+
+    pub fn sample(&self) -> Vec<(f32, f32)> {
+        self.run(|_| self.radius)
+    }
+
+    // Points packed by `density`, which is 0..=1 over the rectangle: where
+    // it's 1 points are `radius` apart, where it's 0 `max_radius` apart
+    pub fn sample_weighted(
+        &self,
+        max_radius: f32,
+        density: impl Fn((f32, f32)) -> f32,
+    ) -> Vec<(f32, f32)> {
+        let max_radius = max_radius.max(self.radius);
+        self.run(|p| {
+            let d = density(p).clamp(0.0, 1.0);
+            max_radius + (self.radius - max_radius) * d
+        })
+    }
+
+    fn run(&self, radius_at: impl Fn((f32, f32)) -> f32) -> Vec<(f32, f32)> {
+        let size = (self.max.0 - self.min.0, self.max.1 - self.min.1);
+        if self.radius <= 0.0 || size.0 <= 0.0 || size.1 <= 0.0 {
+            return Vec::new();
+        }
+
+        // Cells small enough to hold a single point each
+        let cell = self.radius / SQRT_2;
+        let columns = (size.0 / cell).ceil() as usize;
+        let rows = (size.1 / cell).ceil() as usize;
+        let cell_of = |p: (f32, f32)| {
+            let c = (((p.0 - self.min.0) / cell) as usize).min(columns - 1);
+            let r = (((p.1 - self.min.1) / cell) as usize).min(rows - 1);
+            (c, r)
+        };
+        let mut grid: Vec<Option<usize>> = vec![None; columns * rows];
+        let mut points: Vec<(f32, f32)> = Vec::new();
+        let mut active: Vec<usize> = Vec::new();
+        let mut rng = Rng::new(self.seed);
+
+        let first = (
+            rng.range(self.min.0, self.max.0),
+            rng.range(self.min.1, self.max.1),
+        );
+        let (c, r) = cell_of(first);
+        grid[r * columns + c] = Some(0);
+        points.push(first);
+        active.push(0);
+
+        while !active.is_empty() {
+            let slot = rng.below(active.len());
+            let p = points[active[slot]];
+            let r = radius_at(p);
+
+            let mut found = None;
+            for _ in 0..self.attempts {
+                // Uniform over the ring r..2r around p
+                let a = rng.range(0.0, 2.0 * PI);
+                let d = r * (1.0 + 3.0 * rng.next_f32()).sqrt();
+                let q = (p.0 + d * a.cos(), p.1 + d * a.sin());
+                if q.0 < self.min.0 || q.0 >= self.max.0 || q.1 < self.min.1 || q.1 >= self.max.1 {
+                    continue;
+                }
+
+                let rq = radius_at(q);
+                let reach = (rq / cell).ceil() as usize;
+                let (qc, qr) = cell_of(q);
+                let crowded = (qr.saturating_sub(reach)..(qr + reach + 1).min(rows)).any(|r| {
+                    (qc.saturating_sub(reach)..(qc + reach + 1).min(columns)).any(|c| {
+                        grid[r * columns + c].is_some_and(|i| path::dist(points[i], q) < rq)
+                    })
+                });
+                if !crowded {
+                    found = Some(q);
+                    break;
+                }
+            }
+
+            match found {
+                Some(q) => {
+                    let (c, r) = cell_of(q);
+                    grid[r * columns + c] = Some(points.len());
+                    active.push(points.len());
+                    points.push(q);
+                }
+                None => {
+                    active.swap_remove(slot);
+                }
+            }
+        }
+        points
+    }
+}
+
+// Density from the darkness of an image stretched over min..max, for
+// sample_weighted. The top row of pixels is at max.1.
+pub fn darkness(
+    bitmap: &Bitmap,
+    min: (f32, f32),
+    max: (f32, f32),
+) -> impl Fn((f32, f32)) -> f32 + '_ {
+    move |(x, y)| {
+        let u = (x - min.0) / (max.0 - min.0);
+        let v = (max.1 - y) / (max.1 - min.1);
+        let px = (u * bitmap.width as f32).floor() as i64;
+        let py = (v * bitmap.height as f32).floor() as i64;
+        let px = px.clamp(0, bitmap.width as i64 - 1);
+        let py = py.clamp(0, bitmap.height as i64 - 1);
+        1.0 - bitmap.brightness(px, py)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn closest(points: &[(f32, f32)]) -> f32 {
+        let mut min = f32::MAX;
+        for (i, &a) in points.iter().enumerate() {
+            for &b in &points[i + 1..] {
+                min = min.min(path::dist(a, b));
+            }
+        }
+        min
+    }
+
+    #[test]
+    fn evenly_spaced() {
+        let disk = PoissonDisk::new((0.0, 0.0), (50.0, 30.0), 2.0, 1);
+        let points = disk.sample();
+        assert!(closest(&points) >= 2.0);
+        assert!(
+            points
+                .iter()
+                .all(|&p| path::inside_rect(p, disk.min, disk.max))
+        );
+
+        // No holes: everywhere is within twice the radius of a point
+        for i in 0..50 {
+            for j in 0..30 {
+                let p = (i as f32 + 0.5, j as f32 + 0.5);
+                assert!(points.iter().any(|&q| path::dist(p, q) < 4.0), "{:?}", p);
+            }
+        }
+        assert_eq!(points, disk.sample());
+    }
+
+    #[test]
+    fn denser_where_darker() {
+        // Black on the left, white on the right
+        let mut bitmap = Bitmap::new(2, 1, [255, 255, 255]);
+        bitmap.set(0, 0, [0, 0, 0]);
+        let disk = PoissonDisk::new((0.0, 0.0), (40.0, 20.0), 1.0, 3);
+        let points = disk.sample_weighted(3.0, darkness(&bitmap, disk.min, disk.max));
+
+        assert!(closest(&points) >= 1.0);
+        let left = points.iter().filter(|p| p.0 < 20.0).count();
+        let right = points.len() - left;
+        assert!(left > 4 * right, "{} {}", left, right);
+    }
+}
@@ -0,0 +1,894 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::rng::Rng;
+use crate::{AffineTransform, Printer};
+
+// Small scripts that draw on a printer, to try out a sketch without
+// compiling anything. The language looks a little like Rhai:
+//
+//     // A ring of circles
+//     let n = 12;
+//     for i in 0..n {
+//         let a = rad(i * 360 / n);
+//         circle(width / 2 + 40 * cos(a), height / 2 + 40 * sin(a), 10);
+//     }
+//
+// There are numbers, strings and booleans, `let`, assignments (also +=,
+// -=, *= and /=), if/else, while and for over a range a..b, and the usual
+// arithmetic, comparison and logical operators. `width` and `height` hold
+// the size of the work area and PI is π. The functions are those of
+// Interpreter::call.
+
+// Statements and loop rounds a script may take, against endless loops
+const MAX_STEPS: u64 = 50_000_000;
+// Blocks, brackets and operators a script may nest, so deep nesting is an
+// error rather than a stack overflow
+const MAX_DEPTH: usize = 100;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScriptError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ScriptError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for ScriptError {}
+
+fn error<T>(line: usize, message: impl Into<String>) -> Result<T, ScriptError> {
+    Err(ScriptError {
+        line,
+        message: message.into(),
+    })
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(f64),
+    Str(String),
+    Ident(String),
+    Punct(&'static str),
+}
+
+// Longer ones first, so "<=" isn't read as "<" and "="
+const PUNCTS: [&str; 26] = [
+    "..", "==", "!=", "<=", ">=", "&&", "||", "+=", "-=", "*=", "/=", "+", "-", "*", "/", "%", "<",
+    ">", "=", "!", "(", ")", "{", "}", ",", ";",
+];
+
+fn tokenize(source: &str) -> Result<Vec<(Token, usize)>, ScriptError> {
+    let chars: Vec<char> = source.chars().collect();
+    let mut tokens = Vec::new();
+    let mut line = 1;
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let next = chars.get(i + 1).copied();
+        if c == '\n' {
+            line += 1;
+            i += 1;
+        } else if c.is_whitespace() {
+            i += 1;
+        } else if c == '/' && next == Some('/') {
+            while i < chars.len() && chars[i] != '\n' {
+                i += 1;
+            }
+        } else if c.is_ascii_digit() {
+            let start = i;
+            // The dot of 0..5 isn't a decimal point
+            while i < chars.len()
+                && (chars[i].is_ascii_digit() || chars[i] == '.' && chars.get(i + 1) != Some(&'.'))
+            {
+                i += 1;
+            }
+            let text: String = chars[start..i].iter().collect();
+            match text.parse() {
+                Ok(n) => tokens.push((Token::Number(n), line)),
+                Err(_) => return error(line, format!("invalid number {}", text)),
+            }
+        } else if c == '"' {
+            let mut text = String::new();
+            i += 1;
+            loop {
+                match chars.get(i) {
+                    None | Some('\n') => return error(line, "unterminated string"),
+                    Some('"') => break,
+                    Some('\\') => {
+                        text.push(match chars.get(i + 1) {
+                            Some('n') => '\n',
+                            Some(&c @ ('"' | '\\')) => c,
+                            _ => return error(line, "invalid escape in string"),
+                        });
+                        i += 2;
+                    }
+                    Some(&c) => {
+                        text.push(c);
+                        i += 1;
+                    }
+                }
+            }
+            i += 1;
+            tokens.push((Token::Str(text), line));
+        } else if c.is_alphabetic() || c == '_' {
+            let start = i;
+            while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+                i += 1;
+            }
+            tokens.push((Token::Ident(chars[start..i].iter().collect()), line));
+        } else {
+            let rest: String = chars[i..chars.len().min(i + 2)].iter().collect();
+            let Some(p) = PUNCTS.iter().find(|p| rest.starts_with(**p)) else {
+                return error(line, format!("unexpected {}", c));
+            };
+            tokens.push((Token::Punct(p), line));
+            i += p.len();
+        }
+    }
+    Ok(tokens)
+}
+
+#[derive(Debug, Clone)]
+enum Expr {
+    Number(f64, usize),
+    Str(String, usize),
+    Bool(bool, usize),
+    Var(String, usize),
+    Unary(&'static str, Box<Expr>, usize),
+    Binary(&'static str, Box<Expr>, Box<Expr>, usize),
+    Call(String, Vec<Expr>, usize),
+}
+
+#[derive(Debug, Clone)]
+enum Stmt {
+    Let(String, Expr),
+    // Plain assignments have "=" as the operator
+    Assign(String, &'static str, Expr, usize),
+    Expr(Expr),
+    If(Expr, Vec<Stmt>, Vec<Stmt>),
+    While(Expr, Vec<Stmt>),
+    For(String, Expr, Expr, Vec<Stmt>),
+}
+
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    pos: usize,
+    depth: usize,
+}
+
+impl Parser {
+    // Runs `parse` one level deeper
+    fn nested<T>(
+        &mut self,
+        parse: impl FnOnce(&mut Self) -> Result<T, ScriptError>,
+    ) -> Result<T, ScriptError> {
+        if self.depth == MAX_DEPTH {
+            return error(self.line(), "nested too deeply");
+        }
+        self.depth += 1;
+        let result = parse(self);
+        self.depth -= 1;
+        result
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|(t, _)| t)
+    }
+
+    fn line(&self) -> usize {
+        self.tokens
+            .get(self.pos)
+            .or(self.tokens.last())
+            .map_or(1, |(_, line)| *line)
+    }
+
+    fn is(&self, punct: &str) -> bool {
+        matches!(self.peek(), Some(Token::Punct(p)) if *p == punct)
+    }
+
+    fn keyword(&self, word: &str) -> bool {
+        matches!(self.peek(), Some(Token::Ident(w)) if w == word)
+    }
+
+    // Takes `punct` if it's next
+    fn eat(&mut self, punct: &str) -> bool {
+        let found = self.is(punct);
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    fn expect(&mut self, punct: &str) -> Result<(), ScriptError> {
+        if self.eat(punct) {
+            Ok(())
+        } else {
+            error(self.line(), format!("expected {}", punct))
+        }
+    }
+
+    fn ident(&mut self) -> Result<String, ScriptError> {
+        match self.peek() {
+            Some(Token::Ident(name)) => {
+                let name = name.clone();
+                self.pos += 1;
+                Ok(name)
+            }
+            _ => error(self.line(), "expected a name"),
+        }
+    }
+
+    fn program(&mut self) -> Result<Vec<Stmt>, ScriptError> {
+        let mut stmts = Vec::new();
+        while self.peek().is_some() {
+            stmts.push(self.statement()?);
+        }
+        Ok(stmts)
+    }
+
+    fn block(&mut self) -> Result<Vec<Stmt>, ScriptError> {
+        self.nested(Self::block_body)
+    }
+
+    fn block_body(&mut self) -> Result<Vec<Stmt>, ScriptError> {
+        self.expect("{")?;
+        let mut stmts = Vec::new();
+        while !self.eat("}") {
+            if self.peek().is_none() {
+                return error(self.line(), "expected }");
+            }
+            stmts.push(self.statement()?);
+        }
+        Ok(stmts)
+    }
+
+    // The ; after a simple statement, which may be left out before a }
+    fn end(&mut self) -> Result<(), ScriptError> {
+        if self.eat(";") || self.is("}") || self.peek().is_none() {
+            Ok(())
+        } else {
+            error(self.line(), "expected ;")
+        }
+    }
+
+    fn statement(&mut self) -> Result<Stmt, ScriptError> {
+        let line = self.line();
+        if self.keyword("let") {
+            self.pos += 1;
+            let name = self.ident()?;
+            self.expect("=")?;
+            let value = self.expression()?;
+            self.end()?;
+            return Ok(Stmt::Let(name, value));
+        }
+        if self.keyword("if") {
+            return self.if_statement();
+        }
+        if self.keyword("while") {
+            self.pos += 1;
+            let condition = self.expression()?;
+            return Ok(Stmt::While(condition, self.block()?));
+        }
+        if self.keyword("for") {
+            self.pos += 1;
+            let name = self.ident()?;
+            if !self.keyword("in") {
+                return error(self.line(), "expected in");
+            }
+            self.pos += 1;
+            let from = self.expression()?;
+            self.expect("..")?;
+            let to = self.expression()?;
+            return Ok(Stmt::For(name, from, to, self.block()?));
+        }
+        if let (Some(Token::Ident(name)), Some((Token::Punct(op), _))) =
+            (self.peek(), self.tokens.get(self.pos + 1))
+            && ["=", "+=", "-=", "*=", "/="].contains(op)
+        {
+            let (name, op) = (name.clone(), *op);
+            self.pos += 2;
+            let value = self.expression()?;
+            self.end()?;
+            return Ok(Stmt::Assign(name, op, value, line));
+        }
+        let expr = self.expression()?;
+        self.end()?;
+        Ok(Stmt::Expr(expr))
+    }
+
+    fn if_statement(&mut self) -> Result<Stmt, ScriptError> {
+        self.pos += 1;
+        let condition = self.expression()?;
+        let then = self.block()?;
+        let otherwise = if self.keyword("else") {
+            self.pos += 1;
+            if self.keyword("if") {
+                vec![self.nested(Self::if_statement)?]
+            } else {
+                self.block()?
+            }
+        } else {
+            Vec::new()
+        };
+        Ok(Stmt::If(condition, then, otherwise))
+    }
+
+    fn expression(&mut self) -> Result<Expr, ScriptError> {
+        self.nested(|p| p.binary(0))
+    }
+
+    // Operators from the loosest binding to the tightest
+    fn binary(&mut self, level: usize) -> Result<Expr, ScriptError> {
+        const LEVELS: [&[&str]; 5] = [
+            &["||"],
+            &["&&"],
+            &["==", "!=", "<", "<=", ">", ">="],
+            &["+", "-"],
+            &["*", "/", "%"],
+        ];
+        if level == LEVELS.len() {
+            return self.unary();
+        }
+        let mut left = self.binary(level + 1)?;
+        while let Some(Token::Punct(op)) = self.peek()
+            && LEVELS[level].contains(op)
+        {
+            let (op, line) = (*op, self.line());
+            self.pos += 1;
+            let right = self.binary(level + 1)?;
+            left = Expr::Binary(op, Box::new(left), Box::new(right), line);
+        }
+        Ok(left)
+    }
+
+    fn unary(&mut self) -> Result<Expr, ScriptError> {
+        let line = self.line();
+        for op in ["-", "!"] {
+            if self.eat(op) {
+                let operand = self.nested(Self::unary)?;
+                return Ok(Expr::Unary(op, Box::new(operand), line));
+            }
+        }
+        self.primary()
+    }
+
+    fn primary(&mut self) -> Result<Expr, ScriptError> {
+        let line = self.line();
+        let Some(token) = self.peek().cloned() else {
+            return error(line, "unexpected end of script");
+        };
+        self.pos += 1;
+        match token {
+            Token::Number(n) => Ok(Expr::Number(n, line)),
+            Token::Str(s) => Ok(Expr::Str(s, line)),
+            Token::Ident(w) if w == "true" || w == "false" => Ok(Expr::Bool(w == "true", line)),
+            Token::Ident(name) if self.eat("(") => {
+                let mut args = Vec::new();
+                while !self.eat(")") {
+                    if !args.is_empty() {
+                        self.expect(",")?;
+                    }
+                    args.push(self.expression()?);
+                }
+                Ok(Expr::Call(name, args, line))
+            }
+            Token::Ident(name) => Ok(Expr::Var(name, line)),
+            Token::Punct("(") => {
+                let expr = self.expression()?;
+                self.expect(")")?;
+                Ok(expr)
+            }
+            Token::Punct(p) => error(line, format!("unexpected {}", p)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Value {
+    Number(f64),
+    Str(String),
+    Bool(bool),
+    Nothing,
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Value::Number(n) => write!(f, "{}", n),
+            Value::Str(s) => write!(f, "{}", s),
+            Value::Bool(b) => write!(f, "{}", b),
+            Value::Nothing => write!(f, "()"),
+        }
+    }
+}
+
+struct Interpreter<'a> {
+    printer: &'a mut Printer,
+    on_print: &'a mut dyn FnMut(&str),
+    scopes: Vec<HashMap<String, Value>>,
+    rng: Rng,
+    // The polyline of move_to and line_to still to be drawn
+    path: Vec<(f32, f32)>,
+    // Transforms pushed by the script, popped again at the end
+    transforms: usize,
+    steps: u64,
+}
+
+impl Interpreter<'_> {
+    fn step(&mut self, line: usize) -> Result<(), ScriptError> {
+        self.steps += 1;
+        if self.steps > MAX_STEPS {
+            return error(line, "the script takes too long, is there an endless loop?");
+        }
+        Ok(())
+    }
+
+    fn block(&mut self, stmts: &[Stmt]) -> Result<(), ScriptError> {
+        self.scopes.push(HashMap::new());
+        let result = stmts.iter().try_for_each(|s| self.statement(s));
+        self.scopes.pop();
+        result
+    }
+
+    fn condition(&mut self, expr: &Expr) -> Result<bool, ScriptError> {
+        match self.eval(expr)? {
+            Value::Bool(b) => Ok(b),
+            other => error(line_of(expr), format!("{} is not true or false", other)),
+        }
+    }
+
+    fn number(&mut self, expr: &Expr) -> Result<f64, ScriptError> {
+        match self.eval(expr)? {
+            Value::Number(n) => Ok(n),
+            other => error(line_of(expr), format!("{} is not a number", other)),
+        }
+    }
+
+    fn statement(&mut self, stmt: &Stmt) -> Result<(), ScriptError> {
+        match stmt {
+            Stmt::Let(name, expr) => {
+                let value = self.eval(expr)?;
+                self.scopes.last_mut().unwrap().insert(name.clone(), value);
+            }
+            Stmt::Assign(name, op, expr, line) => {
+                let mut value = self.eval(expr)?;
+                if *op != "=" {
+                    let current = self.variable(name, *line)?;
+                    value = binary(&op[..1], current, value, *line)?;
+                }
+                let Some(slot) = self.scopes.iter_mut().rev().find_map(|s| s.get_mut(name)) else {
+                    return error(*line, format!("unknown variable {}, missing let?", name));
+                };
+                *slot = value;
+            }
+            Stmt::Expr(expr) => {
+                self.eval(expr)?;
+            }
+            Stmt::If(condition, then, otherwise) => {
+                if self.condition(condition)? {
+                    self.block(then)?;
+                } else {
+                    self.block(otherwise)?;
+                }
+            }
+            Stmt::While(condition, body) => {
+                while self.condition(condition)? {
+                    self.step(line_of(condition))?;
+                    self.block(body)?;
+                }
+            }
+            Stmt::For(name, from, to, body) => {
+                let (from, to) = (self.number(from)?, self.number(to)?);
+                let mut i = from;
+                while i < to {
+                    self.step(line_of_stmts(body))?;
+                    self.scopes
+                        .push(HashMap::from([(name.clone(), Value::Number(i))]));
+                    let result = self.block(body);
+                    self.scopes.pop();
+                    result?;
+                    i += 1.0;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn variable(&self, name: &str, line: usize) -> Result<Value, ScriptError> {
+        match self.scopes.iter().rev().find_map(|s| s.get(name)) {
+            Some(value) => Ok(value.clone()),
+            None => error(line, format!("unknown variable {}", name)),
+        }
+    }
+
+    fn eval(&mut self, expr: &Expr) -> Result<Value, ScriptError> {
+        match expr {
+            Expr::Number(n, _) => Ok(Value::Number(*n)),
+            Expr::Str(s, _) => Ok(Value::Str(s.clone())),
+            Expr::Bool(b, _) => Ok(Value::Bool(*b)),
+            Expr::Var(name, line) => self.variable(name, *line),
+            Expr::Unary(op, operand, line) => match (*op, self.eval(operand)?) {
+                ("-", Value::Number(n)) => Ok(Value::Number(-n)),
+                ("!", Value::Bool(b)) => Ok(Value::Bool(!b)),
+                (op, value) => error(*line, format!("can't apply {} to {}", op, value)),
+            },
+            // Both sides of && and || are only worked out when needed
+            Expr::Binary(op @ ("&&" | "||"), left, right, _) => {
+                let left = self.condition(left)?;
+                if left == (*op == "||") {
+                    Ok(Value::Bool(left))
+                } else {
+                    Ok(Value::Bool(self.condition(right)?))
+                }
+            }
+            Expr::Binary(op, left, right, line) => {
+                let left = self.eval(left)?;
+                let right = self.eval(right)?;
+                binary(op, left, right, *line)
+            }
+            Expr::Call(name, args, line) => {
+                self.step(*line)?;
+                let args = args
+                    .iter()
+                    .map(|a| self.eval(a))
+                    .collect::<Result<Vec<_>, _>>()?;
+                self.call(name, &args, *line)
+            }
+        }
+    }
+
+    fn stroke(&mut self) {
+        let path = std::mem::take(&mut self.path);
+        if path.len() > 1 {
+            self.printer.draw_polyline(&path);
+        }
+    }
+
+    // The functions scripts can call
+    fn call(&mut self, name: &str, args: &[Value], line: usize) -> Result<Value, ScriptError> {
+        let count = |n: usize| {
+            if args.len() == n {
+                Ok(())
+            } else {
+                error(line, format!("{} takes {} arguments", name, n))
+            }
+        };
+        let n = |i: usize| match &args[i] {
+            Value::Number(n) => Ok(*n as f32),
+            other => error(line, format!("{} needs numbers, not {}", name, other)),
+        };
+        let text = |i: usize| match &args[i] {
+            Value::Str(s) => Ok(s.clone()),
+            other => error(line, format!("{} needs text, not {}", name, other)),
+        };
+        let number = |v: f32| Ok(Value::Number(v as f64));
+
+        match name {
+            "sin" | "cos" | "tan" | "sqrt" | "abs" | "floor" | "ceil" | "round" | "rad" | "deg" => {
+                count(1)?;
+                let x = n(0)?;
+                number(match name {
+                    "sin" => x.sin(),
+                    "cos" => x.cos(),
+                    "tan" => x.tan(),
+                    "sqrt" => x.sqrt(),
+                    "abs" => x.abs(),
+                    "floor" => x.floor(),
+                    "ceil" => x.ceil(),
+                    "round" => x.round(),
+                    "rad" => x.to_radians(),
+                    _ => x.to_degrees(),
+                })
+            }
+            "atan2" | "pow" | "min" | "max" => {
+                count(2)?;
+                let (a, b) = (n(0)?, n(1)?);
+                number(match name {
+                    "atan2" => a.atan2(b),
+                    "pow" => a.powf(b),
+                    "min" => a.min(b),
+                    _ => a.max(b),
+                })
+            }
+            // random() is from 0 to 1, random(a, b) from a to b
+            "random" if args.is_empty() => number(self.rng.next_f32()),
+            "random" => {
+                count(2)?;
+                number(self.rng.range(n(0)?, n(1)?))
+            }
+            "point" => {
+                count(2)?;
+                self.printer.draw_point(n(0)?, n(1)?);
+                Ok(Value::Nothing)
+            }
+            "line" => {
+                count(4)?;
+                self.printer.draw_line(n(0)?, n(1)?, n(2)?, n(3)?);
+                Ok(Value::Nothing)
+            }
+            "circle" => {
+                count(3)?;
+                self.printer.draw_circle(n(0)?, n(1)?, n(2)?);
+                Ok(Value::Nothing)
+            }
+            // Angles in degrees, as Printer::draw_arc takes them
+            "arc" => {
+                count(5)?;
+                self.printer.draw_arc(n(0)?, n(1)?, n(2)?, n(3)?, n(4)?);
+                Ok(Value::Nothing)
+            }
+            "bezier" => {
+                count(8)?;
+                self.printer.draw_cubic_bezier(
+                    (n(0)?, n(1)?),
+                    (n(2)?, n(3)?),
+                    (n(4)?, n(5)?),
+                    (n(6)?, n(7)?),
+                );
+                Ok(Value::Nothing)
+            }
+            "text" => {
+                count(4)?;
+                self.printer.draw_text(n(0)?, n(1)?, n(2)?, &text(3)?);
+                Ok(Value::Nothing)
+            }
+            "layer" => {
+                count(1)?;
+                self.stroke();
+                self.printer.set_layer(&text(0)?);
+                Ok(Value::Nothing)
+            }
+            // A polyline point by point: move_to starts it, line_to adds to
+            // it and stroke draws it. Starting the next one or the end of
+            // the script draws it as well.
+            "move_to" => {
+                count(2)?;
+                self.stroke();
+                self.path.push((n(0)?, n(1)?));
+                Ok(Value::Nothing)
+            }
+            "line_to" => {
+                count(2)?;
+                if self.path.is_empty() {
+                    return error(line, "line_to without move_to");
+                }
+                self.path.push((n(0)?, n(1)?));
+                Ok(Value::Nothing)
+            }
+            "close" => {
+                count(0)?;
+                if let Some(&first) = self.path.first() {
+                    self.path.push(first);
+                }
+                self.stroke();
+                Ok(Value::Nothing)
+            }
+            "stroke" => {
+                count(0)?;
+                self.stroke();
+                Ok(Value::Nothing)
+            }
+            // Each pushes a transform, undone by pop_transform
+            "translate" | "rotate" | "scale" => {
+                let t = match (name, args.len()) {
+                    ("translate", 2) => AffineTransform::translate(n(0)?, n(1)?),
+                    ("rotate", 1) => AffineTransform::rotate_deg(n(0)?),
+                    ("scale", 1) => AffineTransform::scale(n(0)?, n(0)?),
+                    ("scale", 2) => AffineTransform::scale(n(0)?, n(1)?),
+                    _ => return error(line, format!("wrong number of arguments to {}", name)),
+                };
+                self.stroke();
+                self.printer.push_transform(t);
+                self.transforms += 1;
+                Ok(Value::Nothing)
+            }
+            "pop_transform" => {
+                count(0)?;
+                if self.transforms == 0 {
+                    return error(line, "pop_transform without a transform");
+                }
+                self.stroke();
+                self.printer.pop_transform();
+                self.transforms -= 1;
+                Ok(Value::Nothing)
+            }
+            "print" => {
+                let words: Vec<String> = args.iter().map(Value::to_string).collect();
+                (self.on_print)(&words.join(" "));
+                Ok(Value::Nothing)
+            }
+            _ => error(line, format!("unknown function {}", name)),
+        }
+    }
+}
+
+fn binary(op: &str, left: Value, right: Value, line: usize) -> Result<Value, ScriptError> {
+    use Value::{Bool, Number, Str};
+    Ok(match (op, left, right) {
+        ("+", Number(a), Number(b)) => Number(a + b),
+        ("-", Number(a), Number(b)) => Number(a - b),
+        ("*", Number(a), Number(b)) => Number(a * b),
+        ("/", Number(a), Number(b)) => Number(a / b),
+        ("%", Number(a), Number(b)) => Number(a.rem_euclid(b)),
+        ("<", Number(a), Number(b)) => Bool(a < b),
+        ("<=", Number(a), Number(b)) => Bool(a <= b),
+        (">", Number(a), Number(b)) => Bool(a > b),
+        (">=", Number(a), Number(b)) => Bool(a >= b),
+        ("+", Str(a), b) => Str(format!("{}{}", a, b)),
+        ("+", a, Str(b)) => Str(format!("{}{}", a, b)),
+        ("==", a, b) => Bool(a == b),
+        ("!=", a, b) => Bool(a != b),
+        (op, a, b) => return error(line, format!("can't work out {} {} {}", a, op, b)),
+    })
+}
+
+fn line_of(expr: &Expr) -> usize {
+    match expr {
+        Expr::Number(_, line)
+        | Expr::Str(_, line)
+        | Expr::Bool(_, line)
+        | Expr::Var(_, line)
+        | Expr::Unary(_, _, line)
+        | Expr::Binary(_, _, _, line)
+        | Expr::Call(_, _, line) => *line,
+    }
+}
+
+fn line_of_stmts(stmts: &[Stmt]) -> usize {
+    match stmts.first() {
+        Some(Stmt::Assign(_, _, _, line)) => *line,
+        Some(Stmt::Expr(e) | Stmt::Let(_, e) | Stmt::While(e, _) | Stmt::If(e, _, _)) => line_of(e),
+        Some(Stmt::For(_, e, _, _)) => line_of(e),
+        None => 0,
+    }
+}
+
+// Runs `source` on `printer`. random() draws from a generator seeded with
+// `seed`, so the same seed gives the same drawing. The lines of print()
+// go to `on_print`.
+pub fn run(
+    printer: &mut Printer,
+    source: &str,
+    seed: u64,
+    mut on_print: impl FnMut(&str),
+) -> Result<(), ScriptError> {
+    let mut parser = Parser {
+        tokens: tokenize(source)?,
+        pos: 0,
+        depth: 0,
+    };
+    let program = parser.program()?;
+    let globals = HashMap::from([
+        ("width".to_string(), Value::Number(printer.width as f64)),
+        ("height".to_string(), Value::Number(printer.height as f64)),
+        ("PI".to_string(), Value::Number(std::f64::consts::PI)),
+    ]);
+    let mut interpreter = Interpreter {
+        printer,
+        on_print: &mut on_print,
+        scopes: vec![globals],
+        rng: Rng::new(seed),
+        path: Vec::new(),
+        transforms: 0,
+        steps: 0,
+    };
+    let result = interpreter.block(&program);
+    interpreter.stroke();
+    for _ in 0..interpreter.transforms {
+        interpreter.printer.pop_transform();
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::PrinterConfig;
+    use crate::path;
+
+    fn drawn(source: &str) -> Result<Printer, ScriptError> {
+        let mut printer = Printer::new(PrinterConfig::default());
+        run(&mut printer, source, 1, |_| {})?;
+        Ok(printer)
+    }
+
+    #[test]
+    fn drawing() {
+        let printer = drawn(
+            r#"
+            // A ring of circles, a square and a caption
+            let n = 6;
+            for i in 0..n {
+                let a = rad(i * 360 / n);
+                circle(100 + 40 * cos(a), 80 + 40 * sin(a), 5);
+            }
+            translate(10, 10);
+            move_to(0, 0); line_to(20, 0); line_to(20, 20); line_to(0, 20); close();
+            pop_transform();
+            let label = "n = " + n;
+            if n > 5 && !(n == 7) { text(10, 150, 5, label) } else { point(0, 0) }
+            "#,
+        )
+        .unwrap();
+        let ops = printer.ops();
+        assert_eq!(
+            ops.iter()
+                .filter(|op| op.label.as_deref() == Some("draw_text(\"n = 6\")"))
+                .count(),
+            1
+        );
+        let square = ops.iter().find(|op| op.path().len() == 5).unwrap();
+        assert_eq!(square.path()[2], (30.0, 30.0));
+        assert!(printer.transform() == AffineTransform::IDENTITY);
+        let circles = ops.iter().filter(|op| {
+            op.label
+                .as_deref()
+                .is_some_and(|l| l.starts_with("draw_circle"))
+        });
+        assert_eq!(circles.count(), 6);
+    }
+
+    #[test]
+    fn loops_and_variables() {
+        let printer = drawn(
+            "let x = 0; let total = 0;
+             while x < 10 { x += 1; total = total + x % 3; }
+             for i in 0..total { point(i, x) }",
+        )
+        .unwrap();
+        // 1 + 2 + 0 + 1 + 2 + 0 + 1 + 2 + 0 + 1
+        assert_eq!(printer.ops().len(), 10);
+        assert_eq!(printer.ops()[9].path(), [(9.0, 10.0)]);
+
+        // The same seed draws the same
+        let source = "for i in 0..5 { point(random(0, width), random() * height) }";
+        let paths =
+            |p: &Printer| -> Vec<path::Path> { p.ops().iter().map(|op| op.path()).collect() };
+        assert_eq!(
+            paths(&drawn(source).unwrap()),
+            paths(&drawn(source).unwrap())
+        );
+    }
+
+    #[test]
+    fn errors() {
+        let message = |source: &str| drawn(source).err().unwrap().to_string();
+        assert_eq!(
+            message("let a = 1;\nb = 2;"),
+            "line 2: unknown variable b, missing let?"
+        );
+        assert_eq!(message("circle(1, 2)"), "line 1: circle takes 3 arguments");
+        assert_eq!(
+            message("point(1, \"two\")"),
+            "line 1: point needs numbers, not two"
+        );
+        assert_eq!(message("let a = 1 +;"), "line 1: unexpected ;");
+        assert_eq!(message("if 1 { }"), "line 1: 1 is not true or false");
+        assert_eq!(message("\n\nlet s = \"open"), "line 3: unterminated string");
+        assert!(message("while true { }").contains("endless loop"));
+
+        // Too deep to parse without running out of stack
+        let deep = format!("let a = {}1{};", "(".repeat(100_000), ")".repeat(100_000));
+        assert_eq!(message(&deep), "line 1: nested too deeply");
+        assert_eq!(
+            message(&format!("let a = {}1;", "-".repeat(100_000))),
+            "line 1: nested too deeply"
+        );
+        let blocks = format!("{}{}", "if true {".repeat(100_000), "}".repeat(100_000));
+        assert_eq!(message(&blocks), "line 1: nested too deeply");
+        assert!(drawn(&format!("let a = {}1{};", "(".repeat(50), ")".repeat(50))).is_ok());
+    }
+
+    #[test]
+    fn printing() {
+        let mut lines = Vec::new();
+        let mut printer = Printer::new(PrinterConfig::default());
+        let source = "let n = 3; print(\"n is\", n); print(n > 2);";
+        run(&mut printer, source, 1, |line| lines.push(line.to_string())).unwrap();
+        assert_eq!(lines, ["n is 3", "true"]);
+    }
+}
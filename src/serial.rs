@@ -0,0 +1,160 @@
+use std::fs::File;
+use std::io;
+
+// Sets up a serial port for talking to firmware: raw 8N1 at `baud`, reads
+// waiting for at least one byte, and the modem lines ignored so the port
+// works without them. Calls termios directly, on Linux only for now.
+#[cfg(target_os = "linux")]
+pub fn configure(port: &File, baud: u32) -> Result<(), io::Error> {
+    use std::os::fd::AsRawFd;
+
+    let speed = linux::speed(baud).ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("unsupported baud rate {}", baud),
+        )
+    })?;
+    let fd = port.as_raw_fd();
+    let check = |result| match result {
+        0 => Ok(()),
+        _ => Err(io::Error::last_os_error()),
+    };
+    let mut termios = linux::Termios::default();
+    // SAFETY: fd is open for the length of the calls and termios matches
+    // the C struct
+    unsafe {
+        check(linux::tcgetattr(fd, &mut termios))?;
+        linux::cfmakeraw(&mut termios);
+        check(linux::cfsetispeed(&mut termios, speed))?;
+        check(linux::cfsetospeed(&mut termios, speed))?;
+    }
+    termios.c_cflag |= linux::CLOCAL | linux::CREAD;
+    termios.c_cflag &= !(linux::CSTOPB | linux::CRTSCTS);
+    termios.c_cc[linux::VMIN] = 1;
+    termios.c_cc[linux::VTIME] = 0;
+    // SAFETY: as above
+    unsafe { check(linux::tcsetattr(fd, linux::TCSANOW, &termios)) }
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn configure(_port: &File, _baud: u32) -> Result<(), io::Error> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "serial ports can only be set up on Linux, set the port up with stty \
+         and use live::Sender::new",
+    ))
+}
+
+#[cfg(target_os = "linux")]
+mod linux {
+    use std::ffi::c_int;
+
+    // struct termios of glibc and musl
+    #[repr(C)]
+    #[derive(Debug, Default, Copy, Clone)]
+    pub struct Termios {
+        pub c_iflag: u32,
+        pub c_oflag: u32,
+        pub c_cflag: u32,
+        pub c_lflag: u32,
+        pub c_line: u8,
+        pub c_cc: [u8; 32],
+        pub c_ispeed: u32,
+        pub c_ospeed: u32,
+    }
+
+    pub const TCSANOW: c_int = 0;
+    pub const VTIME: usize = 5;
+    pub const VMIN: usize = 6;
+    pub const CSTOPB: u32 = 0o100;
+    pub const CREAD: u32 = 0o200;
+    pub const CLOCAL: u32 = 0o4000;
+    pub const CRTSCTS: u32 = 0o20000000000;
+
+    // The B constant of a baud rate. Rates like Marlin's 250000 need
+    // termios2, which isn't supported.
+    pub fn speed(baud: u32) -> Option<u32> {
+        Some(match baud {
+            9600 => 0o15,
+            19200 => 0o16,
+            38400 => 0o17,
+            57600 => 0o10001,
+            115200 => 0o10002,
+            230400 => 0o10003,
+            460800 => 0o10004,
+            500000 => 0o10005,
+            921600 => 0o10007,
+            1000000 => 0o10010,
+            _ => return None,
+        })
+    }
+
+    unsafe extern "C" {
+        pub fn tcgetattr(fd: c_int, termios: *mut Termios) -> c_int;
+        pub fn tcsetattr(fd: c_int, actions: c_int, termios: *const Termios) -> c_int;
+        pub fn cfmakeraw(termios: *mut Termios);
+        pub fn cfsetispeed(termios: *mut Termios, speed: u32) -> c_int;
+        pub fn cfsetospeed(termios: *mut Termios, speed: u32) -> c_int;
+    }
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+    use crate::live::Sender;
+    use std::ffi::{c_char, c_int};
+    use std::io::{Read, Write};
+    use std::os::fd::{AsRawFd, FromRawFd};
+
+    unsafe extern "C" {
+        fn posix_openpt(flags: c_int) -> c_int;
+        fn grantpt(fd: c_int) -> c_int;
+        fn unlockpt(fd: c_int) -> c_int;
+        fn ptsname_r(fd: c_int, name: *mut c_char, len: usize) -> c_int;
+        fn cfgetospeed(termios: *const linux::Termios) -> u32;
+    }
+
+    // A pseudo terminal standing in for the port: the controlling side and
+    // the path of the device end
+    fn pty() -> (File, String) {
+        const O_RDWR: c_int = 2;
+        const O_NOCTTY: c_int = 0o400;
+        let mut name = [0 as c_char; 128];
+        unsafe {
+            let fd = posix_openpt(O_RDWR | O_NOCTTY);
+            assert!(fd >= 0);
+            assert_eq!(grantpt(fd), 0);
+            assert_eq!(unlockpt(fd), 0);
+            assert_eq!(ptsname_r(fd, name.as_mut_ptr(), name.len()), 0);
+            let path = std::ffi::CStr::from_ptr(name.as_ptr());
+            (File::from_raw_fd(fd), path.to_string_lossy().into_owned())
+        }
+    }
+
+    #[test]
+    fn sends_over_a_configured_port() {
+        let (mut firmware, path) = pty();
+        let mut sender = Sender::open(&path, 115200).unwrap();
+
+        let port = File::open(&path).unwrap();
+        let mut termios = linux::Termios::default();
+        unsafe {
+            assert_eq!(linux::tcgetattr(port.as_raw_fd(), &mut termios), 0);
+            assert_eq!(cfgetospeed(&termios), 0o10002);
+        }
+        // Raw: no line editing or echo
+        const ICANON: u32 = 0o2;
+        const ECHO: u32 = 0o10;
+        assert_eq!(termios.c_lflag & (ICANON | ECHO), 0);
+        assert_ne!(termios.c_cflag & linux::CLOCAL, 0);
+
+        firmware.write_all(b"ok\n").unwrap();
+        sender.send("G28 W").unwrap();
+        let mut sent = [0; 6];
+        firmware.read_exact(&mut sent).unwrap();
+        assert_eq!(&sent, b"G28 W\n");
+
+        let err = Sender::open(&path, 250000).err().unwrap();
+        assert_eq!(err.to_string(), "unsupported baud rate 250000");
+    }
+}
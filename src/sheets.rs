@@ -0,0 +1,253 @@
+use crate::Error;
+use crate::path::Path;
+
+// Splits artwork that is larger than the bed across several sheets of paper.
+// Neighboring sheets overlap, and crosshairs in the overlaps are drawn on
+// both sheets so they can be lined up when the sheets are put together.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Sheets {
+    // Size of one sheet's drawing area, in mm. Set by new, which checks it
+    // against the overlap.
+    pub(crate) sheet: (f32, f32),
+    // Width of the strip drawn on both neighbors, in mm
+    pub(crate) overlap: f32,
+    // Arm length of the alignment crosshairs, in mm
+    pub mark: f32,
+}
+
+// One sheet of the artwork, counted from the bottom left
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Sheet {
+    pub row: usize,
+    pub column: usize,
+    // Bottom left corner in artwork coordinates
+    pub origin: (f32, f32),
+}
+
+impl Sheets {
+    // Fails unless the sheets have a size and the overlap leaves some of
+    // every sheet to itself
+    pub fn new(sheet: (f32, f32), overlap: f32) -> Result<Self, Error> {
+        let invalid = |message: String| Err(Error::Bounds(message));
+        if !(sheet.0 > 0.0 && sheet.1 > 0.0) {
+            return invalid(format!("invalid sheet size {} x {}mm", sheet.0, sheet.1));
+        }
+        if overlap.is_nan() || overlap < 0.0 {
+            return invalid(format!("invalid overlap {}mm", overlap));
+        }
+        if overlap >= sheet.0.min(sheet.1) {
+            return invalid(format!(
+                "an overlap of {}mm doesn't fit sheets of {} x {}mm",
+                overlap, sheet.0, sheet.1
+            ));
+        }
+        Ok(Sheets {
+            sheet,
+            overlap,
+            mark: (overlap / 2.0).min(5.0),
+        })
+    }
+
+    pub fn sheet(&self) -> (f32, f32) {
+        self.sheet
+    }
+
+    pub fn overlap(&self) -> f32 {
+        self.overlap
+    }
+
+    fn step(&self) -> (f32, f32) {
+        (self.sheet.0 - self.overlap, self.sheet.1 - self.overlap)
+    }
+
+    // Sheets needed to cover artwork of `size`, row by row
+    pub fn plan(&self, size: (f32, f32)) -> Vec<Sheet> {
+        let step = self.step();
+        let count = |size: f32, sheet: f32, step: f32| {
+            (((size - sheet) / step).ceil().max(0.0) as usize) + 1
+        };
+        let columns = count(size.0, self.sheet.0, step.0);
+        let rows = count(size.1, self.sheet.1, step.1);
+        self.grid(columns, rows)
+    }
+
+    // `columns` by `rows` sheets from the origin on, row by row
+    pub fn grid(&self, columns: usize, rows: usize) -> Vec<Sheet> {
+        let step = self.step();
+        (0..rows)
+            .flat_map(|row| {
+                (0..columns).map(move |column| Sheet {
+                    row,
+                    column,
+                    origin: (column as f32 * step.0, row as f32 * step.1),
+                })
+            })
+            .collect()
+    }
+
+    // Crosshairs in artwork coordinates, two along every overlap between
+    // neighbors, each one on both sheets
+    pub fn marks(&self, sheets: &[Sheet]) -> Vec<Path> {
+        let mut centers = Vec::new();
+        for s in sheets {
+            let (x0, y0) = s.origin;
+            let (x1, y1) = (x0 + self.sheet.0, y0 + self.sheet.1);
+            let has = |row, column| sheets.iter().any(|o| o.row == row && o.column == column);
+
+            // Right neighbor: the strip is x1 - overlap..x1
+            if has(s.row, s.column + 1) {
+                let x = x1 - self.overlap / 2.0;
+                centers.extend([(x, y0 + self.sheet.1 / 4.0), (x, y1 - self.sheet.1 / 4.0)]);
+            }
+            if has(s.row + 1, s.column) {
+                let y = y1 - self.overlap / 2.0;
+                centers.extend([(x0 + self.sheet.0 / 4.0, y), (x1 - self.sheet.0 / 4.0, y)]);
+            }
+        }
+
+        let arm = self.mark;
+        centers
+            .into_iter()
+            .flat_map(|(x, y)| {
+                [
+                    vec![(x - arm, y), (x + arm, y)],
+                    vec![(x, y - arm), (x, y + arm)],
+                ]
+            })
+            .collect()
+    }
+
+    // Whether the whole path lies on the sheet
+    pub fn on_sheet(&self, sheet: &Sheet, path: &[(f32, f32)]) -> bool {
+        let (x0, y0) = sheet.origin;
+        path.iter().all(|&(x, y)| {
+            x >= x0 - 1e-4
+                && x <= x0 + self.sheet.0 + 1e-4
+                && y >= y0 - 1e-4
+                && y <= y0 + self.sheet.1 + 1e-4
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{assert_within, temp_path, test_config};
+    use crate::{Printer, Shape, path};
+
+    #[test]
+    fn plan_and_marks() {
+        // Two A4 sheets side by side for a 400mm wide artwork
+        let sheets = Sheets::new((297.0, 210.0), 20.0).unwrap();
+        let plan = sheets.plan((400.0, 200.0));
+        assert_eq!(plan.len(), 2);
+        assert_eq!(plan[1].origin, (277.0, 0.0));
+
+        // Both sheets get both crosshairs
+        let marks = sheets.marks(&plan);
+        assert_eq!(marks.len(), 4);
+        for sheet in &plan {
+            assert!(marks.iter().all(|m| sheets.on_sheet(sheet, m)));
+        }
+
+        // Exactly one sheet wide and 2x2
+        assert_eq!(sheets.plan((297.0, 210.0)).len(), 1);
+        let plan = sheets.plan((500.0, 300.0));
+        assert_eq!(plan.len(), 4);
+        assert_eq!(sheets.marks(&plan).len(), 4 * 2 * 2);
+    }
+
+    #[test]
+    fn invalid_sheets() {
+        let message = |sheet, overlap| Sheets::new(sheet, overlap).unwrap_err().to_string();
+        assert_eq!(
+            message((297.0, 210.0), 210.0),
+            "an overlap of 210mm doesn't fit sheets of 297 x 210mm"
+        );
+        assert_eq!(message((0.0, 210.0), 10.0), "invalid sheet size 0 x 210mm");
+        assert_eq!(
+            message((297.0, -1.0), 10.0),
+            "invalid sheet size 297 x -1mm"
+        );
+        assert_eq!(message((297.0, 210.0), -5.0), "invalid overlap -5mm");
+        assert!(Sheets::new((297.0, 210.0), 0.0).is_ok());
+    }
+
+    #[test]
+    fn tiled_saving() {
+        let mut printer = Printer::new(test_config());
+        printer.draw_polyline(&[(0.0, 0.0), (300.0, 300.0)]);
+        let filename = temp_path("tiled.gcode");
+        let filename = filename.to_str().unwrap();
+
+        // 300mm wouldn't fit on one 204 x 177mm bed
+        assert!(printer.save_tiled(1, 1, 20.0, filename).is_err());
+        let names = printer.save_tiled(2, 2, 20.0, filename).unwrap();
+        assert_eq!(names.len(), 4);
+        assert!(names[3].ends_with("r2c2.gcode"));
+        let err = printer.save_tiled(2, 2, 300.0, filename).err().unwrap();
+        assert!(
+            matches!(&err, Error::Bounds(m) if m.contains("overlap")),
+            "{}",
+            err
+        );
+        let mut xs: Vec<f32> = Vec::new();
+        for name in &names {
+            let text = std::fs::read_to_string(name).unwrap();
+            std::fs::remove_file(name).unwrap();
+            assert!(text.contains("alignment mark"));
+            xs.extend(
+                text.lines()
+                    .filter(|l| l.starts_with("G1 X"))
+                    .map(|l| l[4..].split(' ').next().unwrap().parse::<f32>().unwrap()),
+            );
+        }
+        // Tiles of 160mm, with the diagonal clipped to each
+        assert!(xs.iter().any(|&x| x > 150.0));
+        assert!(xs.iter().all(|&x| x <= 160.0 + 1e-3));
+    }
+
+    #[test]
+    fn sheet_splitting() {
+        let mut printer = Printer::new(test_config());
+        // 300mm across two 204mm wide sheets, 3 on the first one
+        printer.draw_polyline(&[(0.0, 50.0), (300.0, 50.0)]);
+        printer.draw_point(10.0, 10.0);
+        printer.draw_point(250.0, 10.0);
+
+        let sheets = Sheets::new((printer.width, printer.height), 20.0).unwrap();
+        let parts = printer.split_sheets(&sheets);
+        assert_eq!(parts.len(), 2);
+        let (sheet, first) = &parts[0];
+        let (second_sheet, second) = &parts[1];
+        assert_eq!(sheet.origin, (0.0, 0.0));
+        assert_eq!(second_sheet.origin, (184.0, 0.0));
+
+        let drawn = |p: &Printer| {
+            p.ops()
+                .iter()
+                .filter(|op| p.layers()[op.layer] != "alignment")
+                .map(|op| path::length(&op.path()))
+                .sum::<f32>()
+        };
+        assert_within(drawn(first), 204.0, 1e-3);
+        assert_within(drawn(second), 116.0, 1e-3);
+        assert!(
+            second
+                .ops()
+                .iter()
+                .any(|op| matches!(op.shape, Shape::Point((x, _)) if (x - 66.0).abs() < 1e-3))
+        );
+
+        // Same crosshairs on both, in the overlap
+        let marks = |p: &Printer, x0: f32| -> Vec<path::Path> {
+            p.ops()
+                .iter()
+                .filter(|op| p.layers()[op.layer] == "alignment")
+                .map(|op| op.path().iter().map(|&(x, y)| (x + x0, y)).collect())
+                .collect()
+        };
+        assert_eq!(marks(first, 0.0).len(), 4);
+        assert_eq!(marks(first, 0.0), marks(second, 184.0));
+    }
+}
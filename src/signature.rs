@@ -0,0 +1,155 @@
+use crate::jog::Corner;
+use crate::path::Path;
+use crate::text;
+
+// What to do with artwork that reaches into the signature zone
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Intrusions {
+    // Leave it, the caller gets told which ops they are
+    Warn,
+    // Cut the parts inside the zone away
+    Clip,
+}
+
+// Signing block for editions, "title — n/N — date" in a corner of the
+// drawing area. The zone around it is kept free of artwork.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Signature {
+    pub title: String,
+    // Print number and edition size
+    pub edition: Option<(u32, u32)>,
+    pub date: String,
+    // Center puts the block in the middle of the bottom edge
+    pub corner: Corner,
+    // Capital height in mm
+    pub height: f32,
+    // Distance from the edges of the drawing area, in mm
+    pub margin: f32,
+    // Free space around the text, in mm
+    pub clearance: f32,
+    pub intrusions: Intrusions,
+}
+
+impl Signature {
+    pub fn new(title: &str, date: &str) -> Self {
+        Signature {
+            title: title.to_string(),
+            edition: None,
+            date: date.to_string(),
+            corner: Corner::BottomRight,
+            height: 2.5,
+            margin: 5.0,
+            clearance: 2.0,
+            intrusions: Intrusions::Clip,
+        }
+    }
+
+    pub fn text(&self) -> String {
+        let mut parts = vec![self.title.clone()];
+        if let Some((n, total)) = self.edition {
+            parts.push(format!("{}/{}", n, total));
+        }
+        parts.push(self.date.clone());
+        parts
+            .into_iter()
+            .filter(|p| !p.is_empty())
+            .collect::<Vec<_>>()
+            .join(" \u{2014} ")
+    }
+
+    // Reserved rectangle in a drawing area of `size`, in mm from its bottom
+    // left corner
+    pub fn zone(&self, size: (f32, f32)) -> ((f32, f32), (f32, f32)) {
+        // Descenders go a third of the capital height below the baseline
+        let w = text::width(&self.text(), self.height) + 2.0 * self.clearance;
+        let h = self.height * 4.0 / 3.0 + 2.0 * self.clearance;
+        let m = self.margin;
+        let (x, y) = match self.corner {
+            Corner::BottomLeft => (m, m),
+            Corner::BottomRight => (size.0 - m - w, m),
+            Corner::TopLeft => (m, size.1 - m - h),
+            Corner::TopRight => (size.0 - m - w, size.1 - m - h),
+            Corner::Center => ((size.0 - w) / 2.0, m),
+        };
+        ((x, y), (x + w, y + h))
+    }
+
+    // The text inside its zone
+    pub fn paths(&self, size: (f32, f32)) -> Vec<Path> {
+        let ((x, y), _) = self.zone(size);
+        let (x0, y0) = (x + self.clearance, y + self.clearance + self.height / 3.0);
+        text::layout(&self.text(), self.height)
+            .into_iter()
+            .map(|p| p.into_iter().map(|(px, py)| (x0 + px, y0 + py)).collect())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::test_config;
+    use crate::{Printer, path};
+
+    #[test]
+    fn block_in_the_corner() {
+        let signature = Signature {
+            edition: Some((3, 50)),
+            ..Signature::new("Dunes", "2026-10-15")
+        };
+        assert_eq!(signature.text(), "Dunes \u{2014} 3/50 \u{2014} 2026-10-15");
+        assert_eq!(Signature::new("", "2026").text(), "2026");
+
+        let size = (297.0, 210.0);
+        let (min, max) = signature.zone(size);
+        assert!((max.0 - 292.0).abs() < 1e-3 && (min.1 - 5.0).abs() < 1e-3);
+        let paths = signature.paths(size);
+        assert!(paths.iter().flatten().all(|&p| path::inside_rect(
+            p,
+            (min.0 + 1.9, min.1 + 1.9),
+            (max.0 - 1.9, max.1 - 1.9)
+        )));
+
+        let top = Signature {
+            corner: Corner::TopLeft,
+            ..signature
+        };
+        let (min, max) = top.zone(size);
+        assert!((min.0 - 5.0).abs() < 1e-3 && (max.1 - 205.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn signing_clears_the_zone() {
+        let mut printer = Printer::new(test_config());
+        let (w, h) = (printer.width, printer.height);
+        printer.draw_polyline(&[(0.0, 7.0), (w, 7.0)]);
+        printer.draw_polyline(&[(0.0, h / 2.0), (w, h / 2.0)]);
+
+        let mut signature = Signature {
+            edition: Some((1, 10)),
+            ..Signature::new("Test", "2026-10-15")
+        };
+        signature.intrusions = Intrusions::Warn;
+        // Warnings leave the artwork alone
+        let mut warned = printer.clone();
+        assert_eq!(warned.sign(&signature), [0]);
+        assert_eq!(warned.ops().iter().filter(|op| op.layer == 0).count(), 2);
+
+        signature.intrusions = Intrusions::Clip;
+        assert_eq!(printer.sign(&signature), [0]);
+        let (min, max) = signature.zone((w, h));
+        let signature_layer = printer.layer_index("signature");
+        for op in printer.ops() {
+            let inside = op.path().iter().any(|&p| {
+                path::inside_rect(
+                    p,
+                    (min.0 + 1e-3, min.1 + 1e-3),
+                    (max.0 - 1e-3, max.1 - 1e-3),
+                )
+            });
+            assert_eq!(inside, op.layer == signature_layer);
+        }
+        // The line through the zone is cut in two
+        assert_eq!(printer.ops().iter().filter(|op| op.layer == 0).count(), 3);
+    }
+}
@@ -0,0 +1,506 @@
+use crate::Code;
+use crate::curve::{self, CubicBezier};
+use crate::feed::Feed;
+
+// One straight move of the tool as the machine would execute it
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Segment {
+    pub from: (f32, f32, f32),
+    pub to: (f32, f32, f32),
+    pub feed: f32,
+    // The pen touches the paper for the whole move
+    pub pen_down: bool,
+    // Seconds lost braking for the corner at the start and speeding up
+    // again, when the acceleration is known
+    pub cornering: f32,
+}
+
+impl Segment {
+    pub fn length(&self) -> f32 {
+        ((self.to.0 - self.from.0).powi(2)
+            + (self.to.1 - self.from.1).powi(2)
+            + (self.to.2 - self.from.2).powi(2))
+        .sqrt()
+    }
+
+    // Estimated time in seconds, feeds are in mm/min
+    pub fn time(&self) -> f32 {
+        if self.feed > 0.0 {
+            self.length() / Feed::mm_per_min(self.feed).per_s() + self.cornering
+        } else {
+            0.0
+        }
+    }
+
+    fn direction(&self) -> (f32, f32, f32) {
+        let length = self.length();
+        (
+            (self.to.0 - self.from.0) / length,
+            (self.to.1 - self.from.1) / length,
+            (self.to.2 - self.from.2) / length,
+        )
+    }
+
+    pub fn from_xy(&self) -> (f32, f32) {
+        (self.from.0, self.from.1)
+    }
+
+    pub fn to_xy(&self) -> (f32, f32) {
+        (self.to.0, self.to.1)
+    }
+}
+
+// Tracks the tool position while stepping through a program
+#[derive(Debug, Clone)]
+pub struct Machine {
+    pos: (f32, f32, f32),
+    z_draw: f32,
+    // Height the pen is considered to be at when raised by a Code::Pen
+    z_up: f32,
+    // In mm/s², None to treat speed changes as instant
+    acceleration: Option<f32>,
+    // What M501 goes back to
+    stored_acceleration: Option<f32>,
+    // Direction and speed in mm/s of the last move, None when standing
+    // still since
+    last: Option<((f32, f32, f32), f32)>,
+    // Speed of the last move when the machine came to a stop after it
+    stopped: Option<f32>,
+}
+
+impl Machine {
+    // The pen counts as down whenever z is at or below `z_draw`
+    pub fn new(start: (f32, f32, f32), z_draw: f32) -> Self {
+        Machine {
+            pos: start,
+            z_draw,
+            z_up: start.2,
+            acceleration: None,
+            stored_acceleration: None,
+            last: None,
+            stopped: None,
+        }
+    }
+
+    // Estimates times with trapezoidal speed ramps at `acceleration`, which
+    // M204 and M501 in the program change as they go
+    pub fn with_acceleration(mut self, acceleration: Option<f32>) -> Self {
+        self.acceleration = acceleration;
+        self.stored_acceleration = acceleration;
+        self
+    }
+
+    pub fn position(&self) -> (f32, f32, f32) {
+        self.pos
+    }
+
+    fn is_down(&self, z: f32) -> bool {
+        z <= self.z_draw + 1e-4
+    }
+
+    fn move_to(&mut self, to: (f32, f32, f32), feed: f32) -> Segment {
+        let from = self.pos;
+        self.pos = to;
+        let mut segment = Segment {
+            from,
+            to,
+            feed,
+            pen_down: self.is_down(from.2) && self.is_down(to.2),
+            cornering: 0.0,
+        };
+        if let Some(a) = self.acceleration.filter(|&a| a > 0.0)
+            && feed > 0.0
+            && segment.length() > 0.0
+        {
+            segment.cornering = self.cornering(&segment, a);
+        }
+        segment
+    }
+
+    // Time lost at the junction before `segment`. Every move is taken at
+    // full speed, slowing down for the corner by how sharply it turns and
+    // speeding up again after it. A move too short to get up to speed is
+    // counted as if it did.
+    fn cornering(&mut self, segment: &Segment, a: f32) -> f32 {
+        let v = Feed::mm_per_min(segment.feed).per_s();
+        let direction = segment.direction();
+        // Time lost ramping between speed `from` and `to` rather than
+        // keeping `from`
+        let ramp = |from: f32, to: f32| (from - to).powi(2) / (2.0 * a * from);
+        let time = match (self.last, self.stopped) {
+            (Some((last, v_last)), _) => {
+                let cos = last.0 * direction.0 + last.1 * direction.1 + last.2 * direction.2;
+                let junction = v.min(v_last) * (1.0 + cos) / 2.0;
+                ramp(v_last, junction) + ramp(v, junction)
+            }
+            (None, Some(v_last)) => ramp(v_last, 0.0) + ramp(v, 0.0),
+            (None, None) => ramp(v, 0.0),
+        };
+        self.last = Some((direction, v));
+        self.stopped = None;
+        time
+    }
+
+    // Comes to a stop, e.g. for a dwell or a pen lifted without moving
+    fn stop(&mut self) {
+        if let Some((_, v)) = self.last.take() {
+            self.stopped = Some(v);
+        }
+    }
+
+    // Executes a single code, returning the moves it made. Curves are split
+    // into straight segments.
+    pub fn step(&mut self, code: &Code) -> Vec<Segment> {
+        match code {
+            Code::Move(p, feed) | Code::Rapid(p, feed) => {
+                let (x, y, z) = self.pos;
+                let to = (p.x.unwrap_or(x), p.y.unwrap_or(y), p.z.unwrap_or(z));
+                vec![self.move_to(to, *feed)]
+            }
+            Code::Bezier(c, feed) => {
+                let (x, y, z) = self.pos;
+                let curve = CubicBezier { p0: (x, y), ..*c };
+                curve.flatten(curve::TOLERANCE)[1..]
+                    .iter()
+                    .map(|&(x, y)| self.move_to((x, y, z), *feed))
+                    .collect()
+            }
+            Code::Arc(arc, feed) => {
+                let z = self.pos.2;
+                arc.flatten(curve::TOLERANCE)[1..]
+                    .iter()
+                    .map(|&(x, y)| self.move_to((x, y, z), *feed))
+                    .collect()
+            }
+            // Pen mechanisms other than the Z axis are replayed as an instant
+            // vertical move so they look the same as Z lifts to everything else
+            Code::Pen(down, _) => {
+                self.stop();
+                let (x, y, _) = self.pos;
+                let z = if *down { self.z_draw } else { self.z_up };
+                vec![self.move_to((x, y, z), 0.0)]
+            }
+            Code::Dwell(_) | Code::Pause(_) => {
+                self.stop();
+                Vec::new()
+            }
+            Code::Raw(src) => {
+                if let Some(a) = src.code.strip_prefix("M204 S") {
+                    self.acceleration = a.parse().ok();
+                } else if src.code == "M501" {
+                    self.acceleration = self.stored_acceleration;
+                }
+                Vec::new()
+            }
+            _ => Vec::new(),
+        }
+    }
+}
+
+// Time spent by a code without moving, in seconds
+pub fn wait_time(code: &Code) -> f32 {
+    match code {
+        Code::Dwell(seconds) => *seconds,
+        _ => 0.0,
+    }
+}
+
+// A plunge straight down followed by a retract straight up leaves a dot
+pub fn is_dot(segment: &Segment, next: Option<&Segment>) -> bool {
+    let vertical = |s: &Segment| s.from_xy() == s.to_xy();
+    vertical(segment)
+        && segment.to.2 < segment.from.2
+        && next.is_some_and(|n| vertical(n) && n.to.2 > n.from.2)
+}
+
+// Replays `code` starting at `start` and returns every move as a segment.
+// The pen counts as down whenever z is at or below `z_draw`.
+pub fn simulate(code: &[Code], start: (f32, f32, f32), z_draw: f32) -> Vec<Segment> {
+    replay(code, Machine::new(start, z_draw))
+}
+
+// Replays `code` on `machine` as it is
+pub fn replay(code: &[Code], mut machine: Machine) -> Vec<Segment> {
+    code.iter().flat_map(|c| machine.step(c)).collect()
+}
+
+// Totals for some part of a program
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub struct Stats {
+    // Distance travelled with the pen on the paper, in mm
+    pub drawn: f32,
+    // Distance travelled with the pen lifted (including plunges), in mm
+    pub travel: f32,
+    // Estimated time in seconds
+    pub time: f32,
+    // Number of emitted commands, not counting comments and blank lines
+    pub commands: usize,
+    // Times the pen is lifted straight up off the paper
+    pub pen_lifts: usize,
+    // Bounding box of every move, pen up or down
+    pub extents: Option<((f32, f32), (f32, f32))>,
+}
+
+impl Stats {
+    pub fn add_segment(&mut self, segment: &Segment) {
+        let len = segment.length();
+        if segment.pen_down {
+            self.drawn += len;
+        } else {
+            self.travel += len;
+        }
+        if segment.from_xy() == segment.to_xy() && segment.to.2 > segment.from.2 {
+            self.pen_lifts += 1;
+        }
+        self.time += segment.time();
+        for (x, y) in [segment.from_xy(), segment.to_xy()] {
+            self.extents = Some(match self.extents {
+                None => ((x, y), (x, y)),
+                Some((lo, hi)) => ((lo.0.min(x), lo.1.min(y)), (hi.0.max(x), hi.1.max(y))),
+            });
+        }
+    }
+
+    pub fn add_code(&mut self, code: &Code) {
+        if !matches!(code, Code::Comment(_) | Code::NOP) {
+            self.commands += 1;
+        }
+        self.time += wait_time(code);
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PlotStats {
+    pub total: Stats,
+    // Breakdown per layer, in layer order
+    pub layers: Vec<(String, Stats)>,
+}
+
+impl PlotStats {
+    pub fn new(layers: &[String]) -> Self {
+        PlotStats {
+            total: Stats::default(),
+            layers: layers
+                .iter()
+                .map(|l| (l.clone(), Stats::default()))
+                .collect(),
+        }
+    }
+
+    pub fn record(&mut self, layer: usize, code: &Code, segments: &[Segment]) {
+        self.total.add_code(code);
+        if let Some((_, stats)) = self.layers.get_mut(layer) {
+            stats.add_code(code);
+            for s in segments {
+                stats.add_segment(s);
+            }
+        }
+        for s in segments {
+            self.total.add_segment(s);
+        }
+    }
+
+    // The breakdown as G-code comments, for the top of the file
+    pub fn to_comments(&self) -> Vec<Code> {
+        let line = |name: &str, s: &Stats| {
+            Code::Comment(format!(
+                "{}: drawn {:.1}mm, travel {:.1}mm, time {}, {} commands",
+                name,
+                s.drawn,
+                s.travel,
+                format_time(s.time),
+                s.commands
+            ))
+        };
+
+        let mut out = vec![Code::Comment("Plot statistics".to_string())];
+        for (name, stats) in &self.layers {
+            out.push(line(&format!("layer {}", name), stats));
+        }
+        out.push(line("total", &self.total));
+        out
+    }
+}
+
+pub fn format_time(seconds: f32) -> String {
+    let total = seconds.max(0.0).round() as u32;
+    format!(
+        "{:02}:{:02}:{:02}",
+        total / 3600,
+        (total % 3600) / 60,
+        total % 60
+    )
+}
+
+// Durations as PrusaSlicer writes them, e.g. "1h 2m 5s"
+pub fn format_time_words(seconds: f32) -> String {
+    let total = seconds.max(0.0).round() as u32;
+    let (d, h, m, s) = (
+        total / 86400,
+        (total % 86400) / 3600,
+        (total % 3600) / 60,
+        total % 60,
+    );
+    if d > 0 {
+        format!("{}d {}h {}m {}s", d, h, m, s)
+    } else if h > 0 {
+        format!("{}h {}m {}s", h, m, s)
+    } else if m > 0 {
+        format!("{}m {}s", m, s)
+    } else {
+        format!("{}s", s)
+    }
+}
+
+// Bounding box of everything the pen touches
+pub fn drawn_extents(segments: &[Segment]) -> Option<((f32, f32), (f32, f32))> {
+    let mut extents: Option<((f32, f32), (f32, f32))> = None;
+    for (i, s) in segments.iter().enumerate() {
+        let points = if s.pen_down {
+            vec![s.from_xy(), s.to_xy()]
+        } else if is_dot(s, segments.get(i + 1)) {
+            vec![s.to_xy()]
+        } else {
+            continue;
+        };
+        for (x, y) in points {
+            extents = Some(match extents {
+                None => ((x, y), (x, y)),
+                Some((lo, hi)) => ((lo.0.min(x), lo.1.min(y)), (hi.0.max(x), hi.1.max(y))),
+            });
+        }
+    }
+    extents
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::test_config;
+    use crate::{Point, Printer, PrinterConfig};
+    use std::time::Duration;
+
+    fn mv(x: Option<f32>, y: Option<f32>, z: Option<f32>) -> Code {
+        Code::Move(Point { x, y, z }, 1000.0)
+    }
+
+    #[test]
+    fn replay_moves() {
+        let code = vec![
+            mv(Some(3.0), Some(4.0), None),
+            Code::Comment("ignored".to_string()),
+            mv(None, None, Some(4.0)),
+            mv(Some(3.0), Some(10.0), None),
+            mv(None, None, Some(6.5)),
+        ];
+        let segments = simulate(&code, (0.0, 0.0, 6.5), 4.0);
+
+        assert_eq!(segments.len(), 4);
+        assert_eq!(segments[0].length(), 5.0);
+        assert!(!segments[0].pen_down);
+        assert!(!segments[1].pen_down);
+        assert!(segments[2].pen_down);
+        assert_eq!(segments[2].from, (3.0, 4.0, 4.0));
+        assert_eq!(segments[2].to, (3.0, 10.0, 4.0));
+        assert!(!segments[3].pen_down);
+        assert_eq!(drawn_extents(&segments), Some(((3.0, 4.0), (3.0, 10.0))));
+    }
+
+    #[test]
+    fn replay_curves() {
+        // The curve starts wherever the machine is, not at its p0
+        let c = CubicBezier::new((5.0, 5.0), (0.0, 10.0), (10.0, 10.0), (10.0, 0.0));
+        let code = vec![Code::Bezier(c, 1000.0)];
+        let segments = simulate(&code, (0.0, 0.0, 4.0), 4.0);
+
+        assert!(segments.len() > 4);
+        assert_eq!(segments[0].from, (0.0, 0.0, 4.0));
+        assert_eq!(segments.last().unwrap().to, (10.0, 0.0, 4.0));
+        assert!(segments.iter().all(|s| s.pen_down));
+    }
+
+    fn assert_within(a: f32, b: f32) {
+        assert!((a - b).abs() < 1e-4, "{} is not {}", a, b);
+    }
+
+    #[test]
+    fn acceleration_ramps() {
+        let time = |code: &[Code], acceleration| -> f32 {
+            let machine = Machine::new((0.0, 0.0, 4.0), 4.0).with_acceleration(acceleration);
+            replay(code, machine).iter().map(|s| s.time()).sum()
+        };
+        // 60mm at 1000mm/min, speeding up from a stand at 100mm/s²
+        let straight = [mv(Some(30.0), None, None), mv(Some(60.0), None, None)];
+        assert_within(time(&straight, None), 3.6);
+        let v: f32 = 1000.0 / 60.0;
+        assert_within(time(&straight, Some(100.0)), 3.6 + v / 200.0);
+
+        // Turning back means stopping and starting again
+        let back = [mv(Some(30.0), None, None), mv(Some(0.0), None, None)];
+        assert_within(time(&back, Some(100.0)), 3.6 + 3.0 * v / 200.0);
+        let corner = [mv(Some(30.0), None, None), mv(None, Some(30.0), None)];
+        let t = time(&corner, Some(100.0));
+        assert!(t > time(&straight, Some(100.0)) && t < time(&back, Some(100.0)));
+
+        // Set along the way
+        let mut set = vec![Code::Raw("M204 S100".into())];
+        set.extend(straight.clone());
+        assert_within(time(&set, None), time(&straight, Some(100.0)));
+        set.insert(2, Code::Raw("M501".into()));
+        // Back to instant speed changes after the first move
+        assert_within(time(&set, None), 3.6 + v / 200.0);
+    }
+
+    #[test]
+    fn stats_per_layer() {
+        let layers = vec!["a".to_string(), "b".to_string()];
+        let mut stats = PlotStats::new(&layers);
+        let mut machine = Machine::new((0.0, 0.0, 4.0), 4.0);
+
+        let code = vec![
+            (0, Code::Comment("a".to_string())),
+            (0, mv(Some(60.0), None, None)),
+            (1, mv(None, None, Some(6.0))),
+            (1, mv(None, Some(60.0), None)),
+        ];
+        for (layer, c) in &code {
+            let segments = machine.step(c);
+            stats.record(*layer, c, &segments);
+        }
+
+        // 1000mm/min is 1mm per 60ms
+        let (_, a) = &stats.layers[0];
+        assert_eq!(a.drawn, 60.0);
+        assert_eq!(a.commands, 1);
+        assert!((a.time - 3.6).abs() < 1e-4);
+
+        let (_, b) = &stats.layers[1];
+        assert_eq!(b.drawn, 0.0);
+        assert_eq!(b.travel, 62.0);
+        assert_eq!(b.pen_lifts, 1);
+        assert_eq!(a.extents, Some(((0.0, 0.0), (60.0, 0.0))));
+        assert_eq!(b.extents, Some(((60.0, 0.0), (60.0, 60.0))));
+        assert_eq!(stats.total.extents, Some(((0.0, 0.0), (60.0, 60.0))));
+        assert_eq!(stats.total.commands, 3);
+        assert_eq!(stats.total.drawn + stats.total.travel, 122.0);
+        assert_eq!(format_time(3725.4), "01:02:05");
+        assert_eq!(format_time_words(3725.4), "1h 2m 5s");
+        assert_eq!(format_time_words(65.0), "1m 5s");
+        assert_eq!(format_time_words(90061.0), "1d 1h 1m 1s");
+    }
+
+    #[test]
+    fn duration_with_acceleration() {
+        let draw = |config: PrinterConfig| {
+            let mut printer = Printer::new(config);
+            printer.draw_polyline(&[(10.0, 10.0), (100.0, 10.0), (100.0, 100.0)]);
+            printer.estimated_duration()
+        };
+        let instant = draw(test_config());
+        let mut config = test_config();
+        config.acceleration = Some(500.0);
+        let ramped = draw(config);
+        assert!(ramped > instant);
+        assert!(ramped < instant + Duration::from_secs(1));
+    }
+}
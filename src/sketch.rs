@@ -0,0 +1,226 @@
+use std::fs;
+use std::path::Path;
+
+use crate::raster;
+use crate::rng::Rng;
+use crate::{Error, Printer, PrinterConfig};
+
+// Settings of one variant of a sketch: its seed and a value for every swept
+// parameter
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Params {
+    pub seed: u64,
+    values: Vec<(String, f32)>,
+}
+
+impl Params {
+    pub fn new(seed: u64) -> Self {
+        Params {
+            seed,
+            values: Vec::new(),
+        }
+    }
+
+    pub fn with(mut self, name: &str, value: f32) -> Self {
+        match self.values.iter_mut().find(|(n, _)| n == name) {
+            Some(v) => v.1 = value,
+            None => self.values.push((name.to_string(), value)),
+        }
+        self
+    }
+
+    pub fn get(&self, name: &str) -> Option<f32> {
+        self.values.iter().find(|(n, _)| n == name).map(|(_, v)| *v)
+    }
+
+    // The value of `name`, or `default` when it isn't swept
+    pub fn value(&self, name: &str, default: f32) -> f32 {
+        self.get(name).unwrap_or(default)
+    }
+
+    pub fn values(&self) -> &[(String, f32)] {
+        &self.values
+    }
+
+    // Random numbers for the variant, the same every run
+    pub fn rng(&self) -> Rng {
+        Rng::new(self.seed)
+    }
+
+    // Part of the file names, e.g. "seed-3_density-0.5"
+    pub fn name(&self) -> String {
+        let mut name = format!("seed-{}", self.seed);
+        for (n, v) in &self.values {
+            name.push_str(&format!("_{}-{}", n, v));
+        }
+        name
+    }
+}
+
+// A generative piece. The runner calls draw once per variant with a fresh
+// printer, so a sketch shouldn't keep drawing state between calls. Sketches
+// draw on the Printer, the crate's drawing surface, and not on a separate
+// canvas type: canvas::Canvas is the shape of the paper, which the printer
+// clips to, and a second surface would only forward to the printer.
+pub trait Sketch {
+    fn draw(&mut self, printer: &mut Printer, params: &Params);
+}
+
+// Any closure taking the printer and params is a sketch, for quick pieces
+impl<F: FnMut(&mut Printer, &Params)> Sketch for F {
+    fn draw(&mut self, printer: &mut Printer, params: &Params) {
+        self(printer, params)
+    }
+}
+
+// Draws every variant of a sketch and writes its G-code, and previews if
+// asked for, into a directory. The seed and swept values are embedded as
+// generator parameters.
+#[derive(Debug, Clone)]
+pub struct Runner {
+    pub name: String,
+    pub config: PrinterConfig,
+    pub output: String,
+    pub seeds: Vec<u64>,
+    // Every combination of the values is drawn, for every seed
+    pub sweeps: Vec<(String, Vec<f32>)>,
+    // Interactive HTML preview next to each file
+    pub html: bool,
+    // PNG preview of this size next to each file
+    pub png: Option<(u32, u32)>,
+}
+
+impl Runner {
+    pub fn new(name: &str, config: PrinterConfig, output: &str) -> Self {
+        Runner {
+            name: name.to_string(),
+            config,
+            output: output.to_string(),
+            seeds: vec![0],
+            sweeps: Vec::new(),
+            html: false,
+            png: None,
+        }
+    }
+
+    pub fn sweep(&mut self, name: &str, values: &[f32]) -> &mut Self {
+        self.sweeps.push((name.to_string(), values.to_vec()));
+        self
+    }
+
+    pub fn variants(&self) -> Vec<Params> {
+        let mut variants: Vec<Params> = self.seeds.iter().map(|&s| Params::new(s)).collect();
+        for (name, values) in &self.sweeps {
+            variants = variants
+                .iter()
+                .flat_map(|p| values.iter().map(|&v| p.clone().with(name, v)))
+                .collect();
+        }
+        variants
+    }
+
+    // Draws a single variant
+    pub fn draw(&self, sketch: &mut impl Sketch, params: &Params) -> Printer {
+        let mut printer = Printer::new(self.config.clone());
+        printer.set_parameter("sketch", &self.name);
+        printer.set_parameter("seed", params.seed);
+        for (name, value) in params.values() {
+            printer.set_parameter(name, value);
+        }
+        sketch.draw(&mut printer, params);
+        printer
+    }
+
+    // Draws and writes every variant, returning the G-code files
+    pub fn run(&self, sketch: &mut impl Sketch) -> Result<Vec<String>, Error> {
+        fs::create_dir_all(&self.output)?;
+        let mut files = Vec::new();
+        for params in self.variants() {
+            let printer = self.draw(sketch, &params);
+            let stem = Path::new(&self.output).join(format!("{}_{}", self.name, params.name()));
+            let stem = stem.to_string_lossy();
+
+            let gcode = format!("{}.gcode", stem);
+            printer.save(&gcode)?;
+            if self.html {
+                printer.preview_html(&format!("{}.html", stem))?;
+            }
+            if let Some((w, h)) = self.png {
+                let bitmap =
+                    raster::render(&printer.simulate(), (printer.width, printer.height), w, h);
+                fs::write(format!("{}.png", stem), bitmap.to_png())?;
+            }
+            files.push(gcode);
+        }
+        Ok(files)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{temp_path, test_config};
+
+    #[test]
+    fn params() {
+        let params = Params::new(3).with("density", 0.5).with("rings", 4.0);
+        assert_eq!(params.name(), "seed-3_density-0.5_rings-4");
+        assert_eq!(params.value("density", 1.0), 0.5);
+        assert_eq!(params.value("width", 1.0), 1.0);
+        assert_eq!(params.with("rings", 5.0).get("rings"), Some(5.0));
+        assert_eq!(
+            Params::new(7).rng().next_u64(),
+            Params::new(7).rng().next_u64()
+        );
+    }
+
+    #[test]
+    fn sketch_runner() {
+        let output = temp_path("sketch");
+        let _ = std::fs::remove_dir_all(&output);
+        let mut runner = Runner::new("rings", test_config(), &output.to_string_lossy());
+        runner.seeds = vec![1, 2];
+        runner.sweep("rings", &[1.0, 3.0]);
+        runner.html = true;
+        runner.png = Some((16, 16));
+
+        let mut drawn = Vec::new();
+        let mut rings = |printer: &mut Printer, params: &Params| {
+            let mut rng = params.rng();
+            for i in 0..params.value("rings", 2.0) as usize {
+                let r = 10.0 + 5.0 * i as f32 + (rng.next_u64() % 100) as f32 / 100.0;
+                let circle: Vec<(f32, f32)> = (0..=36)
+                    .map(|k| (k as f32 * 10.0).to_radians())
+                    .map(|a| (50.0 + r * a.cos(), 50.0 + r * a.sin()))
+                    .collect();
+                printer.draw_polyline(&circle);
+            }
+            drawn.push((params.seed, printer.ops().len()));
+        };
+        let files = runner.run(&mut rings).unwrap();
+        assert_eq!(files.len(), 4);
+        let stem = output.join("rings_seed-2_rings-3");
+        assert_eq!(files[3], format!("{}.gcode", stem.to_string_lossy()));
+        for extension in ["gcode", "html", "png"] {
+            assert!(stem.with_extension(extension).exists(), "{}", extension);
+        }
+
+        // The same seed draws the same thing, with its settings recorded
+        let params = Params::new(2).with("rings", 3.0);
+        let (a, b) = (
+            runner.draw(&mut rings, &params),
+            runner.draw(&mut rings, &params),
+        );
+        assert_eq!(a.simulate(), b.simulate());
+        assert!(
+            a.parameters()
+                .contains(&("seed".to_string(), "2".to_string()))
+        );
+        assert!(
+            a.parameters()
+                .contains(&("rings".to_string(), "3".to_string()))
+        );
+        assert_eq!(drawn[..4], [(1, 1), (1, 3), (2, 1), (2, 3)]);
+        std::fs::remove_dir_all(&output).unwrap();
+    }
+}
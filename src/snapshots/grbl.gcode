@@ -0,0 +1,27 @@
+; Start of generated code
+G21 ; Set units to millimeters
+G90 ; Use absolute coordinates
+$H ; Run homing cycle
+
+G1 Z6.5 F1200.0
+G1 X50.0 Y35.0 F1200.0
+G92 X0 Y0 ; Set current position to origin
+(MSG,0.0%)
+
+; draw_polyline(3 points)
+G1 X10.0 Y10.0 F1200.0
+G1 Z4.0 F500.0
+G1 X40.0 Y10.0 F1200.0
+G1 X40.0 Y40.0 F1200.0
+G1 Z6.5 F800.0
+(MSG,48.2% R00:00:04)
+
+; draw_circle(80.0, 60.0, 5.0)
+G1 X85.0 Y60.0 F1200.0
+G1 Z4.0 F500.0
+G3 X85.0 Y60.0 I-5.0 J0.0 F1200.0
+G1 Z6.5 F800.0
+
+; Lift the head up before turning off
+G1 Z80.0 F1200.0
+
@@ -0,0 +1,35 @@
+; Start of generated code
+; Plot statistics
+; layer default: drawn 90.0mm, travel 19.1mm, time 00:00:07, 6 commands
+; layer circles: drawn 94.1mm, travel 92.3mm, time 00:00:11, 4 commands
+; total: drawn 184.1mm, travel 111.5mm, time 00:00:18, 10 commands
+M862.3 P "MK3S" ; printer model check
+G21 ; Set units to millimeters
+G90 ; Use absolute coordinates
+G28 W ; Home all without mesh bed level
+
+G1 Z6.5 F1000.0
+G1 X50.0 Y35.0 F1000.0
+G92 X0 Y0 ; Set current position to origin
+M117 0.0%
+
+; draw_polyline(4 points)
+G1 X10.0 Y10.0 F1000.0
+G1 Z4.0 F500.0
+G1 X40.0 Y10.0 F1000.0
+G1 X40.0 Y40.0 F1000.0
+G1 X10.0 Y40.0 F1000.0
+M117 36.2% R00:00:11
+G1 Z6.5 F800.0
+
+; draw_circle(80.0, 60.0, 15.0)
+G1 X95.0 Y60.0 F1000.0
+G1 Z4.0 F500.0
+G3 X95.0 Y60.0 I-15.0 J0.0 F1000.0
+G1 Z6.5 F800.0
+M117 100.0% R00:00:00
+
+; Lift the head up before turning off
+G1 Z80.0 F1000.0
+M84 ; Disable motors
+
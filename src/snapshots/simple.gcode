@@ -0,0 +1,21 @@
+; Start of generated code
+M862.3 P "MK3S" ; printer model check
+G21 ; Set units to millimeters
+G90 ; Use absolute coordinates
+G28 W ; Home all without mesh bed level
+
+G1 Z6.5 F1000.0
+G1 X50.0 Y35.0 F1000.0
+G92 X0 Y0 ; Set current position to origin
+M117 0.0%
+
+; draw_point(50.0, 50.0)
+G1 X50.0 Y50.0 F1000.0
+G1 Z4.0 F500.0
+G1 Z6.5 F800.0
+M117 100.0% R00:00:00
+
+; Lift the head up before turning off
+G1 Z80.0 F1000.0
+M84 ; Disable motors
+
@@ -0,0 +1,78 @@
+use crate::raster::Bitmap;
+use crate::sampling::{self, PoissonDisk};
+use crate::{DotWeight, Printer};
+
+// Pointillism: blue-noise dots packed closer where the image is darker, and
+// each dot weighted by the darkness under it so a single pen still shows
+// the tones.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Stipple {
+    // Distance between dots in the darkest parts, in mm
+    pub radius: f32,
+    // Distance between dots in the lightest parts, in mm
+    pub max_radius: f32,
+    // Dots lighter than this darkness (0..=1) are left out
+    pub threshold: f32,
+    pub weight: DotWeight,
+    pub seed: u64,
+}
+
+impl Stipple {
+    pub fn new(radius: f32, weight: DotWeight, seed: u64) -> Self {
+        Stipple {
+            radius,
+            max_radius: radius * 4.0,
+            threshold: 0.05,
+            weight,
+            seed,
+        }
+    }
+
+    // Dots with their weights, with the bitmap stretched over min..max and
+    // its top row at max.1
+    pub fn dots(
+        &self,
+        bitmap: &Bitmap,
+        min: (f32, f32),
+        max: (f32, f32),
+    ) -> Vec<((f32, f32), f32)> {
+        let darkness = sampling::darkness(bitmap, min, max);
+        PoissonDisk::new(min, max, self.radius, self.seed)
+            .sample_weighted(self.max_radius, &darkness)
+            .into_iter()
+            .map(|p| (p, darkness(p)))
+            .filter(|&(_, w)| w >= self.threshold)
+            .collect()
+    }
+
+    // Draws the dots with the bitmap over min..max in drawing coordinates
+    pub fn draw(&self, printer: &mut Printer, bitmap: &Bitmap, min: (f32, f32), max: (f32, f32)) {
+        for ((x, y), weight) in self.dots(bitmap, min, max) {
+            printer.draw_weighted_point(x, y, weight, self.weight);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn darker_dots_are_heavier() {
+        // Black on the left, mid gray on the right, white strip at the top
+        let mut bitmap = Bitmap::new(2, 2, [128, 128, 128]);
+        bitmap.set(0, 1, [0, 0, 0]);
+        bitmap.set(0, 0, [255, 255, 255]);
+        bitmap.set(1, 0, [255, 255, 255]);
+        let stipple = Stipple::new(1.0, DotWeight::Dwell(0.5), 5);
+        let dots = stipple.dots(&bitmap, (0.0, 0.0), (20.0, 20.0));
+
+        // Nothing on white
+        assert!(dots.iter().all(|((_, y), _)| *y < 10.0));
+        let left: Vec<f32> = dots.iter().filter(|d| d.0.0 < 10.0).map(|d| d.1).collect();
+        let right: Vec<f32> = dots.iter().filter(|d| d.0.0 >= 10.0).map(|d| d.1).collect();
+        assert!(left.len() > right.len());
+        assert!(left.iter().all(|&w| w > 0.9));
+        assert!(right.iter().all(|&w| (w - 0.5).abs() < 0.05));
+    }
+}
@@ -0,0 +1,181 @@
+use std::io::Write;
+
+use crate::progress::{ProgressEvent, Tracker};
+use crate::{EmitState, Error, OutOfBounds, Printer, compact, curve, sim, write_code};
+
+// Writes the program while the drawing is being made, for plots too large
+// to hold as a whole. Every flush writes what was drawn since the last one
+// and forgets it again, apart from the last op which the next may need to
+// decide on a drying pause. Header statistics, thumbnails and ordering only
+// see what was drawn before `begin`, and progress messages need the
+// expected total time up front.
+pub struct Stream<W: Write> {
+    printer: Printer,
+    writer: W,
+    state: EmitState,
+    machine: sim::Machine,
+    // Ops of the printer already written
+    written: usize,
+    // Only once the expected time is known
+    tracker: Option<Tracker>,
+    on_progress: Option<Box<dyn FnMut(ProgressEvent)>>,
+    compactor: Option<compact::Compactor>,
+}
+
+impl<W: Write> Stream<W> {
+    // Writes the header right away. Anything already drawn goes out with
+    // the first flush.
+    pub fn begin(printer: Printer, mut writer: W) -> Result<Self, Error> {
+        let mut compactor = printer.compactor();
+        for c in printer.header() {
+            printer.write_output(&mut writer, c, &mut compactor)?;
+        }
+        Ok(Stream {
+            machine: printer.machine(),
+            printer,
+            writer,
+            state: EmitState::default(),
+            written: 0,
+            tracker: None,
+            on_progress: None,
+            compactor,
+        })
+    }
+
+    // Estimated time of the whole plot in seconds, e.g. from a dry run with
+    // fewer points, for progress messages as the moves are written
+    pub fn expect(&mut self, seconds: f32) {
+        self.tracker = Some(Tracker::new(&self.printer.config.progress, seconds));
+    }
+
+    // Called wherever the progress interval falls, after `expect`
+    pub fn on_progress(&mut self, f: impl FnMut(ProgressEvent) + 'static) {
+        self.on_progress = Some(Box::new(f));
+    }
+
+    // For changing layers, overrides and filters, or drawing with any of
+    // the printer's methods before a flush
+    pub fn printer(&mut self) -> &mut Printer {
+        &mut self.printer
+    }
+
+    // Writes everything drawn since the last time
+    pub fn flush(&mut self) -> Result<(), Error> {
+        let end = self.printer.ops.len();
+        if self.printer.config.out_of_bounds == Some(OutOfBounds::Error)
+            && let Some(i) = self
+                .printer
+                .out_of_bounds()
+                .into_iter()
+                .find(|&i| i >= self.written)
+        {
+            let label = self.printer.ops[i].label.clone();
+            return Err(Error::Bounds(format!(
+                "{} reaches outside the work area",
+                label.unwrap_or("op".to_string())
+            )));
+        }
+
+        let code = self.printer.emit_ops(self.written..end, &mut self.state);
+        for (_, c) in code {
+            let seconds =
+                self.machine.step(&c).iter().map(|s| s.time()).sum::<f32>() + sim::wait_time(&c);
+            let event = self.tracker.as_mut().and_then(|t| t.record(&c, seconds));
+            self.printer
+                .write_output(&mut self.writer, c, &mut self.compactor)?;
+            if let Some(event) = event {
+                if let Some(message) = self.tracker.as_ref().and_then(|t| t.message(&event)) {
+                    self.printer
+                        .write_output(&mut self.writer, message, &mut self.compactor)?;
+                }
+                if let Some(f) = &mut self.on_progress {
+                    f(event);
+                }
+            }
+        }
+
+        // Keep the last op for the drying pause before the next
+        self.printer.ops.drain(..end.saturating_sub(1));
+        self.written = self.printer.ops.len();
+        Ok(self.writer.flush()?)
+    }
+
+    pub fn draw_point(&mut self, xp: f32, yp: f32) -> Result<(), Error> {
+        self.printer.draw_point(xp, yp);
+        self.flush()
+    }
+
+    pub fn draw_polyline(&mut self, points: &[(f32, f32)]) -> Result<(), Error> {
+        self.printer.draw_polyline(points);
+        self.flush()
+    }
+
+    pub fn draw_curve(&mut self, curves: &[curve::CubicBezier]) -> Result<(), Error> {
+        self.printer.draw_curve(curves);
+        self.flush()
+    }
+
+    // Writes the footer and hands the writer back
+    pub fn finish(mut self) -> Result<W, Error> {
+        self.flush()?;
+        // Codes inserted after the last draw, then the machine's acceleration
+        // restored as a saved program would
+        let mut restore = std::mem::take(&mut self.printer.pending);
+        self.printer
+            .emit_acceleration(None, &mut self.state, &mut restore);
+        // Only the last op is left to take statistics from
+        self.printer.config.metadata = false;
+        let footer = self.printer.footer();
+        for c in restore.into_iter().chain(footer) {
+            self.printer
+                .write_output(&mut self.writer, c, &mut self.compactor)?;
+        }
+        for c in self.compactor.iter_mut().flat_map(|c| c.finish()) {
+            write_code(&mut self.writer, c)?;
+        }
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::test_config;
+
+    #[test]
+    fn streamed_program() {
+        let draw = |printer: &mut Printer, i: usize| {
+            let x = 10.0 + i as f32;
+            printer.draw_polyline(&[(x, 10.0), (x, 20.0)]);
+            printer.draw_point(x, 30.0);
+        };
+        let mut printer = Printer::new(test_config());
+        for i in 0..50 {
+            draw(&mut printer, i);
+        }
+        let total = printer.stats().total.time;
+        let mut saved = Vec::new();
+        printer.write_to(&mut saved).unwrap();
+
+        let mut stream = Printer::new(test_config()).begin(Vec::new()).unwrap();
+        stream.expect(total);
+        for i in 0..50 {
+            draw(stream.printer(), i);
+            stream.flush().unwrap();
+            assert!(stream.printer().ops().len() <= 1);
+        }
+        let streamed = stream.finish().unwrap();
+
+        // The same moves, only the comments differ
+        let commands = |program: Vec<u8>| -> Vec<String> {
+            String::from_utf8(program)
+                .unwrap()
+                .lines()
+                .map(|l| l.split(';').next().unwrap().trim().to_string())
+                .filter(|l| !l.is_empty())
+                .collect()
+        };
+        assert_eq!(commands(streamed), commands(saved));
+    }
+}
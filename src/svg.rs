@@ -0,0 +1,759 @@
+use std::f32::consts::PI;
+
+use crate::curve::{self, CircularArc, CubicBezier};
+use crate::path::{self, Path};
+use crate::{AffineTransform, Error, Printer};
+
+// Strokes of an SVG drawing in the file's user units, with y pointing down
+// as in the file. Every subpath is a chain of Béziers; straight lines have
+// their control points at the ends.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Svg {
+    // Area of the drawing: the viewBox, the width and height or the bounds
+    // of the strokes, whichever the file has first
+    pub min: (f32, f32),
+    pub max: (f32, f32),
+    pub strokes: Vec<Vec<CubicBezier>>,
+}
+
+fn invalid(message: &str) -> Error {
+    Error::Parse(message.to_string())
+}
+
+fn line(a: (f32, f32), b: (f32, f32)) -> CubicBezier {
+    CubicBezier::new(a, a, b, b)
+}
+
+// Elements whose contents aren't drawn where they are
+const HIDDEN: [&str; 11] = [
+    "defs", "clipPath", "mask", "marker", "pattern", "symbol", "style", "script", "title", "desc",
+    "metadata",
+];
+
+#[derive(Debug)]
+struct Tag<'a> {
+    name: &'a str,
+    attributes: Vec<(&'a str, &'a str)>,
+    closing: bool,
+    empty: bool,
+}
+
+impl Tag<'_> {
+    fn get(&self, name: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|(n, _)| *n == name)
+            .map(|(_, v)| *v)
+    }
+
+    // Numeric attribute, ignoring any unit after it
+    fn number(&self, name: &str) -> f32 {
+        self.get(name).and_then(leading_number).unwrap_or(0.0)
+    }
+
+    fn hidden(&self) -> bool {
+        self.get("display") == Some("none")
+            || self
+                .get("style")
+                .is_some_and(|s| s.replace(' ', "").contains("display:none"))
+    }
+}
+
+fn leading_number(text: &str) -> Option<f32> {
+    Numbers::new(text).next()
+}
+
+// The tags of an XML document in order, without comments, declarations and
+// text
+fn tags(text: &str) -> Result<Vec<Tag<'_>>, Error> {
+    let mut tags = Vec::new();
+    let mut rest = text;
+    while let Some(at) = rest.find('<') {
+        rest = &rest[at..];
+        let skip = [
+            ("<!--", "-->"),
+            ("<![CDATA[", "]]>"),
+            ("<?", "?>"),
+            ("<!", ">"),
+        ]
+        .iter()
+        .find(|(open, _)| rest.starts_with(open));
+        if let Some((_, close)) = skip {
+            let end = rest
+                .find(close)
+                .ok_or_else(|| invalid("unterminated markup"))?;
+            rest = &rest[end + close.len()..];
+            continue;
+        }
+
+        // The end of the tag, skipping > inside attribute values
+        let mut quote = None;
+        let end = rest
+            .char_indices()
+            .find(|&(_, c)| match quote {
+                Some(q) if c == q => {
+                    quote = None;
+                    false
+                }
+                Some(_) => false,
+                None if c == '"' || c == '\'' => {
+                    quote = Some(c);
+                    false
+                }
+                None => c == '>',
+            })
+            .map(|(i, _)| i)
+            .ok_or_else(|| invalid("unterminated tag"))?;
+        let body = &rest[1..end];
+        rest = &rest[end + 1..];
+
+        let closing = body.starts_with('/');
+        let empty = body.ends_with('/');
+        let body = body.trim_start_matches('/').trim_end_matches('/');
+        let name_end = body.find(char::is_whitespace).unwrap_or(body.len());
+        tags.push(Tag {
+            name: &body[..name_end],
+            attributes: attributes(&body[name_end..])?,
+            closing,
+            empty,
+        });
+    }
+    Ok(tags)
+}
+
+fn attributes(mut text: &str) -> Result<Vec<(&str, &str)>, Error> {
+    let mut attributes = Vec::new();
+    loop {
+        text = text.trim_start();
+        let Some(eq) = text.find('=') else {
+            return Ok(attributes);
+        };
+        let name = text[..eq].trim();
+        let value = text[eq + 1..].trim_start();
+        let quote = value
+            .chars()
+            .next()
+            .filter(|&c| c == '"' || c == '\'')
+            .ok_or_else(|| invalid("unquoted attribute"))?;
+        let end = value[1..]
+            .find(quote)
+            .ok_or_else(|| invalid("unterminated attribute"))?;
+        attributes.push((name, &value[1..end + 1]));
+        text = &value[end + 2..];
+    }
+}
+
+// Numbers as written in path data and lists, where separators can be left
+// out: "M1.5.5-2" is M 1.5 0.5 -2
+struct Numbers<'a> {
+    text: &'a str,
+}
+
+impl<'a> Numbers<'a> {
+    fn new(text: &'a str) -> Self {
+        Numbers { text }
+    }
+
+    fn skip_separators(&mut self) {
+        self.text = self
+            .text
+            .trim_start_matches(|c: char| c.is_whitespace() || c == ',');
+    }
+
+    // Arc flags are a single digit that can be followed by the next number
+    // right away
+    fn flag(&mut self) -> Option<bool> {
+        self.skip_separators();
+        let flag = match self.text.chars().next()? {
+            '0' => false,
+            '1' => true,
+            _ => return None,
+        };
+        self.text = &self.text[1..];
+        Some(flag)
+    }
+}
+
+impl Iterator for Numbers<'_> {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        self.skip_separators();
+        let bytes = self.text.as_bytes();
+        let mut end = 0;
+        if end < bytes.len() && (bytes[end] == b'-' || bytes[end] == b'+') {
+            end += 1;
+        }
+        let mut dot = false;
+        while end < bytes.len() && (bytes[end].is_ascii_digit() || (bytes[end] == b'.' && !dot)) {
+            dot |= bytes[end] == b'.';
+            end += 1;
+        }
+        if end < bytes.len() && (bytes[end] == b'e' || bytes[end] == b'E') {
+            let mut exp = end + 1;
+            if exp < bytes.len() && (bytes[exp] == b'-' || bytes[exp] == b'+') {
+                exp += 1;
+            }
+            if exp < bytes.len() && bytes[exp].is_ascii_digit() {
+                end = exp;
+                while end < bytes.len() && bytes[end].is_ascii_digit() {
+                    end += 1;
+                }
+            }
+        }
+        let value = self.text[..end].parse().ok()?;
+        self.text = &self.text[end..];
+        Some(value)
+    }
+}
+
+// Value of a transform attribute, the rightmost transform applied first
+fn transform(text: &str) -> AffineTransform {
+    let mut result = AffineTransform::IDENTITY;
+    let mut rest = text;
+    while let Some(open) = rest.find('(') {
+        let name = rest[..open].trim_matches(|c: char| c.is_whitespace() || c == ',');
+        let Some(close) = rest[open..].find(')') else {
+            break;
+        };
+        let args: Vec<f32> = Numbers::new(&rest[open + 1..open + close]).collect();
+        rest = &rest[open + close + 1..];
+        let arg = |i: usize, default: f32| args.get(i).copied().unwrap_or(default);
+        let t = match name {
+            "matrix" if args.len() == 6 => {
+                AffineTransform::new(args[0], args[1], args[2], args[3], args[4], args[5])
+            }
+            "translate" => AffineTransform::translate(arg(0, 0.0), arg(1, 0.0)),
+            "scale" => AffineTransform::scale(arg(0, 1.0), arg(1, arg(0, 1.0))),
+            "rotate" => {
+                AffineTransform::rotate_about(arg(0, 0.0).to_radians(), arg(1, 0.0), arg(2, 0.0))
+            }
+            "skewX" => AffineTransform::shear(arg(0, 0.0).to_radians().tan(), 0.0),
+            "skewY" => AffineTransform::shear(0.0, arg(0, 0.0).to_radians().tan()),
+            _ => AffineTransform::IDENTITY,
+        };
+        result = result * t;
+    }
+    result
+}
+
+// Arc of the ellipse with radii (rx, ry) turned by `rotation` around
+// `center`, angles in radians
+fn elliptical_arc(
+    center: (f32, f32),
+    (rx, ry): (f32, f32),
+    rotation: f32,
+    start: f32,
+    sweep: f32,
+) -> Vec<CubicBezier> {
+    let t = AffineTransform::scale(rx, ry)
+        .then(&AffineTransform::rotate(rotation))
+        .then(&AffineTransform::translate(center.0, center.1));
+    CircularArc::new((0.0, 0.0), 1.0, start, sweep)
+        .beziers()
+        .iter()
+        .map(|c| CubicBezier::new(t.apply(c.p0), t.apply(c.c0), t.apply(c.c1), t.apply(c.p1)))
+        .collect()
+}
+
+// The A command of path data, from the endpoints to the center as in the
+// SVG implementation notes
+fn endpoint_arc(
+    from: (f32, f32),
+    (mut rx, mut ry): (f32, f32),
+    rotation: f32,
+    large: bool,
+    sweep: bool,
+    to: (f32, f32),
+) -> Vec<CubicBezier> {
+    if from == to {
+        return Vec::new();
+    }
+    rx = rx.abs();
+    ry = ry.abs();
+    if rx == 0.0 || ry == 0.0 {
+        return vec![line(from, to)];
+    }
+    let phi = rotation.to_radians();
+    let (sin, cos) = phi.sin_cos();
+    let (dx, dy) = ((from.0 - to.0) / 2.0, (from.1 - to.1) / 2.0);
+    let (x1, y1) = (cos * dx + sin * dy, -sin * dx + cos * dy);
+
+    // Radii too small to reach are scaled up
+    let lambda = (x1 / rx).powi(2) + (y1 / ry).powi(2);
+    if lambda > 1.0 {
+        rx *= lambda.sqrt();
+        ry *= lambda.sqrt();
+    }
+    let num = (rx * ry).powi(2) - (rx * y1).powi(2) - (ry * x1).powi(2);
+    let den = (rx * y1).powi(2) + (ry * x1).powi(2);
+    let mut k = (num / den).max(0.0).sqrt();
+    if large == sweep {
+        k = -k;
+    }
+    let (cx1, cy1) = (k * rx * y1 / ry, -k * ry * x1 / rx);
+    let center = (
+        cos * cx1 - sin * cy1 + (from.0 + to.0) / 2.0,
+        sin * cx1 + cos * cy1 + (from.1 + to.1) / 2.0,
+    );
+
+    let angle = |ux: f32, uy: f32| uy.atan2(ux);
+    let start = angle((x1 - cx1) / rx, (y1 - cy1) / ry);
+    let end = angle((-x1 - cx1) / rx, (-y1 - cy1) / ry);
+    let mut delta = end - start;
+    if sweep && delta < 0.0 {
+        delta += 2.0 * PI;
+    } else if !sweep && delta > 0.0 {
+        delta -= 2.0 * PI;
+    }
+    let mut arc = elliptical_arc(center, (rx, ry), phi, start, delta);
+    // Exactly on the endpoints so the chain stays connected
+    if let Some(first) = arc.first_mut() {
+        first.p0 = from;
+    }
+    if let Some(last) = arc.last_mut() {
+        last.p1 = to;
+    }
+    arc
+}
+
+// Subpaths of path data. Parsing stops at the first error, keeping what
+// came before as browsers do.
+fn path_data(d: &str) -> Vec<Vec<CubicBezier>> {
+    let mut subpaths: Vec<Vec<CubicBezier>> = Vec::new();
+    let mut current: Vec<CubicBezier> = Vec::new();
+    let mut at = (0.0, 0.0);
+    let mut start = (0.0, 0.0);
+    // Control point of the last C/S or Q/T, for the smooth forms
+    let mut last_cubic: Option<(f32, f32)> = None;
+    let mut last_quad: Option<(f32, f32)> = None;
+
+    let mut rest = d;
+    let mut command = None;
+    loop {
+        rest = rest.trim_start_matches(|c: char| c.is_whitespace() || c == ',');
+        if let Some(c) = rest.chars().next().filter(char::is_ascii_alphabetic) {
+            command = Some(c);
+            rest = &rest[1..];
+        }
+        let Some(c) = command else {
+            break;
+        };
+        let mut numbers = Numbers::new(rest);
+        let relative = c.is_ascii_lowercase();
+        let offset = if relative { at } else { (0.0, 0.0) };
+        let point = |numbers: &mut Numbers| -> Option<(f32, f32)> {
+            Some((numbers.next()? + offset.0, numbers.next()? + offset.1))
+        };
+
+        let mut cubic = None;
+        let mut quad = None;
+        let parsed = match c.to_ascii_uppercase() {
+            'M' => point(&mut numbers).map(|p| {
+                if !current.is_empty() {
+                    subpaths.push(std::mem::take(&mut current));
+                }
+                at = p;
+                start = p;
+                // More pairs after a move are lines
+                command = Some(if relative { 'l' } else { 'L' });
+            }),
+            'L' => point(&mut numbers).map(|p| {
+                current.push(line(at, p));
+                at = p;
+            }),
+            'H' => numbers.next().map(|x| {
+                let p = (x + offset.0, at.1);
+                current.push(line(at, p));
+                at = p;
+            }),
+            'V' => numbers.next().map(|y| {
+                let p = (at.0, y + offset.1);
+                current.push(line(at, p));
+                at = p;
+            }),
+            'C' => (|| {
+                let (c0, c1, p) = (
+                    point(&mut numbers)?,
+                    point(&mut numbers)?,
+                    point(&mut numbers)?,
+                );
+                current.push(CubicBezier::new(at, c0, c1, p));
+                cubic = Some(c1);
+                at = p;
+                Some(())
+            })(),
+            'S' => (|| {
+                let (c1, p) = (point(&mut numbers)?, point(&mut numbers)?);
+                let c0 = last_cubic.map_or(at, |c| (2.0 * at.0 - c.0, 2.0 * at.1 - c.1));
+                current.push(CubicBezier::new(at, c0, c1, p));
+                cubic = Some(c1);
+                at = p;
+                Some(())
+            })(),
+            'Q' | 'T' => (|| {
+                let q = if c.eq_ignore_ascii_case(&'Q') {
+                    point(&mut numbers)?
+                } else {
+                    last_quad.map_or(at, |q| (2.0 * at.0 - q.0, 2.0 * at.1 - q.1))
+                };
+                let p = point(&mut numbers)?;
+                // Degree elevation of the quadratic
+                let c0 = path::lerp(at, q, 2.0 / 3.0);
+                let c1 = path::lerp(p, q, 2.0 / 3.0);
+                current.push(CubicBezier::new(at, c0, c1, p));
+                quad = Some(q);
+                at = p;
+                Some(())
+            })(),
+            'A' => (|| {
+                let radii = (numbers.next()?, numbers.next()?);
+                let rotation = numbers.next()?;
+                let (large, sweep) = (numbers.flag()?, numbers.flag()?);
+                let p = point(&mut numbers)?;
+                current.extend(endpoint_arc(at, radii, rotation, large, sweep, p));
+                at = p;
+                Some(())
+            })(),
+            'Z' => {
+                if at != start {
+                    current.push(line(at, start));
+                }
+                at = start;
+                if !current.is_empty() {
+                    subpaths.push(std::mem::take(&mut current));
+                }
+                command = None;
+                Some(())
+            }
+            _ => None,
+        };
+        if parsed.is_none() {
+            break;
+        }
+        rest = numbers.text;
+        last_cubic = cubic;
+        last_quad = quad;
+    }
+    if !current.is_empty() {
+        subpaths.push(current);
+    }
+    subpaths
+}
+
+fn polyline(points: &[f32], closed: bool) -> Vec<CubicBezier> {
+    let points: Vec<(f32, f32)> = points.chunks_exact(2).map(|p| (p[0], p[1])).collect();
+    let mut chain: Vec<CubicBezier> = points.windows(2).map(|w| line(w[0], w[1])).collect();
+    if closed && points.len() > 2 {
+        chain.push(line(points[points.len() - 1], points[0]));
+    }
+    chain
+}
+
+// Strokes of a single element before its transform
+fn shape(tag: &Tag) -> Vec<Vec<CubicBezier>> {
+    let n = |name: &str| tag.number(name);
+    match tag.name {
+        "path" => path_data(tag.get("d").unwrap_or("")),
+        "line" => vec![vec![line((n("x1"), n("y1")), (n("x2"), n("y2")))]],
+        "polyline" | "polygon" => {
+            let points: Vec<f32> = Numbers::new(tag.get("points").unwrap_or("")).collect();
+            vec![polyline(&points, tag.name == "polygon")]
+        }
+        "rect" => {
+            let (x, y, w, h) = (n("x"), n("y"), n("width"), n("height"));
+            if w <= 0.0 || h <= 0.0 {
+                return Vec::new();
+            }
+            // Either corner radius stands in for the other if missing
+            let rx = tag.get("rx").and_then(leading_number);
+            let ry = tag.get("ry").and_then(leading_number);
+            let rx = rx.or(ry).unwrap_or(0.0).clamp(0.0, w / 2.0);
+            let ry = ry.or(Some(rx)).unwrap_or(0.0).clamp(0.0, h / 2.0);
+            if rx == 0.0 || ry == 0.0 {
+                let corners = [x, y, x + w, y, x + w, y + h, x, y + h];
+                return vec![polyline(&corners, true)];
+            }
+            let mut chain: Vec<CubicBezier> = Vec::new();
+            let corners = [
+                ((x + w - rx, y + ry), -PI / 2.0),
+                ((x + w - rx, y + h - ry), 0.0),
+                ((x + rx, y + h - ry), PI / 2.0),
+                ((x + rx, y + ry), PI),
+            ];
+            for (center, start) in corners {
+                let arc = elliptical_arc(center, (rx, ry), 0.0, start, PI / 2.0);
+                if let Some(last) = chain.last() {
+                    chain.push(line(last.p1, arc[0].p0));
+                }
+                chain.extend(arc);
+            }
+            let (first, last) = (chain[0].p0, chain[chain.len() - 1].p1);
+            chain.push(line(last, first));
+            vec![chain]
+        }
+        "circle" => {
+            let r = n("r");
+            if r <= 0.0 {
+                return Vec::new();
+            }
+            vec![elliptical_arc(
+                (n("cx"), n("cy")),
+                (r, r),
+                0.0,
+                0.0,
+                2.0 * PI,
+            )]
+        }
+        "ellipse" => {
+            let (rx, ry) = (n("rx"), n("ry"));
+            if rx <= 0.0 || ry <= 0.0 {
+                return Vec::new();
+            }
+            vec![elliptical_arc(
+                (n("cx"), n("cy")),
+                (rx, ry),
+                0.0,
+                0.0,
+                2.0 * PI,
+            )]
+        }
+        _ => Vec::new(),
+    }
+}
+
+impl Svg {
+    // Paths, lines, polylines, polygons, rectangles, circles and ellipses,
+    // with the transforms of their groups. Text, images and anything only
+    // referenced, like <use>, are left out.
+    pub fn parse(text: &str) -> Result<Svg, Error> {
+        let tags = tags(text)?;
+        let root = tags
+            .iter()
+            .find(|t| t.name == "svg" && !t.closing)
+            .ok_or_else(|| invalid("no <svg> element"))?;
+
+        // Transforms of the open elements, and whether they're hidden
+        let mut stack = vec![(AffineTransform::IDENTITY, false)];
+        let mut strokes = Vec::new();
+        for tag in &tags {
+            if tag.closing {
+                if stack.len() > 1 {
+                    stack.pop();
+                }
+                continue;
+            }
+            let (parent, hidden) = stack[stack.len() - 1];
+            let t = parent * transform(tag.get("transform").unwrap_or(""));
+            let hidden = hidden || HIDDEN.contains(&tag.name) || tag.hidden();
+            if !hidden {
+                for chain in shape(tag) {
+                    strokes.push(
+                        chain
+                            .iter()
+                            .map(|c| {
+                                CubicBezier::new(
+                                    t.apply(c.p0),
+                                    t.apply(c.c0),
+                                    t.apply(c.c1),
+                                    t.apply(c.p1),
+                                )
+                            })
+                            .collect(),
+                    );
+                }
+            }
+            if !tag.empty {
+                stack.push((t, hidden));
+            }
+        }
+
+        let view_box: Vec<f32> = Numbers::new(root.get("viewBox").unwrap_or("")).collect();
+        let (min, max) = if let [x, y, w, h] = view_box[..] {
+            ((x, y), (x + w, y + h))
+        } else if let (Some(w), Some(h)) = (
+            root.get("width").and_then(leading_number),
+            root.get("height").and_then(leading_number),
+        ) {
+            ((0.0, 0.0), (w, h))
+        } else {
+            let points = strokes
+                .iter()
+                .flatten()
+                .flat_map(|c: &CubicBezier| [c.p0, c.c0, c.c1, c.p1]);
+            points.fold(
+                ((f32::MAX, f32::MAX), (f32::MIN, f32::MIN)),
+                |(min, max), p| {
+                    (
+                        (min.0.min(p.0), min.1.min(p.1)),
+                        (max.0.max(p.0), max.1.max(p.1)),
+                    )
+                },
+            )
+        };
+        Ok(Svg { min, max, strokes })
+    }
+
+    pub fn load(filename: &str) -> Result<Svg, Error> {
+        Svg::parse(&std::fs::read_to_string(filename)?)
+    }
+
+    // Strokes scaled to fit min..max without distorting them and centered,
+    // flipped so that the top of the file is at max.1
+    pub fn fit(&self, min: (f32, f32), max: (f32, f32)) -> Vec<Path> {
+        let (w, h) = (self.max.0 - self.min.0, self.max.1 - self.min.1);
+        if self.strokes.is_empty() || w <= 0.0 || h <= 0.0 {
+            return Vec::new();
+        }
+        let s = ((max.0 - min.0) / w).min((max.1 - min.1) / h);
+        let margin = ((max.0 - min.0 - w * s) / 2.0, (max.1 - min.1 - h * s) / 2.0);
+        let t = AffineTransform::translate(-self.min.0, -self.min.1)
+            .then(&AffineTransform::scale(s, -s))
+            .then(&AffineTransform::translate(
+                min.0 + margin.0,
+                max.1 - margin.1,
+            ));
+        self.strokes
+            .iter()
+            .map(|chain| {
+                let chain: Vec<CubicBezier> = chain
+                    .iter()
+                    .map(|c| {
+                        CubicBezier::new(t.apply(c.p0), t.apply(c.c0), t.apply(c.c1), t.apply(c.p1))
+                    })
+                    .collect();
+                let mut path = curve::flatten(&chain, curve::TOLERANCE);
+                path.dedup();
+                path
+            })
+            .filter(|p| p.len() > 1)
+            .collect()
+    }
+
+    // Fits the drawing into the printer's drawing area and draws it
+    pub fn draw(&self, printer: &mut Printer) {
+        let area = printer
+            .config
+            .scale
+            .unwrap_or((printer.width, printer.height));
+        for path in self.fit((0.0, 0.0), area) {
+            printer.draw_polyline(&path);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{assert_within, test_config};
+
+    fn near(a: (f32, f32), b: (f32, f32)) -> bool {
+        path::dist(a, b) < 1e-3
+    }
+
+    #[test]
+    fn numbers_without_separators() {
+        let numbers: Vec<f32> = Numbers::new("1.5.5-2,3e1 -.5E-1").collect();
+        assert_eq!(numbers, [1.5, 0.5, -2.0, 30.0, -0.05]);
+        let mut flags = Numbers::new("1 0 10,5");
+        assert_eq!(flags.next(), Some(1.0));
+        assert_eq!(flags.flag(), Some(false));
+        assert_eq!(flags.flag(), Some(true));
+        assert_eq!(flags.next(), Some(0.0));
+    }
+
+    #[test]
+    fn path_commands() {
+        let subpaths =
+            path_data("M10 10 20 10 v10 h-10z m5 5 l1 1 M0 0 C0 10 10 10 10 0 S20 -10 20 0");
+        assert_eq!(subpaths.len(), 3);
+        let ends: Vec<(f32, f32)> = subpaths[0].iter().map(|c| c.p1).collect();
+        assert_eq!(
+            ends,
+            [(20.0, 10.0), (20.0, 20.0), (10.0, 20.0), (10.0, 10.0)]
+        );
+        // Relative to where the closed subpath started
+        assert_eq!(subpaths[1][0].p0, (15.0, 15.0));
+        assert_eq!(subpaths[1][0].p1, (16.0, 16.0));
+        // The smooth curve mirrors the last control point
+        assert_eq!(subpaths[2][1].c0, (10.0, -10.0));
+
+        // Half circle from the arc command
+        let arc = &path_data("M0 0 A5 5 0 0 1 10 0")[0];
+        for p in curve::flatten(arc, 0.01) {
+            assert!((path::dist(p, (5.0, 0.0)) - 5.0).abs() < 0.02, "{:?}", p);
+        }
+        // y points down in SVG, so the sweep passes through negative y
+        assert!(near(arc[0].p1, (5.0, -5.0)));
+
+        // Everything up to a bad command is kept
+        assert_eq!(path_data("M0 0 L5 5 L 7 X 1 1").len(), 1);
+        assert_eq!(path_data("M0 0 L5 5 L 7 X 1 1")[0].len(), 1);
+    }
+
+    #[test]
+    fn transforms() {
+        let t = transform("translate(10, 5) rotate(90) scale(2)");
+        assert!(near(t.apply((1.0, 0.0)), (10.0, 7.0)));
+        let skew = transform("skewX(45)");
+        assert!(near(skew.apply((0.0, 1.0)), (1.0, 1.0)));
+        assert!(transform("").is_identity());
+    }
+
+    #[test]
+    fn document() {
+        let svg = Svg::parse(
+            r#"<?xml version="1.0"?>
+            <!-- drawn by hand -->
+            <svg xmlns="http://www.w3.org/2000/svg" width="100mm" height="50mm" viewBox="0 0 200 100">
+              <defs><circle id="dot" r="3"/></defs>
+              <g transform="translate(100 0)">
+                <rect x="10" y="10" width="20" height="30"/>
+                <line x1="0" y1="0" x2="0" y2="100" style="display: none"/>
+              </g>
+              <polygon points="0,0 10,0 10,10"/>
+              <circle cx="50" cy="50" r="10"/>
+            </svg>"#,
+        )
+        .unwrap();
+        assert_eq!((svg.min, svg.max), ((0.0, 0.0), (200.0, 100.0)));
+        assert_eq!(svg.strokes.len(), 3);
+        assert_eq!(svg.strokes[0][0].p0, (110.0, 10.0));
+        assert_eq!(svg.strokes[1].len(), 3);
+
+        // Half the size on paper, upside down
+        let paths = svg.fit((0.0, 0.0), (100.0, 100.0));
+        assert_eq!(paths.len(), 3);
+        assert!(near(paths[0][0], (55.0, 70.0)));
+        let circle = &paths[2];
+        for &p in circle {
+            assert!((path::dist(p, (25.0, 50.0)) - 5.0).abs() < 0.06, "{:?}", p);
+        }
+
+        assert!(Svg::parse("<html></html>").is_err());
+        assert!(Svg::parse("<svg><path d='M0 0").is_err());
+    }
+
+    #[test]
+    fn svg_import() {
+        let drawing = Svg::parse(
+            r#"<svg viewBox="0 0 10 10">
+                 <path d="M0 0 H10 V10 H0 Z"/>
+                 <circle cx="5" cy="5" r="2"/>
+               </svg>"#,
+        )
+        .unwrap();
+        let mut printer = Printer::new(test_config());
+        drawing.draw(&mut printer);
+        assert_eq!(printer.ops().len(), 2);
+
+        // Square, so as tall as the bed and centered across it
+        let side = printer.height;
+        let left = (printer.width - side) / 2.0;
+        let square = printer.ops()[0].path();
+        assert_eq!(square.len(), 5);
+        assert_eq!(square[0], (left, side));
+        assert_within(square[2].0, left + side, 1e-3);
+        assert_within(square[2].1, 0.0, 1e-3);
+    }
+}
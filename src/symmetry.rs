@@ -0,0 +1,137 @@
+use std::f32::consts::PI;
+
+use crate::AffineTransform;
+
+// Radial symmetry for mandalas: everything drawn is repeated `count` times
+// around `center`. Mirrored, every copy is also reflected, so a motif drawn
+// in the wedge between `angle` and `angle` + 180 / `count` degrees fills the
+// whole circle like a kaleidoscope. Otherwise the wedge is 360 / `count`
+// degrees wide.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Symmetry {
+    // In drawing coordinates
+    pub center: (f32, f32),
+    pub count: usize,
+    pub mirror: bool,
+    // Direction of the line copies are mirrored across, in degrees
+    pub angle: f32,
+}
+
+impl Symmetry {
+    pub fn new(center: (f32, f32), count: usize) -> Self {
+        Symmetry {
+            center,
+            count: count.max(1),
+            mirror: false,
+            angle: 0.0,
+        }
+    }
+
+    pub fn mirrored(center: (f32, f32), count: usize) -> Self {
+        Symmetry {
+            mirror: true,
+            ..Symmetry::new(center, count)
+        }
+    }
+
+    // Transforms of all copies in drawing coordinates, the identity first
+    pub fn transforms(&self) -> Vec<AffineTransform> {
+        let (cx, cy) = self.center;
+        let count = self.count.max(1);
+        let rotations: Vec<AffineTransform> = (0..count)
+            .map(|k| AffineTransform::rotate_about(2.0 * PI * k as f32 / count as f32, cx, cy))
+            .collect();
+        if !self.mirror {
+            return rotations;
+        }
+
+        let reflection = AffineTransform::rotate_about(-self.angle.to_radians(), cx, cy)
+            .then(&AffineTransform::translate(0.0, -cy))
+            .then(&AffineTransform::scale(1.0, -1.0))
+            .then(&AffineTransform::translate(0.0, cy))
+            .then(&AffineTransform::rotate_about(
+                self.angle.to_radians(),
+                cx,
+                cy,
+            ));
+        rotations
+            .iter()
+            .copied()
+            .chain(rotations.iter().map(|r| reflection.then(r)))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{assert_within, test_config};
+    use crate::{Printer, path};
+
+    fn near(a: (f32, f32), b: (f32, f32)) -> bool {
+        (a.0 - b.0).abs() < 1e-4 && (a.1 - b.1).abs() < 1e-4
+    }
+
+    #[test]
+    fn copies_around_the_center() {
+        let quarter = Symmetry::new((10.0, 10.0), 4).transforms();
+        assert_eq!(quarter.len(), 4);
+        assert!(near(quarter[0].apply((3.0, 4.0)), (3.0, 4.0)));
+        let copies: Vec<(f32, f32)> = quarter.iter().map(|t| t.apply((15.0, 10.0))).collect();
+        for (copy, expected) in
+            copies
+                .iter()
+                .zip([(15.0, 10.0), (10.0, 15.0), (5.0, 10.0), (10.0, 5.0)])
+        {
+            assert!(near(*copy, expected), "{:?}", copies);
+        }
+
+        // Mirrored across the line at 45 degrees
+        let mirrored = Symmetry {
+            angle: 45.0,
+            ..Symmetry::mirrored((0.0, 0.0), 2)
+        };
+        let copies: Vec<(f32, f32)> = mirrored
+            .transforms()
+            .iter()
+            .map(|t| t.apply((1.0, 0.0)))
+            .collect();
+        assert_eq!(copies.len(), 4);
+        for (copy, expected) in
+            copies
+                .iter()
+                .zip([(1.0, 0.0), (-1.0, 0.0), (0.0, 1.0), (0.0, -1.0)])
+        {
+            assert!(near(*copy, expected), "{:?}", copies);
+        }
+    }
+
+    #[test]
+    fn mandala() {
+        let mut config = test_config();
+        // Twice as many drawing units along x as along y per mm
+        config.scale = Some((408.0, 177.0));
+        let mut printer = Printer::new(config);
+        printer.set_symmetry(Some(Symmetry::mirrored((204.0, 88.5), 3)));
+        printer.draw_polyline(&[(214.0, 88.5), (224.0, 90.0)]);
+        printer.draw_point(234.0, 88.5);
+        printer.set_symmetry(None);
+        printer.draw_point(0.0, 0.0);
+        assert_eq!(printer.ops().len(), 13);
+
+        // The copies of the point lie on a circle around the center in
+        // drawing coordinates, half as wide as it's tall on paper
+        let points: Vec<(f32, f32)> = printer.ops()[6..12]
+            .iter()
+            .map(|op| op.path()[0])
+            .map(|(x, y)| (x * 2.0, y))
+            .collect();
+        for p in &points {
+            assert_within(path::dist(*p, (204.0, 88.5)), 30.0, 1e-3);
+        }
+        assert_within(points[1].0, 204.0 - 15.0, 1e-3);
+        // The mirror images of the point on the axis coincide with it
+        assert!(path::dist(points[0], points[3]) < 1e-3);
+        assert_eq!(printer.ops()[12].path(), [(0.0, 0.0)]);
+    }
+}
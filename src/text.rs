@@ -0,0 +1,372 @@
+use crate::path::{self, Path};
+
+// Single-stroke font on a small grid, in the spirit of the Hershey fonts.
+// Every glyph is a list of strokes separated by spaces, each stroke a run of
+// two-digit points "xy" with x in 0..=4 and y stored as y + 2 so descenders
+// fit (y = -2..=7). Capitals are 6 units tall, lowercase letters 4.
+const GLYPHS: [(char, &str); 96] = [
+    (' ', ""),
+    ('!', "2824 22"),
+    ('"', "1816 3836"),
+    ('#', "1218 3238 0444 0646"),
+    ('$', "473818070615354443321203 2129"),
+    ('%', "0248 0717180807 3242433332"),
+    ('&', "4216172837360403122244"),
+    ('\'', "2826"),
+    ('(', "3827152332"),
+    (')', "1827352312"),
+    ('*', "2327 0446 0644"),
+    ('+', "0545 2327"),
+    (',', "2311"),
+    ('-', "1535"),
+    ('.', "22"),
+    ('/', "0248"),
+    ('0', "120307183847433212 1337"),
+    ('1', "172822 1232"),
+    ('2', "07183847460242"),
+    ('3', "0718384746354443321203 1535"),
+    ('4', "32380444"),
+    ('5', "4808053544433202"),
+    ('6', "4738180703123243443505"),
+    ('7', "084812"),
+    ('8', "15060718384746351504031232434435"),
+    ('9', "0312324347381807061545"),
+    (':', "26 23"),
+    (';', "26 2311"),
+    ('<', "371533"),
+    ('=', "0444 0646"),
+    ('>', "173513"),
+    ('?', "07183847462524 22"),
+    ('@', "34141636334347381807031232"),
+    ('A', "022842 1434"),
+    ('B', "02083847463505 3544433202"),
+    ('C', "4738180703123243"),
+    ('D', "02082846442202"),
+    ('E', "48080242 0535"),
+    ('F', "480802 0535"),
+    ('G', "47381807031232434525"),
+    ('H', "0208 4248 0545"),
+    ('I', "1838 2822 1232"),
+    ('J', "4843321203"),
+    ('K', "0208 4804 1542"),
+    ('L', "080242"),
+    ('M', "0208254842"),
+    ('N', "02084248"),
+    ('O', "120307183847433212"),
+    ('P', "02083847463505"),
+    ('Q', "120307183847433212 2442"),
+    ('R', "02083847463505 2542"),
+    ('S', "473818070615354443321203"),
+    ('T', "0848 2822"),
+    ('U', "080312324348"),
+    ('V', "082248"),
+    ('W', "0812263248"),
+    ('X', "0842 0248"),
+    ('Y', "082548 2522"),
+    ('Z', "08480242"),
+    ('[', "38181232"),
+    ('\\', "0842"),
+    (']', "18383212"),
+    ('^', "162836"),
+    ('_', "0141"),
+    ('`', "1827"),
+    ('a', "4642 4536160503123243"),
+    ('b', "0802 0516364543321203"),
+    ('c', "4536160503123243"),
+    ('d', "4842 4536160503123243"),
+    ('e', "044445361605031232"),
+    ('f', "38281712 0636"),
+    ('g', "4641301001 4536160503123243"),
+    ('h', "0802 0516364542"),
+    ('i', "2622 28"),
+    ('j', "3631201001 38"),
+    ('k', "0802 3603 1432"),
+    ('l', "282332"),
+    ('m', "0602 05162522 25364542"),
+    ('n', "0602 0516364542"),
+    ('o', "120305163645433212"),
+    ('p', "0600 0516364543321203"),
+    ('q', "4640 4536160503123243"),
+    ('r', "0602 042646"),
+    ('s', "45361605143443321203"),
+    ('t', "18132232 0636"),
+    ('u', "0603123243 4642"),
+    ('v', "062246"),
+    ('w', "0612253246"),
+    ('x', "0642 0246"),
+    ('y', "0622 4610"),
+    ('z', "06460242"),
+    ('{', "38272615242332"),
+    ('|', "2129"),
+    ('}', "18272635242312"),
+    ('~', "05163445"),
+    // Em dash, the whole width of the cell
+    ('\u{2014}', "0545"),
+];
+
+// Grid units per capital letter height
+const CAP_HEIGHT: f32 = 6.0;
+// Horizontal distance between the start of two glyphs, in grid units
+const ADVANCE: f32 = 6.0;
+// Distance between two baselines, in grid units
+const LINE_SPACING: f32 = 10.0;
+
+fn glyph(c: char) -> &'static str {
+    GLYPHS
+        .iter()
+        .find(|(g, _)| *g == c)
+        .or_else(|| GLYPHS.iter().find(|(g, _)| *g == '?'))
+        .map(|(_, strokes)| *strokes)
+        .unwrap_or("")
+}
+
+fn digit(b: u8) -> f32 {
+    (b - b'0') as f32
+}
+
+// Strokes of a single glyph with its baseline-left corner at the origin,
+// in grid units
+fn glyph_strokes(c: char) -> Vec<Path> {
+    glyph(c)
+        .split(' ')
+        .filter(|s| !s.is_empty())
+        .map(|stroke| {
+            stroke
+                .as_bytes()
+                .chunks(2)
+                .map(|p| (digit(p[0]), digit(p[1]) - 2.0))
+                .collect()
+        })
+        .collect()
+}
+
+// Width of the widest line of `text` in mm when capitals are `height` tall
+pub fn width(text: &str, height: f32) -> f32 {
+    let scale = height / CAP_HEIGHT;
+    text.lines()
+        .map(|line| {
+            let n = line.chars().count() as f32;
+            // The last glyph has no trailing gap
+            (n * ADVANCE - (ADVANCE - 4.0)).max(0.0) * scale
+        })
+        .fold(0.0, f32::max)
+}
+
+// Lays out `text` as strokes with the baseline of the first line starting at
+// the origin. Capitals are `height` mm tall, further lines go downwards.
+pub fn layout(text: &str, height: f32) -> Vec<Path> {
+    let scale = height / CAP_HEIGHT;
+    let mut paths = Vec::new();
+
+    for (row, line) in text.lines().enumerate() {
+        let y0 = -(row as f32) * LINE_SPACING;
+        for (col, c) in line.chars().enumerate() {
+            let x0 = col as f32 * ADVANCE;
+            for stroke in glyph_strokes(c) {
+                paths.push(
+                    stroke
+                        .iter()
+                        .map(|&(x, y)| ((x0 + x) * scale, (y0 + y) * scale))
+                        .collect(),
+                );
+            }
+        }
+    }
+
+    paths
+}
+
+// Lettering along a path, e.g. around a circle or down a spiral
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct OnPath {
+    // Capital height in mm
+    pub height: f32,
+    // Gap added between glyphs, in mm. Negative tightens the text.
+    pub spacing: f32,
+    // Distance along the path where the text starts, in mm
+    pub offset: f32,
+    // Baseline distance from the path, to the left of its direction
+    pub shift: f32,
+    // Glyphs turn with the path, otherwise they stay upright
+    pub rotate: bool,
+}
+
+impl OnPath {
+    pub fn new(height: f32) -> Self {
+        OnPath {
+            height,
+            spacing: 0.0,
+            offset: 0.0,
+            shift: 0.0,
+            rotate: true,
+        }
+    }
+
+    // Length of path that `text` takes up
+    pub fn length(&self, text: &str) -> f32 {
+        let n = text.chars().count() as f32;
+        if n == 0.0 {
+            return 0.0;
+        }
+        width(&text.replace('\n', " "), self.height) + (n - 1.0) * self.spacing
+    }
+
+    // Strokes of `text` along `path` as a single line. Glyphs that would
+    // run past the end of the path are left out.
+    pub fn layout(&self, text: &str, path: &[(f32, f32)]) -> Vec<Path> {
+        let scale = self.height / CAP_HEIGHT;
+        let total = path::length(path);
+        let mut paths = Vec::new();
+        for (i, c) in text.chars().enumerate() {
+            let start = self.offset + i as f32 * (ADVANCE * scale + self.spacing);
+            // The glyph sits on the chord under its body
+            let end = start + 4.0 * scale;
+            if start < 0.0 || end > total + 1e-4 {
+                continue;
+            }
+            let (Some(a), Some(b)) = (path::point_at(path, start), path::point_at(path, end))
+            else {
+                continue;
+            };
+            let angle = if self.rotate {
+                (b.1 - a.1).atan2(b.0 - a.0)
+            } else {
+                0.0
+            };
+            let (sin, cos) = angle.sin_cos();
+            let origin = if self.rotate {
+                (a.0 - self.shift * sin, a.1 + self.shift * cos)
+            } else {
+                (a.0, a.1 + self.shift)
+            };
+            for stroke in glyph_strokes(c) {
+                paths.push(
+                    stroke
+                        .iter()
+                        .map(|&(x, y)| {
+                            let (x, y) = (x * scale, y * scale);
+                            (origin.0 + x * cos - y * sin, origin.1 + x * sin + y * cos)
+                        })
+                        .collect(),
+                );
+            }
+        }
+        paths
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::test_config;
+    use crate::{AffineTransform, Printer};
+
+    #[test]
+    fn text_on_a_circle() {
+        // Clockwise circle of radius 20 starting on the right, so the left
+        // side and the tops of the glyphs face outwards
+        let circle: Path = (0..=360)
+            .map(|i| {
+                let a = -(i as f32).to_radians();
+                (20.0 * a.cos(), 20.0 * a.sin())
+            })
+            .collect();
+        let on_path = OnPath {
+            shift: 1.0,
+            ..OnPath::new(6.0)
+        };
+        let paths = on_path.layout("I", &circle);
+        // The stem of the I points away from the center, starting 1mm out
+        // from the chord under the glyph
+        let stem = paths
+            .iter()
+            .find(|p| p.len() == 2 && path::dist(p[0], p[1]) > 5.9)
+            .unwrap();
+        let r: Vec<f32> = stem.iter().map(|&p| path::dist(p, (0.0, 0.0))).collect();
+        assert!((r[0].min(r[1]) - 20.9).abs() < 0.05, "{:?}", r);
+        assert!((r[0].max(r[1]) - 26.9).abs() < 0.05, "{:?}", r);
+
+        // Text that doesn't fit is cut off, spacing spreads it out
+        let long = "A".repeat(40);
+        let fits = |o: &OnPath| o.layout(&long, &circle).len() / glyph_strokes('A').len();
+        assert_eq!(fits(&OnPath::new(6.0)), 21);
+        let spaced = OnPath {
+            spacing: 2.0,
+            ..OnPath::new(6.0)
+        };
+        assert!(fits(&spaced) < 21);
+        assert_eq!(OnPath::new(6.0).length("AB"), width("AB", 6.0));
+        assert_eq!(spaced.length("AB"), width("AB", 6.0) + 2.0);
+
+        // Upright glyphs don't turn
+        let upright = OnPath {
+            rotate: false,
+            ..OnPath::new(6.0)
+        };
+        let quarter = circle.len() / 4;
+        let paths = upright.layout("I", &circle[quarter..]);
+        assert!(
+            paths
+                .iter()
+                .any(|p| p.len() == 2 && (p[0].0 - p[1].0).abs() < 1e-3)
+        );
+    }
+
+    #[test]
+    fn font_is_well_formed() {
+        for (c, strokes) in GLYPHS {
+            for stroke in strokes.split(' ').filter(|s| !s.is_empty()) {
+                assert_eq!(stroke.len() % 2, 0, "odd stroke in {:?}", c);
+                assert!(stroke.bytes().all(|b| b.is_ascii_digit()), "{:?}", c);
+                for p in stroke.as_bytes().chunks(2) {
+                    assert!(digit(p[0]) <= 4.0, "{:?} is too wide", c);
+                }
+            }
+        }
+
+        // Every printable ASCII character has a glyph
+        for c in ' '..='~' {
+            assert!(GLYPHS.iter().any(|(g, _)| *g == c), "{:?} is missing", c);
+        }
+    }
+
+    #[test]
+    fn layout_scales_and_advances() {
+        let paths = layout("L", 12.0);
+        assert_eq!(paths, vec![vec![(0.0, 12.0), (0.0, 0.0), (8.0, 0.0)]]);
+
+        let paths = layout("LL", 6.0);
+        assert_eq!(paths[1], vec![(6.0, 6.0), (6.0, 0.0), (10.0, 0.0)]);
+
+        let paths = layout("L\nL", 6.0);
+        assert_eq!(paths[1], vec![(0.0, -4.0), (0.0, -10.0), (4.0, -10.0)]);
+
+        assert_eq!(width("LL", 6.0), 10.0);
+        assert_eq!(width("", 6.0), 0.0);
+    }
+
+    #[test]
+    fn unknown_characters_fall_back() {
+        assert_eq!(layout("\u{e9}", 6.0), layout("?", 6.0));
+        assert!(layout(" ", 6.0).is_empty());
+        // A period is a single dot
+        assert_eq!(layout(".", 6.0), vec![vec![(2.0, 0.0)]]);
+    }
+
+    #[test]
+    fn text_through_transforms() {
+        let mut printer = Printer::new(test_config());
+        printer.push_transform(AffineTransform::rotate_deg(90.0));
+        printer.draw_text(10.0, 0.0, 6.0, "HI");
+        printer.pop_transform();
+        assert_eq!(printer.ops().len(), layout("HI", 6.0).len());
+        assert_eq!(printer.ops()[0].label.as_deref(), Some("draw_text(\"HI\")"));
+        assert!(printer.ops()[1].label.is_none());
+        // Standing on its side, the left leg of the H goes from (0, 10) to
+        // (-6, 10)
+        let leg = printer.ops()[0].path();
+        for (p, e) in leg.iter().zip([(0.0, 10.0), (-6.0, 10.0)]) {
+            assert!(path::dist(*p, e) < 1e-3, "{:?}", leg);
+        }
+    }
+}
@@ -0,0 +1,248 @@
+use std::f32::consts::PI;
+
+use crate::Printer;
+use crate::overlap;
+use crate::path::{self, Path};
+
+const PHI: f32 = 1.618_034;
+
+// Penrose tilings, built from Robinson triangles that are split again and
+// again until the tiles are small enough
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Penrose {
+    // P2
+    KitesAndDarts,
+    // P3, thick and thin rhombs
+    Rhombs,
+}
+
+// Half of a tile, split along BC. Acute triangles are half kites or half
+// thin rhombs, obtuse ones half darts or half thick rhombs.
+#[derive(Debug, Copy, Clone, PartialEq)]
+struct Triangle {
+    obtuse: bool,
+    a: (f32, f32),
+    b: (f32, f32),
+    c: (f32, f32),
+}
+
+// Point at `t` of the way from a to b
+fn at(a: (f32, f32), b: (f32, f32), t: f32) -> (f32, f32) {
+    path::lerp(a, b, t)
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Tiling {
+    pub kind: Penrose,
+    // Longest edge of the tiles, in mm
+    pub edge: f32,
+    // Center of the five-fold symmetry, the middle of the area if not set
+    pub center: Option<(f32, f32)>,
+    // Turn of the whole tiling, in degrees
+    pub rotation: f32,
+}
+
+impl Tiling {
+    pub fn new(kind: Penrose, edge: f32) -> Self {
+        Tiling {
+            kind,
+            edge,
+            center: None,
+            rotation: 0.0,
+        }
+    }
+
+    fn split(&self, triangles: Vec<Triangle>) -> Vec<Triangle> {
+        let t = |obtuse, a, b, c| Triangle { obtuse, a, b, c };
+        let mut out = Vec::with_capacity(triangles.len() * 3);
+        for Triangle { obtuse, a, b, c } in triangles {
+            match (self.kind, obtuse) {
+                (Penrose::Rhombs, false) => {
+                    let p = at(a, b, 1.0 / PHI);
+                    out.extend([t(false, c, p, b), t(true, p, c, a)]);
+                }
+                (Penrose::Rhombs, true) => {
+                    let q = at(b, a, 1.0 / PHI);
+                    let r = at(b, c, 1.0 / PHI);
+                    out.extend([t(true, r, c, a), t(true, q, r, b), t(false, r, q, a)]);
+                }
+                (Penrose::KitesAndDarts, false) => {
+                    let q = at(a, b, 1.0 / PHI);
+                    let r = at(b, c, 1.0 / PHI);
+                    out.extend([t(true, r, q, b), t(false, q, a, r), t(false, c, a, r)]);
+                }
+                (Penrose::KitesAndDarts, true) => {
+                    let p = at(c, a, 1.0 / PHI);
+                    out.extend([t(true, b, p, a), t(false, p, c, b)]);
+                }
+            }
+        }
+        out
+    }
+
+    // Triangles covering at least the circle of `radius` around `center`
+    fn triangles(&self, center: (f32, f32), radius: f32) -> Vec<Triangle> {
+        // A wheel of ten triangles only reaches cos(18°) of the way out
+        // between its spokes
+        let r = radius / (PI / 10.0).cos() * 1.01;
+        let spoke = |i: i32| {
+            let angle = (2 * i - 1) as f32 * PI / 10.0 + self.rotation.to_radians();
+            (center.0 + r * angle.cos(), center.1 + r * angle.sin())
+        };
+        let mut triangles: Vec<Triangle> = (0..10)
+            .map(|i| {
+                let (mut b, mut c) = (spoke(i), spoke(i + 1));
+                // Every other one mirrored
+                if i % 2 == 0 {
+                    std::mem::swap(&mut b, &mut c);
+                }
+                match self.kind {
+                    // Apex of the half rhombs at a, of the half kites at b
+                    Penrose::Rhombs => Triangle {
+                        obtuse: false,
+                        a: center,
+                        b,
+                        c,
+                    },
+                    Penrose::KitesAndDarts => Triangle {
+                        obtuse: false,
+                        a: b,
+                        b: center,
+                        c,
+                    },
+                }
+            })
+            .collect();
+
+        // Every split makes the tiles smaller by the golden ratio
+        let mut edge = r;
+        while edge > self.edge.max(1e-3) {
+            triangles = self.split(triangles);
+            edge /= PHI;
+        }
+        triangles
+    }
+
+    // Edges of the tiles over min..max, each drawn once. Tiles are cut off
+    // at the edge of the area.
+    pub fn paths(&self, min: (f32, f32), max: (f32, f32)) -> Vec<Path> {
+        let center = self
+            .center
+            .unwrap_or(((min.0 + max.0) / 2.0, (min.1 + max.1) / 2.0));
+        let radius = [min, max, (min.0, max.1), (max.0, min.1)]
+            .iter()
+            .map(|&p| path::dist(p, center))
+            .fold(0.0, f32::max);
+        // Both halves of a tile leave out the line between them
+        let halves: Vec<Path> = self
+            .triangles(center, radius)
+            .into_iter()
+            .map(|t| vec![t.b, t.a, t.c])
+            .filter(|p| !path::clip_rect(p, min, max).is_empty())
+            .collect();
+        overlap::dedupe(&halves, self.edge * 1e-3)
+            .iter()
+            .flat_map(|p| path::clip_rect(p, min, max))
+            .collect()
+    }
+
+    pub fn draw(&self, printer: &mut Printer, min: (f32, f32), max: (f32, f32)) {
+        for path in self.paths(min, max) {
+            printer.draw_polyline(&path);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn angles(t: &Triangle) -> [f32; 3] {
+        let angle = |p: (f32, f32), q: (f32, f32), r: (f32, f32)| {
+            let (u, v) = ((q.0 - p.0, q.1 - p.1), (r.0 - p.0, r.1 - p.1));
+            (u.0 * v.1 - u.1 * v.0)
+                .atan2(u.0 * v.0 + u.1 * v.1)
+                .abs()
+                .to_degrees()
+        };
+        let mut a = [
+            angle(t.a, t.b, t.c),
+            angle(t.b, t.a, t.c),
+            angle(t.c, t.a, t.b),
+        ];
+        a.sort_by(f32::total_cmp);
+        a.map(f32::round)
+    }
+
+    #[test]
+    fn robinson_triangles() {
+        for kind in [Penrose::KitesAndDarts, Penrose::Rhombs] {
+            let tiling = Tiling::new(kind, 5.0);
+            let triangles = tiling.triangles((0.0, 0.0), 50.0);
+            // Golden triangles and gnomons only, covering the same area
+            for t in &triangles {
+                let expected = if t.obtuse {
+                    [36.0, 36.0, 108.0]
+                } else {
+                    [36.0, 72.0, 72.0]
+                };
+                assert_eq!(angles(t), expected, "{:?}", kind);
+            }
+            let area = |t: &Triangle| {
+                ((t.b.0 - t.a.0) * (t.c.1 - t.a.1) - (t.b.1 - t.a.1) * (t.c.0 - t.a.0)).abs() / 2.0
+            };
+            let wheel: f32 = Tiling::new(kind, 1e6)
+                .triangles((0.0, 0.0), 50.0)
+                .iter()
+                .map(area)
+                .sum();
+            let total: f32 = triangles.iter().map(area).sum();
+            assert!((total - wheel).abs() / wheel < 1e-3, "{} {}", total, wheel);
+            // Split small enough
+            let longest = triangles
+                .iter()
+                .map(|t| path::dist(t.a, t.b).max(path::dist(t.a, t.c)))
+                .fold(0.0, f32::max);
+            assert!(longest <= 5.0 && longest > 5.0 / PHI / PHI, "{}", longest);
+        }
+    }
+
+    #[test]
+    fn edges_are_drawn_once() {
+        let tiling = Tiling::new(Penrose::Rhombs, 8.0);
+        let paths = tiling.paths((0.0, 0.0), (60.0, 40.0));
+        assert!(paths.iter().flatten().all(|&p| path::inside_rect(
+            p,
+            (-1e-3, -1e-3),
+            (60.001, 40.001)
+        )));
+
+        // No segment is repeated
+        let mut segments: Vec<[(i32, i32); 2]> = paths
+            .iter()
+            .flat_map(|p| p.windows(2))
+            .map(|w| {
+                let key =
+                    |p: (f32, f32)| ((p.0 * 100.0).round() as i32, (p.1 * 100.0).round() as i32);
+                let mut s = [key(w[0]), key(w[1])];
+                s.sort();
+                s
+            })
+            .collect();
+        let count = segments.len();
+        segments.sort();
+        segments.dedup();
+        assert_eq!(segments.len(), count);
+
+        // Tiles all over the area
+        for x in [5.0, 30.0, 55.0] {
+            for y in [5.0, 20.0, 35.0] {
+                let near = paths
+                    .iter()
+                    .flat_map(|p| p.windows(2))
+                    .any(|w| path::dist_to_segment((x, y), w[0], w[1]) < 8.0);
+                assert!(near, "nothing near {:?}", (x, y));
+            }
+        }
+    }
+}
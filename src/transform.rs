@@ -0,0 +1,186 @@
+use std::ops::Mul;
+
+// 2D affine transform stored as the top two rows of a 3x3 matrix:
+//
+//   | a c e |
+//   | b d f |
+//   | 0 0 1 |
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct AffineTransform {
+    pub a: f32,
+    pub b: f32,
+    pub c: f32,
+    pub d: f32,
+    pub e: f32,
+    pub f: f32,
+}
+
+impl Default for AffineTransform {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+impl AffineTransform {
+    pub const IDENTITY: AffineTransform = AffineTransform {
+        a: 1.0,
+        b: 0.0,
+        c: 0.0,
+        d: 1.0,
+        e: 0.0,
+        f: 0.0,
+    };
+
+    pub fn new(a: f32, b: f32, c: f32, d: f32, e: f32, f: f32) -> Self {
+        AffineTransform { a, b, c, d, e, f }
+    }
+
+    pub fn translate(dx: f32, dy: f32) -> Self {
+        Self::new(1.0, 0.0, 0.0, 1.0, dx, dy)
+    }
+
+    pub fn scale(sx: f32, sy: f32) -> Self {
+        Self::new(sx, 0.0, 0.0, sy, 0.0, 0.0)
+    }
+
+    // Counter-clockwise rotation about the origin
+    pub fn rotate(radians: f32) -> Self {
+        let (sin, cos) = radians.sin_cos();
+        Self::new(cos, sin, -sin, cos, 0.0, 0.0)
+    }
+
+    pub fn rotate_deg(degrees: f32) -> Self {
+        Self::rotate(degrees.to_radians())
+    }
+
+    // Flips x, mirroring across the y axis
+    pub fn mirror_x() -> Self {
+        Self::scale(-1.0, 1.0)
+    }
+
+    // Flips y, mirroring across the x axis
+    pub fn mirror_y() -> Self {
+        Self::scale(1.0, -1.0)
+    }
+
+    // x' = x + kx * y, y' = y + ky * x
+    pub fn shear(kx: f32, ky: f32) -> Self {
+        Self::new(1.0, ky, kx, 1.0, 0.0, 0.0)
+    }
+
+    // Rotation about an arbitrary center instead of the origin
+    pub fn rotate_about(radians: f32, cx: f32, cy: f32) -> Self {
+        Self::translate(-cx, -cy)
+            .then(&Self::rotate(radians))
+            .then(&Self::translate(cx, cy))
+    }
+
+    // Returns the transform that applies `self` first and then `next`
+    pub fn then(&self, next: &AffineTransform) -> Self {
+        *next * *self
+    }
+
+    pub fn determinant(&self) -> f32 {
+        self.a * self.d - self.b * self.c
+    }
+
+    pub fn invert(&self) -> Option<Self> {
+        let det = self.determinant();
+        if det.abs() <= f32::EPSILON {
+            return None;
+        }
+
+        let a = self.d / det;
+        let b = -self.b / det;
+        let c = -self.c / det;
+        let d = self.a / det;
+        let e = -(a * self.e + c * self.f);
+        let f = -(b * self.e + d * self.f);
+
+        Some(Self::new(a, b, c, d, e, f))
+    }
+
+    pub fn is_identity(&self) -> bool {
+        *self == Self::IDENTITY
+    }
+
+    pub fn apply(&self, (x, y): (f32, f32)) -> (f32, f32) {
+        (
+            self.a * x + self.c * y + self.e,
+            self.b * x + self.d * y + self.f,
+        )
+    }
+
+    pub fn apply_path(&self, path: &[(f32, f32)]) -> Vec<(f32, f32)> {
+        path.iter().map(|p| self.apply(*p)).collect()
+    }
+
+    // How much lengths are scaled on average, useful for tolerances given in mm
+    pub fn scale_factor(&self) -> f32 {
+        self.determinant().abs().sqrt()
+    }
+}
+
+// Matrix product: (lhs * rhs) applies `rhs` first and then `lhs`
+impl Mul for AffineTransform {
+    type Output = AffineTransform;
+
+    fn mul(self, rhs: AffineTransform) -> AffineTransform {
+        AffineTransform {
+            a: self.a * rhs.a + self.c * rhs.b,
+            b: self.b * rhs.a + self.d * rhs.b,
+            c: self.a * rhs.c + self.c * rhs.d,
+            d: self.b * rhs.c + self.d * rhs.d,
+            e: self.a * rhs.e + self.c * rhs.f + self.e,
+            f: self.b * rhs.e + self.d * rhs.f + self.f,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_point(actual: (f32, f32), expected: (f32, f32)) {
+        let d = ((actual.0 - expected.0).powi(2) + (actual.1 - expected.1).powi(2)).sqrt();
+        if d >= 1e-4 {
+            panic!("Expected {:?} but got {:?}!", expected, actual);
+        }
+    }
+
+    #[test]
+    fn basic_transforms() {
+        let p = (2.0, 1.0);
+        assert_point(AffineTransform::IDENTITY.apply(p), (2.0, 1.0));
+        assert_point(AffineTransform::translate(1.0, -1.0).apply(p), (3.0, 0.0));
+        assert_point(AffineTransform::scale(2.0, 3.0).apply(p), (4.0, 3.0));
+        assert_point(AffineTransform::rotate_deg(90.0).apply(p), (-1.0, 2.0));
+        assert_point(AffineTransform::shear(1.0, 0.0).apply(p), (3.0, 1.0));
+        assert_point(AffineTransform::mirror_x().apply(p), (-2.0, 1.0));
+        assert_point(AffineTransform::mirror_y().apply(p), (2.0, -1.0));
+    }
+
+    #[test]
+    fn composition_order() {
+        let t = AffineTransform::translate(10.0, 0.0).then(&AffineTransform::rotate_deg(90.0));
+        assert_point(t.apply((0.0, 0.0)), (0.0, 10.0));
+
+        let t = AffineTransform::rotate_deg(90.0).then(&AffineTransform::translate(10.0, 0.0));
+        assert_point(t.apply((0.0, 0.0)), (10.0, 0.0));
+
+        let r = AffineTransform::rotate_about(std::f32::consts::PI, 5.0, 5.0);
+        assert_point(r.apply((0.0, 0.0)), (10.0, 10.0));
+    }
+
+    #[test]
+    fn inverse() {
+        let t = AffineTransform::translate(3.0, -2.0)
+            .then(&AffineTransform::rotate_deg(30.0))
+            .then(&AffineTransform::scale(2.0, 0.5));
+        let inv = t.invert().unwrap();
+        assert_point(inv.apply(t.apply((7.0, 11.0))), (7.0, 11.0));
+        assert!((t * inv).invert().is_some());
+
+        assert!(AffineTransform::scale(0.0, 1.0).invert().is_none());
+    }
+}
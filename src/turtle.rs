@@ -0,0 +1,165 @@
+use crate::Printer;
+use crate::path::Path;
+
+// Turtle graphics: a pen that moves forward and turns, as in Logo. What it
+// draws is kept as strokes in drawing coordinates until `draw`, so one
+// turtle can also be drawn several times or looked at first.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Turtle {
+    position: (f32, f32),
+    // Degrees counterclockwise from the x axis
+    heading: f32,
+    down: bool,
+    // Finished strokes, then the one being drawn
+    strokes: Vec<Path>,
+    current: Path,
+    // Saved positions and headings, as for the branches of L-systems
+    stack: Vec<((f32, f32), f32)>,
+}
+
+impl Turtle {
+    // At (x, y) facing along the x axis, with the pen down
+    pub fn new(x: f32, y: f32) -> Self {
+        Turtle {
+            position: (x, y),
+            heading: 0.0,
+            down: true,
+            strokes: Vec::new(),
+            current: Vec::new(),
+            stack: Vec::new(),
+        }
+    }
+
+    pub fn position(&self) -> (f32, f32) {
+        self.position
+    }
+
+    pub fn heading(&self) -> f32 {
+        self.heading
+    }
+
+    pub fn forward(&mut self, distance: f32) -> &mut Self {
+        let (sin, cos) = self.heading.to_radians().sin_cos();
+        let (x, y) = self.position;
+        self.goto(x + distance * cos, y + distance * sin)
+    }
+
+    // Counterclockwise for positive degrees
+    pub fn turn(&mut self, degrees: f32) -> &mut Self {
+        self.heading = (self.heading + degrees).rem_euclid(360.0);
+        self
+    }
+
+    pub fn set_heading(&mut self, degrees: f32) -> &mut Self {
+        self.heading = degrees.rem_euclid(360.0);
+        self
+    }
+
+    pub fn pen_up(&mut self) -> &mut Self {
+        self.down = false;
+        self.end_stroke();
+        self
+    }
+
+    pub fn pen_down(&mut self) -> &mut Self {
+        self.down = true;
+        self
+    }
+
+    // Straight to (x, y), drawing if the pen is down. The heading stays.
+    pub fn goto(&mut self, x: f32, y: f32) -> &mut Self {
+        if self.down {
+            if self.current.is_empty() {
+                self.current.push(self.position);
+            }
+            self.current.push((x, y));
+        }
+        self.position = (x, y);
+        self
+    }
+
+    // Remembers the position and heading for `pop`
+    pub fn push(&mut self) -> &mut Self {
+        self.stack.push((self.position, self.heading));
+        self
+    }
+
+    // Jumps back to where the last `push` was, without drawing
+    pub fn pop(&mut self) -> &mut Self {
+        if let Some((position, heading)) = self.stack.pop() {
+            self.end_stroke();
+            self.position = position;
+            self.heading = heading;
+        }
+        self
+    }
+
+    fn end_stroke(&mut self) {
+        let stroke = std::mem::take(&mut self.current);
+        if stroke.len() > 1 {
+            self.strokes.push(stroke);
+        }
+    }
+
+    // Everything drawn so far, a stroke for every time the pen went down
+    pub fn strokes(&self) -> Vec<Path> {
+        let mut strokes = self.strokes.clone();
+        if self.current.len() > 1 {
+            strokes.push(self.current.clone());
+        }
+        strokes
+    }
+
+    pub fn draw(&self, printer: &mut Printer) {
+        for stroke in self.strokes() {
+            printer.draw_polyline(&stroke);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn near(a: (f32, f32), b: (f32, f32)) -> bool {
+        (a.0 - b.0).abs() < 1e-4 && (a.1 - b.1).abs() < 1e-4
+    }
+
+    #[test]
+    fn square_and_jumps() {
+        let mut turtle = Turtle::new(10.0, 10.0);
+        for _ in 0..4 {
+            turtle.forward(5.0).turn(90.0);
+        }
+        turtle.pen_up().forward(20.0).pen_down().goto(30.0, 20.0);
+
+        let strokes = turtle.strokes();
+        assert_eq!(strokes.len(), 2);
+        let square = [
+            (10.0, 10.0),
+            (15.0, 10.0),
+            (15.0, 15.0),
+            (10.0, 15.0),
+            (10.0, 10.0),
+        ];
+        assert!(strokes[0].iter().zip(square).all(|(&a, b)| near(a, b)));
+        assert_eq!(strokes[1], [(30.0, 10.0), (30.0, 20.0)]);
+        assert_eq!(turtle.heading(), 0.0);
+    }
+
+    #[test]
+    fn branches() {
+        // A Y: the trunk, then two branches from its top
+        let mut turtle = Turtle::new(0.0, 0.0);
+        turtle.set_heading(90.0).forward(10.0);
+        turtle.push().turn(45.0).forward(5.0).pop();
+        turtle.push().turn(-45.0).forward(5.0).pop();
+        assert!(near(turtle.position(), (0.0, 10.0)));
+        assert_eq!(turtle.heading(), 90.0);
+
+        let strokes = turtle.strokes();
+        assert_eq!(strokes.len(), 2);
+        assert_eq!(strokes[0].len(), 3);
+        assert!(near(strokes[1][0], (0.0, 10.0)));
+    }
+}